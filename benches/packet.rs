@@ -0,0 +1,128 @@
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use dns_in_rust::byte_packet_buffer::BytePacketBuffer;
+use dns_in_rust::model::{
+    dns_packet::DnsPacket, dns_question::DnsQuestion, dns_record::DnsRecord, query_type::QueryType,
+};
+
+/// A packet with the question and records, given in presentation format
+fn packet(qname: &str, qtype: QueryType, sections: [&[&str]; 3]) -> DnsPacket {
+    let records = |lines: &[&str]| -> Vec<DnsRecord> {
+        lines
+            .iter()
+            .map(|line| line.parse().expect("valid record"))
+            .collect()
+    };
+
+    let mut packet = DnsPacket::new();
+    packet.header.id = 4660;
+    packet.header.recursion_desired = true;
+    packet.header.query_response = !sections.iter().all(|lines| lines.is_empty());
+    packet
+        .questions
+        .push(DnsQuestion::new(qname.to_string(), qtype));
+    packet.answers = records(sections[0]);
+    packet.authorities = records(sections[1]);
+    packet.resources = records(sections[2]);
+    packet.set_edns(1232, true);
+
+    packet
+}
+
+/// A query, a referral with glue, and a signed answer
+fn packets() -> Vec<(&'static str, DnsPacket)> {
+    vec![
+        (
+            "query",
+            packet("www.example.com", QueryType::A, [&[], &[], &[]]),
+        ),
+        (
+            "referral",
+            packet(
+                "www.example.com",
+                QueryType::A,
+                [
+                    &[],
+                    &[
+                        "com. 172800 IN NS a.gtld-servers.net.",
+                        "com. 172800 IN NS b.gtld-servers.net.",
+                        "com. 172800 IN NS c.gtld-servers.net.",
+                        "com. 172800 IN NS d.gtld-servers.net.",
+                    ],
+                    &[
+                        "a.gtld-servers.net. 172800 IN A 192.5.6.30",
+                        "b.gtld-servers.net. 172800 IN A 192.33.14.30",
+                        "c.gtld-servers.net. 172800 IN A 192.26.92.30",
+                        "d.gtld-servers.net. 172800 IN A 192.31.80.30",
+                        "a.gtld-servers.net. 172800 IN AAAA 2001:503:a83e::2:30",
+                        "b.gtld-servers.net. 172800 IN AAAA 2001:503:231d::2:30",
+                    ],
+                ],
+            ),
+        ),
+        (
+            "signed",
+            packet(
+                "www.example.com",
+                QueryType::A,
+                [
+                    &[
+                        "www.example.com. 300 IN A 192.0.2.1",
+                        "www.example.com. 300 IN A 192.0.2.2",
+                        "www.example.com. 300 IN RRSIG A 13 3 300 20300101000000 \
+                         20200101000000 12345 example.com. \
+                         oJB1W6WNGv+ldvQ3WDG0MQkg5IEhjRip8WTrPYGv07h108dUKGMeDPKijVCHX3DDKdfb+v6o\
+                         B9wfuh3DTJXUAf==",
+                    ],
+                    &[
+                        "example.com. 3600 IN NS ns1.example.com.",
+                        "example.com. 3600 IN NS ns2.example.com.",
+                        "example.com. 3600 IN RRSIG NS 13 2 3600 20300101000000 \
+                         20200101000000 12345 example.com. \
+                         oJB1W6WNGv+ldvQ3WDG0MQkg5IEhjRip8WTrPYGv07h108dUKGMeDPKijVCHX3DDKdfb+v6o\
+                         B9wfuh3DTJXUAf==",
+                    ],
+                    &[
+                        "ns1.example.com. 3600 IN A 192.0.2.53",
+                        "ns2.example.com. 3600 IN AAAA 2001:db8::53",
+                    ],
+                ],
+            ),
+        ),
+    ]
+}
+
+fn from_buffer(c: &mut Criterion) {
+    for (name, mut packet) in packets() {
+        let mut buffer = BytePacketBuffer::new();
+        packet.write(&mut buffer).expect("packet fits");
+
+        c.bench_function(&format!("from_buffer {}", name), |b| {
+            b.iter_batched_ref(
+                || {
+                    let mut copy = BytePacketBuffer::new();
+                    copy.buf = buffer.buf.clone();
+                    copy
+                },
+                |buffer| DnsPacket::from_buffer(black_box(buffer)).expect("valid packet"),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+}
+
+fn write(c: &mut Criterion) {
+    for (name, mut packet) in packets() {
+        c.bench_function(&format!("write {}", name), |b| {
+            b.iter(|| {
+                let mut buffer = BytePacketBuffer::new();
+                black_box(&mut packet)
+                    .write(&mut buffer)
+                    .expect("packet fits");
+                buffer.pos()
+            })
+        });
+    }
+}
+
+criterion_group!(benches, from_buffer, write);
+criterion_main!(benches);
@@ -0,0 +1,24 @@
+#![no_main]
+
+use dns_in_rust::byte_packet_buffer::BytePacketBuffer;
+use dns_in_rust::model::dns_packet::DnsPacket;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let mut buffer = BytePacketBuffer::new();
+    let len = data.len().min(buffer.buf.len());
+    buffer.buf[..len].copy_from_slice(&data[..len]);
+
+    let Ok(mut packet) = DnsPacket::from_buffer(&mut buffer) else {
+        return;
+    };
+
+    // NOTE: Whatever parses and can be written again must parse back to the same packet
+    let mut written = BytePacketBuffer::new();
+    if packet.write(&mut written).is_err() {
+        return;
+    }
+    written.seek(0).unwrap();
+    let reparsed = DnsPacket::from_buffer(&mut written).expect("a written packet parses");
+    assert_eq!(packet, reparsed);
+});
@@ -0,0 +1,13 @@
+#![no_main]
+
+use dns_in_rust::byte_packet_buffer::BytePacketBuffer;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let mut buffer = BytePacketBuffer::new();
+    let len = data.len().min(buffer.buf.len());
+    buffer.buf[..len].copy_from_slice(&data[..len]);
+
+    let mut name = String::new();
+    let _ = buffer.read_qname(&mut name);
+});
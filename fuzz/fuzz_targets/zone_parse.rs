@@ -0,0 +1,8 @@
+#![no_main]
+
+use dns_in_rust::zone::Zone;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|text: &str| {
+    let _ = Zone::parse(text, "example.com");
+});
@@ -0,0 +1,382 @@
+//! An asynchronous resolver on tokio, with the `tokio` feature, for callers which can't block on
+//! `recursive_lookup`. Its queries share a single UDP socket, responses being handed to the query
+//! waiting for them by their id, so any number of resolutions can be in flight at once.
+
+use super::byte_packet_buffer::BytePacketBuffer;
+use super::config::ServerConfig;
+use super::model::{dns_packet::DnsPacket, dns_question::DnsQuestion, query_type::QueryType};
+use super::query::{is_failure, next_step, query_packet, Step};
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+
+/// Queries waiting for their response, by id, with the server they were sent to
+type Waiting = HashMap<u16, (SocketAddr, oneshot::Sender<DnsPacket>)>;
+type Pending = Arc<Mutex<Waiting>>;
+
+/// Resolves names iteratively from its root hints, without validating them. Its futures can be
+/// dropped to cancel them, and its errors can be sent across threads, as the futures can.
+pub struct AsyncResolver {
+    pub root_hints: Vec<Ipv4Addr>,
+    /// Port the servers are asked on
+    pub port: u16,
+    /// How long to wait for each response
+    pub timeout: Duration,
+    socket: Arc<UdpSocket>,
+    pending: Pending,
+    receiver: JoinHandle<()>,
+}
+
+impl AsyncResolver {
+    /// Bind the shared socket and start receiving on it, which needs a tokio runtime
+    pub async fn new(
+        root_hints: Vec<Ipv4Addr>,
+        port: u16,
+        timeout: Duration,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let socket = Arc::new(UdpSocket::bind(("0.0.0.0", 0)).await?);
+        let pending = Pending::default();
+        let receiver = tokio::spawn(receive(Arc::clone(&socket), Arc::clone(&pending)));
+
+        Ok(AsyncResolver {
+            root_hints,
+            port,
+            timeout,
+            socket,
+            pending,
+            receiver,
+        })
+    }
+
+    /// With the root hints, upstream port and timeout of the settings
+    pub async fn from_config(
+        config: &ServerConfig,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        AsyncResolver::new(
+            config.root_hints.clone(),
+            config.upstream_port,
+            config.upstream_timeout,
+        )
+        .await
+    }
+
+    /// Resolve the name from each of the root hints in turn, as `resolve_from_hints` does
+    pub async fn resolve(
+        &self,
+        qname: &str,
+        qtype: QueryType,
+    ) -> Result<DnsPacket, Box<dyn std::error::Error + Send + Sync>> {
+        let mut result = Err("There are no root hints".into());
+        for root in &self.root_hints {
+            result = self.walk(qname, qtype, *root).await;
+            if let Ok(response) = &result {
+                if !is_failure(response) {
+                    break;
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Follow the referrals from the root server, as `resolve` of the blocking API does
+    async fn walk(
+        &self,
+        qname: &str,
+        qtype: QueryType,
+        root: Ipv4Addr,
+    ) -> Result<DnsPacket, Box<dyn std::error::Error + Send + Sync>> {
+        let mut ns = root;
+
+        loop {
+            let response = self.lookup(qname, qtype, ns).await?;
+
+            ns = match next_step(qname, &response) {
+                Step::Done => return Ok(response),
+                Step::Ask(new_ns) => new_ns,
+                Step::Resolve(new_ns_name) => {
+                    let recursive_response =
+                        Box::pin(self.walk(new_ns_name, QueryType::A, root)).await?;
+                    match recursive_response.get_random_a() {
+                        Some(new_ns) => new_ns,
+                        None => return Ok(response),
+                    }
+                }
+            };
+        }
+    }
+
+    /// Ask a single server for the name
+    pub async fn lookup(
+        &self,
+        qname: &str,
+        qtype: QueryType,
+        server: Ipv4Addr,
+    ) -> Result<DnsPacket, Box<dyn std::error::Error + Send + Sync>> {
+        let mut query = query_packet(&DnsQuestion::new(qname.to_string(), qtype), true, false);
+        let addr = SocketAddr::from((server, self.port));
+        let (sender, response) = oneshot::channel();
+
+        // NOTE: The ids of the queries in flight must differ, for their responses to be told apart
+        {
+            let mut pending = self.pending.lock().map_err(|e| e.to_string())?;
+            if self.receiver.is_finished() {
+                return Err("The resolver stopped receiving".into());
+            }
+            while pending.contains_key(&query.header.id) {
+                query.header.id = rand::random();
+            }
+            pending.insert(query.header.id, (addr, sender));
+        }
+        let _registration = Registration {
+            pending: &self.pending,
+            id: query.header.id,
+        };
+
+        let mut buffer = BytePacketBuffer::new();
+        query.write(&mut buffer).map_err(|e| e.to_string())?;
+        self.socket
+            .send_to(&buffer.buf[..buffer.pos()], addr)
+            .await?;
+
+        match tokio::time::timeout(self.timeout, response).await {
+            Ok(Ok(response)) => Ok(response),
+            Ok(Err(_)) => Err("The resolver stopped receiving".into()),
+            Err(_) => Err(format!("Timed out waiting for {}", server).into()),
+        }
+    }
+
+    /// How many queries are waiting for their response
+    pub fn in_flight(&self) -> usize {
+        self.pending.lock().map_or(0, |pending| pending.len())
+    }
+}
+
+impl Drop for AsyncResolver {
+    fn drop(&mut self) {
+        self.receiver.abort();
+    }
+}
+
+/// A query waiting for its response, no longer waiting once dropped, whether it was answered,
+/// timed out or cancelled
+struct Registration<'a> {
+    pending: &'a Mutex<Waiting>,
+    id: u16,
+}
+
+impl Drop for Registration<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.remove(&self.id);
+        }
+    }
+}
+
+/// How long to wait before receiving again after a transient error
+const RECEIVE_BACKOFF: Duration = Duration::from_millis(10);
+
+/// Whether receiving can go on after the error, as after an ICMP error of an earlier send being
+/// reported on the socket
+fn is_transient(kind: ErrorKind) -> bool {
+    matches!(
+        kind,
+        ErrorKind::Interrupted
+            | ErrorKind::WouldBlock
+            | ErrorKind::ConnectionRefused
+            | ErrorKind::ConnectionReset
+    )
+}
+
+/// Hand the responses received on the socket to the queries waiting for them, until the socket
+/// fails for good
+async fn receive(socket: Arc<UdpSocket>, pending: Pending) {
+    loop {
+        let mut buffer = BytePacketBuffer::new();
+        let src = match socket.recv_from(&mut buffer.buf).await {
+            Ok((_, src)) => src,
+            Err(e) if is_transient(e.kind()) => {
+                tokio::time::sleep(RECEIVE_BACKOFF).await;
+                continue;
+            }
+            // NOTE: Dropping the senders tells the waiting queries that no response will come
+            Err(_) => {
+                if let Ok(mut pending) = pending.lock() {
+                    pending.clear();
+                }
+                return;
+            }
+        };
+        let Ok(response) = DnsPacket::from_buffer(&mut buffer) else {
+            continue;
+        };
+
+        // NOTE: A response from another address than the server asked is stray or spoofed
+        let waiting =
+            pending
+                .lock()
+                .ok()
+                .and_then(|mut pending| match pending.get(&response.header.id) {
+                    Some((addr, _)) if *addr == src => pending.remove(&response.header.id),
+                    _ => None,
+                });
+        if let Some((_, sender)) = waiting {
+            let _ = sender.send(response);
+        }
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::super::mock::fixture::{self, addresses, ROOT, SLOW_DELAY};
+    use super::super::mock::{Behavior, MockNetwork};
+    use super::super::model::result_code::ResultCode;
+    use super::{is_transient, AsyncResolver, QueryType};
+    use std::io::ErrorKind;
+    use std::net::Ipv4Addr;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    fn network() -> Result<MockNetwork, Box<dyn std::error::Error + Send + Sync>> {
+        fixture::network(Behavior::Authoritative).map_err(|e| e.to_string().into())
+    }
+
+    async fn resolver(
+        network: &MockNetwork,
+    ) -> Result<AsyncResolver, Box<dyn std::error::Error + Send + Sync>> {
+        AsyncResolver::new(vec![ROOT], network.port, Duration::from_secs(1)).await
+    }
+
+    #[tokio::test]
+    async fn resolve_ok() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        // arrange
+        let network = network()?;
+        let resolver = resolver(&network).await?;
+
+        // act
+        let www = resolver.resolve("www.example.test", QueryType::A).await?;
+        let glueless = resolver.resolve("www.glueless.test", QueryType::A).await?;
+        let nxdomain = resolver.resolve("nope.example.test", QueryType::A).await?;
+
+        // assert
+        assert_eq!(vec![Ipv4Addr::new(192, 0, 2, 1)], addresses(&www));
+        assert_eq!(vec![Ipv4Addr::new(192, 0, 2, 1)], addresses(&glueless));
+        assert_eq!(ResultCode::NXDOMAIN, nxdomain.header.response_code);
+        assert_eq!(0, resolver.in_flight());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn resolve_concurrent_ok() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        // arrange
+        let network = network()?;
+        let resolver = Arc::new(resolver(&network).await?);
+        let start = Instant::now();
+
+        // act
+        let slow = tokio::spawn({
+            let resolver = Arc::clone(&resolver);
+            async move { resolver.resolve("www.slow.test", QueryType::A).await }
+        });
+        let mut fast = Vec::new();
+        for _ in 0..10 {
+            let resolver = Arc::clone(&resolver);
+            fast.push(tokio::spawn(async move {
+                resolver.resolve("www.example.test", QueryType::A).await
+            }));
+        }
+        let mut answered = Vec::new();
+        for task in fast {
+            answered.push(addresses(&task.await??));
+        }
+        let fast_elapsed = start.elapsed();
+        let slow = slow.await??;
+
+        // assert
+        assert_eq!(vec![vec![Ipv4Addr::new(192, 0, 2, 1)]; 10], answered);
+        assert!(fast_elapsed < SLOW_DELAY);
+        assert_eq!(vec![Ipv4Addr::new(192, 0, 2, 1)], addresses(&slow));
+        assert_eq!(0, resolver.in_flight());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn resolve_cancel_ok() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        // arrange
+        let network = network()?;
+        let resolver = resolver(&network).await?;
+
+        // act
+        let cancelled = tokio::time::timeout(
+            Duration::from_millis(100),
+            resolver.resolve("www.slow.test", QueryType::A),
+        )
+        .await;
+
+        // assert
+        assert!(cancelled.is_err());
+        assert_eq!(0, resolver.in_flight());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn resolve_error() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        // arrange
+        let network = network()?;
+        let mut resolver = resolver(&network).await?;
+        resolver.timeout = Duration::from_millis(100);
+        let no_hints = AsyncResolver::new(Vec::new(), network.port, resolver.timeout).await?;
+
+        // act
+        let slow = resolver.resolve("www.slow.test", QueryType::A).await;
+        let nowhere = no_hints.resolve("www.example.test", QueryType::A).await;
+
+        // assert
+        assert_eq!(
+            "Timed out waiting for 127.0.53.23",
+            slow.err().map(|e| e.to_string()).unwrap_or_default()
+        );
+        assert_eq!(
+            "There are no root hints",
+            nowhere.err().map(|e| e.to_string()).unwrap_or_default()
+        );
+        assert_eq!(0, resolver.in_flight());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn lookup_stopped_error() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        // arrange
+        let network = network()?;
+        let resolver = resolver(&network).await?;
+        resolver.receiver.abort();
+        while !resolver.receiver.is_finished() {
+            tokio::task::yield_now().await;
+        }
+
+        // act
+        let stopped = resolver
+            .lookup("www.example.test", QueryType::A, ROOT)
+            .await;
+
+        // assert
+        assert_eq!(
+            "The resolver stopped receiving",
+            stopped.err().map(|e| e.to_string()).unwrap_or_default()
+        );
+        assert!(is_transient(ErrorKind::ConnectionRefused));
+        assert!(!is_transient(ErrorKind::PermissionDenied));
+        assert_eq!(0, resolver.in_flight());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,117 @@
+use dns_in_rust::config::ClientConfig;
+use dns_in_rust::model::{dns_packet::DnsPacket, dns_question::DnsQuestion};
+use dns_in_rust::query::{lookup_with, resolve_traced, LookupOptions, ROOT_SERVER};
+use std::net::Ipv4Addr;
+use std::time::{Duration, Instant};
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let config = ClientConfig::from_args(args.clone())?;
+    let options = LookupOptions {
+        tcp: config.tcp,
+        recursion_desired: config.recurse && !config.trace,
+        dnssec_ok: config.dnssec,
+        ..Default::default()
+    };
+
+    if !config.short {
+        println!(
+            "\n; <<>> DiG-in-Rust {} <<>> {}",
+            env!("CARGO_PKG_VERSION"),
+            args.join(" ")
+        );
+    }
+
+    if config.trace {
+        return trace(&config, &options);
+    }
+
+    let server = (config.server, config.port);
+    let (response, size, elapsed, tcp) = query(&config.question, server, &options)?;
+    if config.short {
+        short(&response);
+        return Ok(());
+    }
+
+    println!(";; Got answer:\n{}", response);
+    println!(";; Query time: {} msec", elapsed.as_millis());
+    println!(
+        ";; SERVER: {}#{}({}) ({})",
+        config.server,
+        config.port,
+        config.server,
+        if tcp { "TCP" } else { "UDP" }
+    );
+    println!(";; MSG SIZE  rcvd: {}", size);
+
+    Ok(())
+}
+
+/// Ask the server, retrying over TCP when the UDP response is truncated. Gives the response,
+/// the size of its message, the time taken and whether TCP was used.
+fn query(
+    question: &DnsQuestion,
+    server: (Ipv4Addr, u16),
+    options: &LookupOptions,
+) -> Result<(DnsPacket, usize, Duration, bool), Box<dyn std::error::Error>> {
+    let start = Instant::now();
+    let (response, size) = lookup_with(question, server, options)?;
+    if !response.header.truncated_message || options.tcp {
+        return Ok((response, size, start.elapsed(), options.tcp));
+    }
+
+    println!(";; Truncated, retrying in TCP mode.");
+    let options = LookupOptions {
+        tcp: true,
+        ..options.clone()
+    };
+    let start = Instant::now();
+    let (response, size) = lookup_with(question, server, &options)?;
+
+    Ok((response, size, start.elapsed(), true))
+}
+
+/// Resolve the name iteratively from the root server, showing each step of the delegation walk
+/// and then the answers
+fn trace(config: &ClientConfig, options: &LookupOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let mut lookup = |qname: &str, qtype, server| {
+        let question = DnsQuestion::new(qname.to_string(), qtype);
+        let (response, ..) = query(&question, (server, 53), options)?;
+
+        Ok(response)
+    };
+
+    let mut steps = Vec::new();
+    let result = resolve_traced(
+        &config.question.name,
+        config.question.qtype,
+        ROOT_SERVER,
+        None,
+        &mut lookup,
+        &mut steps,
+    );
+    if !config.short {
+        for step in &steps {
+            println!(";; {}", step);
+        }
+    }
+    let (response, _) = result?;
+
+    if config.short {
+        short(&response);
+    } else {
+        println!();
+        for rec in response.answers.iter().chain(&response.authorities) {
+            println!("{}", rec);
+        }
+    }
+
+    Ok(())
+}
+
+/// Print only the data of the answers, one per line
+fn short(response: &DnsPacket) {
+    for rec in &response.answers {
+        println!("{}", rec.rdata());
+    }
+}
@@ -0,0 +1,26 @@
+use dns_in_rust::config::PerfConfig;
+use dns_in_rust::perf::{read_queries, run};
+use std::io::Read;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let config = PerfConfig::from_args(std::env::args().skip(1))?;
+
+    let text = if config.file == "-" {
+        let mut text = String::new();
+        std::io::stdin().read_to_string(&mut text)?;
+        text
+    } else {
+        std::fs::read_to_string(&config.file)?
+    };
+    let queries = read_queries(&text)?;
+
+    println!(
+        "Sending queries to {}#{} over {}",
+        config.server,
+        config.port,
+        if config.tcp { "TCP" } else { "UDP" }
+    );
+    print!("{}", run(&queries, &config)?);
+
+    Ok(())
+}
@@ -0,0 +1,481 @@
+//! Filtering of the names we recurse for, as Pi-hole does: names of blocklists are answered
+//! locally with a configurable response, unless an allowlist or an exception allows them. Lists
+//! count the names they decided, to tell which of them are in use.
+
+use super::config::ServerConfig;
+use super::dnssec::name;
+use super::model::{
+    dns_class::DnsClass, dns_packet::DnsPacket, dns_question::DnsQuestion, dns_record::DnsRecord,
+    query_type::QueryType, result_code::ResultCode,
+};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// Names of hosts files which aren't blocked, but the local host in its usual entries
+const LOCAL_NAMES: [&str; 6] = [
+    "localhost",
+    "localhost.localdomain",
+    "local",
+    "broadcasthost",
+    "ip6-localhost",
+    "ip6-loopback",
+];
+
+/// How blocked names are answered
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlockResponse {
+    Nxdomain,
+    /// The name exists without records of the type
+    Nodata,
+    /// `0.0.0.0` to A queries and `::` to AAAA ones, no records to other types
+    Null,
+    /// This address to the queries of its type, no records to other types
+    Sinkhole(IpAddr),
+}
+
+/// Names a rule matches
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+    /// The name only, e.g. `ads.example.com` of hosts files and domain lists
+    Exact(String),
+    /// The names below it only, e.g. `*.example.com` of domain lists
+    Subdomains(String),
+    /// The name and the names below it, e.g. `||example.com^` of Adblock lists
+    Domain(String),
+}
+
+/// A rule of a list, blocking names or allowing them despite the rules blocking them
+#[derive(Debug, Clone, PartialEq)]
+pub enum Rule {
+    Block(Pattern),
+    Allow(Pattern),
+}
+
+/// A list and how many names it decided
+#[derive(Debug, Clone, PartialEq)]
+pub struct List {
+    pub name: String,
+    pub rules: usize,
+    pub hits: u64,
+}
+
+/// The rules of the lists, by the name they are about, along with the first list holding them
+#[derive(Debug, Default)]
+struct Index(HashMap<String, Patterns>);
+
+#[derive(Debug, Default)]
+struct Patterns {
+    exact: Option<usize>,
+    subdomains: Option<usize>,
+    domain: Option<usize>,
+}
+
+impl Index {
+    fn insert(&mut self, pattern: &Pattern, list: usize) {
+        let (Pattern::Exact(name) | Pattern::Subdomains(name) | Pattern::Domain(name)) = pattern;
+        let patterns = self.0.entry(name.clone()).or_default();
+        let slot = match pattern {
+            Pattern::Exact(_) => &mut patterns.exact,
+            Pattern::Subdomains(_) => &mut patterns.subdomains,
+            Pattern::Domain(_) => &mut patterns.domain,
+        };
+        // NOTE: The first list holding a rule is the one counting its hits
+        slot.get_or_insert(list);
+    }
+
+    /// The list of the first rule matching the name, looking it up and then its ancestors, so
+    /// matching takes as many lookups as the name has labels
+    fn find(&self, qname: &str) -> Option<usize> {
+        let qname = name::normalize(qname);
+        let labels = name::labels(&qname);
+
+        (0..labels.len()).find_map(|start| {
+            let patterns = self.0.get(&labels[start..].join("."))?;
+            if start == 0 {
+                patterns.exact.or(patterns.domain)
+            } else {
+                patterns.subdomains.or(patterns.domain)
+            }
+        })
+    }
+}
+
+/// The blocklists and allowlists, and how blocked names are answered
+#[derive(Debug)]
+pub struct Blocklists {
+    pub response: BlockResponse,
+    /// TTL of the answers to blocked names
+    pub ttl: u32,
+    lists: Vec<List>,
+    block: Index,
+    allow: Index,
+}
+
+impl Blocklists {
+    pub fn new(response: BlockResponse, ttl: u32) -> Self {
+        Blocklists {
+            response,
+            ttl,
+            lists: Vec::new(),
+            block: Index::default(),
+            allow: Index::default(),
+        }
+    }
+
+    /// With the lists of the settings, answering blocked names as they say with the TTL of the
+    /// names answered locally
+    pub fn from_config(config: &ServerConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut blocklists = Blocklists::new(config.block_response, config.local_ttl);
+        for (paths, allowlist) in [(&config.blocklists, false), (&config.allowlists, true)] {
+            for path in paths {
+                let text = std::fs::read_to_string(path)
+                    .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+                blocklists.add(path, &text, allowlist);
+            }
+        }
+
+        Ok(blocklists)
+    }
+
+    /// Add a list in any of the formats `parse` reads. All the rules of an allowlist allow the
+    /// names they match.
+    pub fn add(&mut self, name: &str, text: &str, allowlist: bool) {
+        let list = self.lists.len();
+        let rules = parse(text);
+
+        for rule in &rules {
+            match rule {
+                Rule::Allow(pattern) => self.allow.insert(pattern, list),
+                Rule::Block(pattern) if allowlist => self.allow.insert(pattern, list),
+                Rule::Block(pattern) => self.block.insert(pattern, list),
+            }
+        }
+
+        self.lists.push(List {
+            name: name.to_string(),
+            rules: rules.len(),
+            hits: 0,
+        });
+    }
+
+    /// The lists, in the order they were added
+    pub fn lists(&self) -> &[List] {
+        &self.lists
+    }
+
+    /// The list blocking the name, if any, counting a hit for the list deciding it. Allowing
+    /// rules win over blocking ones.
+    pub fn check(&mut self, qname: &str) -> Option<&List> {
+        let list = self.decide(qname)?;
+
+        Some(&self.lists[list])
+    }
+
+    fn decide(&mut self, qname: &str) -> Option<usize> {
+        if let Some(list) = self.allow.find(qname) {
+            self.lists[list].hits += 1;
+            return None;
+        }
+
+        let list = self.block.find(qname)?;
+        self.lists[list].hits += 1;
+
+        Some(list)
+    }
+
+    /// The answer to the question when its name is blocked, along with the list blocking it
+    pub fn answer(&mut self, question: &DnsQuestion) -> Option<(DnsPacket, &List)> {
+        if question.qclass != DnsClass::IN {
+            return None;
+        }
+        let list = self.decide(&question.name)?;
+
+        let mut packet = DnsPacket::new();
+        packet.header.authoritative_answer = true;
+        packet.header.response_code = ResultCode::NOERROR;
+
+        let addr = match (self.response, question.qtype) {
+            (BlockResponse::Nxdomain, _) => {
+                packet.header.response_code = ResultCode::NXDOMAIN;
+                None
+            }
+            (BlockResponse::Null, QueryType::A) => Some(IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
+            (BlockResponse::Null, QueryType::AAAA) => Some(IpAddr::V6(Ipv6Addr::UNSPECIFIED)),
+            (BlockResponse::Sinkhole(addr), QueryType::A) if addr.is_ipv4() => Some(addr),
+            (BlockResponse::Sinkhole(addr), QueryType::AAAA) if addr.is_ipv6() => Some(addr),
+            _ => None,
+        };
+        let domain = question.name.clone();
+        packet.answers = match addr {
+            Some(IpAddr::V4(addr)) => vec![DnsRecord::A {
+                domain,
+                addr,
+                class: DnsClass::IN,
+                ttl: self.ttl,
+            }],
+            Some(IpAddr::V6(addr)) => vec![DnsRecord::AAAA {
+                domain,
+                addr,
+                class: DnsClass::IN,
+                ttl: self.ttl,
+            }],
+            None => Vec::new(),
+        };
+
+        Some((packet, &self.lists[list]))
+    }
+}
+
+/// Parse a list, each line being in any of these formats:
+///
+/// - hosts file: `0.0.0.0 ads.example.com tracker.example.com`, blocking these names
+/// - domain list: `ads.example.com`, blocking the name, or `*.example.com`, blocking the names
+///   below it
+/// - Adblock: `||example.com^`, blocking the name and the names below it, and
+///   `@@||example.com^`, allowing them
+///
+/// Comments (`#` and Adblock `!`), the names of the local host, and the Adblock rules which
+/// aren't about whole names, such as those with options other than `$important`, are skipped.
+pub fn parse(text: &str) -> Vec<Rule> {
+    let mut rules = Vec::new();
+
+    for line in text.lines() {
+        // NOTE: Comments start words only, `##` of Adblock element hiding rules being kept
+        let words: Vec<&str> = line
+            .split_whitespace()
+            .take_while(|word| !word.starts_with('#'))
+            .collect();
+
+        match words[..] {
+            [rule] if rule.starts_with(['!', '[']) => {}
+            [rule] if rule.starts_with("@@||") => {
+                rules.extend(adblock(&rule[4..]).map(Rule::Allow));
+            }
+            [rule] if rule.starts_with("||") => {
+                rules.extend(adblock(&rule[2..]).map(Rule::Block));
+            }
+            [addr, ref names @ ..] if addr.parse::<IpAddr>().is_ok() => {
+                let names = names
+                    .iter()
+                    .map(|name| name::normalize(name))
+                    .filter(|name| valid(name) && !LOCAL_NAMES.contains(&name.as_str()));
+                rules.extend(names.map(|name| Rule::Block(Pattern::Exact(name))));
+            }
+            [name] => {
+                let pattern = match name.strip_prefix("*.") {
+                    Some(parent) => Pattern::Subdomains(name::normalize(parent)),
+                    None => Pattern::Exact(name::normalize(name)),
+                };
+                if let Pattern::Exact(name) | Pattern::Subdomains(name) = &pattern {
+                    if valid(name) {
+                        rules.push(Rule::Block(pattern));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    rules
+}
+
+/// The pattern of an Adblock rule after its `||`, e.g. `example.com^` or `example.com^$important`
+fn adblock(rule: &str) -> Option<Pattern> {
+    let (rule, options) = rule.split_once('$').unwrap_or((rule, ""));
+    if !options
+        .split(',')
+        .all(|option| matches!(option, "" | "important"))
+    {
+        return None;
+    }
+
+    let name = name::normalize(rule.strip_suffix('^')?);
+
+    valid(&name).then_some(Pattern::Domain(name))
+}
+
+/// Whether the text is a name, made of labels of letters, digits, hyphens and underscores
+fn valid(name: &str) -> bool {
+    !name.is_empty()
+        && name.split('.').all(|label| {
+            !label.is_empty()
+                && label
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, BlockResponse, Blocklists, DnsQuestion, Pattern, QueryType, ResultCode};
+    use super::{IpAddr, Ipv4Addr, Rule};
+
+    const HOSTS: &str = "\
+# Blocked with the hosts file format
+127.0.0.1 localhost
+0.0.0.0 ads.example.com tracker.example.com # trailing comment
+:: ads6.example.com
+";
+
+    const DOMAINS: &str = "\
+malware.test
+*.doubleclick.test
+not a rule
+";
+
+    const ADBLOCK: &str = "\
+[Adblock Plus 2.0]
+! Title: DNS filter
+||adnetwork.test^
+||Metrics.Test^$important
+||cdn.test^$third-party
+@@||good.adnetwork.test^
+|https://example.com/ad.js
+example.com##.banner
+";
+
+    fn blocklists(response: BlockResponse) -> Blocklists {
+        let mut blocklists = Blocklists::new(response, 2);
+        blocklists.add("hosts", HOSTS, false);
+        blocklists.add("domains", DOMAINS, false);
+        blocklists.add("adblock", ADBLOCK, false);
+        blocklists.add("allowlist", "tracker.example.com\n", true);
+
+        blocklists
+    }
+
+    fn blocked_by(blocklists: &mut Blocklists, name: &str) -> Option<String> {
+        blocklists.check(name).map(|list| list.name.clone())
+    }
+
+    #[test]
+    fn parse_ok() {
+        // act
+        let hosts = parse(HOSTS);
+        let domains = parse(DOMAINS);
+        let adblock = parse(ADBLOCK);
+
+        // assert
+        let block = |pattern| Rule::Block(pattern);
+        assert_eq!(
+            vec![
+                block(Pattern::Exact("ads.example.com".to_string())),
+                block(Pattern::Exact("tracker.example.com".to_string())),
+                block(Pattern::Exact("ads6.example.com".to_string())),
+            ],
+            hosts
+        );
+        assert_eq!(
+            vec![
+                block(Pattern::Exact("malware.test".to_string())),
+                block(Pattern::Subdomains("doubleclick.test".to_string())),
+            ],
+            domains
+        );
+        assert_eq!(
+            vec![
+                block(Pattern::Domain("adnetwork.test".to_string())),
+                block(Pattern::Domain("metrics.test".to_string())),
+                Rule::Allow(Pattern::Domain("good.adnetwork.test".to_string())),
+            ],
+            adblock
+        );
+    }
+
+    #[test]
+    fn check_ok() {
+        // arrange
+        let mut blocklists = blocklists(BlockResponse::Null);
+
+        // act
+        let ads = blocked_by(&mut blocklists, "ADS.example.com.");
+        let below_exact = blocked_by(&mut blocklists, "www.ads.example.com");
+        let wildcard = blocked_by(&mut blocklists, "ad.g.doubleclick.test");
+        let wildcard_parent = blocked_by(&mut blocklists, "doubleclick.test");
+        let domain = blocked_by(&mut blocklists, "adnetwork.test");
+        let subdomain = blocked_by(&mut blocklists, "x.y.adnetwork.test");
+        let exception = blocked_by(&mut blocklists, "cdn.good.adnetwork.test");
+        let allowed = blocked_by(&mut blocklists, "tracker.example.com");
+        let local = blocked_by(&mut blocklists, "localhost");
+
+        // assert
+        assert_eq!(Some("hosts".to_string()), ads);
+        assert_eq!(None, below_exact);
+        assert_eq!(Some("domains".to_string()), wildcard);
+        assert_eq!(None, wildcard_parent);
+        assert_eq!(Some("adblock".to_string()), domain);
+        assert_eq!(Some("adblock".to_string()), subdomain);
+        assert_eq!(None, exception);
+        assert_eq!(None, allowed);
+        assert_eq!(None, local);
+        assert_eq!(
+            vec![
+                ("hosts".to_string(), 3, 1),
+                ("domains".to_string(), 2, 1),
+                ("adblock".to_string(), 3, 3),
+                ("allowlist".to_string(), 1, 1),
+            ],
+            blocklists
+                .lists()
+                .iter()
+                .map(|list| (list.name.clone(), list.rules, list.hits))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn answer_ok() {
+        // arrange
+        let a = DnsQuestion::new("ads.example.com".to_string(), QueryType::A);
+        let aaaa = DnsQuestion::new("ads.example.com".to_string(), QueryType::AAAA);
+        let mx = DnsQuestion::new("ads.example.com".to_string(), QueryType::MX);
+        let sinkhole = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 99));
+        let answers = |response, question: &DnsQuestion| {
+            blocklists(response).answer(question).map(|(packet, _)| {
+                let answers: Vec<String> = packet.answers.iter().map(|r| r.to_string()).collect();
+                (packet.header.response_code, answers)
+            })
+        };
+
+        // act
+        let null_a = answers(BlockResponse::Null, &a);
+        let null_aaaa = answers(BlockResponse::Null, &aaaa);
+        let null_mx = answers(BlockResponse::Null, &mx);
+        let nxdomain = answers(BlockResponse::Nxdomain, &a);
+        let nodata = answers(BlockResponse::Nodata, &a);
+        let sinkhole_a = answers(BlockResponse::Sinkhole(sinkhole), &a);
+        let sinkhole_aaaa = answers(BlockResponse::Sinkhole(sinkhole), &aaaa);
+        let allowed = answers(
+            BlockResponse::Null,
+            &DnsQuestion::new("example.com".to_string(), QueryType::A),
+        );
+
+        // assert
+        assert_eq!(
+            Some((
+                ResultCode::NOERROR,
+                vec!["ads.example.com. 2 IN A 0.0.0.0".to_string()]
+            )),
+            null_a
+        );
+        assert_eq!(
+            Some((
+                ResultCode::NOERROR,
+                vec!["ads.example.com. 2 IN AAAA ::".to_string()]
+            )),
+            null_aaaa
+        );
+        assert_eq!(Some((ResultCode::NOERROR, Vec::new())), null_mx);
+        assert_eq!(Some((ResultCode::NXDOMAIN, Vec::new())), nxdomain);
+        assert_eq!(Some((ResultCode::NOERROR, Vec::new())), nodata);
+        assert_eq!(
+            Some((
+                ResultCode::NOERROR,
+                vec!["ads.example.com. 2 IN A 192.0.2.99".to_string()]
+            )),
+            sinkhole_a
+        );
+        assert_eq!(Some((ResultCode::NOERROR, Vec::new())), sinkhole_aaaa);
+        assert_eq!(None, allowed);
+    }
+}
@@ -1,12 +1,37 @@
+/// Large enough for the EDNS payload sizes we advertise and accept (RFC 6891), the plain DNS limit
+/// of 512 bytes is enforced when answering clients without EDNS
+pub const MAX_PACKET_SIZE: usize = 4096;
+
+/// The largest message there can be, as its length over TCP takes 16 bits (RFC 1035 §4.2.2)
+pub const MAX_MESSAGE_SIZE: usize = u16::MAX as usize;
+
+/// The longest name, in octets on the wire with its length octets and the root label (RFC 1035
+/// §2.3.4)
+pub const MAX_NAME_LEN: usize = 255;
+
+/// The error of reading or writing past the end of the buffer
+pub const END_OF_BUFFER: &str = "End of buffer";
+
 pub struct BytePacketBuffer {
-    pub buf: [u8; 512],
+    pub buf: Vec<u8>,
     pub pos: usize,
 }
 
+impl Default for BytePacketBuffer {
+    fn default() -> Self {
+        BytePacketBuffer::new()
+    }
+}
+
 impl BytePacketBuffer {
     pub fn new() -> Self {
+        BytePacketBuffer::with_size(MAX_PACKET_SIZE)
+    }
+
+    /// A buffer for messages of up to `size` bytes, such as those over TCP
+    pub fn with_size(size: usize) -> Self {
         BytePacketBuffer {
-            buf: [0; 512],
+            buf: vec![0; size],
             pos: 0,
         }
     }
@@ -32,8 +57,8 @@ impl BytePacketBuffer {
 
     /// Read a single byte and move the position one step forward
     fn read(&mut self) -> Result<u8, Box<dyn std::error::Error>> {
-        if self.pos >= 512 {
-            return Err("End of buffer".into());
+        if self.pos >= self.buf.len() {
+            return Err(END_OF_BUFFER.into());
         }
         let res = self.buf[self.pos];
         self.pos += 1;
@@ -43,8 +68,8 @@ impl BytePacketBuffer {
 
     /// Get a single byte, without changing the buffer position
     fn get(&self, pos: usize) -> Result<u8, Box<dyn std::error::Error>> {
-        if self.pos >= 512 {
-            return Err("End of buffer".into());
+        if pos >= self.buf.len() {
+            return Err(END_OF_BUFFER.into());
         }
 
         Ok(self.buf[pos])
@@ -52,28 +77,44 @@ impl BytePacketBuffer {
 
     /// Get a range of bytes
     pub fn get_range(&self, start: usize, len: usize) -> Result<&[u8], Box<dyn std::error::Error>> {
-        if start + len > 512 {
-            return Err("End of buffer".into());
+        if start + len > self.buf.len() {
+            return Err(END_OF_BUFFER.into());
         }
 
         Ok(&self.buf[start..start + len])
     }
 
+    /// Read a single byte, stepping one step forward
+    pub fn read_u8(&mut self) -> Result<u8, Box<dyn std::error::Error>> {
+        self.read()
+    }
+
     /// Read two bytes, stepping two steps forward
+    // NOTE: The `<< 0` shifts here and below are kept for symmetry with their neighbours
+    #[allow(clippy::identity_op)]
     pub fn read_u16(&mut self) -> Result<u16, Box<dyn std::error::Error>> {
         let res = ((self.read()? as u16) << 8) | ((self.read()? as u16) << 0);
 
-        return Ok(res);
+        Ok(res)
     }
 
     /// Read four bytes, stepping four steps forward
+    #[allow(clippy::identity_op)]
     pub fn read_u32(&mut self) -> Result<u32, Box<dyn std::error::Error>> {
         let res = ((self.read()? as u32) << 24)
             | ((self.read()? as u32) << 16)
             | ((self.read()? as u32) << 8)
             | ((self.read()? as u32) << 0);
 
-        return Ok(res);
+        Ok(res)
+    }
+
+    /// Read a range of bytes, stepping over them
+    pub fn read_bytes(&mut self, len: usize) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let res = self.get_range(self.pos, len)?.to_vec();
+        self.pos += len;
+
+        Ok(res)
     }
 
     /// Read a qname(domain name)
@@ -85,6 +126,7 @@ impl BytePacketBuffer {
         let mut jump_performed = 0;
 
         let mut delim = "";
+        let mut len = 1;
         loop {
             if jump_performed > max_jumps {
                 return Err(format!("Limit of {} jumps exceeded", max_jumps).into());
@@ -112,9 +154,19 @@ impl BytePacketBuffer {
                     break;
                 }
 
+                len += 1 + b as usize;
+                if len > MAX_NAME_LEN {
+                    return Err(format!("Name exceeds {} octets", MAX_NAME_LEN).into());
+                }
+
                 outstr.push_str(delim);
 
                 let str_buffer = self.get_range(pos, b as usize)?;
+                // NOTE: Names are kept as dotted strings, where a dot within a label would read
+                // back as two labels
+                if str_buffer.contains(&b'.') {
+                    return Err("Label contains a dot".into());
+                }
                 outstr.push_str(&String::from_utf8_lossy(str_buffer).to_lowercase());
 
                 delim = ".";
@@ -130,8 +182,8 @@ impl BytePacketBuffer {
     }
 
     fn write(&mut self, val: u8) -> Result<(), Box<dyn std::error::Error>> {
-        if self.pos >= 512 {
-            return Err("End of buffer".into());
+        if self.pos >= self.buf.len() {
+            return Err(END_OF_BUFFER.into());
         }
 
         self.buf[self.pos] = val;
@@ -153,6 +205,7 @@ impl BytePacketBuffer {
         Ok(())
     }
 
+    #[allow(clippy::identity_op)]
     pub fn write_u32(&mut self, val: u32) -> Result<(), Box<dyn std::error::Error>> {
         self.write(((val >> 24) & 0xFF) as u8)?;
         self.write(((val >> 16) & 0xFF) as u8)?;
@@ -162,8 +215,36 @@ impl BytePacketBuffer {
         Ok(())
     }
 
+    pub fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        for b in bytes {
+            self.write(*b)?;
+        }
+
+        Ok(())
+    }
+
     pub fn write_qname(&mut self, qname: &str) -> Result<(), Box<dyn std::error::Error>> {
-        for section in qname.split('.') {
+        // NOTE: The root name ("" or ".") has no sections, and a trailing dot ends the name
+        // without adding an empty section, both being terminated by the zero length label below
+        let qname = qname.strip_suffix('.').unwrap_or(qname);
+        let sections: Vec<&str> = match qname {
+            "" => Vec::new(),
+            _ => qname.split('.').collect(),
+        };
+        if sections.iter().any(|section| section.is_empty()) {
+            return Err(format!("Empty label in {}", qname).into());
+        }
+        if sections
+            .iter()
+            .map(|section| 1 + section.len())
+            .sum::<usize>()
+            + 1
+            > MAX_NAME_LEN
+        {
+            return Err(format!("Name exceeds {} octets", MAX_NAME_LEN).into());
+        }
+
+        for section in sections {
             let len = section.len();
             if len > 0x3f {
                 return Err("Single section exceeds 63 characters of length".into());
@@ -181,6 +262,9 @@ impl BytePacketBuffer {
     }
 
     pub fn set(&mut self, pos: usize, val: u8) -> Result<(), Box<dyn std::error::Error>> {
+        if pos >= self.buf.len() {
+            return Err(END_OF_BUFFER.into());
+        }
         self.buf[pos] = val;
 
         Ok(())
@@ -196,12 +280,14 @@ impl BytePacketBuffer {
 
 #[cfg(test)]
 mod tests {
-    use super::BytePacketBuffer;
+    use super::super::model::strategy::name;
+    use super::{BytePacketBuffer, MAX_NAME_LEN, MAX_PACKET_SIZE};
+    use proptest::prelude::*;
 
     #[test]
     fn new_ok_1() -> Result<(), Box<dyn std::error::Error>> {
         // arrange
-        let expected_buffer_len: usize = 512;
+        let expected_buffer_len: usize = MAX_PACKET_SIZE;
         let expected_pos: usize = 0;
 
         // act
@@ -277,7 +363,7 @@ mod tests {
     fn read_error() {
         // arrange
         let mut buffer = BytePacketBuffer::new();
-        buffer.seek(512).unwrap();
+        buffer.seek(MAX_PACKET_SIZE).unwrap();
 
         // act
         let actual = buffer.read();
@@ -308,15 +394,14 @@ mod tests {
     #[test]
     fn get_error() {
         // arrange
-        let mut buffer = BytePacketBuffer::new();
-        buffer.seek(512).unwrap();
+        let buffer = BytePacketBuffer::new();
 
         // act
-        let actual = buffer.get(10);
+        let actual = buffer.get(MAX_PACKET_SIZE);
 
         // assert
         match actual {
-            Ok(_) => (),
+            Ok(_) => panic!("expected an error"),
             Err(r) => {
                 assert_eq!(r.to_string(), "End of buffer".to_string());
             }
@@ -326,11 +411,11 @@ mod tests {
     #[test]
     fn get_range_ok_1() -> Result<(), Box<dyn std::error::Error>> {
         // arrange
-        let expected: [u8; 512] = [0; 512];
+        let expected: [u8; MAX_PACKET_SIZE] = [0; MAX_PACKET_SIZE];
         let buffer = BytePacketBuffer::new();
 
         // act
-        let actual = buffer.get_range(0, 512)?;
+        let actual = buffer.get_range(0, MAX_PACKET_SIZE)?;
 
         // assert
         assert_eq!(expected, actual);
@@ -357,7 +442,7 @@ mod tests {
         let buffer = BytePacketBuffer::new();
 
         // act
-        let actual = buffer.get_range(0, 513);
+        let actual = buffer.get_range(0, MAX_PACKET_SIZE + 1);
 
         // assert
         match actual {
@@ -374,7 +459,7 @@ mod tests {
         let buffer = BytePacketBuffer::new();
 
         // act
-        let actual = buffer.get_range(1, 512);
+        let actual = buffer.get_range(1, MAX_PACKET_SIZE);
 
         // assert
         match actual {
@@ -404,7 +489,7 @@ mod tests {
         // arrange
         let expected: u16 = 257;
         let mut buffer = BytePacketBuffer::new();
-        buffer.buf = [1; 512];
+        buffer.buf = vec![1; MAX_PACKET_SIZE];
 
         // act
         let actual = buffer.read_u16()?;
@@ -419,7 +504,7 @@ mod tests {
         // arrange
         let expected: u16 = 514;
         let mut buffer = BytePacketBuffer::new();
-        buffer.buf = [2; 512];
+        buffer.buf = vec![2; MAX_PACKET_SIZE];
 
         // act
         let actual = buffer.read_u16()?;
@@ -434,7 +519,7 @@ mod tests {
         // arrange
         let expected: u32 = 0;
         let mut buffer = BytePacketBuffer::new();
-        // buffer.buf = [0; 512];
+        // buffer.buf = vec![0; MAX_PACKET_SIZE];
 
         // act
         let actual = buffer.read_u32()?;
@@ -449,7 +534,7 @@ mod tests {
         // arrange
         let expected: u32 = 16843009;
         let mut buffer = BytePacketBuffer::new();
-        buffer.buf = [1; 512];
+        buffer.buf = vec![1; MAX_PACKET_SIZE];
 
         // act
         let actual = buffer.read_u32()?;
@@ -464,7 +549,7 @@ mod tests {
         // arrange
         let expected: u32 = 33686018;
         let mut buffer = BytePacketBuffer::new();
-        buffer.buf = [2; 512];
+        buffer.buf = vec![2; MAX_PACKET_SIZE];
 
         // act
         let actual = buffer.read_u32()?;
@@ -543,10 +628,10 @@ mod tests {
     fn write_write_qname_ok() -> Result<(), Box<dyn std::error::Error>> {
         // arrange
         let mut sut = BytePacketBuffer::new();
-        let mut input = "google.com.tw".to_string();
+        let input = "google.com.tw".to_string();
 
         // act
-        sut.write_qname(&mut input)?;
+        sut.write_qname(&input)?;
 
         // assert
         println!("{:?}", sut.buf);
@@ -570,6 +655,24 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn read_qname_error() {
+        // arrange
+        let mut sut = BytePacketBuffer::new();
+        sut.buf[..6].copy_from_slice(&[3, b'a', b'.', b'b', 0xc0, 0x04]);
+        let mut dotted = String::new();
+        let mut looping = String::new();
+
+        // act
+        let actual = sut.read_qname(&mut dotted);
+        sut.seek(4).unwrap();
+        let looped = sut.read_qname(&mut looping);
+
+        // assert
+        assert_eq!("Label contains a dot", actual.unwrap_err().to_string());
+        assert_eq!("Limit of 5 jumps exceeded", looped.unwrap_err().to_string());
+    }
+
     #[test]
     fn set_ok() -> Result<(), Box<dyn std::error::Error>> {
         // arrange
@@ -579,11 +682,26 @@ mod tests {
         sut.set(0, 1)?;
 
         // assert
-        sut.buf[0] = 1;
+        assert_eq!(sut.buf[0], 1);
 
         Ok(())
     }
 
+    #[test]
+    fn set_error() {
+        // arrange
+        let mut sut = BytePacketBuffer::new();
+
+        // act
+        let actual = sut.set_u16(MAX_PACKET_SIZE - 1, 0xFFFF);
+
+        // assert
+        match actual {
+            Ok(_) => panic!("expected an error"),
+            Err(r) => assert_eq!(r.to_string(), "End of buffer".to_string()),
+        }
+    }
+
     #[test]
     fn set_u16_ok() -> Result<(), Box<dyn std::error::Error>> {
         // arrange
@@ -593,9 +711,103 @@ mod tests {
         sut.set_u16(0, 0xFFFF)?;
 
         // assert
-        sut.buf[0] = 255;
-        sut.buf[1] = 255;
+        assert_eq!(sut.buf[0], 255);
+        assert_eq!(sut.buf[1], 255);
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_qname_root_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let mut sut = BytePacketBuffer::new();
+
+        // act
+        sut.write_qname("")?;
+        sut.write_qname(".")?;
+        sut.write_qname("com.")?;
+
+        // assert
+        assert_eq!([0, 0, 3, b'c', b'o', b'm', 0], sut.buf[..7]);
 
         Ok(())
     }
+
+    #[test]
+    fn write_qname_empty_label_error() {
+        // arrange
+        let mut sut = BytePacketBuffer::new();
+
+        // act
+        let actual = ["a..b", ".com", "com..", ".."].map(|qname| sut.write_qname(qname));
+
+        // assert
+        assert!(actual.iter().all(|result| result.is_err()));
+    }
+
+    #[test]
+    fn write_qname_error() {
+        // arrange
+        let mut sut = BytePacketBuffer::new();
+        let longest_label = "a".repeat(63);
+        let longest_name = [longest_label.as_str(); 3].join(".") + "." + &"a".repeat(61);
+
+        // act
+        let actual = [
+            sut.write_qname(&longest_label),
+            sut.write_qname(&"a".repeat(64)),
+            sut.write_qname(&longest_name),
+            sut.write_qname(&(longest_name.clone() + "a")),
+        ];
+
+        // assert
+        assert!(actual[0].is_ok());
+        assert_eq!(
+            "Single section exceeds 63 characters of length",
+            actual[1].as_ref().unwrap_err().to_string()
+        );
+        assert!(actual[2].is_ok());
+        assert_eq!(
+            format!("Name exceeds {} octets", MAX_NAME_LEN),
+            actual[3].as_ref().unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn read_qname_too_long_error() {
+        // arrange
+        let mut sut = BytePacketBuffer::new();
+        // NOTE: 4 labels of 63 octets, 257 octets with the root label
+        for i in 0..4 {
+            sut.buf[i * 64] = 63;
+            sut.buf[i * 64 + 1..(i + 1) * 64].fill(b'a');
+        }
+        let mut name = String::new();
+
+        // act
+        let actual = sut.read_qname(&mut name);
+
+        // assert
+        assert_eq!(
+            format!("Name exceeds {} octets", MAX_NAME_LEN),
+            actual.unwrap_err().to_string()
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn qname_round_trip_ok(expected in name()) {
+            // arrange
+            let mut sut = BytePacketBuffer::new();
+            let mut actual = String::new();
+
+            // act
+            sut.write_qname(&expected).unwrap();
+            sut.seek(0).unwrap();
+            sut.read_qname(&mut actual).unwrap();
+
+            // assert
+            prop_assert_eq!(expected, actual);
+        }
+    }
 }
@@ -0,0 +1,971 @@
+use super::blocklist::BlockResponse;
+use super::dissect::InputFormat;
+use super::dnssec::{crypto, zone_signer::Denial};
+use super::encoding::from_hex;
+use super::model::{dns_class::DnsClass, dns_question::DnsQuestion, query_type::QueryType};
+use super::query::ROOT_SERVER;
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::Duration;
+
+/// Settings of the DNS server, read from the command line
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    /// UDP port the server listens on
+    pub port: u16,
+    /// Text answered to CHAOS class `version.bind` and `version.server` queries, if any
+    pub chaos_version: Option<String>,
+    /// Text answered to CHAOS class `id.server` and `hostname.bind` queries, if any
+    pub chaos_id: Option<String>,
+    /// Validate answers with DNSSEC
+    pub dnssec_validation: bool,
+    /// File holding the trust anchors of the root zone, instead of the built-in ones
+    pub trust_anchor_file: Option<String>,
+    /// File keeping the trust anchors up to date (RFC 5011), seeded from the trust anchor file
+    pub anchor_store: Option<String>,
+    /// Zones answered with authority, as their origin and zone file
+    pub zones: Vec<(String, String)>,
+    /// Keys signing the zones online, as the origin and the path of the BIND key files
+    pub zone_keys: Vec<(String, String)>,
+    /// Deny existence with NSEC3 instead of NSEC in the signed zones
+    pub nsec3: bool,
+    /// Log each response as a JSON message (RFC 8427) on its own line, instead of text
+    pub json_log: bool,
+    /// Root servers recursion starts from, the next one being tried when one fails
+    pub root_hints: Vec<Ipv4Addr>,
+    /// Port the servers asked during recursion listen on
+    pub upstream_port: u16,
+    /// How long to wait for each server asked during recursion
+    pub upstream_timeout: Duration,
+    /// Files in the `/etc/hosts` format whose names are answered locally, read again on change
+    pub hosts_files: Vec<String>,
+    /// Records answered locally, in zone file syntax with absolute owner names
+    pub local_records: Vec<String>,
+    /// TTL of the names answered locally, 0 by default as with dnsmasq so changes show at once
+    pub local_ttl: u32,
+    /// Lists of names answered with the block response instead of recursing for them, as hosts
+    /// files, domain lists or Adblock filters
+    pub blocklists: Vec<String>,
+    /// Lists of names never blocked, in the same formats
+    pub allowlists: Vec<String>,
+    /// How blocked names are answered
+    pub block_response: BlockResponse,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            port: 2054,
+            chaos_version: Some(format!("DNS-in-Rust {}", env!("CARGO_PKG_VERSION"))),
+            chaos_id: None,
+            dnssec_validation: false,
+            trust_anchor_file: None,
+            anchor_store: None,
+            zones: Vec::new(),
+            zone_keys: Vec::new(),
+            nsec3: false,
+            json_log: false,
+            root_hints: vec![ROOT_SERVER],
+            upstream_port: 53,
+            upstream_timeout: Duration::from_secs(5),
+            hosts_files: Vec::new(),
+            local_records: Vec::new(),
+            local_ttl: 0,
+            blocklists: Vec::new(),
+            allowlists: Vec::new(),
+            block_response: BlockResponse::Null,
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Build the settings from command line arguments (without the program name), e.g.
+    /// `--port 2054 --chaos-version "my resolver" --chaos-id ns1 --dnssec-validation
+    /// --zone example.com=example.com.zone --zone-key example.com=Kexample.com.+013+12345`.
+    /// Recursion starts from the root servers given with `--root-hint`, if any.
+    pub fn from_args<I>(args: I) -> Result<Self, Box<dyn std::error::Error>>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut config = ServerConfig::default();
+        let mut root_hints = Vec::new();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("Missing value for {}", arg))
+            };
+
+            match arg.as_str() {
+                "--port" => config.port = value()?.parse()?,
+                "--chaos-version" => config.chaos_version = Some(value()?),
+                "--chaos-id" => config.chaos_id = Some(value()?),
+                // NOTE: Stop revealing the version, `version.bind` queries are refused
+                "--no-chaos-version" => config.chaos_version = None,
+                "--dnssec-validation" => config.dnssec_validation = true,
+                // NOTE: Giving trust anchors implies validating with them
+                "--trust-anchor" => {
+                    config.trust_anchor_file = Some(value()?);
+                    config.dnssec_validation = true;
+                }
+                "--anchor-store" => {
+                    config.anchor_store = Some(value()?);
+                    config.dnssec_validation = true;
+                }
+                "--zone" => config.zones.push(origin_and_path(&value()?)?),
+                "--zone-key" => config.zone_keys.push(origin_and_path(&value()?)?),
+                "--nsec3" => config.nsec3 = true,
+                "--json" if cfg!(feature = "json") => config.json_log = true,
+                "--json" => return Err("--json needs the json feature".into()),
+                "--root-hint" => root_hints.push(value()?.parse()?),
+                "--upstream-port" => config.upstream_port = value()?.parse()?,
+                "--upstream-timeout" => {
+                    config.upstream_timeout = Duration::try_from_secs_f64(value()?.parse()?)?
+                }
+                "--hosts-file" => config.hosts_files.push(value()?),
+                "--local-record" => config.local_records.push(value()?),
+                "--local-ttl" => config.local_ttl = value()?.parse()?,
+                "--blocklist" => config.blocklists.push(value()?),
+                "--allowlist" => config.allowlists.push(value()?),
+                "--block-response" => config.block_response = block_response(&value()?)?,
+                _ => return Err(format!("Unknown argument: {}", arg).into()),
+            }
+        }
+        if !root_hints.is_empty() {
+            config.root_hints = root_hints;
+        }
+
+        Ok(config)
+    }
+}
+
+/// Settings of the `sign-zone` command, signing a zone file offline
+#[derive(Debug, Clone)]
+pub struct SignZoneConfig {
+    pub origin: String,
+    pub zone_file: String,
+    /// Paths of the BIND key files signing the zone
+    pub keys: Vec<String>,
+    pub denial: Denial,
+    /// Days the signatures stay valid
+    pub validity: u32,
+    /// File the signed zone is written to, `<zone file>.signed` by default
+    pub output: String,
+}
+
+impl SignZoneConfig {
+    /// Build the settings from the command arguments, e.g.
+    /// `--nsec3 --salt aabbccdd --opt-out example.com example.com.zone Kexample.com.+013+12345`
+    pub fn from_args<I>(args: I) -> Result<Self, Box<dyn std::error::Error>>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut positional = Vec::new();
+        let mut nsec3 = false;
+        let mut salt = Vec::new();
+        let mut iterations = 0;
+        let mut opt_out = false;
+        let mut validity = 30;
+        let mut output = None;
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("Missing value for {}", arg))
+            };
+
+            match arg.as_str() {
+                "--nsec3" => nsec3 = true,
+                // NOTE: The NSEC3 options imply NSEC3
+                "--salt" => {
+                    salt = match value()?.as_str() {
+                        "-" => Vec::new(),
+                        text => from_hex(text)?,
+                    };
+                    nsec3 = true;
+                }
+                "--iterations" => {
+                    iterations = value()?.parse()?;
+                    nsec3 = true;
+                }
+                "--opt-out" => {
+                    opt_out = true;
+                    nsec3 = true;
+                }
+                "--validity" => validity = value()?.parse()?,
+                "--output" => output = Some(value()?),
+                _ if arg.starts_with("--") => {
+                    return Err(format!("Unknown argument: {}", arg).into())
+                }
+                _ => positional.push(arg),
+            }
+        }
+
+        let mut positional = positional.into_iter();
+        let (Some(origin), Some(zone_file)) = (positional.next(), positional.next()) else {
+            return Err("Usage: sign-zone [options] <origin> <zone file> <key>...".into());
+        };
+        let keys: Vec<String> = positional.collect();
+        if keys.is_empty() {
+            return Err("Missing the keys to sign the zone with".into());
+        }
+
+        let denial = if nsec3 {
+            Denial::Nsec3 {
+                salt,
+                iterations,
+                opt_out,
+            }
+        } else {
+            Denial::Nsec
+        };
+
+        Ok(SignZoneConfig {
+            output: output.unwrap_or_else(|| format!("{}.signed", zone_file)),
+            origin,
+            zone_file,
+            keys,
+            denial,
+            validity,
+        })
+    }
+}
+
+/// Settings of the `keygen` command, generating a key pair for a zone
+#[derive(Debug, Clone)]
+pub struct KeygenConfig {
+    pub zone: String,
+    pub algorithm: u8,
+    /// Generate a key signing key, with the SEP flag, instead of a zone signing key
+    pub ksk: bool,
+    /// Size of RSA keys
+    pub bits: usize,
+    /// Directory the key files are written to
+    pub directory: String,
+}
+
+impl KeygenConfig {
+    /// Build the settings from the command arguments, e.g.
+    /// `--algorithm ECDSAP256SHA256 --ksk example.com`
+    pub fn from_args<I>(args: I) -> Result<Self, Box<dyn std::error::Error>>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut config = KeygenConfig {
+            zone: String::new(),
+            algorithm: crypto::ECDSAP256SHA256,
+            ksk: false,
+            bits: 2048,
+            directory: ".".to_string(),
+        };
+        let mut zone = None;
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("Missing value for {}", arg))
+            };
+
+            match arg.as_str() {
+                "--algorithm" => config.algorithm = algorithm(&value()?)?,
+                "--ksk" => config.ksk = true,
+                "--bits" => config.bits = value()?.parse()?,
+                "--directory" => config.directory = value()?,
+                _ if arg.starts_with("--") => {
+                    return Err(format!("Unknown argument: {}", arg).into())
+                }
+                _ if zone.is_none() => zone = Some(arg),
+                _ => return Err(format!("Unexpected argument: {}", arg).into()),
+            }
+        }
+
+        config.zone = zone.ok_or("Usage: keygen [options] <zone>")?;
+        Ok(config)
+    }
+}
+
+/// Settings of the `dissect` command, showing a message as an annotated hexdump
+#[derive(Debug, Clone)]
+pub struct DissectConfig {
+    /// File holding the message, `-` for the standard input
+    pub file: String,
+    /// How the message is written, detected when not given
+    pub format: Option<InputFormat>,
+}
+
+impl DissectConfig {
+    /// Build the settings from the command arguments, e.g. `--hex message.txt`
+    pub fn from_args<I>(args: I) -> Result<Self, Box<dyn std::error::Error>>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut format = None;
+        let mut file = None;
+
+        for arg in args {
+            match arg.as_str() {
+                "--hex" => format = Some(InputFormat::Hex),
+                "--base64" => format = Some(InputFormat::Base64),
+                "--binary" => format = Some(InputFormat::Binary),
+                _ if arg.starts_with("--") => {
+                    return Err(format!("Unknown argument: {}", arg).into())
+                }
+                _ if file.is_none() => file = Some(arg),
+                _ => return Err(format!("Unexpected argument: {}", arg).into()),
+            }
+        }
+
+        Ok(DissectConfig {
+            file: file.unwrap_or_else(|| "-".to_string()),
+            format,
+        })
+    }
+}
+
+/// Settings of the `pcap` and `replay` commands, reading the DNS messages of a capture
+#[derive(Debug, Clone)]
+pub struct CaptureConfig {
+    /// pcap or pcapng file
+    pub file: String,
+    /// Port DNS is served on in the capture
+    pub dns_port: u16,
+    /// Count the messages instead of showing each of them
+    pub summary: bool,
+    /// Server the queries are replayed against, the local DNS by default
+    pub server: Ipv4Addr,
+    pub port: u16,
+    /// Replay the queries at the pace they were captured, rather than as fast as answered
+    pub paced: bool,
+}
+
+impl CaptureConfig {
+    /// Build the settings from the command arguments, e.g. `--server 127.0.0.1 dns.pcap`
+    pub fn from_args<I>(args: I) -> Result<Self, Box<dyn std::error::Error>>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut config = CaptureConfig {
+            file: String::new(),
+            dns_port: 53,
+            summary: false,
+            server: Ipv4Addr::LOCALHOST,
+            port: 2054,
+            paced: true,
+        };
+        let mut file = None;
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("Missing value for {}", arg))
+            };
+
+            match arg.as_str() {
+                "--dns-port" => config.dns_port = value()?.parse()?,
+                "--summary" => config.summary = true,
+                "--server" => config.server = value()?.parse()?,
+                "--port" => config.port = value()?.parse()?,
+                "--no-pace" => config.paced = false,
+                _ if arg.starts_with("--") => {
+                    return Err(format!("Unknown argument: {}", arg).into())
+                }
+                _ if file.is_none() => file = Some(arg),
+                _ => return Err(format!("Unexpected argument: {}", arg).into()),
+            }
+        }
+
+        config.file = file.ok_or("Missing the capture file")?;
+        Ok(config)
+    }
+}
+
+/// Settings of the load generator, read from a dnsperf-like command line
+#[derive(Debug, Clone)]
+pub struct PerfConfig {
+    /// Data file with a `name type` line per query, `-` for the standard input
+    pub file: String,
+    /// Server loaded, the local DNS by default
+    pub server: Ipv4Addr,
+    pub port: u16,
+    /// Query over TCP instead of UDP
+    pub tcp: bool,
+    /// Queries sent per second, as fast as possible if not given
+    pub rate: Option<u32>,
+    /// How long queries are sent for, going over the data file again as needed, instead of once
+    /// through the file
+    pub limit: Option<Duration>,
+    /// How long a response is awaited before the query is counted as lost
+    pub timeout: Duration,
+}
+
+impl Default for PerfConfig {
+    fn default() -> Self {
+        PerfConfig {
+            file: "-".to_string(),
+            server: Ipv4Addr::LOCALHOST,
+            port: 2054,
+            tcp: false,
+            rate: None,
+            limit: None,
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+impl PerfConfig {
+    /// Build the settings from the command arguments, e.g. `-d queries.txt -Q 1000 -l 30`
+    pub fn from_args<I>(args: I) -> Result<Self, Box<dyn std::error::Error>>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut config = PerfConfig::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("Missing value for {}", arg))
+            };
+
+            match arg.as_str() {
+                "-d" => config.file = value()?,
+                "-s" => config.server = value()?.parse()?,
+                "-p" => config.port = value()?.parse()?,
+                "-m" => {
+                    config.tcp = match value()?.as_str() {
+                        "udp" => false,
+                        "tcp" => true,
+                        mode => return Err(format!("Unsupported mode {}", mode).into()),
+                    }
+                }
+                "-Q" => config.rate = Some(value()?.parse()?),
+                "-l" => config.limit = Some(Duration::try_from_secs_f64(value()?.parse()?)?),
+                "-t" => config.timeout = Duration::try_from_secs_f64(value()?.parse()?)?,
+                _ => return Err(format!("Unknown argument: {}", arg).into()),
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+/// Settings of the client, read from a dig-like command line
+#[derive(Debug, Clone)]
+pub struct ClientConfig {
+    /// Server asked, the local DNS by default
+    pub server: Ipv4Addr,
+    pub port: u16,
+    pub question: DnsQuestion,
+    /// Query over TCP instead of UDP
+    pub tcp: bool,
+    /// Ask the server to recurse
+    pub recurse: bool,
+    /// Ask for DNSSEC records
+    pub dnssec: bool,
+    /// Resolve iteratively from the root server, showing each referral
+    pub trace: bool,
+    /// Show only the data of the answers
+    pub short: bool,
+}
+
+impl ClientConfig {
+    /// Build the settings from the command arguments, e.g.
+    /// `@127.0.0.1 -p 2054 example.com AAAA +dnssec`
+    pub fn from_args<I>(args: I) -> Result<Self, Box<dyn std::error::Error>>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut config = ClientConfig {
+            server: Ipv4Addr::LOCALHOST,
+            port: 2054,
+            question: DnsQuestion::new(String::new(), QueryType::A),
+            tcp: false,
+            recurse: true,
+            dnssec: false,
+            trace: false,
+            short: false,
+        };
+        let mut name = None;
+        let mut qtype = None;
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            if let Some(server) = arg.strip_prefix('@') {
+                config.server = server
+                    .parse()
+                    .map_err(|_| format!("Invalid server address: {}", server))?;
+                continue;
+            }
+
+            match arg.as_str() {
+                "-p" => {
+                    config.port = args
+                        .next()
+                        .ok_or_else(|| format!("Missing value for {}", arg))?
+                        .parse()?
+                }
+                "+tcp" => config.tcp = true,
+                "+notcp" => config.tcp = false,
+                "+recurse" => config.recurse = true,
+                "+norecurse" => config.recurse = false,
+                "+dnssec" => config.dnssec = true,
+                "+nodnssec" => config.dnssec = false,
+                "+trace" => config.trace = true,
+                "+notrace" => config.trace = false,
+                "+short" => config.short = true,
+                "+noshort" => config.short = false,
+                _ if arg.starts_with('+') || arg.starts_with('-') => {
+                    return Err(format!("Unknown argument: {}", arg).into())
+                }
+                // NOTE: As with dig, a type or class after the name is taken as such
+                _ if name.is_some() && qtype.is_none() && QueryType::from_name(&arg).is_some() => {
+                    qtype = QueryType::from_name(&arg)
+                }
+                _ if name.is_some() && DnsClass::from_name(&arg).is_some() => {
+                    config.question.qclass = DnsClass::from_name(&arg).unwrap_or(DnsClass::IN)
+                }
+                _ if name.is_none() => name = Some(arg),
+                _ => return Err(format!("Unexpected argument: {}", arg).into()),
+            }
+        }
+
+        // NOTE: Without a name, the root name servers are asked for
+        match name {
+            Some(name) => {
+                config.question.name = name.trim_end_matches('.').to_string();
+                config.question.qtype = qtype.unwrap_or(QueryType::A);
+            }
+            None => config.question.qtype = qtype.unwrap_or(QueryType::NS),
+        }
+        Ok(config)
+    }
+}
+
+/// An algorithm given by number or mnemonic, such as `13` or `ECDSAP256SHA256`
+fn algorithm(value: &str) -> Result<u8, Box<dyn std::error::Error>> {
+    let algorithm = value.parse().ok().or_else(|| {
+        [
+            crypto::RSASHA256,
+            crypto::ECDSAP256SHA256,
+            crypto::ECDSAP384SHA384,
+            crypto::ED25519,
+        ]
+        .into_iter()
+        .find(|algorithm| {
+            crypto::algorithm_name(*algorithm).is_some_and(|name| name.eq_ignore_ascii_case(value))
+        })
+    });
+
+    match algorithm {
+        Some(algorithm) if crypto::algorithm_name(algorithm).is_some() => Ok(algorithm),
+        _ => Err(format!("Unsupported algorithm {}", value).into()),
+    }
+}
+
+/// Split an `<origin>=<path>` value
+fn origin_and_path(value: &str) -> Result<(String, String), Box<dyn std::error::Error>> {
+    match value.split_once('=') {
+        Some((origin, path)) if !path.is_empty() => Ok((origin.to_string(), path.to_string())),
+        _ => Err(format!("Expected <origin>=<path>, got {}", value).into()),
+    }
+}
+
+/// The block response of `nxdomain`, `nodata`, `null` or a sinkhole address
+fn block_response(value: &str) -> Result<BlockResponse, Box<dyn std::error::Error>> {
+    match value.to_lowercase().as_str() {
+        "nxdomain" => Ok(BlockResponse::Nxdomain),
+        "nodata" => Ok(BlockResponse::Nodata),
+        "null" => Ok(BlockResponse::Null),
+        _ => match value.parse::<IpAddr>() {
+            Ok(addr) => Ok(BlockResponse::Sinkhole(addr)),
+            Err(_) => Err(format!(
+                "Expected nxdomain, nodata, null or an address, got {}",
+                value
+            )
+            .into()),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        BlockResponse, CaptureConfig, ClientConfig, Denial, DissectConfig, DnsClass, Duration,
+        InputFormat, Ipv4Addr, KeygenConfig, PerfConfig, QueryType, ServerConfig, SignZoneConfig,
+        ROOT_SERVER,
+    };
+
+    #[test]
+    fn from_args_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let args = ["--port", "5353", "--chaos-id", "ns1.example.com"].map(String::from);
+
+        // act
+        let actual = ServerConfig::from_args(args)?;
+
+        // assert
+        assert_eq!(5353, actual.port);
+        assert_eq!(Some("ns1.example.com".to_string()), actual.chaos_id);
+        assert!(actual.chaos_version.is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    fn from_args_upstream_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let args = [
+            "--root-hint",
+            "127.0.0.2",
+            "--root-hint",
+            "127.0.0.3",
+            "--upstream-port",
+            "5300",
+            "--upstream-timeout",
+            "0.5",
+        ]
+        .map(String::from);
+
+        // act
+        let actual = ServerConfig::from_args(args)?;
+
+        // assert
+        assert_eq!(
+            vec![Ipv4Addr::new(127, 0, 0, 2), Ipv4Addr::new(127, 0, 0, 3)],
+            actual.root_hints
+        );
+        assert_eq!(5300, actual.upstream_port);
+        assert_eq!(Duration::from_millis(500), actual.upstream_timeout);
+        assert_eq!(vec![ROOT_SERVER], ServerConfig::default().root_hints);
+        assert!(ServerConfig::from_args(["--root-hint", "a.root"].map(String::from)).is_err());
+        for timeout in ["-1", "nan", "inf"] {
+            let args = ["--upstream-timeout", timeout].map(String::from);
+            assert!(ServerConfig::from_args(args).is_err());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn from_args_hosts_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let args = [
+            "--hosts-file",
+            "/etc/hosts",
+            "--hosts-file",
+            "dev.hosts",
+            "--local-record",
+            "db.internal. A 10.0.0.5",
+            "--local-ttl",
+            "60",
+        ]
+        .map(String::from);
+
+        // act
+        let actual = ServerConfig::from_args(args)?;
+
+        // assert
+        assert_eq!(vec!["/etc/hosts", "dev.hosts"], actual.hosts_files);
+        assert_eq!(vec!["db.internal. A 10.0.0.5"], actual.local_records);
+        assert_eq!(60, actual.local_ttl);
+        assert_eq!(0, ServerConfig::default().local_ttl);
+
+        Ok(())
+    }
+
+    #[test]
+    fn from_args_blocklist_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let args = [
+            "--blocklist",
+            "ads.txt",
+            "--blocklist",
+            "easylist.txt",
+            "--allowlist",
+            "allow.txt",
+            "--block-response",
+            "192.0.2.1",
+        ]
+        .map(String::from);
+
+        // act
+        let actual = ServerConfig::from_args(args)?;
+
+        // assert
+        assert_eq!(vec!["ads.txt", "easylist.txt"], actual.blocklists);
+        assert_eq!(vec!["allow.txt"], actual.allowlists);
+        assert_eq!(
+            BlockResponse::Sinkhole(Ipv4Addr::new(192, 0, 2, 1).into()),
+            actual.block_response
+        );
+        assert_eq!(BlockResponse::Null, ServerConfig::default().block_response);
+        for (value, expected) in [
+            ("NXDOMAIN", BlockResponse::Nxdomain),
+            ("nodata", BlockResponse::Nodata),
+        ] {
+            let args = ["--block-response", value].map(String::from);
+            assert_eq!(expected, ServerConfig::from_args(args)?.block_response);
+        }
+        assert!(
+            ServerConfig::from_args(["--block-response", "refused"].map(String::from)).is_err()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn from_args_dnssec_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let args = ["--trust-anchor", "root.key", "--anchor-store", "anchors.db"].map(String::from);
+
+        // act
+        let actual = ServerConfig::from_args(args)?;
+
+        // assert
+        assert!(actual.dnssec_validation);
+        assert_eq!(Some("root.key".to_string()), actual.trust_anchor_file);
+        assert_eq!(Some("anchors.db".to_string()), actual.anchor_store);
+        assert!(!ServerConfig::default().dnssec_validation);
+
+        Ok(())
+    }
+
+    #[test]
+    fn from_args_zones_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let args = [
+            "--zone",
+            "example.com=zones/example.com.zone",
+            "--zone-key",
+            "example.com=keys/Kexample.com.+013+12345",
+            "--nsec3",
+        ]
+        .map(String::from);
+
+        // act
+        let actual = ServerConfig::from_args(args)?;
+
+        // assert
+        assert_eq!(
+            vec![(
+                "example.com".to_string(),
+                "zones/example.com.zone".to_string()
+            )],
+            actual.zones
+        );
+        assert_eq!(
+            vec![(
+                "example.com".to_string(),
+                "keys/Kexample.com.+013+12345".to_string()
+            )],
+            actual.zone_keys
+        );
+        assert!(actual.nsec3);
+        assert!(ServerConfig::from_args(["--zone", "example.com"].map(String::from)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn from_args_json_ok() {
+        // arrange
+        let args = ["--json"].map(String::from);
+
+        // act
+        let actual = ServerConfig::from_args(args);
+
+        // assert
+        assert_eq!(
+            cfg!(feature = "json"),
+            actual.is_ok_and(|config| config.json_log)
+        );
+    }
+
+    #[test]
+    fn sign_zone_from_args_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let args = [
+            "--salt",
+            "aabb",
+            "--opt-out",
+            "example.com",
+            "db.example.com",
+            "Kexample.com.+013+12345",
+            "Kexample.com.+013+54321",
+        ]
+        .map(String::from);
+
+        // act
+        let actual = SignZoneConfig::from_args(args)?;
+
+        // assert
+        assert_eq!("example.com", actual.origin);
+        assert_eq!("db.example.com.signed", actual.output);
+        assert_eq!(2, actual.keys.len());
+        assert_eq!(
+            Denial::Nsec3 {
+                salt: vec![0xaa, 0xbb],
+                iterations: 0,
+                opt_out: true
+            },
+            actual.denial
+        );
+        assert!(SignZoneConfig::from_args(["example.com", "db"].map(String::from)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn keygen_from_args_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let args = ["--algorithm", "ed25519", "--ksk", "example.com"].map(String::from);
+
+        // act
+        let actual = KeygenConfig::from_args(args)?;
+
+        // assert
+        assert_eq!("example.com", actual.zone);
+        assert_eq!(15, actual.algorithm);
+        assert!(actual.ksk);
+        assert!(KeygenConfig::from_args(["--algorithm", "5", "x"].map(String::from)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn capture_from_args_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let args = [
+            "--dns-port",
+            "5353",
+            "--server",
+            "192.0.2.1",
+            "--no-pace",
+            "dns.pcap",
+        ]
+        .map(String::from);
+
+        // act
+        let actual = CaptureConfig::from_args(args)?;
+
+        // assert
+        assert_eq!("dns.pcap", actual.file);
+        assert_eq!(5353, actual.dns_port);
+        assert_eq!(Ipv4Addr::new(192, 0, 2, 1), actual.server);
+        assert_eq!(2054, actual.port);
+        assert!(!actual.paced && !actual.summary);
+        assert!(CaptureConfig::from_args([]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn dissect_from_args_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let args = ["--base64", "message.txt"].map(String::from);
+
+        // act
+        let actual = DissectConfig::from_args(args)?;
+
+        // assert
+        assert_eq!("message.txt", actual.file);
+        assert_eq!(Some(InputFormat::Base64), actual.format);
+        assert_eq!("-", DissectConfig::from_args([])?.file);
+        assert!(DissectConfig::from_args(["a", "b"].map(String::from)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn perf_from_args_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let args = [
+            "-d",
+            "queries.txt",
+            "-s",
+            "192.0.2.1",
+            "-m",
+            "tcp",
+            "-Q",
+            "1000",
+            "-l",
+            "30",
+        ]
+        .map(String::from);
+
+        // act
+        let actual = PerfConfig::from_args(args)?;
+
+        // assert
+        assert_eq!("queries.txt", actual.file);
+        assert_eq!(Ipv4Addr::new(192, 0, 2, 1), actual.server);
+        assert_eq!(2054, actual.port);
+        assert!(actual.tcp);
+        assert_eq!(Some(1000), actual.rate);
+        assert_eq!(Some(Duration::from_secs(30)), actual.limit);
+        assert_eq!(Duration::from_secs(5), actual.timeout);
+        assert!(PerfConfig::from_args(["-m", "doh"].map(String::from)).is_err());
+        for (flag, secs) in [("-l", "-1"), ("-t", "nan"), ("-t", "inf")] {
+            assert!(PerfConfig::from_args([flag, secs].map(String::from)).is_err());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn client_from_args_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let args = [
+            "@8.8.8.8",
+            "-p",
+            "53",
+            "version.bind.",
+            "TXT",
+            "CH",
+            "+tcp",
+            "+norecurse",
+            "+short",
+        ]
+        .map(String::from);
+
+        // act
+        let actual = ClientConfig::from_args(args)?;
+
+        // assert
+        assert_eq!(Ipv4Addr::new(8, 8, 8, 8), actual.server);
+        assert_eq!(53, actual.port);
+        assert_eq!("version.bind", actual.question.name);
+        assert_eq!(QueryType::TXT, actual.question.qtype);
+        assert_eq!(DnsClass::CH, actual.question.qclass);
+        assert!(actual.tcp && !actual.recurse && actual.short);
+        assert!(!actual.dnssec && !actual.trace);
+
+        let actual = ClientConfig::from_args([])?;
+        assert_eq!(Ipv4Addr::LOCALHOST, actual.server);
+        assert_eq!(2054, actual.port);
+        assert_eq!("", actual.question.name);
+        assert_eq!(QueryType::NS, actual.question.qtype);
+        assert!(ClientConfig::from_args(["@example.com"].map(String::from)).is_err());
+        assert!(ClientConfig::from_args(["+nssearch"].map(String::from)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn from_args_error() {
+        // arrange
+        let args = ["--port"].map(String::from);
+
+        // act
+        let actual = ServerConfig::from_args(args);
+
+        // assert
+        match actual {
+            Ok(_) => panic!("expected an error"),
+            Err(r) => assert_eq!(r.to_string(), "Missing value for --port".to_string()),
+        }
+    }
+}
@@ -0,0 +1,752 @@
+//! Annotated hexdumps of DNS messages, naming the field each range of bytes belongs to
+
+use super::byte_packet_buffer::{BytePacketBuffer, MAX_PACKET_SIZE};
+use super::encoding::{from_base64, from_hex, to_base32hex, to_base64, to_hex, to_timestamp};
+use super::model::{
+    dns_class::DnsClass, dns_question::DnsQuestion, dns_record::DnsRecord, query_type::QueryType,
+    svc_param, type_bitmap,
+};
+use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+/// Bytes shown on each line of the hexdump
+const BYTES_PER_LINE: usize = 8;
+
+/// How a message to dissect is written
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputFormat {
+    Hex,
+    Base64,
+    Binary,
+}
+
+/// A range of bytes of the message and the field it holds. Fields without bytes head the
+/// header, the questions and the records.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub offset: usize,
+    pub len: usize,
+    pub name: String,
+    pub value: String,
+}
+
+/// The fields of a message, as far as it could be read
+#[derive(Debug, Clone)]
+pub struct Dissection {
+    pub message: Vec<u8>,
+    pub fields: Vec<Field>,
+    /// Why the rest of the message couldn't be read, if it couldn't
+    pub error: Option<String>,
+}
+
+/// Decode a message given as hexadecimal digits, base64 or raw bytes. Without a format, text of
+/// hexadecimal digits is taken as hex, other text as base64 if it decodes, and anything else as
+/// raw bytes.
+pub fn decode(
+    input: &[u8],
+    format: Option<InputFormat>,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let text = std::str::from_utf8(input).ok().map(|text| {
+        text.split_whitespace()
+            .flat_map(|word| word.strip_prefix("0x").unwrap_or(word).chars())
+            .collect::<String>()
+    });
+
+    match (format, text) {
+        (Some(InputFormat::Binary), _) => Ok(input.to_vec()),
+        (Some(InputFormat::Hex), Some(text)) => from_hex(&text),
+        (Some(InputFormat::Base64), Some(text)) => from_base64(&text),
+        (Some(_), None) => Err("The message is not text".into()),
+        (None, Some(text)) if !text.is_empty() => {
+            if text.chars().all(|c| c.is_ascii_hexdigit()) {
+                from_hex(&text)
+            } else {
+                Ok(from_base64(&text).unwrap_or_else(|_| input.to_vec()))
+            }
+        }
+        (None, _) => Ok(input.to_vec()),
+    }
+}
+
+/// Map every byte of the message to the field it belongs to
+pub fn dissect(message: &[u8]) -> Dissection {
+    let mut dissector = Dissector {
+        buffer: BytePacketBuffer::new(),
+        end: message.len().min(MAX_PACKET_SIZE),
+        fields: Vec::new(),
+    };
+    dissector.buffer.buf[..dissector.end].copy_from_slice(&message[..dissector.end]);
+
+    let mut error = dissector.message().err().map(|e| {
+        format!(
+            "{} at offset {}",
+            e,
+            dissector.fields.last().map_or(0, |f| f.offset + f.len)
+        )
+    });
+    // NOTE: The buffer holds the largest message there can be, the bytes beyond it are unread
+    if message.len() > MAX_PACKET_SIZE {
+        let too_large = format!(
+            "Message of {} bytes is too large, the {} bytes past offset {} weren't read",
+            message.len(),
+            message.len() - MAX_PACKET_SIZE,
+            MAX_PACKET_SIZE
+        );
+        error = Some(error.map_or(too_large.clone(), |e| format!("{}; {}", e, too_large)));
+    }
+
+    Dissection {
+        message: message.to_vec(),
+        fields: dissector.fields,
+        error,
+    }
+}
+
+/// Reads the message from a buffer, recording the offset and length of each field read
+struct Dissector {
+    buffer: BytePacketBuffer,
+    end: usize,
+    fields: Vec<Field>,
+}
+
+impl Dissector {
+    fn message(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.heading("header", |_| Ok(String::new()))?;
+        self.u16("id")?;
+        self.u16_with("flags", flags_text)?;
+        let mut counts = [0; 4];
+        for (count, name) in counts.iter_mut().zip([
+            "question count",
+            "answer count",
+            "authority count",
+            "additional count",
+        ]) {
+            *count = self.u16(name)?;
+        }
+
+        for index in 1..=counts[0] {
+            self.heading(&format!("question {}", index), |buffer| {
+                let mut question = DnsQuestion::new(String::new(), QueryType::UNKNOWN(0));
+                question.read(buffer)?;
+                Ok(question.to_string())
+            })?;
+            self.name("name")?;
+            self.qtype()?;
+            self.u16_with("class", |class| DnsClass::from_num(class).to_string())?;
+        }
+        for (section, count) in ["answer", "authority", "additional"]
+            .iter()
+            .zip(&counts[1..])
+        {
+            for index in 1..=*count {
+                self.record(&format!("{} {}", section, index))?;
+            }
+        }
+
+        if self.buffer.pos() < self.end {
+            let start = self.buffer.pos();
+            self.take(self.end - start)?;
+            self.field(
+                start,
+                "trailing data",
+                format!("{} bytes", self.end - start),
+            );
+        }
+
+        Ok(())
+    }
+
+    fn record(&mut self, heading: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.heading(heading, |buffer| Ok(DnsRecord::read(buffer)?.to_string()))?;
+
+        self.name("owner")?;
+        let qtype = self.qtype()?;
+        if qtype == QueryType::OPT {
+            self.u16("udp payload size")?;
+            self.u8("extended rcode")?;
+            self.u8("version")?;
+            self.u16_with("flags", |flags| {
+                if flags & 0x8000 != 0 { "do" } else { "" }.to_string()
+            })?;
+        } else {
+            self.u16_with("class", |class| DnsClass::from_num(class).to_string())?;
+            self.u32("ttl")?;
+        }
+        let len = self.u16("rdlength")? as usize;
+
+        let rdata_end = self.buffer.pos() + len;
+        if rdata_end > self.end {
+            return Err("RDATA past the end of the message".into());
+        }
+        self.rdata(qtype, rdata_end)?;
+        if self.buffer.pos() > rdata_end {
+            return Err("RDATA longer than its rdlength".into());
+        }
+        if self.buffer.pos() < rdata_end {
+            let start = self.buffer.pos();
+            self.take(rdata_end - start)?;
+            self.field(start, "rdata", to_hex(&self.buffer.buf[start..rdata_end]));
+        }
+
+        Ok(())
+    }
+
+    fn rdata(&mut self, qtype: QueryType, end: usize) -> Result<(), Box<dyn std::error::Error>> {
+        match qtype {
+            QueryType::A => {
+                let start = self.buffer.pos();
+                let addr = self.take(4)?;
+                let addr = Ipv4Addr::new(addr[0], addr[1], addr[2], addr[3]);
+                self.field(start, "address", addr.to_string());
+            }
+            QueryType::AAAA => {
+                let start = self.buffer.pos();
+                let addr: [u8; 16] = self.take(16)?.try_into()?;
+                self.field(start, "address", Ipv6Addr::from(addr).to_string());
+            }
+            QueryType::NS | QueryType::CNAME | QueryType::PTR => {
+                self.name("target")?;
+            }
+            QueryType::MX => {
+                self.u16("preference")?;
+                self.name("exchange")?;
+            }
+            QueryType::SOA => {
+                self.name("mname")?;
+                self.name("rname")?;
+                for name in ["serial", "refresh", "retry", "expire", "minimum"] {
+                    self.u32(name)?;
+                }
+            }
+            QueryType::TXT => {
+                while self.buffer.pos() < end {
+                    let len = self.u8("string length")? as usize;
+                    let start = self.buffer.pos();
+                    let text = String::from_utf8_lossy(self.take(len)?).into_owned();
+                    self.field(start, "string", format!("{:?}", text));
+                }
+            }
+            QueryType::SRV => {
+                for name in ["priority", "weight", "port"] {
+                    self.u16(name)?;
+                }
+                self.name("target")?;
+            }
+            QueryType::DS => {
+                self.u16("key tag")?;
+                self.u8("algorithm")?;
+                self.u8("digest type")?;
+                self.rest("digest", end, to_hex)?;
+            }
+            QueryType::DNSKEY => {
+                self.u16("flags")?;
+                self.u8("protocol")?;
+                self.u8("algorithm")?;
+                self.rest("public key", end, to_base64)?;
+            }
+            QueryType::RRSIG => {
+                self.qtype()?;
+                self.u8("algorithm")?;
+                self.u8("labels")?;
+                self.u32("original ttl")?;
+                self.u32_with("expiration", to_timestamp)?;
+                self.u32_with("inception", to_timestamp)?;
+                self.u16("key tag")?;
+                self.name("signer")?;
+                self.rest("signature", end, to_base64)?;
+            }
+            QueryType::OPT => {
+                while self.buffer.pos() < end {
+                    self.u16("option code")?;
+                    let len = self.u16("option length")? as usize;
+                    let start = self.buffer.pos();
+                    let data = to_hex(self.take(len)?);
+                    self.field(start, "option data", data);
+                }
+            }
+            QueryType::NSEC => {
+                self.name("next domain")?;
+                self.type_bitmap(end)?;
+            }
+            QueryType::NSEC3 | QueryType::NSEC3PARAM => {
+                self.u8("hash algorithm")?;
+                self.u8("flags")?;
+                self.u16("iterations")?;
+                let len = self.u8("salt length")? as usize;
+                self.bytes("salt", len, to_hex)?;
+                if qtype == QueryType::NSEC3 {
+                    let len = self.u8("hash length")? as usize;
+                    self.bytes("next hashed owner", len, to_base32hex)?;
+                    self.type_bitmap(end)?;
+                }
+            }
+            QueryType::SVCB | QueryType::HTTPS => {
+                self.u16("priority")?;
+                self.name("target")?;
+                while self.buffer.pos() < end {
+                    self.u16_with("key", svc_param::key_name)?;
+                    let len = self.u16("value length")? as usize;
+                    self.bytes("value", len, to_hex)?;
+                }
+            }
+            QueryType::CAA => {
+                self.u8("flags")?;
+                let len = self.u8("tag length")? as usize;
+                self.bytes("tag", len, |tag| String::from_utf8_lossy(tag).into_owned())?;
+                self.rest("value", end, |value| {
+                    format!("{:?}", String::from_utf8_lossy(value))
+                })?;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// The windows of the type bitmap of NSEC and NSEC3 records, up to `end`
+    fn type_bitmap(&mut self, end: usize) -> Result<(), Box<dyn std::error::Error>> {
+        while self.buffer.pos() < end {
+            let start = self.buffer.pos();
+            self.u8("window")?;
+            let len = self.u8("bitmap length")? as usize;
+            self.bytes("bitmap", len, to_hex)?;
+
+            let window = &self.buffer.buf[start..self.buffer.pos()];
+            if let (Ok(types), Some(bitmap)) = (type_bitmap::decode(window), self.fields.last_mut())
+            {
+                let types: Vec<String> = types.iter().map(|qtype| qtype.to_string()).collect();
+                bitmap.value = types.join(" ");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Read a name label by label, following a compression pointer to the rest of the name
+    fn name(&mut self, name: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let mut labels = Vec::new();
+
+        loop {
+            let start = self.buffer.pos();
+            let len = self.take(1)?[0];
+            match len {
+                0 => {
+                    self.field(start, name, format!("{}.", labels.join(".")));
+                    break;
+                }
+                _ if len & 0xC0 == 0xC0 => {
+                    let low = self.take(1)?[0];
+                    let target = (((len & 0x3F) as usize) << 8) | low as usize;
+                    // NOTE: Pointers only point backwards, which also rules out loops
+                    if target >= start {
+                        return Err(format!(
+                            "Compression pointer to {} doesn't point back",
+                            target
+                        )
+                        .into());
+                    }
+
+                    let mut rest = String::new();
+                    self.buffer.seek(target)?;
+                    self.buffer.read_qname(&mut rest)?;
+                    self.buffer.seek(start + 2)?;
+                    if !rest.is_empty() {
+                        labels.push(rest.clone());
+                    }
+                    self.field(
+                        start,
+                        &format!("{} pointer", name),
+                        format!("to offset {} ({}.), {}.", target, rest, labels.join(".")),
+                    );
+                    break;
+                }
+                _ if len & 0xC0 != 0 => {
+                    return Err(format!("Unsupported label type {:#04x}", len).into());
+                }
+                _ => {
+                    self.field(start, &format!("{} label length", name), len.to_string());
+                    let start = self.buffer.pos();
+                    let label = String::from_utf8_lossy(self.take(len as usize)?).into_owned();
+                    self.field(start, &format!("{} label", name), label.clone());
+                    labels.push(label);
+                }
+            }
+        }
+
+        Ok(labels.join("."))
+    }
+
+    fn qtype(&mut self) -> Result<QueryType, Box<dyn std::error::Error>> {
+        let num = self.u16_with("type", |num| QueryType::from_num(num).to_string())?;
+
+        Ok(QueryType::from_num(num))
+    }
+
+    fn u8(&mut self, name: &str) -> Result<u8, Box<dyn std::error::Error>> {
+        let start = self.buffer.pos();
+        let value = self.take(1)?[0];
+        self.field(start, name, value.to_string());
+
+        Ok(value)
+    }
+
+    fn u16(&mut self, name: &str) -> Result<u16, Box<dyn std::error::Error>> {
+        self.u16_with(name, |value| value.to_string())
+    }
+
+    fn u16_with<F>(&mut self, name: &str, text: F) -> Result<u16, Box<dyn std::error::Error>>
+    where
+        F: Fn(u16) -> String,
+    {
+        let start = self.buffer.pos();
+        let bytes = self.take(2)?;
+        let value = u16::from_be_bytes([bytes[0], bytes[1]]);
+        self.field(start, name, text(value));
+
+        Ok(value)
+    }
+
+    fn u32(&mut self, name: &str) -> Result<u32, Box<dyn std::error::Error>> {
+        self.u32_with(name, |value| value.to_string())
+    }
+
+    fn u32_with<F>(&mut self, name: &str, text: F) -> Result<u32, Box<dyn std::error::Error>>
+    where
+        F: Fn(u32) -> String,
+    {
+        let start = self.buffer.pos();
+        let value = u32::from_be_bytes(self.take(4)?.try_into()?);
+        self.field(start, name, text(value));
+
+        Ok(value)
+    }
+
+    /// The bytes up to `end`, presented with `text`
+    fn rest(
+        &mut self,
+        name: &str,
+        end: usize,
+        text: fn(&[u8]) -> String,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.bytes(name, end.saturating_sub(self.buffer.pos()), text)
+    }
+
+    /// The next `len` bytes, presented with `text`. Without bytes there is no field, as those
+    /// head the fields that follow.
+    fn bytes(
+        &mut self,
+        name: &str,
+        len: usize,
+        text: fn(&[u8]) -> String,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let start = self.buffer.pos();
+        let value = text(self.take(len)?);
+        if len > 0 {
+            self.field(start, name, value);
+        }
+
+        Ok(())
+    }
+
+    /// Step over the next bytes of the message, which must all be within it
+    fn take(&mut self, len: usize) -> Result<&[u8], Box<dyn std::error::Error>> {
+        let start = self.buffer.pos();
+        if start + len > self.end {
+            return Err("Unexpected end of message".into());
+        }
+        self.buffer.step(len)?;
+
+        self.buffer.get_range(start, len)
+    }
+
+    fn field(&mut self, offset: usize, name: &str, value: String) {
+        self.fields.push(Field {
+            offset,
+            len: self.buffer.pos() - offset,
+            name: name.to_string(),
+            value,
+        });
+    }
+
+    /// Head the fields that follow with the text `read` makes of them, the way the rest of the
+    /// crate reads them
+    fn heading<F>(&mut self, name: &str, read: F) -> Result<(), Box<dyn std::error::Error>>
+    where
+        F: FnOnce(&mut BytePacketBuffer) -> Result<String, Box<dyn std::error::Error>>,
+    {
+        let offset = self.buffer.pos();
+        let value = match read(&mut self.buffer) {
+            Ok(_) if self.buffer.pos() > self.end => "unreadable".to_string(),
+            Ok(value) => value,
+            Err(e) => format!("unreadable, {}", e),
+        };
+        self.buffer.seek(offset)?;
+        self.fields.push(Field {
+            offset,
+            len: 0,
+            name: name.to_string(),
+            value,
+        });
+
+        Ok(())
+    }
+}
+
+/// The header flags, such as `qr opcode=QUERY rd ra rcode=NOERROR`
+fn flags_text(flags: u16) -> String {
+    let opcode = match (flags >> 11) & 0xF {
+        0 => "QUERY".to_string(),
+        1 => "IQUERY".to_string(),
+        2 => "STATUS".to_string(),
+        4 => "NOTIFY".to_string(),
+        5 => "UPDATE".to_string(),
+        x => x.to_string(),
+    };
+    let rcode = match flags & 0xF {
+        0 => "NOERROR".to_string(),
+        1 => "FORMERR".to_string(),
+        2 => "SERVFAIL".to_string(),
+        3 => "NXDOMAIN".to_string(),
+        4 => "NOTIMP".to_string(),
+        5 => "REFUSED".to_string(),
+        x => x.to_string(),
+    };
+
+    let mut words = Vec::new();
+    if flags & 0x8000 != 0 {
+        words.push("qr".to_string());
+    }
+    words.push(format!("opcode={}", opcode));
+    for (bit, name) in [
+        (10, "aa"),
+        (9, "tc"),
+        (8, "rd"),
+        (7, "ra"),
+        (6, "z"),
+        (5, "ad"),
+        (4, "cd"),
+    ] {
+        if flags & (1 << bit) != 0 {
+            words.push(name.to_string());
+        }
+    }
+    words.push(format!("rcode={}", rcode));
+
+    words.join(" ")
+}
+
+impl fmt::Display for Dissection {
+    /// A hexdump with a line per field, e.g. `0000  12 34    id: 4660`, long fields continuing
+    /// on the following lines
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for field in &self.fields {
+            if field.len == 0 {
+                if field.value.is_empty() {
+                    writeln!(f, ";; {}", field.name)?;
+                } else {
+                    writeln!(f, ";; {}: {}", field.name, field.value)?;
+                }
+                continue;
+            }
+
+            let bytes = &self.message[field.offset..field.offset + field.len];
+            for (i, chunk) in bytes.chunks(BYTES_PER_LINE).enumerate() {
+                let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+                write!(
+                    f,
+                    "{:04x}  {:<width$}",
+                    field.offset + i * BYTES_PER_LINE,
+                    hex.join(" "),
+                    width = BYTES_PER_LINE * 3 - 1
+                )?;
+                if i == 0 {
+                    write!(f, "  {}: {}", field.name, field.value)?;
+                }
+                writeln!(f)?;
+            }
+        }
+
+        if let Some(error) = &self.error {
+            writeln!(f, ";; error: {}", error)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::encoding::from_hex;
+    use super::super::model::dns_packet::DnsPacket;
+    use super::{
+        decode, dissect, BytePacketBuffer, DnsRecord, Field, InputFormat, MAX_PACKET_SIZE,
+    };
+
+    /// A response for `google.com A` with the answer owner compressed, and EDNS
+    const RESPONSE: &str = "\
+        12348180000100010000000106676f6f676c6503636f6d0000010001\
+        c00c000100010000012c00048efb2aee\
+        0000291000000080000000";
+
+    fn field(offset: usize, len: usize, name: &str, value: &str) -> Field {
+        Field {
+            offset,
+            len,
+            name: name.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn dissect_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let message = from_hex(RESPONSE)?;
+
+        // act
+        let actual = dissect(&message);
+
+        // assert
+        assert_eq!(None, actual.error);
+        let covered: usize = actual.fields.iter().map(|f| f.len).sum();
+        assert_eq!(message.len(), covered);
+        for expected in [
+            field(0, 2, "id", "4660"),
+            field(2, 2, "flags", "qr opcode=QUERY rd ra rcode=NOERROR"),
+            field(12, 0, "question 1", "google.com. IN A"),
+            field(12, 1, "name label length", "6"),
+            field(13, 6, "name label", "google"),
+            field(23, 1, "name", "google.com."),
+            field(28, 0, "answer 1", "google.com. 300 IN A 142.251.42.238"),
+            field(
+                28,
+                2,
+                "owner pointer",
+                "to offset 12 (google.com.), google.com.",
+            ),
+            field(38, 2, "rdlength", "4"),
+            field(40, 4, "address", "142.251.42.238"),
+            field(47, 2, "udp payload size", "4096"),
+            field(51, 2, "flags", "do"),
+        ] {
+            assert!(actual.fields.contains(&expected), "{:?}", expected);
+        }
+        let text = actual.to_string();
+        assert!(text.contains("\n;; answer 1: google.com. 300 IN A 142.251.42.238\n"));
+        assert!(text.contains("\n0028  8e fb 2a ee              address: 142.251.42.238\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn dissect_rdata_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let mut packet = DnsPacket::new();
+        for record in [
+            "example.test. 300 IN CAA 128 issue \"ca.test\"",
+            "example.test. 300 IN HTTPS 1 . alpn=h2 port=8443",
+            "example.test. 300 IN NSEC www.example.test. A NS CAA",
+            "example.test. 300 IN NSEC3 1 1 12 aabbccdd 2t7b4g4vsa5smi47k61mv5bv1a22bojr A RRSIG",
+            "example.test. 300 IN NSEC3PARAM 1 0 12 -",
+        ] {
+            packet.answers.push(record.parse::<DnsRecord>()?);
+        }
+        let mut buffer = BytePacketBuffer::new();
+        packet.write(&mut buffer)?;
+        let message = buffer.buf[..buffer.pos()].to_vec();
+
+        // act
+        let actual = dissect(&message);
+
+        // assert
+        assert_eq!(None, actual.error);
+        let covered: usize = actual.fields.iter().map(|f| f.len).sum();
+        assert_eq!(message.len(), covered);
+        for expected in [
+            field(37, 1, "tag length", "5"),
+            field(38, 5, "tag", "issue"),
+            field(43, 7, "value", "\"ca.test\""),
+            field(77, 2, "key", "alpn"),
+            field(81, 3, "value", "026832"),
+            field(84, 2, "key", "port"),
+            field(131, 1, "next domain", "www.example.test."),
+            field(134, 1, "bitmap", "A NS"),
+            field(135, 1, "window", "1"),
+            field(137, 1, "bitmap", "CAA"),
+            field(167, 4, "salt", "aabbccdd"),
+            field(
+                172,
+                20,
+                "next hashed owner",
+                "2T7B4G4VSA5SMI47K61MV5BV1A22BOJR",
+            ),
+            field(194, 6, "bitmap", "A RRSIG"),
+            field(226, 2, "iterations", "12"),
+            field(228, 1, "salt length", "0"),
+        ] {
+            assert!(actual.fields.contains(&expected), "{:?}", expected);
+        }
+        assert!(!actual.fields.iter().any(|f| f.name == "rdata"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn dissect_error() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let truncated = from_hex(&RESPONSE[..72])?;
+        let looping = from_hex("000000000001000000000000c00c00010001")?;
+        let mut too_large = from_hex(RESPONSE)?;
+        too_large.resize(MAX_PACKET_SIZE + 4, 0);
+
+        // act
+        let actual = [dissect(&truncated), dissect(&looping), dissect(&too_large)];
+
+        // assert
+        assert_eq!(
+            Some("Unexpected end of message at offset 34".to_string()),
+            actual[0].error
+        );
+        assert_eq!(
+            Some("Compression pointer to 12 doesn't point back at offset 12".to_string()),
+            actual[1].error
+        );
+        assert_eq!(
+            Some(
+                "Message of 4100 bytes is too large, the 4 bytes past offset 4096 weren't read"
+                    .to_string()
+            ),
+            actual[2].error
+        );
+        assert_eq!(
+            Some(&field(55, 4041, "trailing data", "4041 bytes")),
+            actual[2].fields.last()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn decode_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let message = vec![0x12, 0x34, 0x81, 0x80];
+
+        // act
+        let actual = [
+            decode(b"12 34\n81 80\n", None)?,
+            decode(b"0x1234 0x8180", None)?,
+            decode(b"EjSBgA==", None)?,
+            decode(&message, None)?,
+            decode(b"1234", Some(InputFormat::Binary))?,
+        ];
+
+        // assert
+        assert_eq!(message, actual[0]);
+        assert_eq!(message, actual[1]);
+        assert_eq!(message, actual[2]);
+        assert_eq!(message, actual[3]);
+        assert_eq!(b"1234".to_vec(), actual[4]);
+        assert!(decode(b"123", Some(InputFormat::Hex)).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,13 @@
+//! DNSSEC (RFC 4033, 4034 and 4035): signatures over record sets, authenticated denial of
+//! existence with NSEC and NSEC3, and the chain of trust from a trust anchor down to an answer
+
+pub mod anchor_store;
+pub mod crypto;
+pub mod denial;
+pub mod key_file;
+pub mod name;
+pub mod signature;
+pub mod signer;
+pub mod trust_anchor;
+pub mod validator;
+pub mod zone_signer;
@@ -0,0 +1,568 @@
+//! Automated updates of trust anchors (RFC 5011): the keys of the root zone are followed as they
+//! are rolled, new keys being trusted once seen for the add hold-down time, and revoked keys being
+//! forgotten after the remove hold-down time
+
+use super::super::encoding::to_timestamp;
+use super::super::model::{dns_record::DnsRecord, query_type::QueryType};
+use super::{crypto, name, signature, trust_anchor};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// How long a new key must be seen before it is trusted, 30 days (RFC 5011 §2.4.1)
+pub const ADD_HOLD_DOWN: u32 = 30 * 24 * 3600;
+
+/// How long a revoked key is remembered before it is removed, 30 days (RFC 5011 §2.4.2)
+pub const REMOVE_HOLD_DOWN: u32 = 30 * 24 * 3600;
+
+/// The state of a trust anchor (RFC 5011 §4)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyState {
+    /// A new key, waiting for the add hold-down time to pass
+    AddPend,
+    /// A trusted key
+    Valid,
+    /// A trusted key which has left the DNSKEY record set
+    Missing,
+    /// A key revoked by its owner, no longer trusted
+    Revoked,
+}
+
+impl KeyState {
+    fn from_str(text: &str) -> Option<Self> {
+        match text {
+            "ADDPEND" => Some(KeyState::AddPend),
+            "VALID" => Some(KeyState::Valid),
+            "MISSING" => Some(KeyState::Missing),
+            "REVOKED" => Some(KeyState::Revoked),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for KeyState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            KeyState::AddPend => "ADDPEND",
+            KeyState::Valid => "VALID",
+            KeyState::Missing => "MISSING",
+            KeyState::Revoked => "REVOKED",
+        };
+
+        write!(f, "{}", text)
+    }
+}
+
+/// A trust anchor along with its state, changed at `since` (seconds since the epoch)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Anchor {
+    /// A DNSKEY record, or a DS record seeding the store until the key it points at is seen
+    pub record: DnsRecord,
+    pub state: KeyState,
+    pub since: u32,
+}
+
+/// The trust anchors of the root zone, saved to a file after every change
+#[derive(Debug, Clone, Default)]
+pub struct AnchorStore {
+    pub anchors: Vec<Anchor>,
+    pub path: Option<PathBuf>,
+}
+
+impl AnchorStore {
+    /// A store trusting the given DS or DNSKEY records
+    pub fn seed(records: Vec<DnsRecord>, now: u32) -> Self {
+        AnchorStore {
+            anchors: records
+                .into_iter()
+                .map(|record| Anchor {
+                    record,
+                    state: KeyState::Valid,
+                    since: now,
+                })
+                .collect(),
+            path: None,
+        }
+    }
+
+    /// Open the store saved at `path`, or create it there from the trust anchors of the seed file
+    /// (the built-in root anchors without one)
+    pub fn open(
+        path: &Path,
+        seed_file: Option<&Path>,
+        now: u32,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut store = if path.exists() {
+            AnchorStore::parse(&std::fs::read_to_string(path)?)?
+        } else {
+            let seeds = match seed_file {
+                Some(file) => trust_anchor::parse(&std::fs::read_to_string(file)?)?,
+                None => trust_anchor::root(),
+            };
+            AnchorStore::seed(seeds, now)
+        };
+        store.path = Some(path.to_path_buf());
+        store.save()?;
+
+        Ok(store)
+    }
+
+    /// Parse a store in the format it is saved in: a trust anchor per line, in the zone file
+    /// format, followed by a comment holding its state, e.g.
+    /// `. 0 IN DNSKEY 257 3 8 AwEAAa... ; state=VALID since=1700000000`
+    pub fn parse(text: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut store = AnchorStore::default();
+
+        for line in text.lines() {
+            let (record, comment) = line.split_once(';').unwrap_or((line, ""));
+            if record.trim().is_empty() {
+                continue;
+            }
+
+            let invalid = || format!("Invalid trust anchor state: {}", line);
+            let record = trust_anchor::parse(record)?.pop().ok_or_else(invalid)?;
+            let mut state = None;
+            let mut since = None;
+            for pair in comment.split_whitespace() {
+                match pair.split_once('=') {
+                    Some(("state", value)) => state = KeyState::from_str(value),
+                    Some(("since", value)) => since = value.parse().ok(),
+                    _ => {}
+                }
+            }
+
+            store.anchors.push(Anchor {
+                record,
+                state: state.ok_or_else(invalid)?,
+                since: since.ok_or_else(invalid)?,
+            });
+        }
+
+        Ok(store)
+    }
+
+    /// Write the store to its file, if it has one
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(path) = &self.path {
+            let mut text = String::from("; RFC 5011 trust anchors, updated automatically\n");
+            for anchor in &self.anchors {
+                text.push_str(&format!(
+                    "{} ; state={} since={}\n",
+                    anchor.record, anchor.state, anchor.since
+                ));
+            }
+            std::fs::write(path, text)?;
+        }
+
+        Ok(())
+    }
+
+    /// The records of the anchors we trust: valid ones, and missing ones which may come back
+    pub fn trusted(&self) -> Vec<DnsRecord> {
+        self.anchors
+            .iter()
+            .filter(|anchor| matches!(anchor.state, KeyState::Valid | KeyState::Missing))
+            .map(|anchor| anchor.record.clone())
+            .collect()
+    }
+
+    /// Update the anchors from the DNSKEY record set of the root zone, along with its RRSIG
+    /// records, returning whether anything changed. Record sets not signed by a trusted key are
+    /// ignored (RFC 5011 §2.2).
+    pub fn observe(
+        &mut self,
+        records: &[DnsRecord],
+        now: u32,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let dnskeys: Vec<&DnsRecord> = records
+            .iter()
+            .filter(|record| {
+                matches!(record, DnsRecord::DNSKEY { .. })
+                    && name::normalize(record.domain()).is_empty()
+            })
+            .collect();
+        let rrsigs = signature::rrsigs_for(records, "", QueryType::DNSKEY);
+
+        let trusted = self.trusted();
+        let keys: Vec<DnsRecord> = dnskeys
+            .iter()
+            .filter(|dnskey| {
+                trusted.iter().any(|anchor| match anchor {
+                    DnsRecord::DS { .. } => crypto::ds_matches(anchor, dnskey),
+                    _ => same_key(anchor, dnskey),
+                })
+            })
+            .map(|dnskey| (*dnskey).clone())
+            .collect();
+        if dnskeys.is_empty() || signature::verify_rrset(&dnskeys, &rrsigs, &keys, "", now).is_err()
+        {
+            return Ok(false);
+        }
+
+        let before = self.anchors.clone();
+
+        // NOTE: Seeding DS records are replaced by the keys they point at
+        for key in &keys {
+            let seeded = self.anchors.iter().any(|anchor| {
+                matches!(anchor.record, DnsRecord::DS { .. })
+                    && crypto::ds_matches(&anchor.record, key)
+            });
+            if seeded
+                && !self
+                    .anchors
+                    .iter()
+                    .any(|anchor| same_key(&anchor.record, key))
+            {
+                self.anchors.push(Anchor {
+                    record: key.clone(),
+                    state: KeyState::Valid,
+                    since: now,
+                });
+            }
+        }
+        self.anchors.retain(|anchor| match anchor.record {
+            DnsRecord::DS { .. } => !keys
+                .iter()
+                .any(|key| crypto::ds_matches(&anchor.record, key)),
+            _ => true,
+        });
+
+        for dnskey in dnskeys.iter().filter(|dnskey| is_sep(dnskey)) {
+            if is_revoked(dnskey) {
+                // NOTE: Only the key itself can revoke it (RFC 5011 §2.1)
+                if !self_signed(dnskey, &dnskeys, &rrsigs, now) {
+                    continue;
+                }
+                if let Some(anchor) = self
+                    .anchors
+                    .iter_mut()
+                    .find(|anchor| same_key(&anchor.record, &unrevoked(dnskey)))
+                {
+                    if anchor.state != KeyState::Revoked {
+                        anchor.record = (*dnskey).clone();
+                        anchor.state = KeyState::Revoked;
+                        anchor.since = now;
+                    }
+                }
+                continue;
+            }
+
+            match self
+                .anchors
+                .iter_mut()
+                .find(|anchor| same_key(&anchor.record, dnskey))
+            {
+                Some(anchor) => match anchor.state {
+                    KeyState::AddPend if now.saturating_sub(anchor.since) >= ADD_HOLD_DOWN => {
+                        anchor.state = KeyState::Valid;
+                        anchor.since = now;
+                    }
+                    KeyState::Missing => {
+                        anchor.state = KeyState::Valid;
+                        anchor.since = now;
+                    }
+                    _ => {}
+                },
+                None => self.anchors.push(Anchor {
+                    record: (*dnskey).clone(),
+                    state: KeyState::AddPend,
+                    since: now,
+                }),
+            }
+        }
+
+        // Keys which left the record set: pending ones are forgotten, trusted ones go missing
+        for anchor in self.anchors.iter_mut() {
+            let present = dnskeys
+                .iter()
+                .any(|dnskey| same_key(&anchor.record, dnskey));
+            if !present && anchor.state == KeyState::Valid {
+                anchor.state = KeyState::Missing;
+                anchor.since = now;
+            }
+        }
+        self.anchors.retain(|anchor| match anchor.state {
+            KeyState::AddPend => dnskeys
+                .iter()
+                .any(|dnskey| same_key(&anchor.record, dnskey)),
+            KeyState::Revoked => now.saturating_sub(anchor.since) < REMOVE_HOLD_DOWN,
+            _ => true,
+        });
+
+        let changed = self.anchors != before;
+        if changed {
+            self.save()?;
+        }
+
+        Ok(changed)
+    }
+}
+
+impl fmt::Display for AnchorStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<8} {:<9} {:<8} {:<14} SINCE",
+            "KEY TAG", "ALGORITHM", "TYPE", "STATE"
+        )?;
+
+        for anchor in &self.anchors {
+            let (key_tag, algorithm) = match &anchor.record {
+                DnsRecord::DS {
+                    key_tag, algorithm, ..
+                } => (*key_tag, *algorithm),
+                DnsRecord::DNSKEY { algorithm, .. } => (
+                    crypto::key_tag(&anchor.record).unwrap_or_default(),
+                    *algorithm,
+                ),
+                _ => continue,
+            };
+            // NOTE: Pending and revoked keys change state once their hold-down time passed
+            let state = match anchor.state {
+                KeyState::AddPend => format!(
+                    "{} (until {})",
+                    anchor.state,
+                    to_timestamp(anchor.since.saturating_add(ADD_HOLD_DOWN))
+                ),
+                KeyState::Revoked => format!(
+                    "{} (until {})",
+                    anchor.state,
+                    to_timestamp(anchor.since.saturating_add(REMOVE_HOLD_DOWN))
+                ),
+                _ => anchor.state.to_string(),
+            };
+
+            writeln!(
+                f,
+                "{:<8} {:<9} {:<8} {:<14} {}",
+                key_tag,
+                algorithm,
+                anchor.record.qtype(),
+                state,
+                to_timestamp(anchor.since)
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+fn is_sep(record: &DnsRecord) -> bool {
+    match record {
+        DnsRecord::DNSKEY { flags, .. } => flags & crypto::FLAG_SECURE_ENTRY_POINT != 0,
+        _ => false,
+    }
+}
+
+fn is_revoked(record: &DnsRecord) -> bool {
+    matches!(record, DnsRecord::DNSKEY { flags, .. } if flags & crypto::FLAG_REVOKE != 0)
+}
+
+/// The key without its revoke flag, as it was trusted before being revoked
+fn unrevoked(record: &DnsRecord) -> DnsRecord {
+    let mut record = record.clone();
+    if let DnsRecord::DNSKEY { flags, .. } = &mut record {
+        *flags &= !crypto::FLAG_REVOKE;
+    }
+
+    record
+}
+
+/// Whether the record set is signed by the key itself, which verifying with trusted keys
+/// doesn't allow for revoked keys
+fn self_signed(key: &DnsRecord, rrset: &[&DnsRecord], rrsigs: &[&DnsRecord], now: u32) -> bool {
+    let DnsRecord::DNSKEY {
+        algorithm,
+        public_key,
+        ..
+    } = key
+    else {
+        return false;
+    };
+
+    rrsigs.iter().any(|rrsig| match rrsig {
+        DnsRecord::RRSIG {
+            algorithm: rrsig_algorithm,
+            expiration,
+            inception,
+            key_tag,
+            signature,
+            ..
+        } => {
+            rrsig_algorithm == algorithm
+                && crypto::key_tag(key) == Some(*key_tag)
+                && (now.wrapping_sub(*inception) as i32) >= 0
+                && (expiration.wrapping_sub(now) as i32) >= 0
+                && signature::signed_data(rrsig, rrset)
+                    .is_ok_and(|data| crypto::verify(*algorithm, public_key, &data, signature))
+        }
+        _ => false,
+    })
+}
+
+/// Whether both records hold the same DNSKEY, whatever their TTL
+fn same_key(a: &DnsRecord, b: &DnsRecord) -> bool {
+    match (a, b) {
+        (
+            DnsRecord::DNSKEY {
+                flags,
+                protocol,
+                algorithm,
+                public_key,
+                ..
+            },
+            DnsRecord::DNSKEY {
+                flags: other_flags,
+                protocol: other_protocol,
+                algorithm: other_algorithm,
+                public_key: other_public_key,
+                ..
+            },
+        ) => {
+            flags == other_flags
+                && protocol == other_protocol
+                && algorithm == other_algorithm
+                && public_key == other_public_key
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::model::dns_class::DnsClass;
+    use super::super::signer::SigningKey;
+    use super::{crypto, AnchorStore, DnsRecord, KeyState, ADD_HOLD_DOWN, REMOVE_HOLD_DOWN};
+
+    const NOW: u32 = 1_700_000_000;
+
+    /// The root DNSKEY record set signed by each key, valid for a year
+    fn rrset(
+        keys: &[&SigningKey],
+        signers: &[&SigningKey],
+    ) -> Result<Vec<DnsRecord>, Box<dyn std::error::Error>> {
+        let mut records: Vec<DnsRecord> = keys.iter().map(|key| key.dnskey(3600)).collect();
+        let rrset: Vec<&DnsRecord> = records.iter().collect();
+        let rrsigs = signers
+            .iter()
+            .map(|signer| signer.sign_rrset(&rrset, NOW, NOW + 400 * 24 * 3600))
+            .collect::<Result<Vec<_>, _>>()?;
+        records.extend(rrsigs);
+
+        Ok(records)
+    }
+
+    fn ds(key: &SigningKey) -> DnsRecord {
+        DnsRecord::DS {
+            domain: "".to_string(),
+            key_tag: key.key_tag(),
+            algorithm: key.algorithm(),
+            digest_type: crypto::DIGEST_SHA256,
+            digest: crypto::ds_digest(&key.dnskey(0), crypto::DIGEST_SHA256).unwrap_or_default(),
+            class: DnsClass::IN,
+            ttl: 0,
+        }
+    }
+
+    fn states(store: &AnchorStore) -> Vec<(u16, KeyState)> {
+        store
+            .anchors
+            .iter()
+            .map(|anchor| {
+                let key_tag = match &anchor.record {
+                    DnsRecord::DS { key_tag, .. } => *key_tag,
+                    record => crypto::key_tag(record).unwrap_or_default(),
+                };
+                (key_tag, anchor.state)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn observe_rollover_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let (old, pkcs8) = SigningKey::generate("", 257, crypto::ED25519)?;
+        let revoked =
+            SigningKey::from_pkcs8("", 257 | crypto::FLAG_REVOKE, crypto::ED25519, &pkcs8)?;
+        let new = SigningKey::generate("", 257, crypto::ED25519)?.0;
+        let mut store = AnchorStore::seed(vec![ds(&old)], NOW);
+
+        // act
+        let published = store.observe(&rrset(&[&old, &new], &[&old])?, NOW)?;
+        let pending = states(&store);
+        let unchanged = store.observe(&rrset(&[&old, &new], &[&old])?, NOW + ADD_HOLD_DOWN - 1)?;
+        store.observe(&rrset(&[&old, &new], &[&old])?, NOW + ADD_HOLD_DOWN)?;
+        let added = states(&store);
+        let rolled = rrset(&[&revoked, &new], &[&revoked, &new])?;
+        store.observe(&rolled, NOW + ADD_HOLD_DOWN + 1)?;
+        let revoking = states(&store);
+        store.observe(&rolled, NOW + ADD_HOLD_DOWN + 1 + REMOVE_HOLD_DOWN)?;
+
+        // assert
+        assert!(published);
+        assert_eq!(
+            vec![
+                (old.key_tag(), KeyState::Valid),
+                (new.key_tag(), KeyState::AddPend)
+            ],
+            pending
+        );
+        assert!(!unchanged);
+        assert_eq!(
+            vec![
+                (old.key_tag(), KeyState::Valid),
+                (new.key_tag(), KeyState::Valid)
+            ],
+            added
+        );
+        assert_eq!(
+            vec![
+                (revoked.key_tag(), KeyState::Revoked),
+                (new.key_tag(), KeyState::Valid)
+            ],
+            revoking
+        );
+        assert_eq!(vec![(new.key_tag(), KeyState::Valid)], states(&store));
+
+        Ok(())
+    }
+
+    #[test]
+    fn observe_untrusted_ignored() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let trusted = SigningKey::generate("", 257, crypto::ED25519)?.0;
+        let other = SigningKey::generate("", 257, crypto::ED25519)?.0;
+        let mut store = AnchorStore::seed(vec![trusted.dnskey(0)], NOW);
+
+        // act
+        let actual = store.observe(&rrset(&[&trusted, &other], &[&other])?, NOW)?;
+
+        // assert
+        assert!(!actual);
+        assert_eq!(vec![(trusted.key_tag(), KeyState::Valid)], states(&store));
+
+        Ok(())
+    }
+
+    #[test]
+    fn open_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let key = SigningKey::generate("", 257, crypto::ED25519)?.0;
+        let path = std::env::temp_dir().join(format!("anchors-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut store = AnchorStore::open(&path, None, NOW)?;
+        store.anchors = AnchorStore::seed(vec![key.dnskey(0)], NOW).anchors;
+        store.anchors[0].state = KeyState::AddPend;
+        store.save()?;
+
+        // act
+        let actual = AnchorStore::open(&path, None, NOW + 1)?;
+        std::fs::remove_file(&path)?;
+
+        // assert
+        assert_eq!(store.anchors, actual.anchors);
+        assert!(actual.to_string().contains("ADDPEND (until"));
+        assert!(AnchorStore::parse(". IN DNSKEY 257 3 15 AAAA ; since=1").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,281 @@
+//! DNSSEC algorithms (RFC 8624): key tags, DS digests and signature verification
+
+use super::super::model::dns_record::DnsRecord;
+use super::name;
+use ring::digest;
+use ring::signature::{self, RsaPublicKeyComponents, UnparsedPublicKey};
+
+/// RSA/SHA-256 (RFC 5702)
+pub const RSASHA256: u8 = 8;
+/// ECDSA Curve P-256 with SHA-256 (RFC 6605)
+pub const ECDSAP256SHA256: u8 = 13;
+/// ECDSA Curve P-384 with SHA-384 (RFC 6605)
+pub const ECDSAP384SHA384: u8 = 14;
+/// Ed25519 (RFC 8080)
+pub const ED25519: u8 = 15;
+
+/// DS digest types (RFC 4509 and RFC 6605)
+pub const DIGEST_SHA1: u8 = 1;
+pub const DIGEST_SHA256: u8 = 2;
+pub const DIGEST_SHA384: u8 = 4;
+
+/// DNSKEY flags (RFC 4034 §2.1.1 and RFC 5011 §7)
+pub const FLAG_ZONE_KEY: u16 = 0x0100;
+pub const FLAG_REVOKE: u16 = 0x0080;
+pub const FLAG_SECURE_ENTRY_POINT: u16 = 0x0001;
+
+/// Whether we can verify signatures made with the algorithm
+pub fn is_supported_algorithm(algorithm: u8) -> bool {
+    matches!(
+        algorithm,
+        RSASHA256 | ECDSAP256SHA256 | ECDSAP384SHA384 | ED25519
+    )
+}
+
+/// The mnemonic of a supported algorithm, as in key files (RFC 4034 Appendix A.1)
+pub fn algorithm_name(algorithm: u8) -> Option<&'static str> {
+    match algorithm {
+        RSASHA256 => Some("RSASHA256"),
+        ECDSAP256SHA256 => Some("ECDSAP256SHA256"),
+        ECDSAP384SHA384 => Some("ECDSAP384SHA384"),
+        ED25519 => Some("ED25519"),
+        _ => None,
+    }
+}
+
+/// Whether we can compute DS digests of the type
+pub fn is_supported_digest(digest_type: u8) -> bool {
+    matches!(digest_type, DIGEST_SHA1 | DIGEST_SHA256 | DIGEST_SHA384)
+}
+
+/// The record data of a DNSKEY record: flags, protocol, algorithm and public key
+pub fn dnskey_rdata(flags: u16, protocol: u8, algorithm: u8, public_key: &[u8]) -> Vec<u8> {
+    let mut rdata = Vec::with_capacity(4 + public_key.len());
+    rdata.extend_from_slice(&flags.to_be_bytes());
+    rdata.push(protocol);
+    rdata.push(algorithm);
+    rdata.extend_from_slice(public_key);
+
+    rdata
+}
+
+/// The key tag of a DNSKEY record (RFC 4034 Appendix B), used by DS and RRSIG records to point
+/// at a key
+pub fn key_tag(record: &DnsRecord) -> Option<u16> {
+    let DnsRecord::DNSKEY {
+        flags,
+        protocol,
+        algorithm,
+        public_key,
+        ..
+    } = record
+    else {
+        return None;
+    };
+
+    let rdata = dnskey_rdata(*flags, *protocol, *algorithm, public_key);
+    let mut ac: u32 = 0;
+    for (i, b) in rdata.iter().enumerate() {
+        ac += if i & 1 == 1 {
+            *b as u32
+        } else {
+            (*b as u32) << 8
+        };
+    }
+    ac += (ac >> 16) & 0xFFFF;
+
+    Some((ac & 0xFFFF) as u16)
+}
+
+/// The digest of a DNSKEY record as published in the DS record of its zone (RFC 4034 §5.1.4):
+/// the hash of the owner name followed by the record data. Unsupported digest types give `None`.
+pub fn ds_digest(record: &DnsRecord, digest_type: u8) -> Option<Vec<u8>> {
+    let DnsRecord::DNSKEY {
+        domain,
+        flags,
+        protocol,
+        algorithm,
+        public_key,
+        ..
+    } = record
+    else {
+        return None;
+    };
+
+    let digest_algorithm = match digest_type {
+        DIGEST_SHA1 => &digest::SHA1_FOR_LEGACY_USE_ONLY,
+        DIGEST_SHA256 => &digest::SHA256,
+        DIGEST_SHA384 => &digest::SHA384,
+        _ => return None,
+    };
+
+    let mut data = name::to_wire(domain).ok()?;
+    data.extend(dnskey_rdata(*flags, *protocol, *algorithm, public_key));
+
+    Some(digest::digest(digest_algorithm, &data).as_ref().to_vec())
+}
+
+/// Whether the DS record points at the DNSKEY record
+pub fn ds_matches(ds: &DnsRecord, dnskey: &DnsRecord) -> bool {
+    match (ds, dnskey) {
+        (
+            DnsRecord::DS {
+                domain,
+                key_tag: tag,
+                algorithm,
+                digest_type,
+                digest,
+                ..
+            },
+            DnsRecord::DNSKEY {
+                domain: key_domain,
+                algorithm: key_algorithm,
+                ..
+            },
+        ) => {
+            name::normalize(domain) == name::normalize(key_domain)
+                && algorithm == key_algorithm
+                && key_tag(dnskey) == Some(*tag)
+                && ds_digest(dnskey, *digest_type).as_ref() == Some(digest)
+        }
+        _ => false,
+    }
+}
+
+/// Verify a signature over data with a public key in the format of the DNSKEY record
+pub fn verify(algorithm: u8, public_key: &[u8], data: &[u8], signature: &[u8]) -> bool {
+    match algorithm {
+        RSASHA256 => match rsa_components(public_key) {
+            Some(components) => components
+                .verify(
+                    &signature::RSA_PKCS1_1024_8192_SHA256_FOR_LEGACY_USE_ONLY,
+                    data,
+                    signature,
+                )
+                .is_ok(),
+            None => false,
+        },
+        // NOTE: DNSSEC keeps the bare point of ECDSA keys (RFC 6605 §4), ring expects the
+        // uncompressed form with its 0x04 prefix
+        ECDSAP256SHA256 | ECDSAP384SHA384 => {
+            let verification = if algorithm == ECDSAP256SHA256 {
+                &signature::ECDSA_P256_SHA256_FIXED
+            } else {
+                &signature::ECDSA_P384_SHA384_FIXED
+            };
+            let mut point = vec![0x04];
+            point.extend_from_slice(public_key);
+
+            UnparsedPublicKey::new(verification, point)
+                .verify(data, signature)
+                .is_ok()
+        }
+        ED25519 => UnparsedPublicKey::new(&signature::ED25519, public_key)
+            .verify(data, signature)
+            .is_ok(),
+        _ => false,
+    }
+}
+
+/// Split an RSA public key (RFC 3110 §2) into its exponent and modulus
+fn rsa_components(public_key: &[u8]) -> Option<RsaPublicKeyComponents<&[u8]>> {
+    let (exponent_len, rest) = match public_key.first()? {
+        0 => {
+            let len = u16::from_be_bytes([*public_key.get(1)?, *public_key.get(2)?]);
+            (len as usize, &public_key[3..])
+        }
+        len => (*len as usize, &public_key[1..]),
+    };
+    if exponent_len == 0 || rest.len() <= exponent_len {
+        return None;
+    }
+
+    Some(RsaPublicKeyComponents {
+        n: &rest[exponent_len..],
+        e: &rest[..exponent_len],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::encoding::{from_base64, from_hex};
+    use super::super::super::model::dns_class::DnsClass;
+    use super::{ds_digest, ds_matches, key_tag, verify, DnsRecord, DIGEST_SHA256, ED25519};
+
+    /// RFC 8080 §6.1: the Ed25519 key of example.com
+    fn dnskey() -> Result<DnsRecord, Box<dyn std::error::Error>> {
+        Ok(DnsRecord::DNSKEY {
+            domain: "example.com".to_string(),
+            flags: 257,
+            protocol: 3,
+            algorithm: ED25519,
+            public_key: from_base64("l02Woi0iS8Aa25FQkUd9RMzZHJpBoRQwAQEX1SxZJA4=")?,
+            class: DnsClass::IN,
+            ttl: 3600,
+        })
+    }
+
+    #[test]
+    fn key_tag_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // act
+        let actual = key_tag(&dnskey()?);
+
+        // assert
+        assert_eq!(Some(3613), actual);
+
+        Ok(())
+    }
+
+    #[test]
+    fn ds_digest_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let ds = DnsRecord::DS {
+            domain: "example.com".to_string(),
+            key_tag: 3613,
+            algorithm: ED25519,
+            digest_type: DIGEST_SHA256,
+            digest: from_hex("3aa5ab37efce57f737fc1627013fee07bdf241bd10f3b1964ab55c78e79a304b")?,
+            class: DnsClass::IN,
+            ttl: 3600,
+        };
+
+        // act
+        let actual = ds_digest(&dnskey()?, DIGEST_SHA256);
+
+        // assert
+        assert_eq!(
+            Some(from_hex(
+                "3aa5ab37efce57f737fc1627013fee07bdf241bd10f3b1964ab55c78e79a304b"
+            )?),
+            actual
+        );
+        assert!(ds_matches(&ds, &dnskey()?));
+
+        Ok(())
+    }
+
+    #[test]
+    fn verify_ed25519_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        // RFC 8080 §6.1: the signature over example.com. 3600 IN MX 10 mail.example.com.
+        let public_key = from_base64("l02Woi0iS8Aa25FQkUd9RMzZHJpBoRQwAQEX1SxZJA4=")?;
+        let signature = from_base64(
+            "oL9krJun7xfBOIWcGHi7mag5/hdZrKWw15jPGrHpjQeRAvTdszaPD+QLs3fx8A4M3e23mRZ9VrbpMngwcrqNAg==",
+        )?;
+        let mut data = from_hex(
+            "000f0f0200000e1055d4fc6055b94ce00e1d076578616d706c6503636f6d00\
+             076578616d706c6503636f6d00000f000100000e100014000a046d61696c076578616d706c6503636f6d00",
+        )?;
+
+        // act
+        let actual = verify(ED25519, &public_key, &data, &signature);
+        data[0] ^= 1;
+        let tampered = verify(ED25519, &public_key, &data, &signature);
+
+        // assert
+        assert!(actual);
+        assert!(!tampered);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,459 @@
+//! Authenticated denial of existence with NSEC (RFC 4035 §5.4) and NSEC3 (RFC 5155 §8) records.
+//! The records given to the proofs below must already be verified with their RRSIG records.
+
+use super::super::model::{dns_record::DnsRecord, query_type::QueryType};
+use super::name;
+use ring::digest;
+use std::cmp::Ordering;
+
+/// The only NSEC3 hash algorithm, SHA-1 (RFC 5155 §11)
+pub const NSEC3_SHA1: u8 = 1;
+
+/// The NSEC3 opt-out flag, set when insecure delegations may be left out of the chain
+pub const NSEC3_OPT_OUT: u8 = 0x01;
+
+/// NSEC3 records with more iterations are ignored, so they can't be used to waste our time
+/// (RFC 9276 §3.2)
+pub const MAX_NSEC3_ITERATIONS: u16 = 150;
+
+/// The hashed owner name of NSEC3 records (RFC 5155 §5): SHA-1 over the canonical name and the
+/// salt, hashed again over itself and the salt for each iteration
+pub fn nsec3_hash(
+    domain: &str,
+    salt: &[u8],
+    iterations: u16,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut data = name::to_wire(domain)?;
+    data.extend_from_slice(salt);
+    let mut hash = digest::digest(&digest::SHA1_FOR_LEGACY_USE_ONLY, &data)
+        .as_ref()
+        .to_vec();
+
+    for _ in 0..iterations {
+        hash.extend_from_slice(salt);
+        hash = digest::digest(&digest::SHA1_FOR_LEGACY_USE_ONLY, &hash)
+            .as_ref()
+            .to_vec();
+    }
+
+    Ok(hash)
+}
+
+/// Whether the records prove that `qname` doesn't exist: neither the name nor a wildcard that
+/// could have been expanded to it
+pub fn proves_nxdomain(qname: &str, records: &[DnsRecord]) -> bool {
+    nsec_nxdomain(qname, records) || nsec3_nxdomain(qname, records)
+}
+
+/// Whether the records prove that `qname` exists, but has no records of `qtype`
+pub fn proves_nodata(qname: &str, qtype: QueryType, records: &[DnsRecord]) -> bool {
+    nsec_nodata(qname, qtype, records) || nsec3_nodata(qname, qtype, records)
+}
+
+/// Whether the records prove that the delegation to `child` has no DS records, so the child zone
+/// is unsigned (RFC 4035 §5.2)
+pub fn proves_insecure_delegation(child: &str, records: &[DnsRecord]) -> bool {
+    nsec_insecure_delegation(child, records) || nsec3_insecure_delegation(child, records)
+}
+
+/// Whether the records prove that no name closer to `qname` than the wildcard with `labels`
+/// labels (from its RRSIG record) exists, so expanding the wildcard was right (RFC 4035 §5.3.4)
+pub fn proves_wildcard_answer(qname: &str, labels: u8, records: &[DnsRecord]) -> bool {
+    let next_closer = name::ancestor(qname, labels as usize + 1);
+
+    nsecs(records).any(|(owner, next, _)| nsec_covers(owner, next, qname))
+        || nsec3_covered(&next_closer, records).is_some()
+}
+
+fn nsecs(records: &[DnsRecord]) -> impl Iterator<Item = (&str, &str, &[QueryType])> {
+    records.iter().filter_map(|record| match record {
+        DnsRecord::NSEC {
+            domain,
+            next_domain,
+            types,
+            ..
+        } => Some((domain.as_str(), next_domain.as_str(), types.as_slice())),
+        _ => None,
+    })
+}
+
+/// Whether the NSEC record from `owner` to `next` covers `qname`, that is `qname` sorts
+/// strictly between both names. The last NSEC record of a zone points back at the apex.
+fn nsec_covers(owner: &str, next: &str, qname: &str) -> bool {
+    let after_owner = name::canonical_cmp(owner, qname) == Ordering::Less;
+    let before_next = name::canonical_cmp(qname, next) == Ordering::Less;
+
+    if name::canonical_cmp(owner, next) == Ordering::Less {
+        after_owner && before_next
+    } else {
+        after_owner || before_next
+    }
+}
+
+/// The number of labels `a` and `b` have in common, from the right
+fn common_labels(a: &str, b: &str) -> usize {
+    name::labels(a)
+        .iter()
+        .rev()
+        .zip(name::labels(b).iter().rev())
+        .take_while(|(x, y)| x.eq_ignore_ascii_case(y))
+        .count()
+}
+
+fn nsec_nxdomain(qname: &str, records: &[DnsRecord]) -> bool {
+    let Some((owner, next, _)) =
+        nsecs(records).find(|(owner, next, _)| nsec_covers(owner, next, qname))
+    else {
+        return false;
+    };
+
+    // NOTE: The closest encloser is the longest ancestor of the name that exists, known from the
+    // names around the gap the name falls in
+    let closest_encloser = name::ancestor(
+        qname,
+        common_labels(qname, owner).max(common_labels(qname, next)),
+    );
+    let wildcard = format!("*.{}", closest_encloser);
+
+    nsecs(records).any(|(owner, next, _)| nsec_covers(owner, next, &wildcard))
+}
+
+fn nsec_nodata(qname: &str, qtype: QueryType, records: &[DnsRecord]) -> bool {
+    let qname = name::normalize(qname);
+
+    nsecs(records).any(|(owner, next, types)| {
+        if name::normalize(owner) == qname {
+            no_type(types, qtype)
+        } else {
+            // NOTE: An empty non-terminal has no NSEC record of its own, but the next name of
+            // the gap it falls in is below it (RFC 4035 §3.1.3.2)
+            nsec_covers(owner, next, &qname)
+                && name::is_subdomain(next, &qname)
+                && name::normalize(next) != qname
+        }
+    }) || nsec_wildcard_nodata(&qname, qtype, records)
+}
+
+fn nsec_wildcard_nodata(qname: &str, qtype: QueryType, records: &[DnsRecord]) -> bool {
+    let Some((owner, next, _)) =
+        nsecs(records).find(|(owner, next, _)| nsec_covers(owner, next, qname))
+    else {
+        return false;
+    };
+    let closest_encloser = name::ancestor(
+        qname,
+        common_labels(qname, owner).max(common_labels(qname, next)),
+    );
+    let wildcard = name::normalize(&format!("*.{}", closest_encloser));
+
+    nsecs(records)
+        .any(|(owner, _, types)| name::normalize(owner) == wildcard && no_type(types, qtype))
+}
+
+fn nsec_insecure_delegation(child: &str, records: &[DnsRecord]) -> bool {
+    let child = name::normalize(child);
+
+    nsecs(records).any(|(owner, _, types)| {
+        name::normalize(owner) == child && is_delegation(types) && !types.contains(&QueryType::DS)
+    })
+}
+
+/// Whether a type bitmap denies `qtype`. Bitmaps from the parent side of a delegation, with NS
+/// but without SOA, only prove the absence of DS records (RFC 4035 §5.4).
+fn no_type(types: &[QueryType], qtype: QueryType) -> bool {
+    !types.contains(&qtype)
+        && !types.contains(&QueryType::CNAME)
+        && (qtype == QueryType::DS || !is_delegation(types))
+}
+
+fn is_delegation(types: &[QueryType]) -> bool {
+    types.contains(&QueryType::NS) && !types.contains(&QueryType::SOA)
+}
+
+struct Nsec3<'a> {
+    zone: String,
+    owner_hash: Vec<u8>,
+    flags: u8,
+    iterations: u16,
+    salt: &'a [u8],
+    next_hashed_owner: &'a [u8],
+    types: &'a [QueryType],
+}
+
+fn nsec3s(records: &[DnsRecord]) -> impl Iterator<Item = Nsec3<'_>> {
+    records.iter().filter_map(|record| match record {
+        DnsRecord::NSEC3 {
+            domain,
+            hash_algorithm,
+            flags,
+            iterations,
+            salt,
+            next_hashed_owner,
+            types,
+            ..
+        } if *hash_algorithm == NSEC3_SHA1 && *iterations <= MAX_NSEC3_ITERATIONS => {
+            let labels = name::labels(domain);
+            let owner_hash = super::super::encoding::from_base32hex(labels.first()?).ok()?;
+
+            Some(Nsec3 {
+                zone: labels[1..].join("."),
+                owner_hash,
+                flags: *flags,
+                iterations: *iterations,
+                salt,
+                next_hashed_owner,
+                types,
+            })
+        }
+        _ => None,
+    })
+}
+
+fn hash_for(qname: &str, nsec3: &Nsec3) -> Option<Vec<u8>> {
+    if !name::is_subdomain(qname, &nsec3.zone) {
+        return None;
+    }
+
+    nsec3_hash(qname, nsec3.salt, nsec3.iterations).ok()
+}
+
+/// The NSEC3 record whose hashed owner name is the hash of `qname`
+fn nsec3_matching<'a>(qname: &str, records: &'a [DnsRecord]) -> Option<Nsec3<'a>> {
+    nsec3s(records).find(|nsec3| hash_for(qname, nsec3).as_ref() == Some(&nsec3.owner_hash))
+}
+
+/// The NSEC3 record whose hash range covers the hash of `qname`
+fn nsec3_covered<'a>(qname: &str, records: &'a [DnsRecord]) -> Option<Nsec3<'a>> {
+    nsec3s(records).find(|nsec3| {
+        let Some(hash) = hash_for(qname, nsec3) else {
+            return false;
+        };
+        let owner = nsec3.owner_hash.as_slice();
+        let next = nsec3.next_hashed_owner;
+
+        if owner < next {
+            owner < hash.as_slice() && hash.as_slice() < next
+        } else {
+            owner < hash.as_slice() || hash.as_slice() < next
+        }
+    })
+}
+
+/// The closest encloser proof (RFC 5155 §8.3): the longest existing ancestor of `qname`, and the
+/// NSEC3 record covering the next closer name, one label longer, which doesn't exist
+fn nsec3_closest_encloser<'a>(
+    qname: &str,
+    records: &'a [DnsRecord],
+) -> Option<(String, Nsec3<'a>)> {
+    let count = name::labels(qname).len();
+
+    (0..count).rev().find_map(|labels| {
+        let candidate = name::ancestor(qname, labels);
+        nsec3_matching(&candidate, records)?;
+        let next_closer = name::ancestor(qname, labels + 1);
+
+        nsec3_covered(&next_closer, records).map(|nsec3| (candidate, nsec3))
+    })
+}
+
+fn nsec3_nxdomain(qname: &str, records: &[DnsRecord]) -> bool {
+    match nsec3_closest_encloser(qname, records) {
+        Some((closest_encloser, _)) => {
+            nsec3_covered(&format!("*.{}", closest_encloser), records).is_some()
+        }
+        None => false,
+    }
+}
+
+fn nsec3_nodata(qname: &str, qtype: QueryType, records: &[DnsRecord]) -> bool {
+    if let Some(nsec3) = nsec3_matching(qname, records) {
+        return no_type(nsec3.types, qtype);
+    }
+
+    match nsec3_closest_encloser(qname, records) {
+        // NOTE: DS records of an unsigned delegation may sit in an opt-out span (RFC 5155 §8.6)
+        Some((_, covering)) if qtype == QueryType::DS => covering.flags & NSEC3_OPT_OUT != 0,
+        Some((closest_encloser, _)) => nsec3_matching(&format!("*.{}", closest_encloser), records)
+            .is_some_and(|wildcard| no_type(wildcard.types, qtype)),
+        None => false,
+    }
+}
+
+fn nsec3_insecure_delegation(child: &str, records: &[DnsRecord]) -> bool {
+    if let Some(nsec3) = nsec3_matching(child, records) {
+        return is_delegation(nsec3.types) && !nsec3.types.contains(&QueryType::DS);
+    }
+
+    nsec3_closest_encloser(child, records)
+        .is_some_and(|(_, covering)| covering.flags & NSEC3_OPT_OUT != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::byte_packet_buffer::BytePacketBuffer;
+    use super::super::super::encoding::{from_base32hex, to_base32hex};
+    use super::super::super::model::{dns_class::DnsClass, dns_packet::DnsPacket};
+    use super::{
+        nsec3_hash, proves_insecure_delegation, proves_nodata, proves_nxdomain,
+        proves_wildcard_answer, DnsRecord, QueryType,
+    };
+
+    fn nsec(owner: &str, next: &str, types: Vec<QueryType>) -> DnsRecord {
+        DnsRecord::NSEC {
+            domain: owner.to_string(),
+            next_domain: next.to_string(),
+            types,
+            class: DnsClass::IN,
+            ttl: 3600,
+        }
+    }
+
+    /// RFC 5155 Appendix A: NSEC3 records of the example zone
+    fn nsec3(owner: &str, next: &str, flags: u8, types: Vec<QueryType>) -> DnsRecord {
+        DnsRecord::NSEC3 {
+            domain: format!("{}.example", owner),
+            hash_algorithm: 1,
+            flags,
+            iterations: 12,
+            salt: vec![0xaa, 0xbb, 0xcc, 0xdd],
+            next_hashed_owner: from_base32hex(next).unwrap(),
+            types,
+            class: DnsClass::IN,
+            ttl: 3600,
+        }
+    }
+
+    #[test]
+    fn nsec3_hash_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let salt = [0xaa, 0xbb, 0xcc, 0xdd];
+
+        // act
+        let apex = nsec3_hash("example", &salt, 12)?;
+        let host = nsec3_hash("a.example", &salt, 12)?;
+
+        // assert
+        assert_eq!("0P9MHAVEQVM6T7VBL5LOP2U3T2RP3TOM", to_base32hex(&apex));
+        assert_eq!("35MTHGPGCU1QG68FAB165KLNSNK3DPVL", to_base32hex(&host));
+
+        Ok(())
+    }
+
+    #[test]
+    fn proves_nxdomain_nsec3_ok() {
+        // arrange
+        // RFC 5155 Appendix B.1: a.c.x.w.example doesn't exist
+        let records = [
+            nsec3(
+                "0p9mhaveqvm6t7vbl5lop2u3t2rp3tom",
+                "2t7b4g4vsa5smi47k61mv5bv1a22bojr",
+                1,
+                vec![QueryType::MX, QueryType::DNSKEY, QueryType::NS],
+            ),
+            nsec3(
+                "b4um86eghhds6nea196smvmlo4ors995",
+                "gjeqe526plbf1g8mklp59enfd789njgi",
+                1,
+                vec![QueryType::MX, QueryType::RRSIG],
+            ),
+            nsec3(
+                "35mthgpgcu1qg68fab165klnsnk3dpvl",
+                "b4um86eghhds6nea196smvmlo4ors995",
+                1,
+                vec![QueryType::NS, QueryType::DS, QueryType::RRSIG],
+            ),
+        ];
+
+        // act
+        let actual = proves_nxdomain("a.c.x.w.example", &records);
+
+        // assert
+        assert!(actual);
+        assert!(!proves_nxdomain("a.c.x.w.example", &records[..2]));
+    }
+
+    #[test]
+    fn proves_nodata_nsec_ok() {
+        // arrange
+        let records = [nsec(
+            "host.example.com",
+            "z.example.com",
+            vec![QueryType::A, QueryType::RRSIG, QueryType::NSEC],
+        )];
+
+        // act
+        let actual = proves_nodata("host.example.com", QueryType::AAAA, &records);
+
+        // assert
+        assert!(actual);
+        assert!(!proves_nodata("host.example.com", QueryType::A, &records));
+    }
+
+    #[test]
+    fn proves_nxdomain_nsec_ok() {
+        // arrange
+        let records = [
+            nsec(
+                "example.com",
+                "host.example.com",
+                vec![QueryType::NS, QueryType::RRSIG, QueryType::NSEC],
+            ),
+            nsec(
+                "host.example.com",
+                "example.com",
+                vec![QueryType::A, QueryType::RRSIG, QueryType::NSEC],
+            ),
+        ];
+
+        // act
+        let actual = proves_nxdomain("missing.example.com", &records);
+
+        // assert
+        assert!(actual);
+        assert!(!proves_nxdomain("host.example.com", &records));
+    }
+
+    #[test]
+    fn proves_insecure_delegation_ok() {
+        // arrange
+        let unsigned = [nsec(
+            "child.example.com",
+            "host.example.com",
+            vec![QueryType::NS, QueryType::RRSIG, QueryType::NSEC],
+        )];
+        let signed = [nsec(
+            "child.example.com",
+            "host.example.com",
+            vec![
+                QueryType::NS,
+                QueryType::DS,
+                QueryType::RRSIG,
+                QueryType::NSEC,
+            ],
+        )];
+
+        // assert
+        assert!(proves_insecure_delegation("child.example.com", &unsigned));
+        assert!(!proves_insecure_delegation("child.example.com", &signed));
+    }
+
+    #[test]
+    fn proves_wildcard_answer_captured_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        // a.pureftpd.org was expanded from *.pureftpd.org, its RRSIG record having 2 labels
+        let data = include_bytes!("../model/testdata/pureftpd_org_nsec3.bin");
+        let mut buffer = BytePacketBuffer::new();
+        buffer.buf[..data.len()].copy_from_slice(data);
+        let response = DnsPacket::from_buffer(&mut buffer)?;
+
+        // act
+        let expanded = proves_wildcard_answer("a.pureftpd.org", 2, &response.authorities);
+        let other = proves_wildcard_answer("www.pureftpd.org", 2, &response.authorities);
+
+        // assert
+        assert!(expanded);
+        assert!(!other);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,410 @@
+//! Key files in the BIND format: `K<name>+<alg>+<id>.key` holds the DNSKEY record and
+//! `K<name>+<alg>+<id>.private` the private key
+
+use super::super::encoding::{from_base64, to_base64, to_timestamp};
+use super::super::model::dns_record::DnsRecord;
+use super::signer::SigningKey;
+use super::{crypto, name, trust_anchor};
+use rsa::traits::{PrivateKeyParts, PublicKeyParts};
+use rsa::RsaPrivateKey;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// The contents of the key files of a new key
+pub struct KeyFiles {
+    /// The name of the files without extension, `K<name>+<alg>+<id>`
+    pub name: String,
+    /// The `.key` file, with the DNSKEY record
+    pub public: String,
+    /// The `.private` file
+    pub private: String,
+}
+
+/// Generate a key for the zone, along with its key files created at `now` (seconds since the
+/// epoch). `bits` is the size of RSA keys, other algorithms have a fixed size.
+pub fn generate(
+    zone: &str,
+    flags: u16,
+    algorithm: u8,
+    bits: usize,
+    now: u32,
+) -> Result<(SigningKey, KeyFiles), Box<dyn std::error::Error>> {
+    let zone = name::normalize(zone);
+    let (key, fields) = if algorithm == crypto::RSASHA256 {
+        let private = RsaPrivateKey::new(&mut rand::thread_rng(), bits)?;
+        let missing = || "RSA key generation failed";
+        let components = vec![
+            ("Modulus", private.n().to_bytes_be()),
+            ("PublicExponent", private.e().to_bytes_be()),
+            ("PrivateExponent", private.d().to_bytes_be()),
+            ("Prime1", private.primes()[0].to_bytes_be()),
+            ("Prime2", private.primes()[1].to_bytes_be()),
+            ("Exponent1", private.dp().ok_or_else(missing)?.to_bytes_be()),
+            ("Exponent2", private.dq().ok_or_else(missing)?.to_bytes_be()),
+            (
+                "Coefficient",
+                private.crt_coefficient().ok_or_else(missing)?.to_bytes_be(),
+            ),
+        ];
+        let der = rsa_private_key_der(
+            &components
+                .iter()
+                .map(|(_, value)| value.clone())
+                .collect::<Vec<_>>(),
+        );
+
+        // NOTE: The public key is only needed by the other algorithms
+        let key = SigningKey::from_private_key(&zone, flags, algorithm, &der, &[])?;
+        (key, components)
+    } else {
+        let (key, pkcs8) = SigningKey::generate(&zone, flags, algorithm)?;
+        (key, vec![("PrivateKey", pkcs8_private_key(&pkcs8)?)])
+    };
+
+    let files = key_files(&key, &fields, now);
+    Ok((key, files))
+}
+
+/// Write the key files into the directory, returning their path without extension. Existing files
+/// are never overwritten, and the private key is only readable by its owner.
+pub fn write(directory: &Path, files: &KeyFiles) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let prefix = directory.join(&files.name);
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    // NOTE: The name holds dots, so the extension is appended rather than set
+    let path = |extension: &str| {
+        let mut path = prefix.clone().into_os_string();
+        path.push(extension);
+        PathBuf::from(path)
+    };
+    options
+        .open(path(".key"))?
+        .write_all(files.public.as_bytes())?;
+
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options
+        .open(path(".private"))?
+        .write_all(files.private.as_bytes())?;
+
+    Ok(prefix)
+}
+
+/// Read the key pair whose files start with `path`, with or without the `.key` or `.private`
+/// extension
+pub fn read(path: &str) -> Result<SigningKey, Box<dyn std::error::Error>> {
+    let prefix = path
+        .strip_suffix(".key")
+        .or_else(|| path.strip_suffix(".private"))
+        .unwrap_or(path);
+
+    let public = std::fs::read_to_string(format!("{}.key", prefix))?;
+    let private = std::fs::read_to_string(format!("{}.private", prefix))?;
+
+    parse(&public, &private)
+}
+
+/// Parse a key pair from the contents of its `.key` and `.private` files
+pub fn parse(public: &str, private: &str) -> Result<SigningKey, Box<dyn std::error::Error>> {
+    let dnskey = trust_anchor::parse(public)?
+        .into_iter()
+        .next()
+        .ok_or("No DNSKEY record in the key file")?;
+    let DnsRecord::DNSKEY {
+        domain,
+        flags,
+        algorithm,
+        public_key,
+        ..
+    } = &dnskey
+    else {
+        return Err("No DNSKEY record in the key file".into());
+    };
+
+    let fields: Vec<(&str, &str)> = private
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .map(|(key, value)| (key.trim(), value.trim()))
+        .collect();
+    let field = |key: &str| -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let value = fields
+            .iter()
+            .find(|(k, _)| *k == key)
+            .ok_or_else(|| format!("Missing {} in the private key file", key))?;
+        from_base64(value.1)
+    };
+
+    // NOTE: The algorithm field reads like `13 (ECDSAP256SHA256)`
+    let private_algorithm = fields
+        .iter()
+        .find(|(key, _)| *key == "Algorithm")
+        .and_then(|(_, value)| value.split_whitespace().next())
+        .and_then(|value| value.parse::<u8>().ok());
+    if private_algorithm != Some(*algorithm) {
+        return Err("The private key doesn't match the algorithm of the DNSKEY record".into());
+    }
+
+    let private_key = if *algorithm == crypto::RSASHA256 {
+        rsa_private_key_der(&[
+            field("Modulus")?,
+            field("PublicExponent")?,
+            field("PrivateExponent")?,
+            field("Prime1")?,
+            field("Prime2")?,
+            field("Exponent1")?,
+            field("Exponent2")?,
+            field("Coefficient")?,
+        ])
+    } else {
+        field("PrivateKey")?
+    };
+
+    SigningKey::from_private_key(
+        &name::normalize(domain),
+        *flags,
+        *algorithm,
+        &private_key,
+        public_key,
+    )
+}
+
+/// The contents of the key files of a key with the fields of its private key
+fn key_files(key: &SigningKey, fields: &[(&str, Vec<u8>)], now: u32) -> KeyFiles {
+    let algorithm = key.algorithm();
+    let mnemonic = crypto::algorithm_name(algorithm).unwrap_or_default();
+    let zone = format!("{}.", key.zone);
+    let created = to_timestamp(now);
+
+    let kind = if key.flags & crypto::FLAG_SECURE_ENTRY_POINT != 0 {
+        "key-signing"
+    } else {
+        "zone-signing"
+    };
+    let public = format!(
+        "; This is a {} key, keyid {}, for {}\n; Created: {}\n{}\n",
+        kind,
+        key.key_tag(),
+        zone,
+        created,
+        key.dnskey(3600)
+    );
+
+    let mut private = format!(
+        "Private-key-format: v1.3\nAlgorithm: {} ({})\n",
+        algorithm, mnemonic
+    );
+    for (field, value) in fields {
+        private.push_str(&format!("{}: {}\n", field, to_base64(value)));
+    }
+    for field in ["Created", "Publish", "Activate"] {
+        private.push_str(&format!("{}: {}\n", field, created));
+    }
+
+    KeyFiles {
+        name: format!("K{}+{:03}+{:05}", zone, algorithm, key.key_tag()),
+        public,
+        private,
+    }
+}
+
+/// The private key in a PKCS#8 document made by ring: the scalar of the ECPrivateKey structure
+/// (RFC 5915) for ECDSA, or the seed (RFC 8410) for Ed25519
+fn pkcs8_private_key(mut pkcs8: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut info = der_read(&mut pkcs8, 0x30)?;
+    // NOTE: Skip the version and the algorithm identifier
+    der_read(&mut info, 0x02)?;
+    der_read(&mut info, 0x30)?;
+    let mut private_key = der_read(&mut info, 0x04)?;
+
+    if private_key.first() == Some(&0x04) {
+        return Ok(der_read(&mut private_key, 0x04)?.to_vec());
+    }
+    let mut ec_private_key = der_read(&mut private_key, 0x30)?;
+    der_read(&mut ec_private_key, 0x02)?;
+
+    Ok(der_read(&mut ec_private_key, 0x04)?.to_vec())
+}
+
+/// Read a DER element of the tag, giving its content and moving past it
+fn der_read<'a>(bytes: &mut &'a [u8], tag: u8) -> Result<&'a [u8], Box<dyn std::error::Error>> {
+    let invalid = "Invalid private key document";
+    if bytes.first() != Some(&tag) {
+        return Err(invalid.into());
+    }
+
+    let (header, len) = match bytes.get(1) {
+        Some(len) if len & 0x80 == 0 => (2, *len as usize),
+        Some(len) => {
+            let count = (len & 0x7f) as usize;
+            let len_bytes = bytes.get(2..2 + count).ok_or(invalid)?;
+            let len = len_bytes
+                .iter()
+                .fold(0usize, |len, byte| (len << 8) | *byte as usize);
+            (2 + count, len)
+        }
+        None => return Err(invalid.into()),
+    };
+    let content = bytes.get(header..header + len).ok_or(invalid)?;
+    *bytes = &bytes[header + len..];
+
+    Ok(content)
+}
+
+/// The RSAPrivateKey DER document (RFC 8017 Appendix A.1.2) of the RSA key components, from the
+/// modulus to the coefficient
+fn rsa_private_key_der(components: &[Vec<u8>]) -> Vec<u8> {
+    // NOTE: The version 0 comes first
+    let mut content = der_integer(&[0]);
+    for component in components {
+        content.extend(der_integer(component));
+    }
+
+    der(0x30, &content)
+}
+
+fn der_integer(bytes: &[u8]) -> Vec<u8> {
+    let start = bytes
+        .iter()
+        .position(|byte| *byte != 0)
+        .unwrap_or(bytes.len());
+    let mut value = bytes[start..].to_vec();
+    // NOTE: Integers are signed, a leading bit set needs a zero byte in front
+    if value.first().is_none_or(|byte| byte & 0x80 != 0) {
+        value.insert(0, 0);
+    }
+
+    der(0x02, &value)
+}
+
+fn der(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut result = vec![tag];
+    let len = content.len();
+    if len < 0x80 {
+        result.push(len as u8);
+    } else {
+        let bytes: Vec<u8> = len
+            .to_be_bytes()
+            .into_iter()
+            .skip_while(|byte| *byte == 0)
+            .collect();
+        result.push(0x80 | bytes.len() as u8);
+        result.extend(bytes);
+    }
+    result.extend_from_slice(content);
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::model::dns_class::DnsClass;
+    use super::super::crypto;
+    use super::super::signature;
+    use super::{generate, parse, DnsRecord};
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn parse_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let files = [
+            (
+                include_str!("testdata/Kexample.+008+00346.key"),
+                include_str!("testdata/Kexample.+008+00346.private"),
+                346,
+            ),
+            (
+                include_str!("testdata/Kexample.+013+62436.key"),
+                include_str!("testdata/Kexample.+013+62436.private"),
+                62436,
+            ),
+            (
+                include_str!("testdata/Kexample.+015+30028.key"),
+                include_str!("testdata/Kexample.+015+30028.private"),
+                30028,
+            ),
+        ];
+        let record = DnsRecord::A {
+            domain: "www.example".to_string(),
+            addr: Ipv4Addr::new(192, 0, 2, 1),
+            class: DnsClass::IN,
+            ttl: 300,
+        };
+
+        for (public, private, key_tag) in files {
+            // act
+            let key = parse(public, private)?;
+
+            // assert
+            let rrsig = key.sign_rrset(&[&record], 1000, 2000)?;
+            let actual = signature::verify_rrset(
+                &[&record],
+                &[&rrsig],
+                &[key.dnskey(3600)],
+                "example",
+                1500,
+            );
+            assert!(actual.is_ok(), "algorithm {}", key.algorithm());
+            assert_eq!(key_tag, key.key_tag());
+            assert_eq!("example", key.zone);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_error() {
+        // arrange
+        let public = include_str!("testdata/Kexample.+013+62436.key");
+        let private = include_str!("testdata/Kexample.+015+30028.private");
+
+        // act
+        let actual = parse(public, private);
+
+        // assert
+        assert!(actual.is_err());
+        assert!(parse(public, "Algorithm: 13 (ECDSAP256SHA256)\n").is_err());
+    }
+
+    #[test]
+    fn generate_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let algorithms = [
+            (crypto::RSASHA256, 257),
+            (crypto::ECDSAP256SHA256, 257),
+            (crypto::ED25519, 256),
+        ];
+
+        for (algorithm, flags) in algorithms {
+            // act
+            let (key, files) = generate("example.", flags, algorithm, 2048, 1704067200)?;
+
+            // assert
+            let actual = parse(&files.public, &files.private)?;
+            assert_eq!(key.dnskey(3600), actual.dnskey(3600));
+            assert_eq!(
+                format!("Kexample.+{:03}+{:05}", algorithm, key.key_tag()),
+                files.name
+            );
+            assert!(files.private.contains("Created: 20240101000000"));
+
+            let record = DnsRecord::A {
+                domain: "www.example".to_string(),
+                addr: Ipv4Addr::new(192, 0, 2, 1),
+                class: DnsClass::IN,
+                ttl: 300,
+            };
+            let rrsig = actual.sign_rrset(&[&record], 1000, 2000)?;
+            let verified = signature::verify_rrset(
+                &[&record],
+                &[&rrsig],
+                &[key.dnskey(3600)],
+                "example",
+                1500,
+            );
+            assert!(verified.is_ok(), "algorithm {}", algorithm);
+        }
+
+        Ok(())
+    }
+}
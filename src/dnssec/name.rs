@@ -0,0 +1,118 @@
+//! Domain name helpers for DNSSEC, where names are compared and hashed in their canonical form
+//! (RFC 4034 §6)
+
+use super::super::BytePacketBuffer;
+use std::cmp::Ordering;
+
+/// The labels of a name, without the empty labels of the root and trailing dots
+pub fn labels(name: &str) -> Vec<&str> {
+    name.split('.').filter(|label| !label.is_empty()).collect()
+}
+
+/// Lowercase a name and drop the trailing dot, so names can be compared as strings
+pub fn normalize(name: &str) -> String {
+    labels(name).join(".").to_lowercase()
+}
+
+/// Whether `name` is `zone` itself or a name below it
+pub fn is_subdomain(name: &str, zone: &str) -> bool {
+    let name = labels(name);
+    let zone = labels(zone);
+
+    name.len() >= zone.len()
+        && name[name.len() - zone.len()..]
+            .iter()
+            .zip(zone.iter())
+            .all(|(a, b)| a.eq_ignore_ascii_case(b))
+}
+
+/// The name made of the last `count` labels of `name`
+pub fn ancestor(name: &str, count: usize) -> String {
+    let labels = labels(name);
+    labels[labels.len().saturating_sub(count)..].join(".")
+}
+
+/// The canonical ordering of names (RFC 4034 §6.1): labels are compared from the rightmost one,
+/// as lowercase bytes
+pub fn canonical_cmp(a: &str, b: &str) -> Ordering {
+    let a = normalize(a);
+    let b = normalize(b);
+
+    a.split('.')
+        .filter(|label| !label.is_empty())
+        .rev()
+        .map(str::as_bytes)
+        .cmp(
+            b.split('.')
+                .filter(|label| !label.is_empty())
+                .rev()
+                .map(str::as_bytes),
+        )
+}
+
+/// The canonical wire format of a name: uncompressed and lowercase
+pub fn to_wire(name: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut buffer = BytePacketBuffer::new();
+    buffer.write_qname(&normalize(name))?;
+
+    Ok(buffer.buf[..buffer.pos()].to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ancestor, canonical_cmp, is_subdomain, to_wire};
+
+    #[test]
+    fn canonical_cmp_ok() {
+        // arrange
+        // RFC 4034 §6.1: names in canonical order
+        let mut names = vec![
+            "\\200.z.example",
+            "*.z.example",
+            "zABC.a.EXAMPLE",
+            "z.example",
+            "a.example",
+            "Z.a.example",
+            "yljkjljk.a.example",
+            "example",
+        ];
+
+        // act
+        names.sort_by(|a, b| canonical_cmp(a, b));
+
+        // assert
+        assert_eq!(
+            vec![
+                "example",
+                "a.example",
+                "yljkjljk.a.example",
+                "Z.a.example",
+                "zABC.a.EXAMPLE",
+                "z.example",
+                "*.z.example",
+                "\\200.z.example",
+            ],
+            names
+        );
+    }
+
+    #[test]
+    fn is_subdomain_ok() {
+        // assert
+        assert!(is_subdomain("www.Example.com.", "example.com"));
+        assert!(is_subdomain("example.com", "example.com"));
+        assert!(is_subdomain("example.com", ""));
+        assert!(!is_subdomain("badexample.com", "example.com"));
+        assert!(!is_subdomain("com", "example.com"));
+    }
+
+    #[test]
+    fn ancestor_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // assert
+        assert_eq!("example.com", ancestor("a.b.example.com", 2));
+        assert_eq!("", ancestor("a.b.example.com", 0));
+        assert_eq!(vec![3, b'c', b'o', b'm', 0], to_wire("COM.")?);
+
+        Ok(())
+    }
+}
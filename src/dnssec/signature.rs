@@ -0,0 +1,332 @@
+//! Signatures over record sets (RFC 4034 §3 and RFC 4035 §5.3): the data an RRSIG record signs
+//! and the checks made before a record set is trusted
+
+use super::super::model::{dns_record::DnsRecord, query_type::QueryType};
+use super::super::BytePacketBuffer;
+use super::{crypto, name};
+
+/// Group records into record sets, the records sharing an owner name, a type and a class. RRSIG
+/// records are left out, as they are looked up for each record set.
+pub fn rrsets(records: &[DnsRecord]) -> Vec<Vec<&DnsRecord>> {
+    let mut result: Vec<Vec<&DnsRecord>> = Vec::new();
+
+    for record in records {
+        if matches!(record, DnsRecord::RRSIG { .. } | DnsRecord::OPT { .. }) {
+            continue;
+        }
+
+        match result.iter_mut().find(|rrset| same_rrset(rrset[0], record)) {
+            Some(rrset) => rrset.push(record),
+            None => result.push(vec![record]),
+        }
+    }
+
+    result
+}
+
+/// The RRSIG records covering the record set of `owner` and `qtype`
+pub fn rrsigs_for<'a>(
+    records: &'a [DnsRecord],
+    owner: &str,
+    qtype: QueryType,
+) -> Vec<&'a DnsRecord> {
+    records
+        .iter()
+        .filter(|record| match record {
+            DnsRecord::RRSIG {
+                domain,
+                type_covered,
+                ..
+            } => *type_covered == qtype && name::normalize(domain) == name::normalize(owner),
+            _ => false,
+        })
+        .collect()
+}
+
+/// The number of labels of an owner name as counted by RRSIG records, leaving out the asterisk of
+/// wildcards (RFC 4034 §3.1.3)
+pub fn label_count(owner: &str) -> u8 {
+    let labels = name::labels(owner);
+    let count = labels.len() - usize::from(labels.first() == Some(&"*"));
+
+    count as u8
+}
+
+/// The record data in canonical form (RFC 4034 §6.2): uncompressed, with the domain names of the
+/// older record types lowercased
+pub fn canonical_rdata(record: &DnsRecord) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    // NOTE: Writing never compresses names and reading lowercases them, so a record read back
+    // and written again holds its canonical record data
+    let mut buffer = BytePacketBuffer::new();
+    record.write(&mut buffer)?;
+    buffer.seek(0)?;
+    let record = DnsRecord::read(&mut buffer)?;
+
+    let mut buffer = BytePacketBuffer::new();
+    record.write(&mut buffer)?;
+    buffer.seek(0)?;
+    let mut owner = String::new();
+    buffer.read_qname(&mut owner)?;
+    buffer.step(8)?;
+    let len = buffer.read_u16()?;
+
+    buffer.read_bytes(len as usize)
+}
+
+/// The data signed by an RRSIG record (RFC 4034 §3.1.8.1): the RRSIG record data without the
+/// signature, followed by the records of the set in canonical form and order
+pub fn signed_data(
+    rrsig: &DnsRecord,
+    rrset: &[&DnsRecord],
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let DnsRecord::RRSIG {
+        type_covered,
+        algorithm,
+        labels,
+        original_ttl,
+        expiration,
+        inception,
+        key_tag,
+        signer_name,
+        ..
+    } = rrsig
+    else {
+        return Err("Not a RRSIG record".into());
+    };
+
+    let mut data = Vec::new();
+    data.extend_from_slice(&type_covered.to_num().to_be_bytes());
+    data.push(*algorithm);
+    data.push(*labels);
+    data.extend_from_slice(&original_ttl.to_be_bytes());
+    data.extend_from_slice(&expiration.to_be_bytes());
+    data.extend_from_slice(&inception.to_be_bytes());
+    data.extend_from_slice(&key_tag.to_be_bytes());
+    data.extend(name::to_wire(signer_name)?);
+
+    let mut rdatas = rrset
+        .iter()
+        .map(|record| canonical_rdata(record))
+        .collect::<Result<Vec<_>, _>>()?;
+    rdatas.sort();
+    rdatas.dedup();
+
+    // NOTE: Records expanded from a wildcard are signed with the wildcard as owner name
+    let first = rrset.first().ok_or("Empty record set")?;
+    let owner = if label_count(first.domain()) > *labels {
+        format!("*.{}", name::ancestor(first.domain(), *labels as usize))
+    } else {
+        first.domain().to_string()
+    };
+    let owner = name::to_wire(&owner)?;
+
+    for rdata in rdatas {
+        data.extend_from_slice(&owner);
+        data.extend_from_slice(&first.qtype().to_num().to_be_bytes());
+        data.extend_from_slice(&first.class().to_num().to_be_bytes());
+        data.extend_from_slice(&original_ttl.to_be_bytes());
+        data.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        data.extend(rdata);
+    }
+
+    Ok(data)
+}
+
+/// Verify a record set with one of its RRSIG records and the trusted keys of the signer zone,
+/// returning the RRSIG record that verified it (RFC 4035 §5.3.1)
+pub fn verify_rrset<'a>(
+    rrset: &[&DnsRecord],
+    rrsigs: &[&'a DnsRecord],
+    keys: &[DnsRecord],
+    zone: &str,
+    now: u32,
+) -> Result<&'a DnsRecord, Box<dyn std::error::Error>> {
+    let first = rrset.first().ok_or("Empty record set")?;
+    let mut reason = format!("No RRSIG records for {} {}", first.domain(), first.qtype());
+
+    for &rrsig in rrsigs {
+        let DnsRecord::RRSIG {
+            algorithm,
+            labels,
+            expiration,
+            inception,
+            key_tag,
+            signer_name,
+            signature,
+            ..
+        } = rrsig
+        else {
+            continue;
+        };
+
+        if name::normalize(signer_name) != name::normalize(zone)
+            || !name::is_subdomain(first.domain(), signer_name)
+        {
+            reason = format!("RRSIG signer {} is not the zone {}", signer_name, zone);
+            continue;
+        }
+        if *labels > label_count(first.domain()) {
+            reason = "RRSIG labels exceed the owner name".to_string();
+            continue;
+        }
+        // NOTE: Validity times use serial number arithmetic (RFC 4034 §3.1.5)
+        if (now.wrapping_sub(*inception) as i32) < 0 || (expiration.wrapping_sub(now) as i32) < 0 {
+            reason = format!(
+                "RRSIG of {} {} is not valid now",
+                first.domain(),
+                first.qtype()
+            );
+            continue;
+        }
+
+        let data = signed_data(rrsig, rrset)?;
+        let verified = keys.iter().any(|key| match key {
+            DnsRecord::DNSKEY {
+                flags,
+                algorithm: key_algorithm,
+                public_key,
+                ..
+            } => {
+                key_algorithm == algorithm
+                    && flags & crypto::FLAG_ZONE_KEY != 0
+                    && flags & crypto::FLAG_REVOKE == 0
+                    && crypto::key_tag(key) == Some(*key_tag)
+                    && crypto::verify(*algorithm, public_key, &data, signature)
+            }
+            _ => false,
+        });
+        if verified {
+            return Ok(rrsig);
+        }
+
+        reason = format!(
+            "RRSIG of {} {} does not verify",
+            first.domain(),
+            first.qtype()
+        );
+    }
+
+    Err(reason.into())
+}
+
+fn same_rrset(a: &DnsRecord, b: &DnsRecord) -> bool {
+    a.qtype() == b.qtype()
+        && a.class() == b.class()
+        && name::normalize(a.domain()) == name::normalize(b.domain())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::encoding::{from_base64, from_hex};
+    use super::super::super::model::dns_class::DnsClass;
+    use super::super::crypto::ED25519;
+    use super::{label_count, signed_data, verify_rrset, DnsRecord, QueryType};
+
+    fn mx() -> DnsRecord {
+        DnsRecord::MX {
+            domain: "example.com".to_string(),
+            priority: 10,
+            host: "Mail.Example.com".to_string(),
+            class: DnsClass::IN,
+            ttl: 3600,
+        }
+    }
+
+    /// RFC 8080 §6.1: the Ed25519 signature over the MX record of example.com
+    fn rrsig() -> Result<DnsRecord, Box<dyn std::error::Error>> {
+        Ok(DnsRecord::RRSIG {
+            domain: "example.com".to_string(),
+            type_covered: QueryType::MX,
+            algorithm: ED25519,
+            labels: 2,
+            original_ttl: 3600,
+            expiration: 1440021600,
+            inception: 1438207200,
+            key_tag: 3613,
+            signer_name: "example.com".to_string(),
+            signature: from_base64(
+                "oL9krJun7xfBOIWcGHi7mag5/hdZrKWw15jPGrHpjQeRAvTdszaPD+QLs3fx8A4M3e23mRZ9VrbpMngwcrqNAg==",
+            )?,
+            class: DnsClass::IN,
+            ttl: 3600,
+        })
+    }
+
+    fn dnskey() -> Result<DnsRecord, Box<dyn std::error::Error>> {
+        Ok(DnsRecord::DNSKEY {
+            domain: "example.com".to_string(),
+            flags: 257,
+            protocol: 3,
+            algorithm: ED25519,
+            public_key: from_base64("l02Woi0iS8Aa25FQkUd9RMzZHJpBoRQwAQEX1SxZJA4=")?,
+            class: DnsClass::IN,
+            ttl: 3600,
+        })
+    }
+
+    #[test]
+    fn signed_data_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let expected = from_hex(
+            "000f0f0200000e1055d4fc6055b94ce00e1d076578616d706c6503636f6d00\
+             076578616d706c6503636f6d00000f000100000e100014000a046d61696c076578616d706c6503636f6d00",
+        )?;
+
+        // act
+        let actual = signed_data(&rrsig()?, &[&mx()])?;
+
+        // assert
+        assert_eq!(expected, actual);
+
+        Ok(())
+    }
+
+    #[test]
+    fn verify_rrset_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let rrsig = rrsig()?;
+        let keys = [dnskey()?];
+
+        // act
+        let actual = verify_rrset(&[&mx()], &[&rrsig], &keys, "example.com.", 1440000000);
+
+        // assert
+        assert!(actual.is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn verify_rrset_error() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let rrsig = rrsig()?;
+        let keys = [dnskey()?];
+        let mut tampered = mx();
+        if let DnsRecord::MX { priority, .. } = &mut tampered {
+            *priority = 20;
+        }
+
+        // act
+        let expired = verify_rrset(&[&mx()], &[&rrsig], &keys, "example.com", 1450000000);
+        let wrong_zone = verify_rrset(&[&mx()], &[&rrsig], &keys, "com", 1440000000);
+        let wrong_data = verify_rrset(&[&tampered], &[&rrsig], &keys, "example.com", 1440000000);
+
+        // assert
+        assert!(expired.is_err());
+        assert!(wrong_zone.is_err());
+        assert_eq!(
+            "RRSIG of example.com MX does not verify",
+            wrong_data.unwrap_err().to_string()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn label_count_ok() {
+        // assert
+        assert_eq!(3, label_count("*.a.example.com"));
+        assert_eq!(0, label_count(""));
+        assert_eq!(2, label_count("example.com."));
+    }
+}
@@ -0,0 +1,297 @@
+//! Signing record sets with the private key of a zone
+
+use super::super::model::{dns_class::DnsClass, dns_record::DnsRecord};
+use super::{crypto, signature};
+use ring::rand::SystemRandom;
+use ring::signature::{
+    EcdsaKeyPair, Ed25519KeyPair, KeyPair, RsaKeyPair, RsaPublicKeyComponents,
+    ECDSA_P256_SHA256_FIXED_SIGNING, ECDSA_P384_SHA384_FIXED_SIGNING, RSA_PKCS1_SHA256,
+};
+
+enum Pair {
+    Rsa(RsaKeyPair),
+    Ecdsa(u8, EcdsaKeyPair),
+    Ed25519(Ed25519KeyPair),
+}
+
+/// A private key of a zone, with the flags of its DNSKEY record
+pub struct SigningKey {
+    pub zone: String,
+    pub flags: u16,
+    pair: Pair,
+}
+
+impl SigningKey {
+    /// Generate a new key, returned along with its private key as a PKCS#8 document. RSA keys
+    /// can't be generated here, only loaded.
+    pub fn generate(
+        zone: &str,
+        flags: u16,
+        algorithm: u8,
+    ) -> Result<(SigningKey, Vec<u8>), Box<dyn std::error::Error>> {
+        let rng = SystemRandom::new();
+        let pkcs8 = match algorithm {
+            crypto::ECDSAP256SHA256 => {
+                EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng)
+            }
+            crypto::ECDSAP384SHA384 => {
+                EcdsaKeyPair::generate_pkcs8(&ECDSA_P384_SHA384_FIXED_SIGNING, &rng)
+            }
+            crypto::ED25519 => Ed25519KeyPair::generate_pkcs8(&rng),
+            _ => return Err(format!("Can't generate keys of algorithm {}", algorithm).into()),
+        }
+        .map_err(|_| "Key generation failed")?;
+
+        let key = SigningKey::from_pkcs8(zone, flags, algorithm, pkcs8.as_ref())?;
+
+        Ok((key, pkcs8.as_ref().to_vec()))
+    }
+
+    /// Load a key from its private key as a PKCS#8 document
+    pub fn from_pkcs8(
+        zone: &str,
+        flags: u16,
+        algorithm: u8,
+        pkcs8: &[u8],
+    ) -> Result<SigningKey, Box<dyn std::error::Error>> {
+        let rejected = |e: ring::error::KeyRejected| format!("Invalid private key: {}", e);
+        let pair = match algorithm {
+            crypto::RSASHA256 => Pair::Rsa(RsaKeyPair::from_pkcs8(pkcs8).map_err(rejected)?),
+            crypto::ECDSAP256SHA256 => Pair::Ecdsa(
+                algorithm,
+                EcdsaKeyPair::from_pkcs8(
+                    &ECDSA_P256_SHA256_FIXED_SIGNING,
+                    pkcs8,
+                    &SystemRandom::new(),
+                )
+                .map_err(rejected)?,
+            ),
+            crypto::ECDSAP384SHA384 => Pair::Ecdsa(
+                algorithm,
+                EcdsaKeyPair::from_pkcs8(
+                    &ECDSA_P384_SHA384_FIXED_SIGNING,
+                    pkcs8,
+                    &SystemRandom::new(),
+                )
+                .map_err(rejected)?,
+            ),
+            crypto::ED25519 => {
+                Pair::Ed25519(Ed25519KeyPair::from_pkcs8_maybe_unchecked(pkcs8).map_err(rejected)?)
+            }
+            _ => return Err(format!("Unsupported algorithm {}", algorithm).into()),
+        };
+
+        Ok(SigningKey {
+            zone: zone.to_string(),
+            flags,
+            pair,
+        })
+    }
+
+    /// Load a key from its raw private key: an RSAPrivateKey DER document for RSA, the private
+    /// scalar for ECDSA, or the seed for Ed25519, along with the public key of the DNSKEY record
+    pub fn from_private_key(
+        zone: &str,
+        flags: u16,
+        algorithm: u8,
+        private_key: &[u8],
+        public_key: &[u8],
+    ) -> Result<SigningKey, Box<dyn std::error::Error>> {
+        let rejected = |e: ring::error::KeyRejected| format!("Invalid private key: {}", e);
+        // NOTE: ring wants the uncompressed point with its 0x04 prefix
+        let point = [&[0x04], public_key].concat();
+        let pair = match algorithm {
+            crypto::RSASHA256 => Pair::Rsa(RsaKeyPair::from_der(private_key).map_err(rejected)?),
+            crypto::ECDSAP256SHA256 => Pair::Ecdsa(
+                algorithm,
+                EcdsaKeyPair::from_private_key_and_public_key(
+                    &ECDSA_P256_SHA256_FIXED_SIGNING,
+                    private_key,
+                    &point,
+                    &SystemRandom::new(),
+                )
+                .map_err(rejected)?,
+            ),
+            crypto::ECDSAP384SHA384 => Pair::Ecdsa(
+                algorithm,
+                EcdsaKeyPair::from_private_key_and_public_key(
+                    &ECDSA_P384_SHA384_FIXED_SIGNING,
+                    private_key,
+                    &point,
+                    &SystemRandom::new(),
+                )
+                .map_err(rejected)?,
+            ),
+            crypto::ED25519 => Pair::Ed25519(
+                Ed25519KeyPair::from_seed_and_public_key(private_key, public_key)
+                    .map_err(rejected)?,
+            ),
+            _ => return Err(format!("Unsupported algorithm {}", algorithm).into()),
+        };
+
+        Ok(SigningKey {
+            zone: zone.to_string(),
+            flags,
+            pair,
+        })
+    }
+
+    /// The DNSSEC algorithm number of the key
+    pub fn algorithm(&self) -> u8 {
+        match self.pair {
+            Pair::Rsa(_) => crypto::RSASHA256,
+            Pair::Ecdsa(algorithm, _) => algorithm,
+            Pair::Ed25519(_) => crypto::ED25519,
+        }
+    }
+
+    /// The public key in the format of the DNSKEY record
+    pub fn public_key(&self) -> Vec<u8> {
+        match &self.pair {
+            Pair::Rsa(pair) => {
+                // RFC 3110 §2: the exponent length, the exponent, then the modulus
+                let components = RsaPublicKeyComponents::<Vec<u8>>::from(pair.public());
+                let mut key = Vec::new();
+                if components.e.len() < 256 {
+                    key.push(components.e.len() as u8);
+                } else {
+                    key.push(0);
+                    key.extend_from_slice(&(components.e.len() as u16).to_be_bytes());
+                }
+                key.extend(components.e);
+                key.extend(components.n);
+
+                key
+            }
+            // NOTE: Strip the 0x04 prefix of the uncompressed point (RFC 6605 §4)
+            Pair::Ecdsa(_, pair) => pair.public_key().as_ref()[1..].to_vec(),
+            Pair::Ed25519(pair) => pair.public_key().as_ref().to_vec(),
+        }
+    }
+
+    /// The DNSKEY record publishing the key
+    pub fn dnskey(&self, ttl: u32) -> DnsRecord {
+        DnsRecord::DNSKEY {
+            domain: self.zone.clone(),
+            flags: self.flags,
+            protocol: 3,
+            algorithm: self.algorithm(),
+            public_key: self.public_key(),
+            class: DnsClass::IN,
+            ttl,
+        }
+    }
+
+    /// The key tag of the DNSKEY record of the key
+    pub fn key_tag(&self) -> u16 {
+        crypto::key_tag(&self.dnskey(0)).unwrap_or_default()
+    }
+
+    /// Sign data, giving the signature in the format of the RRSIG record
+    pub fn sign(&self, data: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let rng = SystemRandom::new();
+        match &self.pair {
+            Pair::Rsa(pair) => {
+                let mut signature = vec![0; pair.public().modulus_len()];
+                pair.sign(&RSA_PKCS1_SHA256, &rng, data, &mut signature)
+                    .map_err(|_| "Signing failed")?;
+
+                Ok(signature)
+            }
+            Pair::Ecdsa(_, pair) => Ok(pair
+                .sign(&rng, data)
+                .map_err(|_| "Signing failed")?
+                .as_ref()
+                .to_vec()),
+            Pair::Ed25519(pair) => Ok(pair.sign(data).as_ref().to_vec()),
+        }
+    }
+
+    /// The RRSIG record over a record set, valid from `inception` until `expiration` (seconds
+    /// since the epoch)
+    pub fn sign_rrset(
+        &self,
+        rrset: &[&DnsRecord],
+        inception: u32,
+        expiration: u32,
+    ) -> Result<DnsRecord, Box<dyn std::error::Error>> {
+        let first = rrset.first().ok_or("Empty record set")?;
+        let mut rrsig = DnsRecord::RRSIG {
+            domain: first.domain().to_string(),
+            type_covered: first.qtype(),
+            algorithm: self.algorithm(),
+            labels: signature::label_count(first.domain()),
+            original_ttl: first.ttl(),
+            expiration,
+            inception,
+            key_tag: self.key_tag(),
+            signer_name: self.zone.clone(),
+            signature: Vec::new(),
+            class: first.class(),
+            ttl: first.ttl(),
+        };
+
+        let data = signature::signed_data(&rrsig, rrset)?;
+        if let DnsRecord::RRSIG { signature, .. } = &mut rrsig {
+            *signature = self.sign(&data)?;
+        }
+
+        Ok(rrsig)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::model::{dns_class::DnsClass, query_type::QueryType};
+    use super::super::{crypto, signature};
+    use super::{DnsRecord, SigningKey};
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn sign_rrset_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let record = DnsRecord::A {
+            domain: "www.example.com".to_string(),
+            addr: Ipv4Addr::new(192, 0, 2, 1),
+            class: DnsClass::IN,
+            ttl: 300,
+        };
+        let rsa = SigningKey::from_pkcs8(
+            "example.com",
+            256,
+            crypto::RSASHA256,
+            include_bytes!("testdata/rsasha256.pk8"),
+        )?;
+        let keys = [
+            rsa,
+            SigningKey::generate("example.com", 256, crypto::ECDSAP256SHA256)?.0,
+            SigningKey::generate("example.com", 256, crypto::ECDSAP384SHA384)?.0,
+            SigningKey::generate("example.com", 256, crypto::ED25519)?.0,
+        ];
+
+        for key in keys {
+            // act
+            let rrsig = key.sign_rrset(&[&record], 1000, 2000)?;
+
+            // assert
+            let actual = signature::verify_rrset(
+                &[&record],
+                &[&rrsig],
+                &[key.dnskey(300)],
+                "example.com",
+                1500,
+            );
+            assert!(actual.is_ok(), "algorithm {}", key.algorithm());
+            assert!(matches!(
+                rrsig,
+                DnsRecord::RRSIG {
+                    type_covered: QueryType::A,
+                    labels: 3,
+                    ..
+                }
+            ));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,166 @@
+//! Trust anchors: the DS or DNSKEY records we trust without validation, where chains of trust
+//! start
+
+use super::super::encoding::{from_base64, from_hex};
+use super::super::model::{dns_class::DnsClass, dns_record::DnsRecord};
+use super::name;
+
+/// The DS records of the root key signing keys KSK-2017 and KSK-2024, published by IANA at
+/// https://data.iana.org/root-anchors/root-anchors.xml
+pub const ROOT_ANCHORS: &str = "\
+. IN DS 20326 8 2 E06D44B80B8F1D39A95C0B0D7C65D08458E880409BBC683457104237C7F8EC8D
+. IN DS 38696 8 2 683D2D0ACB8C9B712A1948B27F741219298D0A450D612C483AF444A4C0FB2B16
+";
+
+/// The trust anchors of the root zone
+pub fn root() -> Vec<DnsRecord> {
+    parse(ROOT_ANCHORS).unwrap_or_default()
+}
+
+/// Parse trust anchors in the zone file format, one DS or DNSKEY record per entry:
+/// `<owner> [<ttl>] [IN] DS|DNSKEY <rdata>`. Comments start with `;` and parentheses let an entry
+/// span several lines.
+pub fn parse(text: &str) -> Result<Vec<DnsRecord>, Box<dyn std::error::Error>> {
+    let mut anchors = Vec::new();
+    let mut entry: Vec<String> = Vec::new();
+    let mut depth = 0;
+
+    for line in text.lines() {
+        let line = line.split(';').next().unwrap_or_default();
+        for token in line.split_whitespace() {
+            for part in token.split_inclusive(['(', ')']) {
+                let word = part.trim_end_matches(['(', ')']);
+                if !word.is_empty() {
+                    entry.push(word.to_string());
+                }
+                match part.chars().last() {
+                    Some('(') => depth += 1,
+                    Some(')') => depth -= 1,
+                    _ => {}
+                }
+            }
+        }
+
+        if depth == 0 && !entry.is_empty() {
+            anchors.push(parse_entry(&entry)?);
+            entry.clear();
+        }
+    }
+
+    if depth != 0 || !entry.is_empty() {
+        return Err("Unbalanced parentheses in trust anchors".into());
+    }
+
+    Ok(anchors)
+}
+
+fn parse_entry(tokens: &[String]) -> Result<DnsRecord, Box<dyn std::error::Error>> {
+    let invalid = || format!("Invalid trust anchor: {}", tokens.join(" "));
+
+    let domain = name::normalize(tokens.first().ok_or_else(invalid)?);
+    let mut ttl = 0;
+    let mut rest = &tokens[1..];
+    while let Some(token) = rest.first() {
+        if let Ok(value) = token.parse() {
+            ttl = value;
+        } else if !token.eq_ignore_ascii_case("IN") {
+            break;
+        }
+        rest = &rest[1..];
+    }
+
+    let number = |index: usize| -> Result<u64, Box<dyn std::error::Error>> {
+        rest.get(index)
+            .and_then(|token| token.parse().ok())
+            .ok_or_else(|| invalid().into())
+    };
+
+    match rest.first().map(|token| token.to_uppercase()).as_deref() {
+        Some("DS") if rest.len() >= 5 => Ok(DnsRecord::DS {
+            domain,
+            key_tag: number(1)? as u16,
+            algorithm: number(2)? as u8,
+            digest_type: number(3)? as u8,
+            digest: from_hex(&rest[4..].concat())?,
+            class: DnsClass::IN,
+            ttl,
+        }),
+        Some("DNSKEY") if rest.len() >= 5 => Ok(DnsRecord::DNSKEY {
+            domain,
+            flags: number(1)? as u16,
+            protocol: number(2)? as u8,
+            algorithm: number(3)? as u8,
+            public_key: from_base64(&rest[4..].concat())?,
+            class: DnsClass::IN,
+            ttl,
+        }),
+        _ => Err(invalid().into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::crypto;
+    use super::{parse, root, DnsRecord};
+
+    #[test]
+    fn parse_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let text = "; RFC 8080 example\n\
+                    example.com. 3600 IN DNSKEY 257 3 15 (\n\
+                        l02Woi0iS8Aa25FQkUd9RMzZHJpBoRQwAQEX1SxZJA4= ) ; KSK\n\
+                    example.com. DS 3613 15 2 3aa5ab37efce57f737fc1627013fee07 \
+                        bdf241bd10f3b1964ab55c78e79a304b\n";
+
+        // act
+        let actual = parse(text)?;
+
+        // assert
+        assert_eq!(2, actual.len());
+        assert_eq!(Some(3613), crypto::key_tag(&actual[0]));
+        assert!(crypto::ds_matches(&actual[1], &actual[0]));
+        assert!(parse("example.com. IN A 192.0.2.1").is_err());
+        assert!(parse("example.com. IN DS ( 3613 15 2 3aa5").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn root_ok() {
+        // act
+        let actual = root();
+
+        // assert
+        assert_eq!(2, actual.len());
+        assert!(matches!(
+            actual[0],
+            DnsRecord::DS {
+                key_tag: 20326,
+                algorithm: 8,
+                digest_type: 2,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn root_key_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        // The root key signing key KSK-2017, as published in the root zone
+        let text = ". IN DNSKEY 257 3 8 ( \
+            AwEAAaz/tAm8yTn4Mfeh5eyI96WSVexTBAvkMgJzkKTOiW1vkIbzxeF3+/4RgWOq7HrxRixH \
+            lFlExOLAJr5emLvN7SWXgnLh4+B5xQlNVz8Og8kvArMtNROxVQuCaSnIDdD5LKyWbRd2n9WG \
+            e2R8PzgCmr3EgVLrjyBxWezF0jLHwVN8efS3rCj/EWgvIWgb9tarpVUDK/b58Da+sqqls3eN \
+            buv7pr+eoZG+SrDK6nWeL3c6H5Apxz7LjVc1uTIdsIXxuOLYA4/ilBmSVIzuDWfdRUfhHdY6 \
+            +cn8HFRm+2hM8AnXGXws9555KrUB5qihylGa8subX2Nn6UwNR1AkUTV74bU= )";
+
+        // act
+        let actual = parse(text)?;
+
+        // assert
+        assert_eq!(Some(20326), crypto::key_tag(&actual[0]));
+        assert!(crypto::ds_matches(&root()[0], &actual[0]));
+
+        Ok(())
+    }
+}
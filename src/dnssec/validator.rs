@@ -0,0 +1,589 @@
+//! The chain of trust (RFC 4035 §5): from a trust anchor, through the DS and DNSKEY records of
+//! each delegation the resolver follows, down to the RRSIG records of the answer
+
+use super::super::model::{
+    dns_packet::DnsPacket, dns_record::DnsRecord, query_type::QueryType, result_code::ResultCode,
+};
+use super::anchor_store::AnchorStore;
+use super::{crypto, denial, name, signature};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The security status of an answer (RFC 4035 §4.3)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Security {
+    /// Every record of the answer is signed by a chain of trust from a trust anchor
+    Secure,
+    /// The answer comes from a zone proven to be unsigned, or there is no trust anchor
+    Insecure,
+    /// The answer should be signed, but its signatures or proofs are missing or wrong
+    Bogus(String),
+}
+
+/// Validates answers of the resolver from trust anchors
+#[derive(Debug, Clone)]
+pub struct Validator {
+    /// DS or DNSKEY records of the root zone we trust
+    pub anchors: Vec<DnsRecord>,
+    /// Validate at this time (seconds since the epoch) instead of the current time
+    pub now: Option<u32>,
+    /// Trust anchors kept up to date from the root keys we see, used instead of `anchors`
+    pub store: Option<RefCell<AnchorStore>>,
+    /// The verified keys of the root zone, and until when they may be used without asking again
+    root_keys: RefCell<Option<(Vec<DnsRecord>, u32)>>,
+    /// Why the store couldn't be updated from the root keys last seen, until it is taken
+    store_error: RefCell<Option<String>>,
+}
+
+/// What we know about the keys of the zone the resolver is asking
+#[derive(Debug, Clone)]
+enum Trust {
+    Secure(Vec<DnsRecord>),
+    Insecure,
+    Bogus(String),
+}
+
+/// The chain of trust down to the zone the resolver is asking, built as delegations are followed
+pub struct ChainOfTrust<'a> {
+    validator: &'a Validator,
+    zone: String,
+    trust: Trust,
+}
+
+impl Validator {
+    pub fn new(anchors: Vec<DnsRecord>) -> Self {
+        Validator {
+            anchors,
+            now: None,
+            store: None,
+            root_keys: RefCell::new(None),
+            store_error: RefCell::new(None),
+        }
+    }
+
+    /// A validator trusting the anchors of the store, which follows the rollovers of the root keys
+    pub fn with_store(store: AnchorStore) -> Self {
+        Validator {
+            anchors: Vec::new(),
+            now: None,
+            store: Some(RefCell::new(store)),
+            root_keys: RefCell::new(None),
+            store_error: RefCell::new(None),
+        }
+    }
+
+    fn now(&self) -> u32 {
+        self.now.unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_secs() as u32)
+                .unwrap_or_default()
+        })
+    }
+
+    /// Start a chain of trust at the root zone, whose keys are asked from a root server and
+    /// checked against the trust anchors. Verified keys are kept until their TTL runs out or
+    /// their RRSIG record expires.
+    pub fn start<F>(
+        &self,
+        server: Ipv4Addr,
+        lookup: &mut F,
+    ) -> Result<ChainOfTrust<'_>, Box<dyn std::error::Error>>
+    where
+        F: FnMut(&str, QueryType, Ipv4Addr) -> Result<DnsPacket, Box<dyn std::error::Error>>,
+    {
+        let anchors: Vec<DnsRecord> = match &self.store {
+            Some(store) => store.borrow().trusted(),
+            None => self.anchors.clone(),
+        }
+        .into_iter()
+        .filter(|anchor| name::normalize(anchor.domain()).is_empty())
+        .collect();
+
+        let trust = if anchors.is_empty() {
+            Trust::Insecure
+        } else if let Some(keys) = self.cached_root_keys() {
+            Trust::Secure(keys)
+        } else {
+            let response = lookup("", QueryType::DNSKEY, server)?;
+            let trust = self.trusted_keys("", &response, &anchors);
+            if let Trust::Secure(keys) = &trust {
+                self.cache_root_keys(keys, &response.answers);
+            }
+
+            // NOTE: Every root DNSKEY record set we see moves the RFC 5011 timers on
+            if let Some(store) = &self.store {
+                if let Err(e) = store.borrow_mut().observe(&response.answers, self.now()) {
+                    *self.store_error.borrow_mut() = Some(e.to_string());
+                }
+            }
+
+            trust
+        };
+
+        Ok(ChainOfTrust {
+            validator: self,
+            zone: String::new(),
+            trust,
+        })
+    }
+
+    /// Why the trust anchors couldn't be updated from the root keys, if they couldn't since this
+    /// was last asked, for the caller to report
+    pub fn take_store_error(&self) -> Option<String> {
+        self.store_error.borrow_mut().take()
+    }
+
+    fn cached_root_keys(&self) -> Option<Vec<DnsRecord>> {
+        let now = self.now();
+
+        match &*self.root_keys.borrow() {
+            Some((keys, until)) if (until.wrapping_sub(now) as i32) > 0 => Some(keys.clone()),
+            _ => None,
+        }
+    }
+
+    fn cache_root_keys(&self, keys: &[DnsRecord], answers: &[DnsRecord]) {
+        let now = self.now();
+        let ttl = keys.iter().map(|key| key.ttl()).min().unwrap_or_default();
+        // NOTE: Expiration times are compared with serial number arithmetic (RFC 4034 §3.1.5)
+        let until = signature::rrsigs_for(answers, "", QueryType::DNSKEY)
+            .into_iter()
+            .filter_map(|rrsig| match rrsig {
+                DnsRecord::RRSIG { expiration, .. } => Some(*expiration),
+                _ => None,
+            })
+            .fold(now.wrapping_add(ttl), |until, expiration| {
+                if (expiration.wrapping_sub(until) as i32) < 0 {
+                    expiration
+                } else {
+                    until
+                }
+            });
+
+        *self.root_keys.borrow_mut() = Some((keys.to_vec(), until));
+    }
+
+    /// The keys of a zone, from the DNSKEY record set signed by a key the DS records (or trust
+    /// anchors) point at
+    fn trusted_keys(&self, zone: &str, response: &DnsPacket, anchors: &[DnsRecord]) -> Trust {
+        let supported: Vec<&DnsRecord> = anchors
+            .iter()
+            .filter(|anchor| match anchor {
+                DnsRecord::DS {
+                    algorithm,
+                    digest_type,
+                    ..
+                } => {
+                    crypto::is_supported_algorithm(*algorithm)
+                        && crypto::is_supported_digest(*digest_type)
+                }
+                DnsRecord::DNSKEY { algorithm, .. } => crypto::is_supported_algorithm(*algorithm),
+                _ => false,
+            })
+            .collect();
+        // NOTE: A zone signed only with algorithms we don't know is treated as unsigned
+        // (RFC 4035 §5.2)
+        if supported.is_empty() {
+            return Trust::Insecure;
+        }
+
+        let dnskeys: Vec<&DnsRecord> = response
+            .answers
+            .iter()
+            .filter(|record| {
+                matches!(record, DnsRecord::DNSKEY { .. })
+                    && name::normalize(record.domain()) == name::normalize(zone)
+            })
+            .collect();
+        if dnskeys.is_empty() {
+            return Trust::Bogus(format!("No DNSKEY records for {}", fqdn(zone)));
+        }
+
+        let entry_keys: Vec<DnsRecord> = dnskeys
+            .iter()
+            .filter(|dnskey| {
+                supported.iter().any(|anchor| match anchor {
+                    DnsRecord::DS { .. } => crypto::ds_matches(anchor, dnskey),
+                    _ => same_key(anchor, dnskey),
+                })
+            })
+            .map(|dnskey| (*dnskey).clone())
+            .collect();
+
+        let rrsigs = signature::rrsigs_for(&response.answers, zone, QueryType::DNSKEY);
+        match signature::verify_rrset(&dnskeys, &rrsigs, &entry_keys, zone, self.now()) {
+            Ok(_) => Trust::Secure(dnskeys.into_iter().cloned().collect()),
+            Err(e) => Trust::Bogus(format!("DNSKEY of {}: {}", fqdn(zone), e)),
+        }
+    }
+}
+
+impl ChainOfTrust<'_> {
+    /// The zone the chain of trust reaches
+    pub fn zone(&self) -> &str {
+        &self.zone
+    }
+
+    /// Follow a referral to the child zone: its DS records must be signed by the current zone,
+    /// and point at its keys, or be proven absent which makes the child zone unsigned
+    pub fn follow_referral<F>(
+        &mut self,
+        child: &str,
+        referral: &DnsPacket,
+        server: Ipv4Addr,
+        lookup: &mut F,
+    ) -> Result<(), Box<dyn std::error::Error>>
+    where
+        F: FnMut(&str, QueryType, Ipv4Addr) -> Result<DnsPacket, Box<dyn std::error::Error>>,
+    {
+        let child = name::normalize(child);
+
+        if let Trust::Secure(keys) = &self.trust {
+            if child == self.zone || !name::is_subdomain(&child, &self.zone) {
+                self.trust = Trust::Bogus(format!(
+                    "Referral to {} is not below {}",
+                    fqdn(&child),
+                    fqdn(&self.zone)
+                ));
+            } else {
+                self.trust =
+                    self.child_trust(&child, &referral.authorities, keys, server, lookup)?;
+            }
+        }
+        self.zone = child;
+
+        Ok(())
+    }
+
+    /// Check the response to the question with the chain of trust. Records signed by another zone,
+    /// as the target of a CNAME record can be, are checked with the keys `signer_keys` returns
+    /// for that zone, or none when they can't be trusted.
+    pub fn validate<F, K>(
+        &mut self,
+        qname: &str,
+        qtype: QueryType,
+        response: &DnsPacket,
+        server: Ipv4Addr,
+        lookup: &mut F,
+        signer_keys: &mut K,
+    ) -> Result<Security, Box<dyn std::error::Error>>
+    where
+        F: FnMut(&str, QueryType, Ipv4Addr) -> Result<DnsPacket, Box<dyn std::error::Error>>,
+        K: FnMut(&str, &mut F) -> Result<Option<Vec<DnsRecord>>, Box<dyn std::error::Error>>,
+    {
+        // NOTE: A server may answer from a child zone it also serves, without a referral. The
+        // chain of trust then goes down to the signer, or to a proven unsigned delegation.
+        if let Trust::Secure(_) = self.trust {
+            let signer = response
+                .answers
+                .iter()
+                .chain(response.authorities.iter())
+                .find_map(|record| match record {
+                    DnsRecord::RRSIG { signer_name, .. } => Some(name::normalize(signer_name)),
+                    _ => None,
+                });
+
+            match signer {
+                Some(signer) if signer != self.zone && name::is_subdomain(&signer, &self.zone) => {
+                    self.descend(&signer, server, lookup)?
+                }
+                None => self.descend(qname, server, lookup)?,
+                _ => {}
+            }
+        }
+
+        let keys = match &self.trust {
+            Trust::Secure(keys) => keys.clone(),
+            Trust::Insecure => return Ok(Security::Insecure),
+            Trust::Bogus(reason) => return Ok(Security::Bogus(reason.clone())),
+        };
+        let mut zones = HashMap::from([(self.zone.clone(), Some(keys))]);
+        let mut keys_of =
+            |zone: &str| -> Result<Option<Vec<DnsRecord>>, Box<dyn std::error::Error>> {
+                let zone = name::normalize(zone);
+                if let Some(keys) = zones.get(&zone) {
+                    return Ok(keys.clone());
+                }
+                let keys = signer_keys(&zone, lookup)?;
+                zones.insert(zone, keys.clone());
+                Ok(keys)
+            };
+
+        self.check_response(qname, qtype, response, &mut keys_of)
+    }
+
+    /// Walk down from the current zone towards `target`, asking the server for the DS records
+    /// of each name in between
+    fn descend<F>(
+        &mut self,
+        target: &str,
+        server: Ipv4Addr,
+        lookup: &mut F,
+    ) -> Result<(), Box<dyn std::error::Error>>
+    where
+        F: FnMut(&str, QueryType, Ipv4Addr) -> Result<DnsPacket, Box<dyn std::error::Error>>,
+    {
+        let start = name::labels(&self.zone).len() + 1;
+
+        for count in start..=name::labels(target).len() {
+            let Trust::Secure(keys) = &self.trust else {
+                break;
+            };
+
+            let child = name::ancestor(target, count);
+            let response = lookup(&child, QueryType::DS, server)?;
+            let has_ds = response.answers.iter().any(|record| {
+                matches!(record, DnsRecord::DS { .. }) && name::normalize(record.domain()) == child
+            });
+            let insecure = !has_ds
+                && verified_denial(
+                    &response.authorities,
+                    keys,
+                    &self.zone,
+                    self.validator.now(),
+                )
+                .is_ok_and(|denial| denial::proves_insecure_delegation(&child, &denial));
+
+            // NOTE: Names without DS records which aren't delegations stay in the current zone
+            if has_ds || insecure {
+                let records = if has_ds {
+                    &response.answers
+                } else {
+                    &response.authorities
+                };
+                self.trust = self.child_trust(&child, records, keys, server, lookup)?;
+                self.zone = child;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The trust in a child zone, from the records about its delegation signed by the parent
+    fn child_trust<F>(
+        &self,
+        child: &str,
+        records: &[DnsRecord],
+        keys: &[DnsRecord],
+        server: Ipv4Addr,
+        lookup: &mut F,
+    ) -> Result<Trust, Box<dyn std::error::Error>>
+    where
+        F: FnMut(&str, QueryType, Ipv4Addr) -> Result<DnsPacket, Box<dyn std::error::Error>>,
+    {
+        let now = self.validator.now();
+        let ds: Vec<&DnsRecord> = records
+            .iter()
+            .filter(|record| {
+                matches!(record, DnsRecord::DS { .. }) && name::normalize(record.domain()) == child
+            })
+            .collect();
+
+        if ds.is_empty() {
+            let proven = verified_denial(records, keys, &self.zone, now)
+                .is_ok_and(|denial| denial::proves_insecure_delegation(child, &denial));
+
+            return Ok(if proven {
+                Trust::Insecure
+            } else {
+                Trust::Bogus(format!(
+                    "No DS records for {} and no proof of an unsigned delegation",
+                    fqdn(child)
+                ))
+            });
+        }
+
+        let rrsigs = signature::rrsigs_for(records, child, QueryType::DS);
+        if let Err(e) = signature::verify_rrset(&ds, &rrsigs, keys, &self.zone, now) {
+            return Ok(Trust::Bogus(format!("DS of {}: {}", fqdn(child), e)));
+        }
+
+        let ds: Vec<DnsRecord> = ds.into_iter().cloned().collect();
+        let response = lookup(child, QueryType::DNSKEY, server)?;
+
+        Ok(self.validator.trusted_keys(child, &response, &ds))
+    }
+
+    /// Check the records of a response from a zone whose keys we trust, each record set with the
+    /// keys of the zone that signed it
+    fn check_response<K>(
+        &self,
+        qname: &str,
+        qtype: QueryType,
+        response: &DnsPacket,
+        keys_of: &mut K,
+    ) -> Result<Security, Box<dyn std::error::Error>>
+    where
+        K: FnMut(&str) -> Result<Option<Vec<DnsRecord>>, Box<dyn std::error::Error>>,
+    {
+        let now = self.validator.now();
+
+        for rrset in signature::rrsets(&response.answers) {
+            let owner = rrset[0].domain();
+            let rrsigs = signature::rrsigs_for(&response.answers, owner, rrset[0].qtype());
+            let signer = signer(&rrsigs).unwrap_or_else(|| self.zone.clone());
+            let Some(keys) = keys_of(&signer)? else {
+                return Ok(Security::Bogus(format!(
+                    "No trusted keys for the signer {}",
+                    fqdn(&signer)
+                )));
+            };
+            let rrsig = match signature::verify_rrset(&rrset, &rrsigs, &keys, &signer, now) {
+                Ok(rrsig) => rrsig,
+                Err(e) => return Ok(Security::Bogus(e.to_string())),
+            };
+
+            // NOTE: Records expanded from a wildcard need a proof that no closer name exists
+            if let DnsRecord::RRSIG { labels, .. } = rrsig {
+                if *labels < signature::label_count(owner) {
+                    let proven = verified_denial(&response.authorities, &keys, &signer, now)
+                        .is_ok_and(|denial| {
+                            denial::proves_wildcard_answer(owner, *labels, &denial)
+                        });
+                    if !proven {
+                        return Ok(Security::Bogus(format!(
+                            "No proof for the wildcard expansion of {}",
+                            fqdn(owner)
+                        )));
+                    }
+                }
+            }
+        }
+
+        // NOTE: Negative answers are about the end of the CNAME chain in the answer
+        let target = cname_target(qname, &response.answers);
+        let answered = response.answers.iter().any(|record| {
+            let owner = name::normalize(record.domain());
+            record.qtype() == qtype && (owner == target || owner == name::normalize(qname))
+        });
+        if answered && response.header.response_code != ResultCode::NXDOMAIN {
+            return Ok(Security::Secure);
+        }
+
+        // NOTE: The proof comes from the zone of the target, which signed the NSEC records
+        let proofs: Vec<&DnsRecord> = response
+            .authorities
+            .iter()
+            .filter(|record| match record {
+                DnsRecord::RRSIG { type_covered, .. } => {
+                    *type_covered == QueryType::NSEC || *type_covered == QueryType::NSEC3
+                }
+                _ => false,
+            })
+            .collect();
+        let signer = signer(&proofs).unwrap_or_else(|| self.zone.clone());
+        let Some(keys) = keys_of(&signer)? else {
+            return Ok(Security::Bogus(format!(
+                "No trusted keys for the signer {}",
+                fqdn(&signer)
+            )));
+        };
+        let denial = match verified_denial(&response.authorities, &keys, &signer, now) {
+            Ok(denial) => denial,
+            Err(e) => return Ok(Security::Bogus(e.to_string())),
+        };
+        let proven = if response.header.response_code == ResultCode::NXDOMAIN {
+            denial::proves_nxdomain(&target, &denial)
+        } else {
+            denial::proves_nodata(&target, qtype, &denial)
+        };
+
+        Ok(if proven {
+            Security::Secure
+        } else {
+            Security::Bogus(format!(
+                "No proof of the negative answer for {} {}",
+                fqdn(&target),
+                qtype
+            ))
+        })
+    }
+}
+
+/// The NSEC and NSEC3 records among the records, all verified with the keys of the zone
+fn verified_denial(
+    records: &[DnsRecord],
+    keys: &[DnsRecord],
+    zone: &str,
+    now: u32,
+) -> Result<Vec<DnsRecord>, Box<dyn std::error::Error>> {
+    let mut result = Vec::new();
+
+    for rrset in signature::rrsets(records) {
+        let qtype = rrset[0].qtype();
+        if qtype != QueryType::NSEC && qtype != QueryType::NSEC3 {
+            continue;
+        }
+
+        let rrsigs = signature::rrsigs_for(records, rrset[0].domain(), qtype);
+        signature::verify_rrset(&rrset, &rrsigs, keys, zone, now)?;
+        result.extend(rrset.into_iter().cloned());
+    }
+
+    Ok(result)
+}
+
+/// The zone that made the first of the RRSIG records
+fn signer(rrsigs: &[&DnsRecord]) -> Option<String> {
+    rrsigs.iter().find_map(|rrsig| match rrsig {
+        DnsRecord::RRSIG { signer_name, .. } => Some(name::normalize(signer_name)),
+        _ => None,
+    })
+}
+
+/// Follow the CNAME records of the answer from `qname`
+fn cname_target(qname: &str, answers: &[DnsRecord]) -> String {
+    let mut target = name::normalize(qname);
+
+    // NOTE: Bounded by the number of records, so a CNAME loop can't hang us
+    for _ in 0..answers.len() {
+        let next = answers.iter().find_map(|record| match record {
+            DnsRecord::CNAME { domain, host, .. } if name::normalize(domain) == target => {
+                Some(name::normalize(host))
+            }
+            _ => None,
+        });
+        match next {
+            Some(next) => target = next,
+            None => break,
+        }
+    }
+
+    target
+}
+
+/// Whether both DNSKEY records hold the same key, whatever their TTL
+fn same_key(a: &DnsRecord, b: &DnsRecord) -> bool {
+    match (a, b) {
+        (
+            DnsRecord::DNSKEY {
+                flags,
+                protocol,
+                algorithm,
+                public_key,
+                ..
+            },
+            DnsRecord::DNSKEY {
+                flags: other_flags,
+                protocol: other_protocol,
+                algorithm: other_algorithm,
+                public_key: other_public_key,
+                ..
+            },
+        ) => {
+            flags & crypto::FLAG_REVOKE == 0
+                && flags == other_flags
+                && protocol == other_protocol
+                && algorithm == other_algorithm
+                && public_key == other_public_key
+        }
+        _ => false,
+    }
+}
+
+fn fqdn(name: &str) -> String {
+    format!("{}.", name::normalize(name))
+}
@@ -0,0 +1,580 @@
+//! Signing whole zones (RFC 4035 §2): the DNSKEY records of the keys, an NSEC or NSEC3 chain
+//! over the names of the zone, and RRSIG records over every authoritative record set
+
+use super::super::model::{dns_class::DnsClass, dns_record::DnsRecord, query_type::QueryType};
+use super::super::zone::Zone;
+use super::signer::SigningKey;
+use super::{crypto, denial, name, signature};
+
+/// How long signatures made online stay valid, 14 days
+pub const SIGNATURE_VALIDITY: u32 = 14 * 24 * 3600;
+
+/// Zones signed online are signed again when their signatures expire within 7 days
+pub const RESIGN_BEFORE: u32 = 7 * 24 * 3600;
+
+/// Signatures start an hour in the past, for validators whose clock is behind ours
+const INCEPTION_OFFSET: u32 = 3600;
+
+/// How the zone proves names and types don't exist
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Denial {
+    Nsec,
+    /// With opt-out, unsigned delegations are left out of the chain (RFC 5155 §6)
+    Nsec3 {
+        salt: Vec<u8>,
+        iterations: u16,
+        opt_out: bool,
+    },
+}
+
+/// Sign a zone with its keys: the key signing keys (with the SEP flag) sign the DNSKEY record
+/// set and the zone signing keys everything else. A zone with only one kind of keys is signed
+/// with them all. Earlier DNSSEC records of the zone are replaced.
+pub fn sign_zone(
+    zone: &Zone,
+    keys: &[SigningKey],
+    denial: &Denial,
+    inception: u32,
+    expiration: u32,
+) -> Result<Zone, Box<dyn std::error::Error>> {
+    if keys.is_empty() {
+        return Err(format!("No keys to sign {} with", zone.origin).into());
+    }
+    let (soa_ttl, minimum) = match zone.soa() {
+        Some(DnsRecord::SOA { ttl, minimum, .. }) => (*ttl, *minimum),
+        _ => return Err(format!("No SOA record at the apex of {}", zone.origin).into()),
+    };
+
+    let mut records: Vec<DnsRecord> = zone
+        .records
+        .iter()
+        .filter(|record| {
+            !matches!(
+                record,
+                DnsRecord::RRSIG { .. }
+                    | DnsRecord::NSEC { .. }
+                    | DnsRecord::NSEC3 { .. }
+                    | DnsRecord::NSEC3PARAM { .. }
+            )
+        })
+        .cloned()
+        .collect();
+    for key in keys {
+        let public_key = key.public_key();
+        let published = records.iter().any(|record| {
+            matches!(record, DnsRecord::DNSKEY { public_key: other, .. } if *other == public_key)
+        });
+        if !published {
+            records.push(key.dnskey(soa_ttl));
+        }
+    }
+
+    let names = Names::new(&zone.origin, &records);
+    // NOTE: Negative answers are cached for the smaller of the SOA TTL and minimum (RFC 9077)
+    let denial_ttl = soa_ttl.min(minimum);
+    match denial {
+        Denial::Nsec => {
+            let chain = nsec_chain(&names, &records, denial_ttl);
+            records.extend(chain);
+        }
+        Denial::Nsec3 {
+            salt,
+            iterations,
+            opt_out,
+        } => {
+            let chain = nsec3_chain(
+                zone,
+                &names,
+                &records,
+                denial_ttl,
+                salt,
+                *iterations,
+                *opt_out,
+            )?;
+            records.extend(chain);
+            records.push(DnsRecord::NSEC3PARAM {
+                domain: zone.origin.clone(),
+                hash_algorithm: denial::NSEC3_SHA1,
+                flags: 0,
+                iterations: *iterations,
+                salt: salt.clone(),
+                class: zone.soa().map(DnsRecord::class).unwrap_or(DnsClass::IN),
+                ttl: 0,
+            });
+        }
+    }
+
+    let key_signing: Vec<&SigningKey> = keys
+        .iter()
+        .filter(|key| key.flags & crypto::FLAG_SECURE_ENTRY_POINT != 0)
+        .collect();
+    let zone_signing: Vec<&SigningKey> = keys
+        .iter()
+        .filter(|key| key.flags & crypto::FLAG_SECURE_ENTRY_POINT == 0)
+        .collect();
+    let all: Vec<&SigningKey> = keys.iter().collect();
+
+    let mut rrsigs = Vec::new();
+    for rrset in signature::rrsets(&records) {
+        let owner = name::normalize(rrset[0].domain());
+        let qtype = rrset[0].qtype();
+        // NOTE: Only the DS and NSEC records of a delegation are the parent's to sign, and glue
+        // isn't signed at all
+        if names.is_glue(&owner)
+            || (names.is_cut(&owner) && qtype != QueryType::DS && qtype != QueryType::NSEC)
+        {
+            continue;
+        }
+
+        let signers = match (
+            qtype == QueryType::DNSKEY,
+            key_signing.is_empty(),
+            zone_signing.is_empty(),
+        ) {
+            (true, false, _) => &key_signing,
+            (false, _, false) => &zone_signing,
+            _ => &all,
+        };
+        for key in signers {
+            rrsigs.push(key.sign_rrset(&rrset, inception, expiration)?);
+        }
+    }
+    records.extend(rrsigs);
+    // NOTE: Stable sorting keeps the records of each set in order
+    records.sort_by(|a, b| {
+        name::canonical_cmp(a.domain(), b.domain()).then_with(|| file_order(a).cmp(&file_order(b)))
+    });
+
+    Ok(Zone {
+        origin: zone.origin.clone(),
+        records,
+    })
+}
+
+/// Where a record goes among those of its owner name in a zone file: the SOA record first, then
+/// by type, each RRSIG record after the records it covers
+fn file_order(record: &DnsRecord) -> (bool, u16, bool) {
+    let qtype = match record {
+        DnsRecord::RRSIG { type_covered, .. } => *type_covered,
+        _ => record.qtype(),
+    };
+
+    (
+        qtype != QueryType::SOA,
+        qtype.to_num(),
+        record.qtype() == QueryType::RRSIG,
+    )
+}
+
+/// The names of a zone, with its delegation points
+struct Names {
+    /// Authoritative names and delegation points in canonical order, leaving out glue
+    owners: Vec<String>,
+    cuts: Vec<String>,
+}
+
+impl Names {
+    fn new(origin: &str, records: &[DnsRecord]) -> Self {
+        let cuts: Vec<String> = records
+            .iter()
+            .filter(|record| record.qtype() == QueryType::NS)
+            .map(|record| name::normalize(record.domain()))
+            .filter(|owner| owner != origin)
+            .collect();
+
+        let mut names = Names {
+            owners: Vec::new(),
+            cuts,
+        };
+        let mut owners: Vec<String> = records
+            .iter()
+            .map(|record| name::normalize(record.domain()))
+            .filter(|owner| !names.is_glue(owner))
+            .collect();
+        owners.sort_by(|a, b| name::canonical_cmp(a, b));
+        owners.dedup();
+        names.owners = owners;
+
+        names
+    }
+
+    fn is_cut(&self, owner: &str) -> bool {
+        self.cuts.iter().any(|cut| cut == owner)
+    }
+
+    /// Whether the name is below a delegation point, where only glue records belong
+    fn is_glue(&self, owner: &str) -> bool {
+        self.cuts
+            .iter()
+            .any(|cut| cut != owner && name::is_subdomain(owner, cut))
+    }
+}
+
+/// The types of the records owned by the name, in the order of their numbers
+fn types_at(records: &[DnsRecord], owner: &str) -> Vec<QueryType> {
+    let mut types: Vec<QueryType> = records
+        .iter()
+        .filter(|record| name::normalize(record.domain()) == owner)
+        .map(DnsRecord::qtype)
+        .collect();
+    types.sort_by_key(QueryType::to_num);
+    types.dedup();
+
+    types
+}
+
+/// Whether the record sets of the name get signed: all but those of unsigned delegations
+fn is_signed(names: &Names, types: &[QueryType], owner: &str) -> bool {
+    !types.is_empty() && (!names.is_cut(owner) || types.contains(&QueryType::DS))
+}
+
+fn with_types(mut types: Vec<QueryType>, extra: &[QueryType]) -> Vec<QueryType> {
+    types.extend_from_slice(extra);
+    types.sort_by_key(QueryType::to_num);
+    types.dedup();
+
+    types
+}
+
+/// The NSEC chain (RFC 4034 §4): each name points at the next in canonical order, the last one
+/// back at the apex
+fn nsec_chain(names: &Names, records: &[DnsRecord], ttl: u32) -> Vec<DnsRecord> {
+    let class = records
+        .first()
+        .map(DnsRecord::class)
+        .unwrap_or(DnsClass::IN);
+
+    names
+        .owners
+        .iter()
+        .enumerate()
+        .map(|(i, owner)| {
+            let types = types_at(records, owner);
+            // NOTE: The NSEC record of a delegation is signed even when its NS records aren't
+            let types = with_types(types, &[QueryType::RRSIG, QueryType::NSEC]);
+
+            DnsRecord::NSEC {
+                domain: owner.clone(),
+                next_domain: names.owners[(i + 1) % names.owners.len()].clone(),
+                types,
+                class,
+                ttl,
+            }
+        })
+        .collect()
+}
+
+/// The NSEC3 chain (RFC 5155 §7.1): the hashed names of the zone, empty non-terminals included,
+/// each pointing at the next hash
+fn nsec3_chain(
+    zone: &Zone,
+    names: &Names,
+    records: &[DnsRecord],
+    ttl: u32,
+    salt: &[u8],
+    iterations: u16,
+    opt_out: bool,
+) -> Result<Vec<DnsRecord>, Box<dyn std::error::Error>> {
+    let class = records
+        .first()
+        .map(DnsRecord::class)
+        .unwrap_or(DnsClass::IN);
+    let origin_labels = name::labels(&zone.origin).len();
+
+    let mut hashed: Vec<(Vec<u8>, Vec<QueryType>)> = Vec::new();
+    let mut seen: Vec<String> = Vec::new();
+    for owner in &names.owners {
+        let types = types_at(records, owner);
+        if opt_out && names.is_cut(owner) && !types.contains(&QueryType::DS) {
+            continue;
+        }
+
+        // NOTE: Empty non-terminals between the name and the apex are in the chain too
+        for count in origin_labels..=name::labels(owner).len() {
+            let ancestor = name::ancestor(owner, count);
+            if seen.contains(&ancestor) {
+                continue;
+            }
+            let types = if ancestor == *owner {
+                types.clone()
+            } else {
+                types_at(records, &ancestor)
+            };
+            let mut types = if is_signed(names, &types, &ancestor) {
+                with_types(types, &[QueryType::RRSIG])
+            } else {
+                types
+            };
+            if ancestor == zone.origin {
+                types = with_types(types, &[QueryType::NSEC3PARAM]);
+            }
+
+            hashed.push((denial::nsec3_hash(&ancestor, salt, iterations)?, types));
+            seen.push(ancestor);
+        }
+    }
+    hashed.sort();
+
+    let flags = if opt_out { denial::NSEC3_OPT_OUT } else { 0 };
+    Ok((0..hashed.len())
+        .map(|i| DnsRecord::NSEC3 {
+            domain: zone.nsec3_owner(&hashed[i].0),
+            hash_algorithm: denial::NSEC3_SHA1,
+            flags,
+            iterations,
+            salt: salt.to_vec(),
+            next_hashed_owner: hashed[(i + 1) % hashed.len()].0.clone(),
+            types: hashed[i].1.clone(),
+            class,
+            ttl,
+        })
+        .collect())
+}
+
+/// A zone served with signatures made online, signed again before they expire. Without keys,
+/// the zone is served unsigned.
+pub struct OnlineSigner {
+    pub zone: Zone,
+    keys: Vec<SigningKey>,
+    denial: Denial,
+    signed: Option<(Zone, u32)>,
+}
+
+impl OnlineSigner {
+    pub fn new(zone: Zone, keys: Vec<SigningKey>, denial: Denial) -> Self {
+        OnlineSigner {
+            zone,
+            keys,
+            denial,
+            signed: None,
+        }
+    }
+
+    /// The zone to answer from at `now` (seconds since the epoch), signing it first when it has
+    /// no signatures yet or they are about to expire
+    pub fn signed(&mut self, now: u32) -> Result<&Zone, Box<dyn std::error::Error>> {
+        if self.keys.is_empty() {
+            return Ok(&self.zone);
+        }
+
+        let fresh = matches!(
+            self.signed,
+            Some((_, expiration)) if expiration.saturating_sub(now) > RESIGN_BEFORE
+        );
+        if !fresh {
+            let expiration = now.saturating_add(SIGNATURE_VALIDITY);
+            let zone = sign_zone(
+                &self.zone,
+                &self.keys,
+                &self.denial,
+                now.saturating_sub(INCEPTION_OFFSET),
+                expiration,
+            )?;
+            self.signed = Some((zone, expiration));
+        }
+
+        match &self.signed {
+            Some((zone, _)) => Ok(zone),
+            None => Ok(&self.zone),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::model::result_code::ResultCode;
+    use super::super::super::zone::Zone;
+    use super::super::{crypto, denial, signature};
+    use super::{sign_zone, Denial, OnlineSigner, SigningKey, RESIGN_BEFORE, SIGNATURE_VALIDITY};
+    use super::{DnsRecord, QueryType};
+
+    const NOW: u32 = 1_700_000_000;
+
+    const ZONE: &str = "\
+$ORIGIN example.
+$TTL 3600
+@        SOA ns1 hostmaster 1 7200 3600 1209600 300
+         NS  ns1
+ns1      A   192.0.2.53
+www      A   192.0.2.1
+a.b      A   192.0.2.2
+*.wild   A   192.0.2.9
+secure   NS  ns.secure
+secure   DS  12345 13 2 0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef
+insecure NS  ns.insecure
+ns.insecure A 192.0.2.77
+";
+
+    fn keys() -> Result<Vec<SigningKey>, Box<dyn std::error::Error>> {
+        Ok(vec![
+            SigningKey::generate("example", 257, crypto::ECDSAP256SHA256)?.0,
+            SigningKey::generate("example", 256, crypto::ED25519)?.0,
+        ])
+    }
+
+    /// Verify the record sets of a response section with the keys, returning its NSEC and NSEC3
+    /// records
+    fn verified(
+        records: &[DnsRecord],
+        keys: &[DnsRecord],
+    ) -> Result<Vec<DnsRecord>, Box<dyn std::error::Error>> {
+        for rrset in signature::rrsets(records) {
+            // NOTE: The NS records of delegations aren't signed by the parent
+            if rrset[0].qtype() == QueryType::NS && rrset[0].domain() != "example" {
+                continue;
+            }
+            let rrsigs = signature::rrsigs_for(records, rrset[0].domain(), rrset[0].qtype());
+            signature::verify_rrset(&rrset, &rrsigs, keys, "example", NOW)?;
+        }
+
+        Ok(records
+            .iter()
+            .filter(|record| matches!(record, DnsRecord::NSEC { .. } | DnsRecord::NSEC3 { .. }))
+            .cloned()
+            .collect())
+    }
+
+    #[test]
+    fn sign_zone_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let zone = Zone::parse(ZONE, "example")?;
+        let keys = keys()?;
+        let denials = [
+            Denial::Nsec,
+            Denial::Nsec3 {
+                salt: vec![0xaa, 0xbb],
+                iterations: 5,
+                opt_out: false,
+            },
+            Denial::Nsec3 {
+                salt: Vec::new(),
+                iterations: 0,
+                opt_out: true,
+            },
+        ];
+
+        for denial in denials {
+            // act
+            let signed = sign_zone(&zone, &keys, &denial, NOW - 3600, NOW + 3600)?;
+
+            // assert
+            let dnskeys = signed.answer("example", QueryType::DNSKEY, true);
+            let keys: Vec<DnsRecord> = dnskeys
+                .answers
+                .iter()
+                .filter(|record| matches!(record, DnsRecord::DNSKEY { .. }))
+                .cloned()
+                .collect();
+            assert_eq!(2, keys.len());
+            verified(&dnskeys.answers, &keys)?;
+
+            let www = signed.answer("www.example", QueryType::A, true);
+            assert_eq!(2, verified(&www.answers, &keys).map(|_| www.answers.len())?);
+
+            let nxdomain = signed.answer("nope.example", QueryType::A, true);
+            let proof = verified(&nxdomain.authorities, &keys)?;
+            assert_eq!(ResultCode::NXDOMAIN, nxdomain.header.response_code);
+            assert!(
+                denial::proves_nxdomain("nope.example", &proof),
+                "{:?}",
+                denial
+            );
+
+            let nodata = signed.answer("www.example", QueryType::MX, true);
+            let proof = verified(&nodata.authorities, &keys)?;
+            assert!(
+                denial::proves_nodata("www.example", QueryType::MX, &proof),
+                "{:?}",
+                denial
+            );
+
+            let empty_non_terminal = signed.answer("b.example", QueryType::A, true);
+            let proof = verified(&empty_non_terminal.authorities, &keys)?;
+            assert!(
+                denial::proves_nodata("b.example", QueryType::A, &proof),
+                "{:?}",
+                denial
+            );
+
+            let wildcard = signed.answer("x.wild.example", QueryType::A, true);
+            let proof = verified(&wildcard.answers, &keys)
+                .and_then(|_| verified(&wildcard.authorities, &keys))?;
+            assert!(
+                denial::proves_wildcard_answer("x.wild.example", 2, &proof),
+                "{:?}",
+                denial
+            );
+
+            let secure = signed.answer("www.secure.example", QueryType::A, true);
+            assert!(secure
+                .authorities
+                .iter()
+                .any(|record| matches!(record, DnsRecord::DS { .. })));
+            verified(&secure.authorities, &keys)?;
+
+            let insecure = signed.answer("www.insecure.example", QueryType::A, true);
+            let proof = verified(&insecure.authorities, &keys)?;
+            assert!(
+                denial::proves_insecure_delegation("insecure.example", &proof),
+                "{:?}",
+                denial
+            );
+            assert_eq!(1, insecure.resources.len());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn sign_zone_file_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let zone = Zone::parse(ZONE, "example")?;
+        let denial = Denial::Nsec3 {
+            salt: vec![0xaa, 0xbb],
+            iterations: 1,
+            opt_out: true,
+        };
+        let signed = sign_zone(&zone, &keys()?, &denial, NOW - 3600, NOW + 3600)?;
+
+        // act
+        let actual = Zone::parse(&signed.to_string(), "example")?;
+
+        // assert
+        assert_eq!(signed.records, actual.records);
+        assert!(matches!(actual.records[0], DnsRecord::SOA { .. }));
+        assert!(matches!(
+            actual.records[1],
+            DnsRecord::RRSIG {
+                type_covered: QueryType::SOA,
+                ..
+            }
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn online_signer_resign_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let zone = Zone::parse(ZONE, "example")?;
+        let mut signer = OnlineSigner::new(zone, keys()?, Denial::Nsec);
+        let expiration = |zone: &Zone| {
+            zone.records.iter().find_map(|record| match record {
+                DnsRecord::RRSIG { expiration, .. } => Some(*expiration),
+                _ => None,
+            })
+        };
+
+        // act
+        let first = expiration(signer.signed(NOW)?);
+        let kept = expiration(signer.signed(NOW + SIGNATURE_VALIDITY - RESIGN_BEFORE - 1)?);
+        let renewed = expiration(signer.signed(NOW + SIGNATURE_VALIDITY - RESIGN_BEFORE)?);
+
+        // assert
+        assert_eq!(Some(NOW + SIGNATURE_VALIDITY), first);
+        assert_eq!(first, kept);
+        assert_eq!(Some(NOW + 2 * SIGNATURE_VALIDITY - RESIGN_BEFORE), renewed);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,309 @@
+//! Text encodings of binary data used in the presentation format of records
+
+const BASE32HEX_ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHIJKLMNOPQRSTUV";
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encode bytes as lowercase hexadecimal digits
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Decode hexadecimal digits in either case
+pub fn from_hex(text: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    if !text.len().is_multiple_of(2) || !text.is_ascii() {
+        return Err(format!("Invalid hexadecimal {:?}", text).into());
+    }
+
+    (0..text.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&text[i..i + 2], 16)
+                .map_err(|_| format!("Invalid hexadecimal {:?}", text).into())
+        })
+        .collect()
+}
+
+/// Encode bytes as padded base64 (RFC 4648 §4)
+pub fn to_base64(bytes: &[u8]) -> String {
+    let mut result = String::with_capacity(bytes.len().div_ceil(3) * 4);
+
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = ((b[0] as u32) << 16) | ((b[1] as u32) << 8) | (b[2] as u32);
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let index = (n >> (18 - 6 * i)) & 0x3F;
+                result.push(BASE64_ALPHABET[index as usize] as char);
+            } else {
+                result.push('=');
+            }
+        }
+    }
+
+    result
+}
+
+/// Decode padded base64, ignoring whitespace since zone files split long values
+pub fn from_base64(text: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut result = Vec::with_capacity(text.len() / 4 * 3);
+
+    let mut bits: u32 = 0;
+    let mut bit_count = 0;
+    let mut padding = 0;
+    for c in text.bytes().filter(|c| !c.is_ascii_whitespace()) {
+        if c == b'=' {
+            padding += 1;
+            continue;
+        }
+        let index = match BASE64_ALPHABET.iter().position(|&a| a == c) {
+            Some(index) if padding == 0 => index as u32,
+            _ => return Err(format!("Invalid base64 {:?}", text).into()),
+        };
+
+        bits = (bits << 6) | index;
+        bit_count += 6;
+        if bit_count >= 8 {
+            bit_count -= 8;
+            result.push((bits >> bit_count) as u8);
+        }
+    }
+
+    if padding > 2 || bit_count >= 6 {
+        return Err(format!("Invalid base64 {:?}", text).into());
+    }
+
+    Ok(result)
+}
+
+/// Encode bytes as unpadded base32 with the extended hex alphabet (RFC 4648 §7), as used for the
+/// hashed owner names of NSEC3 records
+pub fn to_base32hex(bytes: &[u8]) -> String {
+    let mut result = String::with_capacity((bytes.len() * 8).div_ceil(5));
+
+    let mut bits: u32 = 0;
+    let mut bit_count = 0;
+    for &b in bytes {
+        bits = (bits << 8) | b as u32;
+        bit_count += 8;
+
+        while bit_count >= 5 {
+            bit_count -= 5;
+            let index = (bits >> bit_count) & 0x1F;
+            result.push(BASE32HEX_ALPHABET[index as usize] as char);
+        }
+    }
+    if bit_count > 0 {
+        let index = (bits << (5 - bit_count)) & 0x1F;
+        result.push(BASE32HEX_ALPHABET[index as usize] as char);
+    }
+
+    result
+}
+
+/// Decode unpadded base32 with the extended hex alphabet in either case
+pub fn from_base32hex(text: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut result = Vec::with_capacity(text.len() * 5 / 8);
+
+    let mut bits: u32 = 0;
+    let mut bit_count = 0;
+    for c in text.bytes() {
+        let index = BASE32HEX_ALPHABET
+            .iter()
+            .position(|&a| a == c.to_ascii_uppercase())
+            .ok_or_else(|| format!("Invalid base32hex {:?}", text))?;
+
+        bits = (bits << 5) | index as u32;
+        bit_count += 5;
+        if bit_count >= 8 {
+            bit_count -= 8;
+            result.push((bits >> bit_count) as u8);
+        }
+    }
+
+    Ok(result)
+}
+
+/// Present seconds since the epoch as `YYYYMMDDHHmmSS` in UTC, as in the RRSIG record
+pub fn to_timestamp(secs: u32) -> String {
+    let days = (secs / 86400) as i64;
+    let rem = secs % 86400;
+
+    // NOTE: Convert days to a civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}{:02}{:02}{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+/// Read a time of the RRSIG record, as `YYYYMMDDHHmmSS` in UTC or as seconds since the epoch
+/// (RFC 4034 §3.2)
+pub fn from_timestamp(text: &str) -> Result<u32, Box<dyn std::error::Error>> {
+    let invalid = || format!("Invalid timestamp {:?}", text);
+    if !text.bytes().all(|byte| byte.is_ascii_digit()) {
+        return Err(invalid().into());
+    }
+    if text.len() != 14 {
+        return text.parse().map_err(|_| invalid().into());
+    }
+
+    let field = |range: std::ops::Range<usize>| text[range].parse::<i64>().unwrap_or_default();
+    let (year, month, day) = (field(0..4), field(4..6), field(6..8));
+    let (hour, minute, second) = (field(8..10), field(10..12), field(12..14));
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 {
+        return Err(invalid().into());
+    }
+
+    // NOTE: The inverse of `to_timestamp`, from the same date algorithms
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+
+    u32::try_from(days * 86400 + hour * 3600 + minute * 60 + second).map_err(|_| invalid().into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_base32hex, from_base64, from_hex, from_timestamp};
+    use super::{to_base32hex, to_base64, to_timestamp};
+
+    #[test]
+    fn to_base64_ok() {
+        // arrange
+        let inputs: [&[u8]; 4] = [b"", b"f", b"fo", b"foobar"];
+
+        // act
+        let actual: Vec<String> = inputs.iter().map(|input| to_base64(input)).collect();
+
+        // assert
+        assert_eq!(vec!["", "Zg==", "Zm8=", "Zm9vYmFy"], actual);
+    }
+
+    #[test]
+    fn from_base64_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let inputs = ["", "Zg==", "Zm8=", "Zm9v YmFy"];
+
+        // act
+        let actual = inputs
+            .iter()
+            .map(|input| from_base64(input))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // assert
+        assert_eq!(
+            vec![
+                b"".to_vec(),
+                b"f".to_vec(),
+                b"fo".to_vec(),
+                b"foobar".to_vec()
+            ],
+            actual
+        );
+        assert!(from_base64("Zm9v!").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn from_hex_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // act
+        let actual = from_hex("E06d44")?;
+
+        // assert
+        assert_eq!(vec![0xe0, 0x6d, 0x44], actual);
+        assert!(from_hex("E06").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn to_base32hex_ok() {
+        // arrange
+        let inputs: [&[u8]; 3] = [b"f", b"foobar", &[0xff; 20]];
+
+        // act
+        let actual: Vec<String> = inputs.iter().map(|input| to_base32hex(input)).collect();
+
+        // assert
+        assert_eq!(
+            vec!["CO", "CPNMUOJ1E8", "VVVVVVVVVVVVVVVVVVVVVVVVVVVVVVVV"],
+            actual
+        );
+    }
+
+    #[test]
+    fn from_base32hex_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // act
+        let actual = from_base32hex("cpnmuoj1e8")?;
+
+        // assert
+        assert_eq!(b"foobar".to_vec(), actual);
+
+        Ok(())
+    }
+
+    #[test]
+    fn to_timestamp_ok() {
+        // arrange
+        let inputs = [0, 1440021600, 951782400];
+
+        // act
+        let actual: Vec<String> = inputs.iter().map(|input| to_timestamp(*input)).collect();
+
+        // assert
+        assert_eq!(
+            vec!["19700101000000", "20150819220000", "20000229000000"],
+            actual
+        );
+    }
+
+    #[test]
+    fn from_timestamp_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let inputs = [
+            "19700101000000",
+            "20150819220000",
+            "20000229000000",
+            "1440021600",
+        ];
+
+        // act
+        let actual = inputs
+            .iter()
+            .map(|input| from_timestamp(input))
+            .collect::<Result<Vec<u32>, _>>()?;
+
+        // assert
+        assert_eq!(vec![0, 1440021600, 951782400, 1440021600], actual);
+        assert!(from_timestamp("20151319220000").is_err());
+        assert!(from_timestamp("2015-08-19").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,478 @@
+//! Names answered locally before recursing, so developers can override them: those of hosts
+//! files (the `/etc/hosts` format) and a table of static records, which replace the records of
+//! the files with their name and type. The addresses get PTR records of their own, and the files
+//! are read again when they change.
+
+use super::config::ServerConfig;
+use super::dnssec::name;
+use super::model::{
+    dns_class::DnsClass, dns_packet::DnsPacket, dns_question::DnsQuestion, dns_record::DnsRecord,
+    query_type::QueryType, result_code::ResultCode,
+};
+use super::zone::Zone;
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+use std::time::{Duration, Instant, SystemTime};
+
+/// The records answered locally, by owner name
+pub struct Hosts {
+    /// TTL of the records of the hosts files and of the static ones without their own
+    pub ttl: u32,
+    /// How long to wait before looking at the hosts files for changes again
+    pub reload_interval: Duration,
+    files: Vec<HostsFile>,
+    static_records: Vec<DnsRecord>,
+    records: HashMap<String, Vec<DnsRecord>>,
+    checked: Instant,
+}
+
+/// A hosts file, with its records as of when it was last read
+struct HostsFile {
+    path: String,
+    /// When it was modified and its size, to tell when it changes
+    stamp: Option<(SystemTime, u64)>,
+    records: Vec<DnsRecord>,
+    /// Whether reading it again failed, so the failure is only reported once
+    failed: bool,
+}
+
+impl HostsFile {
+    fn read(path: &str, ttl: u32) -> Result<Self, Box<dyn std::error::Error>> {
+        let stamp = stamp(path);
+        let text =
+            std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+
+        Ok(HostsFile {
+            path: path.to_string(),
+            stamp,
+            records: parse(&text, ttl),
+            failed: false,
+        })
+    }
+}
+
+fn stamp(path: &str) -> Option<(SystemTime, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+impl Hosts {
+    /// Read the hosts files and parse the static records, each in zone file syntax with an
+    /// absolute owner name, e.g. `db.internal. A 10.0.0.5`
+    pub fn new(
+        files: &[String],
+        static_records: &[String],
+        ttl: u32,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut parsed = Vec::new();
+        for record in static_records {
+            let zone = Zone::parse(&format!("$TTL {}\n{}", ttl, record), "")
+                .map_err(|e| format!("Invalid local record {:?}: {}", record, e))?;
+            parsed.extend(zone.records);
+        }
+
+        let mut hosts = Hosts {
+            ttl,
+            reload_interval: Duration::from_secs(1),
+            files: files
+                .iter()
+                .map(|path| HostsFile::read(path, ttl))
+                .collect::<Result<_, _>>()?,
+            static_records: parsed,
+            records: HashMap::new(),
+            checked: Instant::now(),
+        };
+        hosts.index();
+
+        Ok(hosts)
+    }
+
+    /// With the hosts files, static records and TTL of the settings
+    pub fn from_config(config: &ServerConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        Hosts::new(&config.hosts_files, &config.local_records, config.local_ttl)
+    }
+
+    /// Read the hosts files which changed since they were last read, telling whether any did.
+    /// They are looked at once per `reload_interval` at most. A file which can't be read keeps
+    /// its previous records, and its failure is reported once until it can be read again.
+    pub fn reload(&mut self) -> Result<bool, Box<dyn std::error::Error>> {
+        if self.checked.elapsed() < self.reload_interval {
+            return Ok(false);
+        }
+        self.checked = Instant::now();
+
+        let mut reloaded = false;
+        let mut failure = None;
+
+        for file in &mut self.files {
+            let current = stamp(&file.path);
+            if current.is_some() && current == file.stamp {
+                continue;
+            }
+
+            match HostsFile::read(&file.path, self.ttl) {
+                Ok(read) => {
+                    *file = read;
+                    reloaded = true;
+                }
+                Err(e) => {
+                    if !file.failed {
+                        failure = Some(e);
+                    }
+                    file.failed = true;
+                }
+            }
+        }
+
+        if reloaded {
+            self.index();
+        }
+
+        match failure {
+            Some(e) => Err(e),
+            None => Ok(reloaded),
+        }
+    }
+
+    /// Gather the records by owner name: the static records first, then those of the files unless
+    /// static records have their name and type, and the PTR records of their addresses without
+    /// one, the name first given for an address being its name
+    fn index(&mut self) {
+        self.records.clear();
+        let shadowed: HashSet<(String, QueryType)> = self
+            .static_records
+            .iter()
+            .map(|record| (name::normalize(record.domain()), record.qtype()))
+            .collect();
+        let records: Vec<&DnsRecord> = self
+            .static_records
+            .iter()
+            .chain(
+                self.files
+                    .iter()
+                    .flat_map(|file| &file.records)
+                    .filter(|record| {
+                        !shadowed.contains(&(name::normalize(record.domain()), record.qtype()))
+                    }),
+            )
+            .collect();
+        for &record in &records {
+            let records = self
+                .records
+                .entry(name::normalize(record.domain()))
+                .or_default();
+            if !records.contains(record) {
+                records.push(record.clone());
+            }
+        }
+
+        for record in records {
+            let addr = match record {
+                DnsRecord::A { addr, .. } => IpAddr::from(*addr),
+                DnsRecord::AAAA { addr, .. } => IpAddr::from(*addr),
+                _ => continue,
+            };
+            let reverse = reverse_name(addr);
+            let records = self.records.entry(reverse.clone()).or_default();
+            if !records.iter().any(|rec| rec.qtype() == QueryType::PTR) {
+                records.push(DnsRecord::PTR {
+                    domain: reverse,
+                    host: record.domain().to_string(),
+                    class: DnsClass::IN,
+                    ttl: record.ttl(),
+                });
+            }
+        }
+    }
+
+    /// The authoritative answer to the question when its name is answered locally, with the
+    /// records of its type, if any
+    pub fn answer(&self, question: &DnsQuestion) -> Option<DnsPacket> {
+        if question.qclass != DnsClass::IN {
+            return None;
+        }
+        let records = self.records.get(&name::normalize(&question.name))?;
+
+        let mut packet = DnsPacket::new();
+        packet.header.authoritative_answer = true;
+        packet.header.response_code = ResultCode::NOERROR;
+        packet.answers = records
+            .iter()
+            .filter(|rec| rec.qtype() == question.qtype)
+            .cloned()
+            .collect();
+
+        Some(packet)
+    }
+}
+
+/// Parse a hosts file: an address per line followed by its name and aliases, `#` starting a
+/// comment. Lines without a valid address or name are skipped, as the C library does.
+pub fn parse(text: &str, ttl: u32) -> Vec<DnsRecord> {
+    let mut records = Vec::new();
+
+    for line in text.lines() {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut words = line.split_whitespace();
+        let Some(Ok(addr)) = words.next().map(str::parse::<IpAddr>) else {
+            continue;
+        };
+
+        for host in words {
+            let domain = name::normalize(host);
+            records.push(match addr {
+                IpAddr::V4(addr) => DnsRecord::A {
+                    domain,
+                    addr,
+                    class: DnsClass::IN,
+                    ttl,
+                },
+                IpAddr::V6(addr) => DnsRecord::AAAA {
+                    domain,
+                    addr,
+                    class: DnsClass::IN,
+                    ttl,
+                },
+            });
+        }
+    }
+
+    records
+}
+
+/// The name of the address under `in-addr.arpa` or `ip6.arpa` (RFC 1035 §3.5, RFC 3596 §2.5)
+pub fn reverse_name(addr: IpAddr) -> String {
+    match addr {
+        IpAddr::V4(addr) => {
+            let [a, b, c, d] = addr.octets();
+            format!("{}.{}.{}.{}.in-addr.arpa", d, c, b, a)
+        }
+        IpAddr::V6(addr) => {
+            let nibbles: Vec<String> = addr
+                .octets()
+                .iter()
+                .rev()
+                .flat_map(|byte| [byte & 0x0F, byte >> 4])
+                .map(|nibble| format!("{:x}", nibble))
+                .collect();
+            format!("{}.ip6.arpa", nibbles.join("."))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, reverse_name, DnsQuestion, Hosts, QueryType, ResultCode};
+    use super::{DnsClass, IpAddr};
+    use std::net::{Ipv4Addr, Ipv6Addr};
+    use std::time::Duration;
+
+    const HOSTS: &str = "\
+# Static table lookup for hostnames
+127.0.0.1   localhost
+192.0.2.10  app.dev.test app  # the app under development
+192.0.2.11  db.dev.test
+2001:db8::10 App.Dev.Test
+not-an-address  nowhere.dev.test
+192.0.2.12
+";
+
+    /// A file of its own in the temporary directory, removed when dropped
+    struct TempFile(String);
+
+    impl TempFile {
+        fn new(name: &str, text: &str) -> Result<Self, Box<dyn std::error::Error>> {
+            let path = std::env::temp_dir().join(format!("{}-{}", std::process::id(), name));
+            std::fs::write(&path, text)?;
+
+            Ok(TempFile(path.to_string_lossy().to_string()))
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn answers(hosts: &Hosts, name: &str, qtype: QueryType) -> Option<Vec<String>> {
+        let answer = hosts.answer(&DnsQuestion::new(name.to_string(), qtype))?;
+        assert!(answer.header.authoritative_answer);
+        assert_eq!(ResultCode::NOERROR, answer.header.response_code);
+
+        Some(answer.answers.iter().map(|rec| rec.to_string()).collect())
+    }
+
+    #[test]
+    fn parse_ok() {
+        // act
+        let actual = parse(HOSTS, 60);
+
+        // assert
+        assert_eq!(
+            vec![
+                "localhost. 60 IN A 127.0.0.1",
+                "app.dev.test. 60 IN A 192.0.2.10",
+                "app. 60 IN A 192.0.2.10",
+                "db.dev.test. 60 IN A 192.0.2.11",
+                "app.dev.test. 60 IN AAAA 2001:db8::10",
+            ],
+            actual.iter().map(|rec| rec.to_string()).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn reverse_name_ok() {
+        // act
+        let v4 = reverse_name(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 10)));
+        let v6 = reverse_name(IpAddr::V6(Ipv6Addr::new(
+            0x2001, 0xdb8, 0, 0, 0, 0, 0, 0x10,
+        )));
+
+        // assert
+        assert_eq!("10.2.0.192.in-addr.arpa", v4);
+        assert_eq!(
+            "0.1.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.ip6.arpa",
+            v6
+        );
+    }
+
+    #[test]
+    fn answer_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let file = TempFile::new("answer_ok.hosts", HOSTS)?;
+        let static_records = [
+            "db.dev.test. A 10.0.0.5".to_string(),
+            "db.dev.test. 3600 TXT \"local\"".to_string(),
+            "5.0.0.10.in-addr.arpa. PTR database.dev.test.".to_string(),
+        ];
+        let hosts = Hosts::new(std::slice::from_ref(&file.0), &static_records, 60)?;
+
+        // act
+        let a = answers(&hosts, "APP.dev.test", QueryType::A);
+        let aaaa = answers(&hosts, "app.dev.test", QueryType::AAAA);
+        let db = answers(&hosts, "db.dev.test", QueryType::A);
+        let txt = answers(&hosts, "db.dev.test", QueryType::TXT);
+        let nodata = answers(&hosts, "app", QueryType::MX);
+        let ptr = answers(&hosts, "10.2.0.192.in-addr.arpa", QueryType::PTR);
+        let ptr6 = answers(
+            &hosts,
+            "0.1.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.ip6.arpa",
+            QueryType::PTR,
+        );
+        let static_ptr = answers(&hosts, "5.0.0.10.in-addr.arpa", QueryType::PTR);
+        let shadowed_ptr = answers(&hosts, "11.2.0.192.in-addr.arpa", QueryType::PTR);
+        let unknown = answers(&hosts, "www.dev.test", QueryType::A);
+        let mut chaos = DnsQuestion::new("localhost".to_string(), QueryType::A);
+        chaos.qclass = DnsClass::CH;
+
+        // assert
+        assert_eq!(
+            Some(vec!["app.dev.test. 60 IN A 192.0.2.10".to_string()]),
+            a
+        );
+        assert_eq!(
+            Some(vec!["app.dev.test. 60 IN AAAA 2001:db8::10".to_string()]),
+            aaaa
+        );
+        assert_eq!(Some(vec!["db.dev.test. 60 IN A 10.0.0.5".to_string()]), db);
+        assert_eq!(
+            Some(vec!["db.dev.test. 3600 IN TXT \"local\"".to_string()]),
+            txt
+        );
+        assert_eq!(Some(Vec::new()), nodata);
+        assert_eq!(
+            Some(vec![
+                "10.2.0.192.in-addr.arpa. 60 IN PTR app.dev.test.".to_string()
+            ]),
+            ptr
+        );
+        assert_eq!(1, ptr6.map_or(0, |ptr| ptr.len()));
+        assert_eq!(
+            Some(vec![
+                "5.0.0.10.in-addr.arpa. 60 IN PTR database.dev.test.".to_string()
+            ]),
+            static_ptr
+        );
+        assert_eq!(None, shadowed_ptr);
+        assert_eq!(None, unknown);
+        assert!(hosts.answer(&chaos).is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn reload_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let file = TempFile::new("reload_ok.hosts", "192.0.2.10 app.dev.test\n")?;
+        let mut hosts = Hosts::new(std::slice::from_ref(&file.0), &[], 0)?;
+        hosts.reload_interval = Duration::ZERO;
+        let unchanged = hosts.reload()?;
+        std::fs::write(
+            &file.0,
+            "192.0.2.20 app.dev.test\n192.0.2.21 api.dev.test\n",
+        )?;
+
+        // act
+        let reloaded = hosts.reload()?;
+
+        // assert
+        assert!(!unchanged);
+        assert!(reloaded);
+        assert_eq!(
+            Some(vec!["app.dev.test. 0 IN A 192.0.2.20".to_string()]),
+            answers(&hosts, "app.dev.test", QueryType::A)
+        );
+        assert!(answers(&hosts, "api.dev.test", QueryType::A).is_some());
+        assert!(answers(&hosts, "10.2.0.192.in-addr.arpa", QueryType::PTR).is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn reload_error() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let file = TempFile::new("reload_error.hosts", "192.0.2.10 app.dev.test\n")?;
+        let mut hosts = Hosts::new(std::slice::from_ref(&file.0), &[], 0)?;
+        hosts.reload_interval = Duration::ZERO;
+        std::fs::remove_file(&file.0)?;
+
+        // act
+        let actual = hosts.reload();
+        let again = hosts.reload();
+
+        // assert
+        assert!(actual.is_err());
+        assert!(matches!(again, Ok(false)));
+        assert_eq!(
+            Some(vec!["app.dev.test. 0 IN A 192.0.2.10".to_string()]),
+            answers(&hosts, "app.dev.test", QueryType::A)
+        );
+        assert!(Hosts::new(std::slice::from_ref(&file.0), &[], 0).is_err());
+        assert!(Hosts::new(&[], &["db.dev.test. A nope".to_string()], 0).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn reload_interval_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let file = TempFile::new("reload_interval_ok.hosts", "192.0.2.10 app.dev.test\n")?;
+        let mut hosts = Hosts::new(std::slice::from_ref(&file.0), &[], 0)?;
+        hosts.reload_interval = Duration::from_secs(3600);
+        std::fs::write(&file.0, "192.0.2.20 app.dev.test\n")?;
+
+        // act
+        let reloaded = hosts.reload()?;
+
+        // assert
+        assert!(!reloaded);
+        assert_eq!(
+            Some(vec!["app.dev.test. 0 IN A 192.0.2.10".to_string()]),
+            answers(&hosts, "app.dev.test", QueryType::A)
+        );
+
+        Ok(())
+    }
+}
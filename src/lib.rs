@@ -0,0 +1,20 @@
+use byte_packet_buffer::BytePacketBuffer;
+
+#[cfg(feature = "tokio")]
+pub mod async_resolver;
+pub mod blocklist;
+pub mod byte_packet_buffer;
+pub mod config;
+pub mod dissect;
+pub mod dnssec;
+pub mod encoding;
+pub mod hosts;
+pub mod mock;
+pub mod model;
+pub mod pcap;
+pub mod perf;
+pub mod query;
+pub mod replay;
+pub mod stub;
+pub mod transport;
+pub mod zone;
@@ -1,18 +1,112 @@
-use byte_packet_buffer::BytePacketBuffer;
-use query::handle_query;
+use dns_in_rust::blocklist::Blocklists;
+use dns_in_rust::config::{
+    CaptureConfig, DissectConfig, KeygenConfig, ServerConfig, SignZoneConfig,
+};
+use dns_in_rust::dissect::{decode, dissect};
+use dns_in_rust::dnssec::zone_signer::{sign_zone, Denial, OnlineSigner};
+use dns_in_rust::dnssec::{
+    anchor_store::AnchorStore, crypto, key_file, name, trust_anchor, validator::Validator,
+};
+use dns_in_rust::hosts::Hosts;
+use dns_in_rust::pcap;
+use dns_in_rust::query::{handle_query, LookupOptions};
+use dns_in_rust::replay::replay;
+use dns_in_rust::zone::Zone;
+use std::collections::BTreeMap;
+use std::io::Read;
 use std::net::UdpSocket;
-
-mod byte_packet_buffer;
-mod model;
-mod query;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Bind an UDP socket on port 2054
-    let socket = UdpSocket::bind(("0.0.0.0", 2054))?;
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    match args.first().map(String::as_str) {
+        Some("anchors") => {
+            return anchors_command(args.get(1).ok_or("Missing the trust anchor store file")?)
+        }
+        Some("sign-zone") => {
+            return sign_zone_command(SignZoneConfig::from_args(args.into_iter().skip(1))?)
+        }
+        Some("keygen") => {
+            return keygen_command(KeygenConfig::from_args(args.into_iter().skip(1))?)
+        }
+        Some("pcap") => return pcap_command(CaptureConfig::from_args(args.into_iter().skip(1))?),
+        Some("replay") => {
+            return replay_command(CaptureConfig::from_args(args.into_iter().skip(1))?)
+        }
+        Some("dissect") => {
+            return dissect_command(DissectConfig::from_args(args.into_iter().skip(1))?)
+        }
+        _ => {}
+    }
+
+    let config = ServerConfig::from_args(args)?;
+
+    let validator = match (&config.anchor_store, config.dnssec_validation) {
+        (Some(path), _) => {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as u32;
+            let seed_file = config.trust_anchor_file.as_ref().map(Path::new);
+            Some(Validator::with_store(AnchorStore::open(
+                Path::new(path),
+                seed_file,
+                now,
+            )?))
+        }
+        (None, true) => {
+            let anchors = match &config.trust_anchor_file {
+                Some(file) => trust_anchor::parse(&std::fs::read_to_string(file)?)?,
+                None => trust_anchor::root(),
+            };
+            Some(Validator::new(anchors))
+        }
+        (None, false) => None,
+    };
+
+    // NOTE: NSEC3 without salt or extra iterations, as RFC 9276 recommends
+    let denial = if config.nsec3 {
+        Denial::Nsec3 {
+            salt: Vec::new(),
+            iterations: 0,
+            opt_out: false,
+        }
+    } else {
+        Denial::Nsec
+    };
+    let mut zones = Vec::new();
+    for (origin, file) in &config.zones {
+        let zone = Zone::parse(&std::fs::read_to_string(file)?, origin)?;
+        let mut keys = Vec::new();
+        for (_, path) in config
+            .zone_keys
+            .iter()
+            .filter(|(key_origin, _)| name::normalize(key_origin) == zone.origin)
+        {
+            let key = key_file::read(path)?;
+            if key.zone != zone.origin {
+                return Err(format!("The key {} is not for the zone {}", path, zone.origin).into());
+            }
+            keys.push(key);
+        }
+        zones.push(OnlineSigner::new(zone, keys, denial.clone()));
+    }
+
+    let mut hosts = Hosts::from_config(&config)?;
+    let mut blocklists = Blocklists::from_config(&config)?;
+
+    // Bind an UDP socket on port 2054 by default
+    let socket = UdpSocket::bind(("0.0.0.0", config.port))?;
 
     // Queries handled sequentially
     loop {
-        match handle_query(&socket) {
+        match handle_query(
+            &socket,
+            &config,
+            validator.as_ref(),
+            &mut zones,
+            &mut hosts,
+            &mut blocklists,
+        ) {
             Ok(_) => {}
             Err(e) => {
                 eprintln!("An error occurred: {}", e)
@@ -20,3 +114,156 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     }
 }
+
+/// `anchors <file>` shows the states of the trust anchors kept in the store
+fn anchors_command(path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    print!("{}", AnchorStore::parse(&std::fs::read_to_string(path)?)?);
+
+    Ok(())
+}
+
+/// `sign-zone` signs a zone file with the keys, writing the signed zone file
+fn sign_zone_command(config: SignZoneConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let zone = Zone::parse(&std::fs::read_to_string(&config.zone_file)?, &config.origin)?;
+    let keys = config
+        .keys
+        .iter()
+        .map(|path| key_file::read(path))
+        .collect::<Result<Vec<_>, _>>()?;
+    if let Some(key) = keys.iter().find(|key| key.zone != zone.origin) {
+        return Err(format!(
+            "The key {} is not for the zone {}",
+            key.key_tag(),
+            zone.origin
+        )
+        .into());
+    }
+
+    // NOTE: Signatures start an hour in the past, for validators whose clock is behind ours
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as u32;
+    let signed = sign_zone(
+        &zone,
+        &keys,
+        &config.denial,
+        now.saturating_sub(3600),
+        now.saturating_add(config.validity.saturating_mul(86400)),
+    )?;
+    std::fs::write(&config.output, signed.to_string())?;
+    println!("{}", config.output);
+
+    Ok(())
+}
+
+/// `keygen` generates a key for a zone, writing its key files in the BIND format
+fn keygen_command(config: KeygenConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let flags = crypto::FLAG_ZONE_KEY
+        | if config.ksk {
+            crypto::FLAG_SECURE_ENTRY_POINT
+        } else {
+            0
+        };
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as u32;
+    let (_, files) = key_file::generate(&config.zone, flags, config.algorithm, config.bits, now)?;
+    let path = key_file::write(Path::new(&config.directory), &files)?;
+    println!("{}", path.display());
+
+    Ok(())
+}
+
+/// `dissect` shows a message as a hexdump annotated with the field of each byte
+fn dissect_command(config: DissectConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let input = if config.file == "-" {
+        let mut input = Vec::new();
+        std::io::stdin().read_to_end(&mut input)?;
+        input
+    } else {
+        std::fs::read(&config.file)?
+    };
+
+    let dissection = dissect(&decode(&input, config.format)?);
+    print!("{}", dissection);
+
+    match dissection.error {
+        Some(_) => Err("The message is malformed".into()),
+        None => Ok(()),
+    }
+}
+
+/// `pcap` shows the DNS messages of a capture, or counts them with `--summary`
+fn pcap_command(config: CaptureConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let messages = pcap::read(&std::fs::read(&config.file)?, config.dns_port)?;
+
+    if !config.summary {
+        for message in &messages {
+            println!("{}", message);
+            match message.packet() {
+                Ok(packet) => println!("{}", packet),
+                Err(e) => println!(";; unreadable: {}\n", e),
+            }
+        }
+
+        return Ok(());
+    }
+
+    // NOTE: Totals of queries, responses and unreadable messages, then counts by type and code
+    let mut totals = [0; 3];
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    for message in &messages {
+        match message.packet() {
+            Ok(packet) if packet.header.query_response => {
+                totals[1] += 1;
+                let key = format!("response {:?}", packet.header.response_code);
+                *counts.entry(key).or_default() += 1;
+            }
+            Ok(packet) => {
+                totals[0] += 1;
+                for question in &packet.questions {
+                    *counts
+                        .entry(format!("query {}", question.qtype))
+                        .or_default() += 1;
+                }
+            }
+            Err(_) => totals[2] += 1,
+        }
+    }
+    println!(
+        "{} messages: {} queries, {} responses, {} unreadable",
+        messages.len(),
+        totals[0],
+        totals[1],
+        totals[2]
+    );
+    for (key, count) in counts {
+        println!("{:<24} {}", key, count);
+    }
+
+    Ok(())
+}
+
+/// `replay` sends the queries of a capture to a server, reporting answers that differ from the
+/// recorded ones
+fn replay_command(config: CaptureConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let messages = pcap::read(&std::fs::read(&config.file)?, config.dns_port)?;
+    let report = replay(
+        &messages,
+        config.dns_port,
+        (config.server, config.port),
+        LookupOptions::default().timeout,
+        config.paced,
+    );
+
+    for (question, reason) in &report.failures {
+        println!("failed: {}: {}", question, reason);
+    }
+    for (question, difference) in &report.mismatches {
+        println!("mismatch: {}: {}", question, difference);
+    }
+    println!(
+        "{} queries replayed, {} failed, {} mismatched",
+        report.queries,
+        report.failures.len(),
+        report.mismatches.len()
+    );
+
+    Ok(())
+}
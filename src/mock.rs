@@ -0,0 +1,584 @@
+//! Fake authoritative servers on loopback, answering from zones given in zone file syntax, so
+//! recursion can be tested end to end without the internet. Each server listens on its own
+//! loopback address, all of them on the same port, which the resolver is told to ask on along
+//! with the root servers to start from. Only Linux lets addresses of 127.0.0.0/8 other than
+//! 127.0.0.1 be bound without configuring them first, so the tests starting servers run there.
+
+use super::byte_packet_buffer::BytePacketBuffer;
+use super::dnssec::name;
+use super::model::{dns_packet::DnsPacket, dns_record::DnsRecord, result_code::ResultCode};
+use super::zone::Zone;
+use std::io::ErrorKind;
+use std::net::{Ipv4Addr, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+#[cfg(test)]
+pub mod fixture;
+
+/// How a mock server answers
+#[derive(Debug, Clone, PartialEq)]
+pub enum Behavior {
+    /// Answer from its zones with authority, refusing names outside of them
+    Authoritative,
+    /// Refuse every query, as a server delegated to without serving the zone
+    Lame,
+    /// Answer with authority, after a delay
+    Slow(Duration),
+    /// Answer with authority, with this address in place of those of the A records
+    Lying(Ipv4Addr),
+}
+
+/// A server of the mock network: its loopback address, its zones as their origin and text, and
+/// how it answers
+#[derive(Debug, Clone)]
+pub struct MockServer {
+    pub addr: Ipv4Addr,
+    pub zones: Vec<(String, String)>,
+    pub behavior: Behavior,
+}
+
+impl MockServer {
+    /// An authoritative server of a single zone
+    pub fn new(addr: Ipv4Addr, origin: &str, zone: &str) -> Self {
+        MockServer {
+            addr,
+            zones: vec![(origin.to_string(), zone.to_string())],
+            behavior: Behavior::Authoritative,
+        }
+    }
+
+    /// The server serving another zone as well
+    pub fn serving(mut self, origin: &str, zone: &str) -> Self {
+        self.zones.push((origin.to_string(), zone.to_string()));
+        self
+    }
+
+    pub fn behaving(mut self, behavior: Behavior) -> Self {
+        self.behavior = behavior;
+        self
+    }
+
+    /// The zones of the server, parsed
+    pub fn parse_zones(&self) -> Result<Vec<Zone>, Box<dyn std::error::Error>> {
+        self.zones
+            .iter()
+            .map(|(origin, text)| Zone::parse(text, origin))
+            .collect()
+    }
+}
+
+/// Queries received by the servers of a mock network, as the server and the question
+type QueryLog = Arc<Mutex<Vec<(Ipv4Addr, String)>>>;
+
+/// Mock servers answering on their own loopback addresses and a shared UDP port, until dropped
+pub struct MockNetwork {
+    pub port: u16,
+    queries: QueryLog,
+    stop: Arc<AtomicBool>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl MockNetwork {
+    /// Start the servers, on a port free on all of their addresses
+    pub fn start(servers: Vec<MockServer>) -> Result<MockNetwork, Box<dyn std::error::Error>> {
+        let mut parsed = Vec::new();
+        for server in &servers {
+            parsed.push((server.addr, server.parse_zones()?, server.behavior.clone()));
+        }
+
+        let sockets = bind(&servers)?;
+        let port = sockets
+            .first()
+            .map_or(Ok(0), |s| s.local_addr().map(|a| a.port()))?;
+        let queries = QueryLog::default();
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let mut threads = Vec::new();
+        for (socket, (addr, zones, behavior)) in sockets.into_iter().zip(parsed) {
+            let queries = Arc::clone(&queries);
+            let stop = Arc::clone(&stop);
+            socket.set_read_timeout(Some(Duration::from_millis(20)))?;
+
+            threads.push(std::thread::spawn(move || {
+                serve(&socket, addr, &zones, &behavior, &queries, &stop)
+            }));
+        }
+
+        Ok(MockNetwork {
+            port,
+            queries,
+            stop,
+            threads,
+        })
+    }
+
+    /// The queries received so far, in order, as the server and the question in zone file syntax
+    pub fn queries(&self) -> Vec<(Ipv4Addr, String)> {
+        self.queries.lock().map(|q| q.clone()).unwrap_or_default()
+    }
+}
+
+impl Drop for MockNetwork {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Bind a socket per server on the same port, trying other ports when one is taken on some of
+/// the addresses
+fn bind(servers: &[MockServer]) -> Result<Vec<UdpSocket>, Box<dyn std::error::Error>> {
+    for _ in 0..10 {
+        let Some(first) = servers.first() else {
+            return Ok(Vec::new());
+        };
+        let socket = UdpSocket::bind((first.addr, 0))?;
+        let port = socket.local_addr()?.port();
+
+        let mut sockets = vec![socket];
+        for server in &servers[1..] {
+            match UdpSocket::bind((server.addr, port)) {
+                Ok(socket) => sockets.push(socket),
+                Err(e) if e.kind() == ErrorKind::AddrInUse => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        if sockets.len() == servers.len() {
+            return Ok(sockets);
+        }
+    }
+
+    Err("No port is free on all of the mock server addresses".into())
+}
+
+fn serve(
+    socket: &UdpSocket,
+    addr: Ipv4Addr,
+    zones: &[Zone],
+    behavior: &Behavior,
+    queries: &Mutex<Vec<(Ipv4Addr, String)>>,
+    stop: &AtomicBool,
+) {
+    while !stop.load(Ordering::Relaxed) {
+        let mut buffer = BytePacketBuffer::new();
+        let Ok((_, src)) = socket.recv_from(&mut buffer.buf) else {
+            continue;
+        };
+        let Ok(request) = DnsPacket::from_buffer(&mut buffer) else {
+            continue;
+        };
+        if let (Some(question), Ok(mut queries)) = (request.questions.first(), queries.lock()) {
+            queries.push((addr, question.to_string()));
+        }
+
+        let mut response = answer(zones, behavior, &request);
+        if let Behavior::Slow(delay) = behavior {
+            std::thread::sleep(*delay);
+        }
+
+        let mut buffer = BytePacketBuffer::new();
+        if response.write(&mut buffer).is_ok() {
+            let _ = socket.send_to(&buffer.buf[..buffer.pos()], src);
+        }
+    }
+}
+
+/// The response of a server to the request, from the zone with the longest origin holding the
+/// name. Slow servers are delayed by the caller.
+pub fn answer(zones: &[Zone], behavior: &Behavior, request: &DnsPacket) -> DnsPacket {
+    let mut response = DnsPacket::new();
+    response.header.id = request.header.id;
+    response.header.query_response = true;
+    response.header.recursion_desired = request.header.recursion_desired;
+    response.questions = request.questions.clone();
+
+    let answered = request.questions.first().and_then(|question| {
+        zones
+            .iter()
+            .filter(|zone| zone.contains(&question.name))
+            .max_by_key(|zone| name::labels(&zone.origin).len())
+            .map(|zone| zone.answer(&question.name, question.qtype, false))
+    });
+    let result = match (behavior, answered) {
+        (Behavior::Lame, _) | (_, None) => {
+            response.header.response_code = ResultCode::REFUSED;
+            return response;
+        }
+        (_, Some(result)) => result,
+    };
+
+    response.header.authoritative_answer = result.header.authoritative_answer;
+    response.header.response_code = result.header.response_code;
+    response.answers = result.answers;
+    response.authorities = result.authorities;
+    response.resources = result.resources;
+
+    if let Behavior::Lying(lie) = behavior {
+        for record in &mut response.answers {
+            if let DnsRecord::A { addr, .. } = record {
+                *addr = *lie;
+            }
+        }
+    }
+
+    response
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::super::blocklist::{BlockResponse, Blocklists};
+    use super::super::byte_packet_buffer::BytePacketBuffer;
+    use super::super::config::ServerConfig;
+    use super::super::hosts::Hosts;
+    use super::super::model::dns_class::DnsClass;
+    use super::super::model::dns_question::DnsQuestion;
+    use super::super::model::query_type::QueryType;
+    use super::super::query::{
+        exchange, handle_query, lookup_with, query_packet, recursive_lookup, LookupOptions,
+        Resolver,
+    };
+    use super::fixture::{addresses, network, LAME_ROOT, LEAF, ROOT, TLD};
+    use super::{Behavior, MockNetwork, ResultCode};
+    use std::net::{Ipv4Addr, UdpSocket};
+    use std::time::Duration;
+
+    fn config(network: &MockNetwork, root_hints: &[Ipv4Addr]) -> ServerConfig {
+        ServerConfig {
+            root_hints: root_hints.to_vec(),
+            upstream_port: network.port,
+            upstream_timeout: Duration::from_millis(100),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn recursive_lookup_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let network = network(Behavior::Authoritative)?;
+        let config = config(&network, &[ROOT]);
+
+        // act
+        let www = recursive_lookup("www.example.test", QueryType::A, &config)?;
+        let alias = recursive_lookup("alias.example.test", QueryType::A, &config)?;
+        let glueless = recursive_lookup("www.glueless.test", QueryType::A, &config)?;
+        let nxdomain = recursive_lookup("nope.example.test", QueryType::A, &config)?;
+
+        // assert
+        assert_eq!(vec![Ipv4Addr::new(192, 0, 2, 1)], addresses(&www));
+        assert!(www.header.authoritative_answer);
+        assert_eq!(vec![Ipv4Addr::new(192, 0, 2, 1)], addresses(&alias));
+        assert_eq!(vec![Ipv4Addr::new(192, 0, 2, 1)], addresses(&glueless));
+        assert_eq!(ResultCode::NXDOMAIN, nxdomain.header.response_code);
+        assert_eq!(
+            vec![
+                (ROOT, "www.example.test. IN A".to_string()),
+                (TLD, "www.example.test. IN A".to_string()),
+                (LEAF, "www.example.test. IN A".to_string()),
+            ],
+            network.queries()[..3]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn recursive_lookup_lame_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let network = network(Behavior::Lame)?;
+        let config = config(&network, &[LAME_ROOT, ROOT]);
+        let mut resolver = Resolver::from_config(&config);
+        let mut trace = Vec::new();
+
+        // act
+        let (www, _) =
+            resolver.resolve("www.example.test", QueryType::A, None, false, &mut trace)?;
+        let lame = recursive_lookup("www.lame.test", QueryType::A, &config)?;
+
+        // assert
+        assert_eq!(vec![Ipv4Addr::new(192, 0, 2, 1)], addresses(&www));
+        assert_eq!(
+            vec![
+                (LAME_ROOT, Ok(ResultCode::REFUSED)),
+                (ROOT, Ok(ResultCode::NOERROR)),
+                (TLD, Ok(ResultCode::NOERROR)),
+                (LEAF, Ok(ResultCode::NOERROR)),
+            ],
+            trace
+                .iter()
+                .map(|step| (step.server, step.response_code.clone()))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(ResultCode::REFUSED, lame.header.response_code);
+
+        Ok(())
+    }
+
+    #[test]
+    fn recursive_lookup_slow_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let network = network(Behavior::Authoritative)?;
+        let mut config = config(&network, &[ROOT]);
+
+        // act
+        let timed_out = recursive_lookup("www.slow.test", QueryType::A, &config);
+        config.upstream_timeout = Duration::from_secs(2);
+        let waited = recursive_lookup("www.slow.test", QueryType::A, &config)?;
+
+        // assert
+        assert!(timed_out.is_err());
+        assert_eq!(vec![Ipv4Addr::new(192, 0, 2, 1)], addresses(&waited));
+
+        Ok(())
+    }
+
+    #[test]
+    fn recursive_lookup_lying_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let network = network(Behavior::Authoritative)?;
+        let config = config(&network, &[ROOT]);
+
+        // act
+        let actual = recursive_lookup("www.lying.test", QueryType::A, &config)?;
+
+        // assert
+        // NOTE: Without DNSSEC, nothing tells the lie apart from the real address
+        assert_eq!(vec![Ipv4Addr::new(203, 0, 113, 66)], addresses(&actual));
+
+        Ok(())
+    }
+
+    #[test]
+    fn handle_query_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let network = network(Behavior::Authoritative)?;
+        let config = config(&network, &[ROOT]);
+        let socket = UdpSocket::bind(("127.0.0.1", 0))?;
+        let port = socket.local_addr()?.port();
+        let server = std::thread::spawn(move || {
+            let mut hosts = Hosts::new(&[], &[], 0).map_err(|e| e.to_string())?;
+            let mut blocklists = Blocklists::new(BlockResponse::Null, 0);
+            handle_query(&socket, &config, None, &mut [], &mut hosts, &mut blocklists)
+                .map_err(|e| e.to_string())
+        });
+        let question = DnsQuestion::new("alias.example.test".to_string(), QueryType::A);
+
+        // act
+        let (actual, _) = lookup_with(
+            &question,
+            (Ipv4Addr::LOCALHOST, port),
+            &LookupOptions::default(),
+        )?;
+        server.join().map_err(|_| "The server failed")??;
+
+        // assert
+        assert!(actual.header.recursion_available);
+        assert_eq!(ResultCode::NOERROR, actual.header.response_code);
+        assert_eq!(
+            vec![
+                "alias.example.test. 300 IN CNAME www.example.test.".to_string(),
+                "www.example.test. 300 IN A 192.0.2.1".to_string(),
+            ],
+            actual
+                .answers
+                .iter()
+                .map(|rec| rec.to_string())
+                .collect::<Vec<_>>()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn handle_query_hosts_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let network = network(Behavior::Authoritative)?;
+        let mut config = config(&network, &[ROOT]);
+        config.local_records = vec!["www.example.test. A 10.0.0.5".to_string()];
+        config.local_ttl = 60;
+        let socket = UdpSocket::bind(("127.0.0.1", 0))?;
+        let port = socket.local_addr()?.port();
+        let server = std::thread::spawn(move || {
+            let mut hosts = Hosts::from_config(&config).map_err(|e| e.to_string())?;
+            let mut blocklists = Blocklists::new(BlockResponse::Null, 0);
+            handle_query(&socket, &config, None, &mut [], &mut hosts, &mut blocklists)
+                .map_err(|e| e.to_string())
+        });
+        let question = DnsQuestion::new("www.example.test".to_string(), QueryType::A);
+
+        // act
+        let (actual, _) = lookup_with(
+            &question,
+            (Ipv4Addr::LOCALHOST, port),
+            &LookupOptions::default(),
+        )?;
+        server.join().map_err(|_| "The server failed")??;
+
+        // assert
+        assert!(actual.header.authoritative_answer);
+        assert_eq!(ResultCode::NOERROR, actual.header.response_code);
+        assert_eq!(
+            vec!["www.example.test. 60 IN A 10.0.0.5".to_string()],
+            actual
+                .answers
+                .iter()
+                .map(|rec| rec.to_string())
+                .collect::<Vec<_>>()
+        );
+        assert!(network.queries().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn handle_query_class_refused() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let network = network(Behavior::Authoritative)?;
+        let config = config(&network, &[ROOT]);
+        let socket = UdpSocket::bind(("127.0.0.1", 0))?;
+        let port = socket.local_addr()?.port();
+        let server = std::thread::spawn(move || {
+            let mut hosts = Hosts::new(&[], &[], 0).map_err(|e| e.to_string())?;
+            let mut blocklists = Blocklists::new(BlockResponse::Null, 0);
+            handle_query(&socket, &config, None, &mut [], &mut hosts, &mut blocklists)
+                .map_err(|e| e.to_string())
+        });
+        let mut question = DnsQuestion::new("www.example.test".to_string(), QueryType::A);
+        question.qclass = DnsClass::HS;
+
+        // act
+        let (actual, _) = lookup_with(
+            &question,
+            (Ipv4Addr::LOCALHOST, port),
+            &LookupOptions::default(),
+        )?;
+        server.join().map_err(|_| "The server failed")??;
+
+        // assert
+        assert_eq!(ResultCode::REFUSED, actual.header.response_code);
+        assert!(actual.answers.is_empty());
+        assert!(network.queries().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn handle_query_truncated_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let network = network(Behavior::Authoritative)?;
+        let mut config = config(&network, &[ROOT]);
+        // NOTE: 40 addresses don't fit the 512 bytes of a client without EDNS
+        config.local_records = (0..40)
+            .map(|i| format!("big.example.test. A 10.0.0.{}", i))
+            .collect();
+        let socket = UdpSocket::bind(("127.0.0.1", 0))?;
+        let port = socket.local_addr()?.port();
+        let server = std::thread::spawn(move || {
+            let mut hosts = Hosts::from_config(&config).map_err(|e| e.to_string())?;
+            let mut blocklists = Blocklists::new(BlockResponse::Null, 0);
+            handle_query(&socket, &config, None, &mut [], &mut hosts, &mut blocklists)
+                .map_err(|e| e.to_string())
+        });
+        let question = DnsQuestion::new("big.example.test".to_string(), QueryType::A);
+        let mut request = query_packet(&question, true, false);
+        request.resources.clear();
+        let mut buffer = BytePacketBuffer::new();
+        request.write(&mut buffer)?;
+
+        // act
+        let (actual, size) = exchange(
+            &buffer.buf[..buffer.pos()],
+            (Ipv4Addr::LOCALHOST, port),
+            false,
+            Duration::from_secs(5),
+        )?;
+        server.join().map_err(|_| "The server failed")??;
+
+        // assert
+        assert!(actual.header.truncated_message);
+        assert!(size <= 512);
+        assert!(actual.answers.is_empty());
+        assert_eq!(vec![question], actual.questions);
+
+        Ok(())
+    }
+
+    #[test]
+    fn handle_query_truncated_large_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let network = network(Behavior::Authoritative)?;
+        let mut config = config(&network, &[ROOT]);
+        // NOTE: 300 addresses don't fit the 4096 bytes we send over UDP, whatever the client takes
+        config.local_records = (0..300)
+            .map(|i| format!("big.example.test. A 10.0.{}.{}", i / 256, i % 256))
+            .collect();
+        let socket = UdpSocket::bind(("127.0.0.1", 0))?;
+        let port = socket.local_addr()?.port();
+        let server = std::thread::spawn(move || {
+            let mut hosts = Hosts::from_config(&config).map_err(|e| e.to_string())?;
+            let mut blocklists = Blocklists::new(BlockResponse::Null, 0);
+            handle_query(&socket, &config, None, &mut [], &mut hosts, &mut blocklists)
+                .map_err(|e| e.to_string())
+        });
+        let question = DnsQuestion::new("big.example.test".to_string(), QueryType::A);
+        let mut request = query_packet(&question, true, false);
+        request.set_edns(u16::MAX, false);
+        let mut buffer = BytePacketBuffer::new();
+        request.write(&mut buffer)?;
+
+        // act
+        let (actual, size) = exchange(
+            &buffer.buf[..buffer.pos()],
+            (Ipv4Addr::LOCALHOST, port),
+            false,
+            Duration::from_secs(5),
+        )?;
+        server.join().map_err(|_| "The server failed")??;
+
+        // assert
+        assert!(actual.header.truncated_message);
+        assert!(size <= 4096);
+        assert!(actual.answers.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn handle_query_blocked_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let network = network(Behavior::Authoritative)?;
+        let config = config(&network, &[ROOT]);
+        let socket = UdpSocket::bind(("127.0.0.1", 0))?;
+        let port = socket.local_addr()?.port();
+        let server = std::thread::spawn(move || {
+            let mut hosts = Hosts::new(&[], &[], 0).map_err(|e| e.to_string())?;
+            let mut blocklists = Blocklists::new(BlockResponse::Nxdomain, 0);
+            blocklists.add("ads", "||example.test^\n", false);
+            handle_query(&socket, &config, None, &mut [], &mut hosts, &mut blocklists)
+                .map_err(|e| e.to_string())?;
+            Ok::<_, String>(blocklists.lists()[0].hits)
+        });
+        let question = DnsQuestion::new("www.example.test".to_string(), QueryType::A);
+
+        // act
+        let (actual, _) = lookup_with(
+            &question,
+            (Ipv4Addr::LOCALHOST, port),
+            &LookupOptions::default(),
+        )?;
+        let hits = server.join().map_err(|_| "The server failed")??;
+
+        // assert
+        assert!(actual.header.authoritative_answer);
+        assert_eq!(ResultCode::NXDOMAIN, actual.header.response_code);
+        assert!(actual.answers.is_empty());
+        assert_eq!(1, hits);
+        assert!(network.queries().is_empty());
+
+        Ok(())
+    }
+}
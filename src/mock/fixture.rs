@@ -0,0 +1,105 @@
+//! The mock network the tests of the resolvers share: a root server delegating `test` to the
+//! server of the zone, which delegates the leaf zones below it to servers of their own, each
+//! answering in its own way
+
+use super::super::model::{dns_packet::DnsPacket, dns_record::DnsRecord};
+#[cfg(target_os = "linux")]
+use super::MockNetwork;
+use super::{Behavior, MockServer};
+use std::net::Ipv4Addr;
+use std::time::Duration;
+
+pub const ROOT: Ipv4Addr = Ipv4Addr::new(127, 0, 53, 1);
+/// Another root server, answering as the tests want
+pub const LAME_ROOT: Ipv4Addr = Ipv4Addr::new(127, 0, 53, 2);
+pub const TLD: Ipv4Addr = Ipv4Addr::new(127, 0, 53, 11);
+pub const LEAF: Ipv4Addr = Ipv4Addr::new(127, 0, 53, 21);
+pub const LAME: Ipv4Addr = Ipv4Addr::new(127, 0, 53, 22);
+pub const SLOW: Ipv4Addr = Ipv4Addr::new(127, 0, 53, 23);
+pub const LYING: Ipv4Addr = Ipv4Addr::new(127, 0, 53, 24);
+/// A second server of `example.test`, which isn't delegated to
+pub const BACKUP: Ipv4Addr = Ipv4Addr::new(127, 0, 53, 25);
+
+/// How long the slow server takes to answer
+pub const SLOW_DELAY: Duration = Duration::from_millis(300);
+
+pub const ROOT_ZONE: &str = "\
+$TTL 86400
+@        IN SOA a.root-servers.test. hostmaster.test. 1 1800 900 604800 86400
+@        IN NS  a.root-servers.test.
+test.    IN NS  ns.test.
+other.   IN NS  ns.test.
+ns.test. IN A   127.0.53.11
+";
+
+pub const TEST_ZONE: &str = "\
+$TTL 3600
+@          IN SOA ns hostmaster 1 7200 3600 1209600 300
+@          IN NS  ns
+ns         IN A   127.0.53.11
+example    IN NS  ns.example
+ns.example IN A   127.0.53.21
+glueless   IN NS  ns.other.
+lame       IN NS  ns.lame
+ns.lame    IN A   127.0.53.22
+slow       IN NS  ns.slow
+ns.slow    IN A   127.0.53.23
+lying      IN NS  ns.lying
+ns.lying   IN A   127.0.53.24
+";
+
+pub const OTHER_ZONE: &str = "\
+$TTL 3600
+@  IN SOA ns.test. hostmaster.test. 1 7200 3600 1209600 300
+@  IN NS  ns.test.
+ns IN A   127.0.53.21
+";
+
+/// A leaf zone whose name server has the address
+pub fn leaf_zone(addr: &str) -> String {
+    format!(
+        "$TTL 300\n\
+         @      IN SOA  ns hostmaster 1 7200 3600 1209600 300\n\
+         @      IN NS   ns\n\
+         ns     IN A    {addr}\n\
+         www    IN A    192.0.2.1\n\
+         www    IN AAAA 2001:db8::1\n\
+         alias  IN CNAME www\n\
+         mail   IN MX   10 www\n\
+         www.lab IN A   192.0.2.2\n"
+    )
+}
+
+/// The servers of the network, the other root server answering as given
+pub fn servers(lame_root: Behavior) -> Vec<MockServer> {
+    vec![
+        MockServer::new(ROOT, "", ROOT_ZONE),
+        MockServer::new(LAME_ROOT, "", ROOT_ZONE).behaving(lame_root),
+        MockServer::new(TLD, "test", TEST_ZONE).serving("other", OTHER_ZONE),
+        MockServer::new(LEAF, "example.test", &leaf_zone("127.0.53.21"))
+            .serving("glueless.test", &leaf_zone("127.0.53.21")),
+        MockServer::new(LAME, "lame.test", &leaf_zone("127.0.53.22")).behaving(Behavior::Lame),
+        MockServer::new(SLOW, "slow.test", &leaf_zone("127.0.53.23"))
+            .behaving(Behavior::Slow(SLOW_DELAY)),
+        MockServer::new(LYING, "lying.test", &leaf_zone("127.0.53.24"))
+            .behaving(Behavior::Lying(Ipv4Addr::new(203, 0, 113, 66))),
+        MockServer::new(BACKUP, "example.test", &leaf_zone("127.0.53.21")),
+    ]
+}
+
+#[cfg(target_os = "linux")]
+pub fn network(lame_root: Behavior) -> Result<MockNetwork, Box<dyn std::error::Error>> {
+    MockNetwork::start(servers(lame_root))
+}
+
+/// The addresses of the A records of the answer
+pub fn addresses(response: &DnsPacket) -> Vec<Ipv4Addr> {
+    response
+        .answers
+        .iter()
+        .filter_map(|rec| match rec {
+            DnsRecord::A { addr, .. } => Some(*addr),
+            _ => None,
+        })
+        .collect()
+}
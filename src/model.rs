@@ -1,6 +1,14 @@
+pub mod dns_class;
 pub mod dns_header;
 pub mod dns_packet;
 pub mod dns_question;
 pub mod dns_record;
+#[cfg(feature = "json")]
+pub mod json;
+pub mod presentation;
 pub mod query_type;
 pub mod result_code;
+#[cfg(test)]
+pub mod strategy;
+pub mod svc_param;
+pub mod type_bitmap;
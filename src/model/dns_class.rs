@@ -0,0 +1,61 @@
+use std::fmt;
+
+/// Class of a question or a record
+#[derive(PartialEq, Eq, Debug, Clone, Hash, Copy, PartialOrd, Ord)]
+pub enum DnsClass {
+    UNKNOWN(u16),
+    IN,   // 1
+    CH,   // 3
+    HS,   // 4
+    NONE, // 254
+    ANY,  // 255
+}
+
+impl DnsClass {
+    pub fn to_num(&self) -> u16 {
+        match *self {
+            DnsClass::UNKNOWN(x) => x,
+            DnsClass::IN => 1,
+            DnsClass::CH => 3,
+            DnsClass::HS => 4,
+            DnsClass::NONE => 254,
+            DnsClass::ANY => 255,
+        }
+    }
+
+    pub fn from_num(num: u16) -> Self {
+        match num {
+            1 => DnsClass::IN,
+            3 => DnsClass::CH,
+            4 => DnsClass::HS,
+            254 => DnsClass::NONE,
+            255 => DnsClass::ANY,
+            _ => DnsClass::UNKNOWN(num),
+        }
+    }
+}
+
+impl DnsClass {
+    /// The class of a zone file mnemonic, such as `IN` or `CLASS32`, ignoring case
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_uppercase();
+        if let Some(num) = name.strip_prefix("CLASS") {
+            return num.parse().ok().map(DnsClass::from_num);
+        }
+
+        [1, 3, 4, 254, 255]
+            .into_iter()
+            .map(DnsClass::from_num)
+            .find(|class| class.to_string() == name)
+    }
+}
+
+impl fmt::Display for DnsClass {
+    /// The mnemonic used in zone files, with `CLASS<num>` for unknown classes (RFC 3597)
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            DnsClass::UNKNOWN(x) => write!(f, "CLASS{}", x),
+            _ => write!(f, "{:?}", self),
+        }
+    }
+}
@@ -1,7 +1,8 @@
 use super::super::BytePacketBuffer;
 use super::result_code::ResultCode;
+use std::fmt;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct DnsHeader {
     pub id: u16, // 16 bits
 
@@ -23,6 +24,12 @@ pub struct DnsHeader {
     pub additional_count: u16, // pub resource_entries: u16,      // 16 bits
 }
 
+impl Default for DnsHeader {
+    fn default() -> Self {
+        DnsHeader::new()
+    }
+}
+
 impl DnsHeader {
     pub fn new() -> Self {
         DnsHeader {
@@ -76,6 +83,7 @@ impl DnsHeader {
         Ok(())
     }
 
+    #[allow(clippy::identity_op)]
     pub fn write(&self, buffer: &mut BytePacketBuffer) -> Result<(), Box<dyn std::error::Error>> {
         buffer.write_u16(self.id)?;
 
@@ -89,7 +97,7 @@ impl DnsHeader {
         //      E
         buffer.write_u8(
             ((self.query_response as u8) << 7)
-                | ((self.opcode as u8) << 3)
+                | (self.opcode << 3)
                 | ((self.authoritative_answer as u8) << 2)
                 | ((self.truncated_message as u8) << 1)
                 | ((self.recursion_desired as u8) << 0),
@@ -112,11 +120,57 @@ impl DnsHeader {
     }
 }
 
+/// The header as dig shows it, e.g.
+/// `;; ->>HEADER<<- opcode: QUERY, status: NOERROR, id: 34346` then
+/// `;; flags: qr rd ra; QUERY: 1, ANSWER: 1, AUTHORITY: 0, ADDITIONAL: 1`
+impl fmt::Display for DnsHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let opcode = match self.opcode {
+            0 => "QUERY".to_string(),
+            1 => "IQUERY".to_string(),
+            2 => "STATUS".to_string(),
+            4 => "NOTIFY".to_string(),
+            5 => "UPDATE".to_string(),
+            opcode => format!("RESERVED{}", opcode),
+        };
+        writeln!(
+            f,
+            ";; ->>HEADER<<- opcode: {}, status: {:?}, id: {}",
+            opcode, self.response_code, self.id
+        )?;
+
+        let flags: Vec<&str> = [
+            (self.query_response, "qr"),
+            (self.authoritative_answer, "aa"),
+            (self.truncated_message, "tc"),
+            (self.recursion_desired, "rd"),
+            (self.recursion_available, "ra"),
+            (self.authed_data, "ad"),
+            (self.checking_disabled, "cd"),
+        ]
+        .into_iter()
+        .filter(|(set, _)| *set)
+        .map(|(_, flag)| flag)
+        .collect();
+        write!(
+            f,
+            ";; flags: {}; QUERY: {}, ANSWER: {}, AUTHORITY: {}, ADDITIONAL: {}",
+            flags.join(" "),
+            self.questions_count,
+            self.answers_count,
+            self.authority_count,
+            self.additional_count
+        )
+    }
+}
+
 #[cfg(test)]
 pub mod tests {
+    use super::super::strategy::dns_header;
     use super::BytePacketBuffer;
     use super::DnsHeader;
     use super::ResultCode;
+    use proptest::prelude::*;
 
     #[test]
     fn dns_header_read_ok() -> Result<(), Box<dyn std::error::Error>> {
@@ -124,7 +178,7 @@ pub mod tests {
         let mut sut = DnsHeader::new();
         let mut buffer = BytePacketBuffer::new();
 
-        buffer.buf = [
+        buffer.buf[..512].copy_from_slice(&[
             0x86, 0x2a, 0x01, 0x20, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0, 0, 0, 0, 0,
             0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
             0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
@@ -144,7 +198,7 @@ pub mod tests {
             0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
             0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
             0, 0,
-        ];
+        ]);
 
         // act
         sut.read(&mut buffer)?;
@@ -160,16 +214,16 @@ pub mod tests {
         //      E
         assert_eq!(sut.id, 34346);
 
-        assert_eq!(sut.query_response, false);
+        assert!(!sut.query_response);
         assert_eq!(sut.opcode, 0);
-        assert_eq!(sut.authoritative_answer, false);
-        assert_eq!(sut.truncated_message, false);
-        assert_eq!(sut.recursion_desired, true);
-
-        assert_eq!(sut.recursion_available, false);
-        assert_eq!(sut.z, false);
-        assert_eq!(sut.authed_data, true);
-        assert_eq!(sut.checking_disabled, false);
+        assert!(!sut.authoritative_answer);
+        assert!(!sut.truncated_message);
+        assert!(sut.recursion_desired);
+
+        assert!(!sut.recursion_available);
+        assert!(!sut.z);
+        assert!(sut.authed_data);
+        assert!(!sut.checking_disabled);
         assert_eq!(sut.response_code, ResultCode::from_number(0));
 
         assert_eq!(sut.questions_count, 1);
@@ -232,8 +286,49 @@ pub mod tests {
         sut.write(&mut buffer)?;
 
         // assert
-        assert_eq!(expected, buffer.buf);
+        assert_eq!(expected, buffer.buf[..512]);
 
         Ok(())
     }
+
+    #[test]
+    fn dns_header_display_ok() {
+        // arrange
+        let mut sut = DnsHeader::new();
+        sut.id = 34346;
+        sut.query_response = true;
+        sut.recursion_desired = true;
+        sut.recursion_available = true;
+        sut.response_code = ResultCode::NXDOMAIN;
+        sut.questions_count = 1;
+        sut.authority_count = 1;
+
+        // act
+        let actual = sut.to_string();
+
+        // assert
+        assert_eq!(
+            ";; ->>HEADER<<- opcode: QUERY, status: NXDOMAIN, id: 34346\n\
+             ;; flags: qr rd ra; QUERY: 1, ANSWER: 0, AUTHORITY: 1, ADDITIONAL: 0",
+            actual
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn dns_header_round_trip_ok(expected in dns_header()) {
+            // arrange
+            let mut buffer = BytePacketBuffer::new();
+            let mut actual = DnsHeader::new();
+
+            // act
+            expected.write(&mut buffer).unwrap();
+            buffer.seek(0).unwrap();
+            actual.read(&mut buffer).unwrap();
+
+            // assert
+            prop_assert_eq!(expected, actual);
+            prop_assert_eq!(12, buffer.pos());
+        }
+    }
 }
@@ -1,9 +1,12 @@
 use super::super::BytePacketBuffer;
 use super::query_type::QueryType;
+use super::svc_param::SvcParam;
 use super::{dns_header::DnsHeader, dns_question::DnsQuestion, dns_record::DnsRecord};
+use rand::Rng;
+use std::fmt;
 use std::net::Ipv4Addr;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct DnsPacket {
     pub header: DnsHeader,
     pub questions: Vec<DnsQuestion>,
@@ -12,6 +15,12 @@ pub struct DnsPacket {
     pub resources: Vec<DnsRecord>,
 }
 
+impl Default for DnsPacket {
+    fn default() -> Self {
+        DnsPacket::new()
+    }
+}
+
 impl DnsPacket {
     pub fn new() -> Self {
         DnsPacket {
@@ -83,6 +92,40 @@ impl DnsPacket {
         Ok(())
     }
 
+    /// Add the EDNS pseudo record (RFC 6891) advertising the UDP payload size we accept, with the
+    /// DO bit asking for DNSSEC records when `dnssec_ok` is set (RFC 3225)
+    pub fn set_edns(&mut self, packet_len: u16, dnssec_ok: bool) {
+        self.resources
+            .retain(|record| !matches!(record, DnsRecord::OPT { .. }));
+        self.resources.push(DnsRecord::OPT {
+            domain: "".to_string(),
+            packet_len,
+            flags: if dnssec_ok { 0x8000 } else { 0 },
+            data: Vec::new(),
+        });
+    }
+
+    /// The EDNS pseudo record of the packet, if any
+    pub fn edns(&self) -> Option<&DnsRecord> {
+        self.resources
+            .iter()
+            .find(|record| matches!(record, DnsRecord::OPT { .. }))
+    }
+
+    /// Whether the sender asked for DNSSEC records with the DO bit
+    pub fn dnssec_ok(&self) -> bool {
+        matches!(self.edns(), Some(DnsRecord::OPT { flags, .. }) if flags & 0x8000 != 0)
+    }
+
+    /// The largest response the sender accepts over UDP: its EDNS payload size, but never less
+    /// than the 512 bytes every client accepts
+    pub fn max_udp_size(&self) -> usize {
+        match self.edns() {
+            Some(DnsRecord::OPT { packet_len, .. }) => (*packet_len as usize).max(512),
+            _ => 512,
+        }
+    }
+
     // If we get multiple IP's for a single name, it doesn't matter which one we choose, so in
     // those cases we can now pick one at random.
     pub fn get_random_a(&self) -> Option<Ipv4Addr> {
@@ -126,33 +169,237 @@ impl DnsPacket {
         // Get an iterator over the name servers in the authorities section
         self.get_ns(qname)
             // Finally, pick the first one
-            .find_map(|(_, host)| Some(host))
+            .map(|(_, host)| host)
+            .next()
+    }
+
+    /// Return the targets of the SRV records in the answer section as (host, port) tuples, in the
+    /// order a client should try them.
+    pub fn get_ordered_srv(&self) -> Vec<(&str, u16)> {
+        self.get_ordered_srv_with(&mut rand::thread_rng())
+    }
+
+    /// Same as `get_ordered_srv`, but draws the weighted random selection from the given `rng`.
+    ///
+    /// Following RFC 2782, targets are tried in ascending priority. Within one priority, the next
+    /// target is picked at random with a probability proportional to its weight, where records of
+    /// weight 0 are placed first so they only have a small chance of being selected. A target of
+    /// "." means the service is decidedly not available, so it is never returned.
+    pub fn get_ordered_srv_with<R: Rng + ?Sized>(&self, rng: &mut R) -> Vec<(&str, u16)> {
+        // (priority, weight, host, port)
+        let mut records: Vec<(u16, u16, &str, u16)> = self
+            .answers
+            .iter()
+            .filter_map(|record| match record {
+                DnsRecord::SRV {
+                    priority,
+                    weight,
+                    port,
+                    host,
+                    ..
+                } if !host.is_empty() && host != "." => {
+                    Some((*priority, *weight, host.as_str(), *port))
+                }
+                _ => None,
+            })
+            .collect();
+        // NOTE: The sort is stable, so zero weights come first within each priority
+        records.sort_by_key(|(priority, weight, _, _)| (*priority, *weight != 0));
+
+        let mut ordered = Vec::with_capacity(records.len());
+        for group in records.chunk_by(|a, b| a.0 == b.0) {
+            let mut group = group.to_vec();
+
+            while !group.is_empty() {
+                let total: u32 = group.iter().map(|(_, weight, _, _)| *weight as u32).sum();
+                let target = rng.gen_range(0..=total);
+
+                // Select the first record whose running sum of weights reaches the random number
+                let mut running_sum = 0;
+                let index = group
+                    .iter()
+                    .position(|(_, weight, _, _)| {
+                        running_sum += *weight as u32;
+                        running_sum >= target
+                    })
+                    .unwrap_or(group.len() - 1);
+
+                let (_, _, host, port) = group.remove(index);
+                ordered.push((host, port));
+            }
+        }
+
+        ordered
+    }
+
+    /// SVCB and HTTPS records carry address hints for their target. This helper turns the hints of
+    /// the records in the answer section into A and AAAA records, which can be placed in the
+    /// additional section as glue. Addresses already present there are not repeated.
+    pub fn get_svcb_hint_glue(&self) -> Vec<DnsRecord> {
+        let mut glue: Vec<DnsRecord> = Vec::new();
+
+        for record in &self.answers {
+            let (domain, target, params, class, ttl) = match record {
+                DnsRecord::SVCB {
+                    domain,
+                    target,
+                    params,
+                    class,
+                    ttl,
+                    ..
+                }
+                | DnsRecord::HTTPS {
+                    domain,
+                    target,
+                    params,
+                    class,
+                    ttl,
+                    ..
+                } => (domain, target, params, *class, *ttl),
+                _ => continue,
+            };
+
+            // In service mode, a target of "." stands for the owner name itself
+            let host = if target.is_empty() || target == "." {
+                domain
+            } else {
+                target
+            };
+
+            for param in params {
+                let hints: Vec<DnsRecord> = match param {
+                    SvcParam::Ipv4Hint(addrs) => addrs
+                        .iter()
+                        .map(|addr| DnsRecord::A {
+                            domain: host.clone(),
+                            addr: *addr,
+                            class,
+                            ttl,
+                        })
+                        .collect(),
+                    SvcParam::Ipv6Hint(addrs) => addrs
+                        .iter()
+                        .map(|addr| DnsRecord::AAAA {
+                            domain: host.clone(),
+                            addr: *addr,
+                            class,
+                            ttl,
+                        })
+                        .collect(),
+                    _ => continue,
+                };
+
+                for hint in hints {
+                    let known = self.resources.iter().chain(glue.iter()).any(|record| {
+                        record.domain() == hint.domain()
+                            && match (record, &hint) {
+                                (DnsRecord::A { addr: a, .. }, DnsRecord::A { addr: b, .. }) => {
+                                    a == b
+                                }
+                                (
+                                    DnsRecord::AAAA { addr: a, .. },
+                                    DnsRecord::AAAA { addr: b, .. },
+                                ) => a == b,
+                                _ => false,
+                            }
+                    });
+
+                    if !known {
+                        glue.push(hint);
+                    }
+                }
+            }
+        }
+
+        glue
+    }
+}
+
+/// The packet as dig shows it: the header, the EDNS pseudosection, then each section that has
+/// records, in zone file syntax
+impl fmt::Display for DnsPacket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // NOTE: The counts of the header are only updated when writing, so they're taken from the
+        // sections
+        let mut header = self.header.clone();
+        header.questions_count = self.questions.len() as u16;
+        header.answers_count = self.answers.len() as u16;
+        header.authority_count = self.authorities.len() as u16;
+        header.additional_count = self.resources.len() as u16;
+        writeln!(f, "{}", header)?;
+
+        if let Some(edns) = self.edns() {
+            write!(f, "\n;; OPT PSEUDOSECTION:\n{}\n", edns)?;
+        }
+
+        writeln!(f, "\n;; QUESTION SECTION:")?;
+        for question in &self.questions {
+            writeln!(f, ";{}", question)?;
+        }
+
+        let additionals: Vec<&DnsRecord> = self
+            .resources
+            .iter()
+            .filter(|rec| !matches!(rec, DnsRecord::OPT { .. }))
+            .collect();
+        let sections = [
+            ("ANSWER", self.answers.iter().collect::<Vec<_>>()),
+            ("AUTHORITY", self.authorities.iter().collect()),
+            ("ADDITIONAL", additionals),
+        ];
+        for (name, records) in sections {
+            if records.is_empty() {
+                continue;
+            }
+
+            writeln!(f, "\n;; {} SECTION:", name)?;
+            for rec in records {
+                writeln!(f, "{}", rec)?;
+            }
+        }
+
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::super::super::encoding::from_base32hex;
+    use super::super::dns_class::DnsClass;
+    use super::super::query_type::QueryType;
+    use super::super::strategy::{
+        dns_packet, dns_question, dns_record, label, name, short_name, wire_len,
+    };
+    use super::BytePacketBuffer;
     use super::DnsPacket;
+    use super::DnsQuestion;
     use super::DnsRecord;
-    use std::net::Ipv4Addr;
+    use super::SvcParam;
+    use proptest::collection::vec;
+    use proptest::option;
+    use proptest::prelude::*;
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::net::{Ipv4Addr, Ipv6Addr};
 
     #[test]
     fn get_random_a_ok() {
         // arrange
         let mut dns_packet = DnsPacket::new();
-        let ips = vec![
+        let ips = [
             Ipv4Addr::new(216, 239, 34, 10),
             Ipv4Addr::new(216, 239, 38, 10),
         ];
         dns_packet.answers.push(DnsRecord::A {
             domain: String::from("google.com"),
             addr: Ipv4Addr::new(216, 239, 34, 10),
+            class: DnsClass::IN,
             ttl: 87,
         });
 
         dns_packet.answers.push(DnsRecord::A {
             domain: String::from("yahoo.com"),
             addr: Ipv4Addr::new(216, 239, 38, 10),
+            class: DnsClass::IN,
             ttl: 88,
         });
 
@@ -176,18 +423,21 @@ mod tests {
         dns_packet.authorities.push(DnsRecord::NS {
             domain: String::from("google.com"),
             host: String::from("ns2.google.com."),
+            class: DnsClass::IN,
             ttl: 88,
         });
 
         dns_packet.authorities.push(DnsRecord::NS {
             domain: String::from("google.com"),
             host: String::from("ns1.google.com."),
+            class: DnsClass::IN,
             ttl: 91,
         });
 
         dns_packet.authorities.push(DnsRecord::NS {
             domain: String::from("yahoo.com"),
             host: String::from("ns1.yahoo.com."),
+            class: DnsClass::IN,
             ttl: 90,
         });
 
@@ -203,7 +453,7 @@ mod tests {
     fn get_resolved_ns_ok() {
         // arrange
         let mut dns_packet = DnsPacket::new();
-        let expected = vec![
+        let expected = [
             Ipv4Addr::new(216, 239, 34, 10),
             Ipv4Addr::new(216, 239, 32, 10),
         ];
@@ -211,30 +461,35 @@ mod tests {
         dns_packet.authorities.push(DnsRecord::NS {
             domain: String::from("google.com"),
             host: String::from("ns2.google.com."),
+            class: DnsClass::IN,
             ttl: 88,
         });
 
         dns_packet.authorities.push(DnsRecord::NS {
             domain: String::from("google.com"),
             host: String::from("ns1.google.com."),
+            class: DnsClass::IN,
             ttl: 91,
         });
 
         dns_packet.authorities.push(DnsRecord::NS {
             domain: String::from("yahoo.com"),
             host: String::from("ns1.yahoo.com."),
+            class: DnsClass::IN,
             ttl: 90,
         });
 
         dns_packet.resources.push(DnsRecord::A {
             domain: String::from("ns2.google.com."),
             addr: Ipv4Addr::new(216, 239, 32, 10),
+            class: DnsClass::IN,
             ttl: 40,
         });
 
         dns_packet.resources.push(DnsRecord::A {
             domain: String::from("ns1.google.com."),
             addr: Ipv4Addr::new(216, 239, 34, 10),
+            class: DnsClass::IN,
             ttl: 38,
         });
 
@@ -249,7 +504,7 @@ mod tests {
     fn get_unsolved_ns_ok() {
         // arrange
         let mut dns_packet = DnsPacket::new();
-        let expected = vec![
+        let expected = [
             String::from("ns1.google.com."),
             String::from("ns2.google.com."),
         ];
@@ -257,18 +512,21 @@ mod tests {
         dns_packet.authorities.push(DnsRecord::NS {
             domain: String::from("google.com"),
             host: String::from("ns2.google.com."),
+            class: DnsClass::IN,
             ttl: 88,
         });
 
         dns_packet.authorities.push(DnsRecord::NS {
             domain: String::from("google.com"),
             host: String::from("ns1.google.com."),
+            class: DnsClass::IN,
             ttl: 91,
         });
 
         dns_packet.authorities.push(DnsRecord::NS {
             domain: String::from("yahoo.com"),
             host: String::from("ns1.yahoo.com."),
+            class: DnsClass::IN,
             ttl: 90,
         });
 
@@ -278,4 +536,342 @@ mod tests {
         // assert
         assert!(expected.contains(&actual.to_string()));
     }
+
+    #[test]
+    fn get_ordered_srv_ok() {
+        // arrange
+        let mut dns_packet = DnsPacket::new();
+        for (priority, weight, host) in [
+            (20, 0, "backup.example.com"),
+            (10, 60, "big.example.com"),
+            (10, 0, "spare.example.com"),
+            (10, 20, "small.example.com"),
+            (30, 0, "."),
+        ] {
+            dns_packet.answers.push(DnsRecord::SRV {
+                domain: String::from("_http._tcp.example.com"),
+                priority,
+                weight,
+                port: 80,
+                host: String::from(host),
+                class: DnsClass::IN,
+                ttl: 300,
+            });
+        }
+        let mut rng = StdRng::seed_from_u64(2782);
+
+        // act
+        let actual = dns_packet.get_ordered_srv_with(&mut rng);
+
+        // assert
+        assert_eq!(4, actual.len());
+        let mut first_priority: Vec<&str> = actual[..3].iter().map(|(host, _)| *host).collect();
+        first_priority.sort();
+        assert_eq!(
+            vec!["big.example.com", "small.example.com", "spare.example.com"],
+            first_priority
+        );
+        assert_eq!(("backup.example.com", 80), actual[3]);
+    }
+
+    #[test]
+    fn get_ordered_srv_weighted_ok() {
+        // arrange
+        let mut dns_packet = DnsPacket::new();
+        for (weight, host) in [(1, "light.example.com"), (99, "heavy.example.com")] {
+            dns_packet.answers.push(DnsRecord::SRV {
+                domain: String::from("_http._tcp.example.com"),
+                priority: 10,
+                weight,
+                port: 8080,
+                host: String::from(host),
+                class: DnsClass::IN,
+                ttl: 300,
+            });
+        }
+        let mut rng = StdRng::seed_from_u64(2782);
+
+        // act
+        let heavy_first = (0..1000)
+            .filter(|_| dns_packet.get_ordered_srv_with(&mut rng)[0].0 == "heavy.example.com")
+            .count();
+
+        // assert
+        assert!(heavy_first > 900);
+    }
+
+    #[test]
+    fn get_svcb_hint_glue_ok() {
+        // arrange
+        let mut dns_packet = DnsPacket::new();
+        dns_packet.answers.push(DnsRecord::HTTPS {
+            domain: String::from("example.com"),
+            priority: 1,
+            target: String::from(""),
+            params: vec![
+                SvcParam::Ipv4Hint(vec![
+                    Ipv4Addr::new(192, 0, 2, 1),
+                    Ipv4Addr::new(192, 0, 2, 2),
+                ]),
+                SvcParam::Ipv6Hint(vec![Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1)]),
+            ],
+            class: DnsClass::IN,
+            ttl: 300,
+        });
+        dns_packet.answers.push(DnsRecord::SVCB {
+            domain: String::from("_dns.example.com"),
+            priority: 1,
+            target: String::from("dns.example.com"),
+            params: vec![SvcParam::Ipv4Hint(vec![Ipv4Addr::new(192, 0, 2, 53)])],
+            class: DnsClass::IN,
+            ttl: 60,
+        });
+        dns_packet.resources.push(DnsRecord::A {
+            domain: String::from("example.com"),
+            addr: Ipv4Addr::new(192, 0, 2, 2),
+            class: DnsClass::IN,
+            ttl: 300,
+        });
+
+        // act
+        let actual = dns_packet.get_svcb_hint_glue();
+
+        // assert
+        assert_eq!(
+            vec![
+                DnsRecord::A {
+                    domain: String::from("example.com"),
+                    addr: Ipv4Addr::new(192, 0, 2, 1),
+                    class: DnsClass::IN,
+                    ttl: 300,
+                },
+                DnsRecord::AAAA {
+                    domain: String::from("example.com"),
+                    addr: Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1),
+                    class: DnsClass::IN,
+                    ttl: 300,
+                },
+                DnsRecord::A {
+                    domain: String::from("dns.example.com"),
+                    addr: Ipv4Addr::new(192, 0, 2, 53),
+                    class: DnsClass::IN,
+                    ttl: 60,
+                },
+            ],
+            actual
+        );
+    }
+
+    #[test]
+    fn edns_round_trip_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let mut sut = DnsPacket::new();
+        sut.set_edns(1232, true);
+        let mut buffer = BytePacketBuffer::new();
+
+        // act
+        sut.write(&mut buffer)?;
+        buffer.seek(0)?;
+        let actual = DnsPacket::from_buffer(&mut buffer)?;
+
+        // assert
+        assert!(actual.dnssec_ok());
+        assert_eq!(1232, actual.max_udp_size());
+        assert_eq!(
+            "; EDNS: version: 0, flags: do; udp: 1232",
+            actual.edns().unwrap().to_string()
+        );
+        assert_eq!(512, DnsPacket::new().max_udp_size());
+
+        Ok(())
+    }
+
+    /// A response captured from the signed zones stdc.org and pureftpd.org: a DNAME record (read as
+    /// unknown) and a CNAME record to a wildcard expansion, with its NSEC3 proof
+    fn captured_response() -> Result<DnsPacket, Box<dyn std::error::Error>> {
+        let data = include_bytes!("testdata/pureftpd_org_nsec3.bin");
+        let mut buffer = BytePacketBuffer::new();
+        buffer.buf[..data.len()].copy_from_slice(data);
+
+        DnsPacket::from_buffer(&mut buffer)
+    }
+
+    #[test]
+    fn from_buffer_captured_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // act
+        let actual = captured_response()?;
+
+        // assert
+        let types = |records: &[DnsRecord]| -> Vec<QueryType> {
+            records.iter().map(|record| record.qtype()).collect()
+        };
+        assert_eq!(
+            vec![
+                QueryType::UNKNOWN(39),
+                QueryType::RRSIG,
+                QueryType::CNAME,
+                QueryType::A,
+                QueryType::RRSIG,
+            ],
+            types(&actual.answers)
+        );
+        assert_eq!(
+            vec![
+                QueryType::NSEC3,
+                QueryType::RRSIG,
+                QueryType::NS,
+                QueryType::NS,
+                QueryType::RRSIG,
+            ],
+            types(&actual.authorities)
+        );
+        assert!(matches!(
+            &actual.answers[4],
+            DnsRecord::RRSIG {
+                type_covered: QueryType::A,
+                algorithm: 13,
+                labels: 2,
+                original_ttl: 43200,
+                expiration: 1498658546,
+                inception: 1496066546,
+                key_tag: 12787,
+                signer_name,
+                signature,
+                ..
+            } if signer_name == "pureftpd.org" && signature.len() == 64
+        ));
+        assert_eq!(
+            DnsRecord::NSEC3 {
+                domain: "bl7qdf8n1rihs7el5d56ckav3rqhec1h.pureftpd.org".to_string(),
+                hash_algorithm: 1,
+                flags: 0,
+                iterations: 2,
+                salt: vec![0x01],
+                next_hashed_owner: from_base32hex("ICCS9NEKGHLK80S2GA5SKTUV4Q9UTO10")?,
+                types: vec![QueryType::A, QueryType::RRSIG],
+                class: DnsClass::IN,
+                ttl: 3600,
+            },
+            actual.authorities[0]
+        );
+        assert!(actual.dnssec_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn captured_round_trip_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let mut sut = captured_response()?;
+        let mut buffer = BytePacketBuffer::new();
+
+        // act
+        sut.write(&mut buffer)?;
+        buffer.seek(0)?;
+        let actual = DnsPacket::from_buffer(&mut buffer)?;
+
+        // assert
+        assert_eq!(captured_response()?, actual);
+
+        Ok(())
+    }
+
+    #[test]
+    fn dns_packet_display_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let mut sut = DnsPacket::new();
+        sut.header.id = 1;
+        sut.header.query_response = true;
+        sut.header.recursion_desired = true;
+        sut.header.recursion_available = true;
+        sut.questions
+            .push(DnsQuestion::new("google.com".to_string(), QueryType::A));
+        sut.answers
+            .push("google.com. 300 IN A 142.251.42.238".parse::<DnsRecord>()?);
+        sut.set_edns(1232, true);
+
+        // act
+        let actual = sut.to_string();
+
+        // assert
+        assert_eq!(
+            ";; ->>HEADER<<- opcode: QUERY, status: NOERROR, id: 1\n\
+             ;; flags: qr rd ra; QUERY: 1, ANSWER: 1, AUTHORITY: 0, ADDITIONAL: 1\n\
+             \n\
+             ;; OPT PSEUDOSECTION:\n\
+             ; EDNS: version: 0, flags: do; udp: 1232\n\
+             \n\
+             ;; QUESTION SECTION:\n\
+             ;google.com. IN A\n\
+             \n\
+             ;; ANSWER SECTION:\n\
+             google.com. 300 IN A 142.251.42.238\n",
+            actual
+        );
+
+        Ok(())
+    }
+
+    proptest! {
+        #[test]
+        fn dns_packet_round_trip_ok(mut expected in dns_packet()) {
+            // arrange
+            let mut buffer = BytePacketBuffer::new();
+
+            // act
+            expected.write(&mut buffer).unwrap();
+            let len = buffer.pos();
+            buffer.seek(0).unwrap();
+            let actual = DnsPacket::from_buffer(&mut buffer).unwrap();
+
+            // assert
+            prop_assert_eq!(expected, actual);
+            prop_assert_eq!(len, buffer.pos());
+        }
+
+        #[test]
+        fn dns_packet_compressed_round_trip_ok(
+            question in dns_question(name()),
+            answers in vec((option::of(label()), dns_record(short_name())), 1..4),
+        ) {
+            // arrange
+            let mut expected = DnsPacket::new();
+            expected.questions.push(question.clone());
+            for (prefix, mut record) in answers.clone() {
+                *record.domain_mut() = match prefix {
+                    Some(prefix) if question.name.is_empty() => prefix,
+                    Some(prefix) => format!("{}.{}", prefix, question.name),
+                    None => question.name.clone(),
+                };
+                expected.answers.push(record);
+            }
+            prop_assume!(expected.answers.iter().all(|rec| wire_len(rec.domain()) <= 255));
+
+            // NOTE: Written without compression, then each answer owner made a pointer to the
+            // question name at offset 12, after its own first label if any
+            let mut plain = BytePacketBuffer::new();
+            expected.write(&mut plain).unwrap();
+            let mut compressed = plain.buf[..12 + wire_len(&question.name) + 4].to_vec();
+            for (record, (prefix, _)) in expected.answers.iter().zip(&answers) {
+                let mut buffer = BytePacketBuffer::new();
+                record.write(&mut buffer).unwrap();
+
+                if let Some(prefix) = prefix {
+                    compressed.push(prefix.len() as u8);
+                    compressed.extend_from_slice(prefix.as_bytes());
+                }
+                compressed.extend_from_slice(&[0xc0, 0x0c]);
+                compressed.extend_from_slice(&buffer.buf[wire_len(record.domain())..buffer.pos()]);
+            }
+            let mut buffer = BytePacketBuffer::new();
+            buffer.buf[..compressed.len()].copy_from_slice(&compressed);
+
+            // act
+            let actual = DnsPacket::from_buffer(&mut buffer).unwrap();
+
+            // assert
+            prop_assert_eq!(expected, actual);
+            prop_assert_eq!(compressed.len(), buffer.pos());
+        }
+    }
 }
@@ -1,15 +1,22 @@
 use super::super::BytePacketBuffer;
+use super::dns_class::DnsClass;
 use super::query_type::QueryType;
+use std::fmt;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DnsQuestion {
     pub name: String,
     pub qtype: QueryType,
+    pub qclass: DnsClass,
 }
 
 impl DnsQuestion {
     pub fn new(name: String, qtype: QueryType) -> Self {
-        DnsQuestion { name, qtype }
+        DnsQuestion {
+            name,
+            qtype,
+            qclass: DnsClass::IN,
+        }
     }
 
     pub fn read(
@@ -18,7 +25,7 @@ impl DnsQuestion {
     ) -> Result<(), Box<dyn std::error::Error>> {
         buffer.read_qname(&mut self.name)?; // for domain name
         self.qtype = QueryType::from_num(buffer.read_u16()?); // for qtype
-        let _ = buffer.read_u16()?; // for class
+        self.qclass = DnsClass::from_num(buffer.read_u16()?); // for class
 
         Ok(())
     }
@@ -28,17 +35,34 @@ impl DnsQuestion {
 
         let type_num = self.qtype.to_num();
         buffer.write_u16(type_num)?;
-        buffer.write_u16(1)?; // In pratice, the class is always set to 1
+        buffer.write_u16(self.qclass.to_num())?;
 
         Ok(())
     }
 }
 
+/// The question in zone file syntax, e.g. `google.com. IN A`
+impl fmt::Display for DnsQuestion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // NOTE: The root name is a single dot
+        write!(
+            f,
+            "{}. {} {}",
+            self.name.trim_end_matches('.'),
+            self.qclass,
+            self.qtype
+        )
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use super::super::strategy::{dns_question, name, wire_len};
     use super::BytePacketBuffer;
+    use super::DnsClass;
     use super::DnsQuestion;
     use super::QueryType;
+    use proptest::prelude::*;
 
     #[test]
     fn dns_question_read_ok() -> Result<(), Box<dyn std::error::Error>> {
@@ -46,7 +70,7 @@ mod tests {
         let mut sut = DnsQuestion::new("".to_string(), QueryType::A);
         let mut buffer = BytePacketBuffer::new();
 
-        buffer.buf = [
+        buffer.buf[..512].copy_from_slice(&[
             0x86, 0x2a, 0x01, 0x20, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x06, 0x67,
             0x6f, 0x6f, 0x67, 0x6c, 0x65, 0x03, 0x63, 0x6f, 0x6d, 0x00, 0x00, 0x01, 0x00, 0x01, 0,
             0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
@@ -66,7 +90,7 @@ mod tests {
             0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
             0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
             0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        ];
+        ]);
         buffer.pos = 12; // skip header
 
         // act
@@ -76,6 +100,7 @@ mod tests {
         assert_eq!(buffer.pos(), 28);
         assert_eq!(sut.name, "google.com".to_string());
         assert_eq!(sut.qtype, QueryType::A);
+        assert_eq!(sut.qclass, DnsClass::IN);
 
         Ok(())
     }
@@ -117,8 +142,64 @@ mod tests {
         sut.write(&mut buffer)?;
 
         // assert
-        assert_eq!(expected, buffer.buf);
+        assert_eq!(expected, buffer.buf[..512]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn dns_question_chaos_round_trip_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let mut sut = DnsQuestion::new("version.bind".to_string(), QueryType::TXT);
+        sut.qclass = DnsClass::CH;
+        let mut buffer = BytePacketBuffer::new();
+
+        // act
+        sut.write(&mut buffer)?;
+        buffer.seek(0)?;
+        let mut actual = DnsQuestion::new("".to_string(), QueryType::A);
+        actual.read(&mut buffer)?;
+
+        // assert
+        assert_eq!([0x00, 0x10, 0x00, 0x03], buffer.buf[14..18]);
+        assert_eq!(actual.name, "version.bind".to_string());
+        assert_eq!(actual.qtype, QueryType::TXT);
+        assert_eq!(actual.qclass, DnsClass::CH);
 
         Ok(())
     }
+
+    #[test]
+    fn dns_question_display_ok() {
+        // arrange
+        let sut = DnsQuestion::new("google.com".to_string(), QueryType::AAAA);
+        let root = DnsQuestion::new("".to_string(), QueryType::NS);
+
+        // act
+        let actual = (sut.to_string(), root.to_string());
+
+        // assert
+        assert_eq!(
+            ("google.com. IN AAAA".to_string(), ". IN NS".to_string()),
+            actual
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn dns_question_round_trip_ok(expected in dns_question(name())) {
+            // arrange
+            let mut buffer = BytePacketBuffer::new();
+            let mut actual = DnsQuestion::new("".to_string(), QueryType::A);
+
+            // act
+            expected.write(&mut buffer).unwrap();
+            buffer.seek(0).unwrap();
+            actual.read(&mut buffer).unwrap();
+
+            // assert
+            prop_assert_eq!(wire_len(&expected.name) + 4, buffer.pos());
+            prop_assert_eq!(expected, actual);
+        }
+    }
 }
@@ -0,0 +1,500 @@
+//! JSON representation of DNS messages (RFC 8427), behind the `json` feature: the header fields
+//! are members of the message, and each record carries its record data in hex (`RDATAHEX`) along
+//! with its presentation format (e.g. `rdataA`)
+
+use super::super::encoding::{from_hex, to_hex};
+use super::super::BytePacketBuffer;
+use super::{
+    dns_class::DnsClass, dns_header::DnsHeader, dns_packet::DnsPacket, dns_question::DnsQuestion,
+    dns_record::DnsRecord, query_type::QueryType, result_code::ResultCode,
+};
+use serde::de::Error as _;
+use serde::ser::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeMap;
+
+/// The header members of a message object (RFC 8427 §2.1), flags being 0 or 1
+#[derive(Serialize, Deserialize)]
+struct Header {
+    #[serde(rename = "ID", default)]
+    id: u16,
+    #[serde(rename = "QR", default)]
+    qr: u8,
+    #[serde(rename = "Opcode", default)]
+    opcode: u8,
+    #[serde(rename = "AA", default)]
+    aa: u8,
+    #[serde(rename = "TC", default)]
+    tc: u8,
+    #[serde(rename = "RD", default)]
+    rd: u8,
+    #[serde(rename = "RA", default)]
+    ra: u8,
+    #[serde(rename = "AD", default)]
+    ad: u8,
+    #[serde(rename = "CD", default)]
+    cd: u8,
+    #[serde(rename = "RCODE", default)]
+    rcode: u8,
+    #[serde(rename = "QDCOUNT", default)]
+    qdcount: u16,
+    #[serde(rename = "ANCOUNT", default)]
+    ancount: u16,
+    #[serde(rename = "NSCOUNT", default)]
+    nscount: u16,
+    #[serde(rename = "ARCOUNT", default)]
+    arcount: u16,
+}
+
+/// A question as a resource record object without TTL and record data
+#[derive(Serialize, Deserialize)]
+struct Question {
+    #[serde(rename = "NAME")]
+    name: String,
+    #[serde(rename = "TYPE")]
+    qtype: u16,
+    #[serde(rename = "TYPEname", default, skip_serializing_if = "Option::is_none")]
+    type_name: Option<String>,
+    #[serde(rename = "CLASS", default = "class_in")]
+    class: u16,
+    #[serde(rename = "CLASSname", default, skip_serializing_if = "Option::is_none")]
+    class_name: Option<String>,
+}
+
+/// A resource record object (RFC 8427 §2.2)
+#[derive(Serialize, Deserialize)]
+struct ResourceRecord {
+    #[serde(rename = "NAME")]
+    name: String,
+    #[serde(rename = "TYPE")]
+    qtype: u16,
+    #[serde(rename = "TYPEname", default, skip_serializing_if = "Option::is_none")]
+    type_name: Option<String>,
+    #[serde(rename = "CLASS", default = "class_in")]
+    class: u16,
+    #[serde(rename = "CLASSname", default, skip_serializing_if = "Option::is_none")]
+    class_name: Option<String>,
+    #[serde(rename = "TTL", default)]
+    ttl: u32,
+    #[serde(rename = "RDLENGTH", default, skip_serializing_if = "Option::is_none")]
+    rdlength: Option<u16>,
+    #[serde(rename = "RDATAHEX", default, skip_serializing_if = "Option::is_none")]
+    rdata_hex: Option<String>,
+    /// The record data in presentation format, as a member named after the type like `rdataA`
+    #[serde(flatten)]
+    rdata: BTreeMap<String, serde_json::Value>,
+}
+
+/// A message object (RFC 8427 §2.1)
+#[derive(Serialize, Deserialize)]
+struct Message {
+    #[serde(flatten)]
+    header: Header,
+    #[serde(rename = "QNAME", default, skip_serializing_if = "Option::is_none")]
+    qname: Option<String>,
+    #[serde(rename = "QTYPE", default, skip_serializing_if = "Option::is_none")]
+    qtype: Option<u16>,
+    #[serde(rename = "QCLASS", default, skip_serializing_if = "Option::is_none")]
+    qclass: Option<u16>,
+    #[serde(rename = "questionRRs", default)]
+    questions: Vec<Question>,
+    #[serde(rename = "answerRRs", default)]
+    answers: Vec<ResourceRecord>,
+    #[serde(rename = "authorityRRs", default)]
+    authorities: Vec<ResourceRecord>,
+    #[serde(rename = "additionalRRs", default)]
+    resources: Vec<ResourceRecord>,
+}
+
+fn class_in() -> u16 {
+    DnsClass::IN.to_num()
+}
+
+fn fqdn(name: &str) -> String {
+    format!("{}.", name.trim_end_matches('.'))
+}
+
+impl From<&DnsHeader> for Header {
+    fn from(header: &DnsHeader) -> Self {
+        Header {
+            id: header.id,
+            qr: header.query_response as u8,
+            opcode: header.opcode,
+            aa: header.authoritative_answer as u8,
+            tc: header.truncated_message as u8,
+            rd: header.recursion_desired as u8,
+            ra: header.recursion_available as u8,
+            ad: header.authed_data as u8,
+            cd: header.checking_disabled as u8,
+            rcode: header.response_code as u8,
+            qdcount: header.questions_count,
+            ancount: header.answers_count,
+            nscount: header.authority_count,
+            arcount: header.additional_count,
+        }
+    }
+}
+
+impl From<Header> for DnsHeader {
+    fn from(header: Header) -> Self {
+        let mut result = DnsHeader::new();
+        result.id = header.id;
+        result.query_response = header.qr != 0;
+        result.opcode = header.opcode & 0x0F;
+        result.authoritative_answer = header.aa != 0;
+        result.truncated_message = header.tc != 0;
+        result.recursion_desired = header.rd != 0;
+        result.recursion_available = header.ra != 0;
+        result.authed_data = header.ad != 0;
+        result.checking_disabled = header.cd != 0;
+        result.response_code = ResultCode::from_number(header.rcode);
+        result.questions_count = header.qdcount;
+        result.answers_count = header.ancount;
+        result.authority_count = header.nscount;
+        result.additional_count = header.arcount;
+
+        result
+    }
+}
+
+impl From<&DnsQuestion> for Question {
+    fn from(question: &DnsQuestion) -> Self {
+        Question {
+            name: fqdn(&question.name),
+            qtype: question.qtype.to_num(),
+            type_name: Some(question.qtype.to_string()),
+            class: question.qclass.to_num(),
+            class_name: Some(question.qclass.to_string()),
+        }
+    }
+}
+
+impl From<Question> for DnsQuestion {
+    fn from(question: Question) -> Self {
+        DnsQuestion {
+            name: question.name.trim_end_matches('.').to_string(),
+            qtype: QueryType::from_num(question.qtype),
+            qclass: DnsClass::from_num(question.class),
+        }
+    }
+}
+
+impl TryFrom<&DnsRecord> for ResourceRecord {
+    type Error = Box<dyn std::error::Error>;
+
+    fn try_from(record: &DnsRecord) -> Result<Self, Self::Error> {
+        // NOTE: The numbers of the record, and its data, are read back from the wire format so
+        // pseudo records like OPT keep their own meaning of the class and TTL
+        let mut buffer = BytePacketBuffer::new();
+        record.write(&mut buffer)?;
+        buffer.seek(0)?;
+        let mut name = String::new();
+        buffer.read_qname(&mut name)?;
+        let qtype = buffer.read_u16()?;
+        let class = buffer.read_u16()?;
+        let ttl = buffer.read_u32()?;
+        let rdlength = buffer.read_u16()?;
+        let data = buffer.read_bytes(rdlength as usize)?;
+
+        let mut rdata = BTreeMap::new();
+        if !matches!(record, DnsRecord::OPT { .. } | DnsRecord::UNKNOWN { .. }) {
+            // NOTE: The presentation format is the owner name, TTL, class and type, then the data
+            let text = record.to_string();
+            if let Some(presentation) = text.splitn(5, ' ').nth(4) {
+                rdata.insert(
+                    format!("rdata{}", record.qtype()),
+                    serde_json::Value::String(presentation.to_string()),
+                );
+            }
+        }
+
+        Ok(ResourceRecord {
+            name: fqdn(record.domain()),
+            qtype,
+            type_name: Some(QueryType::from_num(qtype).to_string()),
+            class,
+            class_name: (qtype != QueryType::OPT.to_num())
+                .then(|| DnsClass::from_num(class).to_string()),
+            ttl,
+            rdlength: Some(rdlength),
+            rdata_hex: Some(to_hex(&data).to_uppercase()),
+            rdata,
+        })
+    }
+}
+
+impl TryFrom<ResourceRecord> for DnsRecord {
+    type Error = Box<dyn std::error::Error>;
+
+    fn try_from(record: ResourceRecord) -> Result<Self, Self::Error> {
+        let qtype = QueryType::from_num(record.qtype);
+        let name = record.name.trim_end_matches('.');
+
+        if let Some(hex) = &record.rdata_hex {
+            let data = from_hex(hex)?;
+            let mut buffer = BytePacketBuffer::new();
+            buffer.write_qname(name)?;
+            buffer.write_u16(record.qtype)?;
+            buffer.write_u16(record.class)?;
+            buffer.write_u32(record.ttl)?;
+            buffer.write_u16(data.len() as u16)?;
+            buffer.write_bytes(&data)?;
+            buffer.seek(0)?;
+
+            return DnsRecord::read(&mut buffer);
+        }
+
+        match record.rdata.get(&format!("rdata{}", qtype)) {
+            Some(serde_json::Value::String(presentation)) => format!(
+                "{} {} {} {} {}",
+                fqdn(name),
+                record.ttl,
+                DnsClass::from_num(record.class),
+                qtype,
+                presentation
+            )
+            .parse(),
+            _ => Err(format!("No record data for {} {}", name, qtype).into()),
+        }
+    }
+}
+
+impl TryFrom<&DnsPacket> for Message {
+    type Error = Box<dyn std::error::Error>;
+
+    fn try_from(packet: &DnsPacket) -> Result<Self, Self::Error> {
+        let records = |records: &[DnsRecord]| -> Result<Vec<ResourceRecord>, Self::Error> {
+            records.iter().map(ResourceRecord::try_from).collect()
+        };
+
+        // NOTE: The counts of the header are only updated when writing, so they're taken from the
+        // sections
+        let mut header = Header::from(&packet.header);
+        header.qdcount = packet.questions.len() as u16;
+        header.ancount = packet.answers.len() as u16;
+        header.nscount = packet.authorities.len() as u16;
+        header.arcount = packet.resources.len() as u16;
+
+        // NOTE: A single question is also given by the QNAME, QTYPE and QCLASS members
+        let single = match packet.questions.as_slice() {
+            [question] => Some(question),
+            _ => None,
+        };
+
+        Ok(Message {
+            header,
+            qname: single.map(|question| fqdn(&question.name)),
+            qtype: single.map(|question| question.qtype.to_num()),
+            qclass: single.map(|question| question.qclass.to_num()),
+            questions: packet.questions.iter().map(Question::from).collect(),
+            answers: records(&packet.answers)?,
+            authorities: records(&packet.authorities)?,
+            resources: records(&packet.resources)?,
+        })
+    }
+}
+
+impl TryFrom<Message> for DnsPacket {
+    type Error = Box<dyn std::error::Error>;
+
+    fn try_from(message: Message) -> Result<Self, Self::Error> {
+        let records = |records: Vec<ResourceRecord>| -> Result<Vec<DnsRecord>, Self::Error> {
+            records.into_iter().map(DnsRecord::try_from).collect()
+        };
+
+        let mut questions: Vec<DnsQuestion> = message
+            .questions
+            .into_iter()
+            .map(DnsQuestion::from)
+            .collect();
+        if let (true, Some(qname)) = (questions.is_empty(), message.qname) {
+            questions.push(DnsQuestion {
+                name: qname.trim_end_matches('.').to_string(),
+                qtype: QueryType::from_num(message.qtype.unwrap_or_default()),
+                qclass: DnsClass::from_num(message.qclass.unwrap_or(class_in())),
+            });
+        }
+
+        Ok(DnsPacket {
+            header: DnsHeader::from(message.header),
+            questions,
+            answers: records(message.answers)?,
+            authorities: records(message.authorities)?,
+            resources: records(message.resources)?,
+        })
+    }
+}
+
+impl Serialize for DnsPacket {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Message::try_from(self)
+            .map_err(S::Error::custom)?
+            .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for DnsPacket {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        DnsPacket::try_from(Message::deserialize(deserializer)?).map_err(D::Error::custom)
+    }
+}
+
+impl Serialize for DnsHeader {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Header::from(self).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for DnsHeader {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(DnsHeader::from(Header::deserialize(deserializer)?))
+    }
+}
+
+impl Serialize for DnsQuestion {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Question::from(self).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for DnsQuestion {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(DnsQuestion::from(Question::deserialize(deserializer)?))
+    }
+}
+
+impl Serialize for DnsRecord {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ResourceRecord::try_from(self)
+            .map_err(S::Error::custom)?
+            .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for DnsRecord {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        DnsRecord::try_from(ResourceRecord::deserialize(deserializer)?).map_err(D::Error::custom)
+    }
+}
+
+/// Types, classes and response codes are given by number
+impl Serialize for QueryType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u16(self.to_num())
+    }
+}
+
+impl<'de> Deserialize<'de> for QueryType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(QueryType::from_num(u16::deserialize(deserializer)?))
+    }
+}
+
+impl Serialize for DnsClass {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u16(self.to_num())
+    }
+}
+
+impl<'de> Deserialize<'de> for DnsClass {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(DnsClass::from_num(u16::deserialize(deserializer)?))
+    }
+}
+
+impl Serialize for ResultCode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8(*self as u8)
+    }
+}
+
+impl<'de> Deserialize<'de> for ResultCode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(ResultCode::from_number(u8::deserialize(deserializer)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DnsPacket, DnsQuestion, DnsRecord, QueryType};
+
+    #[test]
+    fn dns_packet_to_json_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let mut packet = DnsPacket::new();
+        packet.header.id = 19678;
+        packet.header.query_response = true;
+        packet.header.recursion_desired = true;
+        packet
+            .questions
+            .push(DnsQuestion::new("example.com".to_string(), QueryType::A));
+        packet
+            .answers
+            .push("example.com. 3600 IN A 192.0.2.1".parse::<DnsRecord>()?);
+
+        // act
+        let actual: serde_json::Value = serde_json::to_value(&packet)?;
+
+        // assert
+        assert_eq!(19678, actual["ID"]);
+        assert_eq!(1, actual["QR"]);
+        assert_eq!(0, actual["AA"]);
+        assert_eq!(1, actual["ANCOUNT"]);
+        assert_eq!("example.com.", actual["QNAME"]);
+        assert_eq!(1, actual["QTYPE"]);
+        assert_eq!(
+            serde_json::json!({
+                "NAME": "example.com.",
+                "TYPE": 1,
+                "TYPEname": "A",
+                "CLASS": 1,
+                "CLASSname": "IN",
+                "TTL": 3600,
+                "RDLENGTH": 4,
+                "RDATAHEX": "C0000201",
+                "rdataA": "192.0.2.1"
+            }),
+            actual["answerRRs"][0]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn dns_packet_from_json_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let mut expected = DnsPacket::new();
+        expected.header.id = 1;
+        expected.header.query_response = true;
+        expected
+            .questions
+            .push(DnsQuestion::new("example.com".to_string(), QueryType::MX));
+        expected.answers.extend([
+            "example.com. 300 IN MX 10 mail.example.com.".parse::<DnsRecord>()?,
+            "example.com. 300 IN TXT \"v=spf1 -all\"".parse()?,
+        ]);
+        expected.set_edns(1232, true);
+        let text = r#"{
+            "ID": 1, "QR": 1, "QNAME": "example.com.", "QTYPE": 15,
+            "answerRRs": [
+                { "NAME": "example.com.", "TYPE": 15, "CLASS": 1, "TTL": 300,
+                  "rdataMX": "10 mail.example.com." },
+                { "NAME": "example.com.", "TYPE": 16, "TTL": 300, "RDATAHEX": "0B763D73706631202D616C6C" }
+            ]
+        }"#;
+
+        // act
+        let mut actual: DnsPacket = serde_json::from_str(text)?;
+        actual.set_edns(1232, true);
+        let round_trip: DnsPacket = serde_json::from_str(&serde_json::to_string(&expected)?)?;
+
+        // assert
+        assert_eq!(1, actual.questions.len());
+        assert_eq!(expected.answers, actual.answers);
+        assert_eq!(expected.answers, round_trip.answers);
+        assert_eq!(expected.resources, round_trip.resources);
+        assert!(round_trip.header.query_response);
+
+        Ok(())
+    }
+}
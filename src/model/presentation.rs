@@ -0,0 +1,517 @@
+//! The presentation format of records (RFC 1035 §5.1), as written in zone files and read back
+//! by `Zone::parse` and `DnsRecord::from_str`
+
+use super::super::encoding::{from_base32hex, from_base64, from_hex, from_timestamp};
+use super::super::BytePacketBuffer;
+use super::svc_param::{self, SvcParam};
+use super::{dns_class::DnsClass, dns_record::DnsRecord, query_type::QueryType};
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+/// A word of a zone file entry, with quoted strings unescaped
+pub struct Token {
+    pub bytes: Vec<u8>,
+    pub quoted: bool,
+}
+
+impl Token {
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.bytes).to_string()
+    }
+}
+
+/// An entry of a zone file, and whether it starts with an owner name
+pub type Entry = (bool, Vec<Token>);
+
+/// Parse a record from the words of its entry after the owner name: its optional TTL and class,
+/// its type and its record data, the TTL defaulting to `default_ttl`
+pub fn parse_record(
+    owner: &str,
+    tokens: &[Token],
+    default_ttl: Option<u32>,
+    origin: &str,
+) -> Result<DnsRecord, Box<dyn std::error::Error>> {
+    let mut rest = tokens;
+
+    // NOTE: The TTL and the class may come in either order, and both may be left out
+    let mut ttl = None;
+    let mut class = DnsClass::IN;
+    while let Some(token) = rest.first() {
+        let text = token.text();
+        if text.starts_with(|c: char| c.is_ascii_digit()) {
+            ttl = Some(parse_ttl(&text)?);
+        } else if let Some(value) = DnsClass::from_name(&text) {
+            class = value;
+        } else {
+            break;
+        }
+        rest = &rest[1..];
+    }
+
+    let qtype_text = rest.first().map(Token::text).unwrap_or_default();
+    let qtype = QueryType::from_name(&qtype_text)
+        .ok_or_else(|| format!("Unknown record type {:?} for {}", qtype_text, owner))?;
+    let ttl = ttl
+        .or(default_ttl)
+        .ok_or_else(|| format!("No TTL for {}", owner))?;
+
+    parse_rdata(owner, qtype, class, ttl, &rest[1..], origin)
+}
+
+/// Complete a name of the zone file with the origin, `@` standing for the origin itself
+pub fn absolute(name: &str, origin: &str) -> String {
+    if name == "@" {
+        origin.to_string()
+    } else if name.ends_with('.') || origin.is_empty() {
+        normalize(name)
+    } else {
+        format!("{}.{}", normalize(name), origin)
+    }
+}
+
+/// Lowercase a name and drop the empty labels of the root and trailing dots
+fn normalize(name: &str) -> String {
+    let labels: Vec<&str> = name.split('.').filter(|label| !label.is_empty()).collect();
+
+    labels.join(".").to_lowercase()
+}
+
+/// Parse a TTL given in seconds, or with units such as `1h30m` or `1W`
+pub fn parse_ttl(text: &str) -> Result<u32, Box<dyn std::error::Error>> {
+    if let Ok(ttl) = text.parse() {
+        return Ok(ttl);
+    }
+
+    let mut total: u32 = 0;
+    let mut value: u32 = 0;
+    for c in text.chars() {
+        match c.to_ascii_lowercase() {
+            digit @ '0'..='9' => {
+                value = value
+                    .saturating_mul(10)
+                    .saturating_add(digit.to_digit(10).unwrap_or_default())
+            }
+            unit @ ('s' | 'm' | 'h' | 'd' | 'w') => {
+                let seconds = match unit {
+                    's' => 1,
+                    'm' => 60,
+                    'h' => 3600,
+                    'd' => 86400,
+                    _ => 604800,
+                };
+                total = total.saturating_add(value.saturating_mul(seconds));
+                value = 0;
+            }
+            _ => return Err(format!("Invalid TTL {:?}", text).into()),
+        }
+    }
+
+    Ok(total.saturating_add(value))
+}
+
+/// Split a zone file into entries, each one a record or a directive made of its words. Comments
+/// start with `;` and parentheses let an entry span several lines.
+pub fn entries(text: &str) -> Result<Vec<Entry>, Box<dyn std::error::Error>> {
+    let mut result = Vec::new();
+    let mut tokens = Vec::new();
+    let mut owner_given = false;
+    let mut depth = 0;
+
+    for line in text.lines() {
+        if depth == 0 {
+            owner_given = !line.starts_with([' ', '\t']);
+        }
+
+        let mut chars = line.chars().peekable();
+        let mut word: Option<Token> = None;
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => {
+                    let mut bytes = Vec::new();
+                    let mut closed = false;
+                    while let Some(c) = chars.next() {
+                        match c {
+                            '"' => {
+                                closed = true;
+                                break;
+                            }
+                            // NOTE: `\DDD` is a byte in decimal, `\X` the character X
+                            '\\' => {
+                                let digits: String =
+                                    std::iter::from_fn(|| chars.next_if(|c| c.is_ascii_digit()))
+                                        .take(3)
+                                        .collect();
+                                if digits.is_empty() {
+                                    if let Some(c) = chars.next() {
+                                        let mut buf = [0; 4];
+                                        bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                                    }
+                                } else {
+                                    bytes.push(digits.parse::<u8>()?);
+                                }
+                            }
+                            c => {
+                                let mut buf = [0; 4];
+                                bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                            }
+                        }
+                    }
+                    if !closed {
+                        return Err(format!("Unterminated quoted string: {}", line).into());
+                    }
+                    tokens.extend(word.take());
+                    tokens.push(Token {
+                        bytes,
+                        quoted: true,
+                    });
+                }
+                ';' => break,
+                '(' | ')' => {
+                    tokens.extend(word.take());
+                    depth += if c == '(' { 1 } else { -1 };
+                    if depth < 0 {
+                        return Err(format!("Unbalanced parentheses: {}", line).into());
+                    }
+                }
+                c if c.is_whitespace() => tokens.extend(word.take()),
+                c => {
+                    let mut buf = [0; 4];
+                    word.get_or_insert_with(|| Token {
+                        bytes: Vec::new(),
+                        quoted: false,
+                    })
+                    .bytes
+                    .extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                }
+            }
+        }
+        tokens.extend(word.take());
+
+        if depth == 0 && !tokens.is_empty() {
+            result.push((owner_given, std::mem::take(&mut tokens)));
+        }
+    }
+
+    if depth != 0 {
+        return Err("Unbalanced parentheses at the end of the zone file".into());
+    }
+
+    Ok(result)
+}
+
+/// Parse the `key=value` service parameters of SVCB and HTTPS records, whose values may be
+/// quoted as in `alpn="h2,h3"`
+fn svc_params(tokens: &[Token]) -> Result<Vec<SvcParam>, Box<dyn std::error::Error>> {
+    let mut params = Vec::new();
+    let mut tokens = tokens.iter().peekable();
+
+    while let Some(token) = tokens.next() {
+        let text = token.text();
+        let param = match text.split_once('=') {
+            // NOTE: A quoted value is a word of its own after the `=`
+            Some((key, "")) => match tokens.next_if(|next| next.quoted) {
+                Some(value) => svc_param::from_text(key, Some(&value.bytes))?,
+                None => svc_param::from_text(key, Some(b""))?,
+            },
+            Some((key, value)) => svc_param::from_text(key, Some(&unescaped(value)?))?,
+            None => svc_param::from_text(&text, None)?,
+        };
+        if params
+            .iter()
+            .any(|other: &SvcParam| other.key() == param.key())
+        {
+            return Err(format!("Repeated SvcParam {}", svc_param::key_name(param.key())).into());
+        }
+        params.push(param);
+    }
+
+    Ok(params)
+}
+
+/// The bytes of an unquoted word, where `\DDD` is a byte in decimal and `\X` the character X
+fn unescaped(text: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut bytes = Vec::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let c = match c {
+            '\\' => {
+                let digits: String = std::iter::from_fn(|| chars.next_if(|c| c.is_ascii_digit()))
+                    .take(3)
+                    .collect();
+                if !digits.is_empty() {
+                    bytes.push(digits.parse::<u8>()?);
+                    continue;
+                }
+                match chars.next() {
+                    Some(c) => c,
+                    None => break,
+                }
+            }
+            c => c,
+        };
+        let mut buf = [0; 4];
+        bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+    }
+
+    Ok(bytes)
+}
+
+/// RFC 8659: a CAA tag is a non-empty sequence of ASCII letters and digits
+fn validate_caa_tag(tag: String) -> Result<String, Box<dyn std::error::Error>> {
+    if tag.is_empty() || tag.len() > 255 || !tag.bytes().all(|b| b.is_ascii_alphanumeric()) {
+        return Err(format!("Invalid CAA tag {:?}", tag).into());
+    }
+
+    Ok(tag)
+}
+
+/// Parse a number of the record data into the type of its field, rejecting values out of its
+/// range
+fn number<T: FromStr>(
+    text: &str,
+    domain: &str,
+    qtype: QueryType,
+) -> Result<T, Box<dyn std::error::Error>> {
+    text.parse()
+        .map_err(|_| format!("Invalid number in {} {}", domain, qtype).into())
+}
+
+/// Build a record from the words of its record data in the zone file
+pub fn parse_rdata(
+    domain: &str,
+    qtype: QueryType,
+    class: DnsClass,
+    ttl: u32,
+    tokens: &[Token],
+    origin: &str,
+) -> Result<DnsRecord, Box<dyn std::error::Error>> {
+    let domain = domain.to_string();
+    let word = |i: usize| -> Result<String, Box<dyn std::error::Error>> {
+        tokens
+            .get(i)
+            .map(Token::text)
+            .ok_or_else(|| format!("Missing record data for {} {}", domain, qtype).into())
+    };
+    let rest = |i: usize| -> String { tokens.iter().skip(i).map(Token::text).collect() };
+    let qtype_at = |text: &str| -> Result<QueryType, Box<dyn std::error::Error>> {
+        QueryType::from_name(text)
+            .ok_or_else(|| format!("Unknown type {} in {}", text, domain).into())
+    };
+    let types = |i: usize| -> Result<Vec<QueryType>, Box<dyn std::error::Error>> {
+        tokens
+            .iter()
+            .skip(i)
+            .map(|token| qtype_at(&token.text()))
+            .collect()
+    };
+    // NOTE: An empty salt is written as `-`
+    let salt = |i: usize| -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        match word(i)?.as_str() {
+            "-" => Ok(Vec::new()),
+            text => from_hex(text),
+        }
+    };
+
+    // NOTE: The generic format of RFC 3597 §5, read back through the wire format so known types
+    // get their own variant
+    if tokens
+        .first()
+        .is_some_and(|token| !token.quoted && token.bytes == b"\\#")
+    {
+        let data = from_hex(&rest(2))?;
+        if data.len() != number::<usize>(&word(1)?, &domain, qtype)? {
+            return Err(format!("Wrong record data length for {} {}", domain, qtype).into());
+        }
+
+        let mut buffer = BytePacketBuffer::new();
+        DnsRecord::UNKNOWN {
+            domain,
+            qtype: qtype.to_num(),
+            class,
+            ttl,
+            data,
+        }
+        .write(&mut buffer)?;
+        buffer.seek(0)?;
+
+        return DnsRecord::read(&mut buffer);
+    }
+
+    let record = match qtype {
+        QueryType::A => DnsRecord::A {
+            addr: word(0)?.parse::<Ipv4Addr>()?,
+            domain,
+            class,
+            ttl,
+        },
+        QueryType::AAAA => DnsRecord::AAAA {
+            addr: word(0)?.parse::<Ipv6Addr>()?,
+            domain,
+            class,
+            ttl,
+        },
+        QueryType::NS => DnsRecord::NS {
+            host: absolute(&word(0)?, origin),
+            domain,
+            class,
+            ttl,
+        },
+        QueryType::CNAME => DnsRecord::CNAME {
+            host: absolute(&word(0)?, origin),
+            domain,
+            class,
+            ttl,
+        },
+        QueryType::PTR => DnsRecord::PTR {
+            host: absolute(&word(0)?, origin),
+            domain,
+            class,
+            ttl,
+        },
+        QueryType::SOA => DnsRecord::SOA {
+            mname: absolute(&word(0)?, origin),
+            rname: absolute(&word(1)?, origin),
+            serial: number(&word(2)?, &domain, qtype)?,
+            refresh: parse_ttl(&word(3)?)?,
+            retry: parse_ttl(&word(4)?)?,
+            expire: parse_ttl(&word(5)?)?,
+            minimum: parse_ttl(&word(6)?)?,
+            domain,
+            class,
+            ttl,
+        },
+        QueryType::MX => DnsRecord::MX {
+            priority: number(&word(0)?, &domain, qtype)?,
+            host: absolute(&word(1)?, origin),
+            domain,
+            class,
+            ttl,
+        },
+        QueryType::TXT => {
+            if tokens.iter().any(|token| token.bytes.len() > 255) {
+                return Err(format!("TXT string of {} exceeds 255 characters", domain).into());
+            }
+
+            DnsRecord::TXT {
+                data: tokens.iter().map(|token| token.bytes.clone()).collect(),
+                domain,
+                class,
+                ttl,
+            }
+        }
+        QueryType::SRV => DnsRecord::SRV {
+            priority: number(&word(0)?, &domain, qtype)?,
+            weight: number(&word(1)?, &domain, qtype)?,
+            port: number(&word(2)?, &domain, qtype)?,
+            host: absolute(&word(3)?, origin),
+            domain,
+            class,
+            ttl,
+        },
+        QueryType::CAA => DnsRecord::CAA {
+            flags: number(&word(0)?, &domain, qtype)?,
+            tag: validate_caa_tag(word(1)?)?,
+            value: tokens
+                .get(2)
+                .map(|token| token.bytes.clone())
+                .unwrap_or_default(),
+            domain,
+            class,
+            ttl,
+        },
+        QueryType::DS => DnsRecord::DS {
+            key_tag: number(&word(0)?, &domain, qtype)?,
+            algorithm: number(&word(1)?, &domain, qtype)?,
+            digest_type: number(&word(2)?, &domain, qtype)?,
+            digest: from_hex(&rest(3))?,
+            domain,
+            class,
+            ttl,
+        },
+        QueryType::DNSKEY => DnsRecord::DNSKEY {
+            flags: number(&word(0)?, &domain, qtype)?,
+            protocol: number(&word(1)?, &domain, qtype)?,
+            algorithm: number(&word(2)?, &domain, qtype)?,
+            public_key: from_base64(&rest(3))?,
+            domain,
+            class,
+            ttl,
+        },
+        QueryType::SVCB | QueryType::HTTPS => {
+            let priority: u16 = number(&word(0)?, &domain, qtype)?;
+            let target = absolute(&word(1)?, origin);
+            let params = svc_params(&tokens[2.min(tokens.len())..])?;
+
+            if qtype == QueryType::SVCB {
+                DnsRecord::SVCB {
+                    priority,
+                    target,
+                    params,
+                    domain,
+                    class,
+                    ttl,
+                }
+            } else {
+                DnsRecord::HTTPS {
+                    priority,
+                    target,
+                    params,
+                    domain,
+                    class,
+                    ttl,
+                }
+            }
+        }
+        QueryType::RRSIG => DnsRecord::RRSIG {
+            type_covered: qtype_at(&word(0)?)?,
+            algorithm: number(&word(1)?, &domain, qtype)?,
+            labels: number(&word(2)?, &domain, qtype)?,
+            original_ttl: number(&word(3)?, &domain, qtype)?,
+            expiration: from_timestamp(&word(4)?)?,
+            inception: from_timestamp(&word(5)?)?,
+            key_tag: number(&word(6)?, &domain, qtype)?,
+            signer_name: absolute(&word(7)?, origin),
+            signature: from_base64(&rest(8))?,
+            domain,
+            class,
+            ttl,
+        },
+        QueryType::NSEC => DnsRecord::NSEC {
+            next_domain: absolute(&word(0)?, origin),
+            types: types(1)?,
+            domain,
+            class,
+            ttl,
+        },
+        QueryType::NSEC3 => DnsRecord::NSEC3 {
+            hash_algorithm: number(&word(0)?, &domain, qtype)?,
+            flags: number(&word(1)?, &domain, qtype)?,
+            iterations: number(&word(2)?, &domain, qtype)?,
+            salt: salt(3)?,
+            next_hashed_owner: from_base32hex(&word(4)?)?,
+            types: types(5)?,
+            domain,
+            class,
+            ttl,
+        },
+        QueryType::NSEC3PARAM => DnsRecord::NSEC3PARAM {
+            hash_algorithm: number(&word(0)?, &domain, qtype)?,
+            flags: number(&word(1)?, &domain, qtype)?,
+            iterations: number(&word(2)?, &domain, qtype)?,
+            salt: salt(3)?,
+            domain,
+            class,
+            ttl,
+        },
+        _ => {
+            return Err(format!(
+                "Unsupported record type {} in zone files, use the \\# format of RFC 3597",
+                qtype
+            )
+            .into())
+        }
+    };
+
+    Ok(record)
+}
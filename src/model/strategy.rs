@@ -0,0 +1,387 @@
+//! proptest strategies for the messages of the model, for round-trip properties through `write`
+//! and `from_buffer`. Names are lowercase, as they are read, and values stay small enough for a
+//! packet of a few records to fit in a buffer.
+
+use super::super::byte_packet_buffer::MAX_NAME_LEN;
+use super::dns_class::DnsClass;
+use super::dns_header::DnsHeader;
+use super::dns_packet::DnsPacket;
+use super::dns_question::DnsQuestion;
+use super::dns_record::DnsRecord;
+use super::query_type::QueryType;
+use super::result_code::ResultCode;
+use super::svc_param::SvcParam;
+use proptest::collection::{btree_set, vec};
+use proptest::prelude::*;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+/// The length of a name on the wire, with its length octets and the root label
+pub fn wire_len(name: &str) -> usize {
+    name.split('.')
+        .filter(|label| !label.is_empty())
+        .map(|label| 1 + label.len())
+        .sum::<usize>()
+        + 1
+}
+
+/// A label of up to 63 letters, digits, hyphens and underscores
+pub fn label() -> impl Strategy<Value = String> {
+    "[a-z0-9_-]{1,63}"
+}
+
+/// A name of a few short labels, the root name included
+pub fn short_name() -> BoxedStrategy<String> {
+    vec("[a-z0-9_-]{1,12}", 0..5)
+        .prop_map(|labels| labels.join("."))
+        .boxed()
+}
+
+/// A name of labels up to the longest one, sometimes of exactly the longest length: three labels
+/// of 63 octets and one of 61
+pub fn name() -> BoxedStrategy<String> {
+    let longest = vec("[a-z0-9]{63}", 3).prop_flat_map(|labels| {
+        "[a-z0-9]{61}".prop_map(move |last| format!("{}.{}", labels.join("."), last))
+    });
+
+    prop_oneof![
+        4 => short_name(),
+        4 => vec(label(), 1..5)
+            .prop_map(|labels| labels.join("."))
+            .prop_filter("at most 255 octets", |name| wire_len(name) <= MAX_NAME_LEN),
+        1 => longest,
+    ]
+    .boxed()
+}
+
+fn bytes() -> impl Strategy<Value = Vec<u8>> {
+    vec(any::<u8>(), 0..32)
+}
+
+fn class() -> impl Strategy<Value = DnsClass> {
+    any::<u16>().prop_map(DnsClass::from_num)
+}
+
+fn qtype() -> impl Strategy<Value = QueryType> {
+    any::<u16>().prop_map(QueryType::from_num)
+}
+
+/// Types as type bitmaps decode them, sorted and without duplicates
+fn types() -> impl Strategy<Value = Vec<QueryType>> {
+    btree_set(any::<u16>(), 0..8)
+        .prop_map(|nums| nums.into_iter().map(QueryType::from_num).collect())
+}
+
+pub fn dns_header() -> impl Strategy<Value = DnsHeader> {
+    let flags = (
+        any::<bool>(),
+        0..16u8,
+        any::<bool>(),
+        any::<bool>(),
+        any::<bool>(),
+        any::<bool>(),
+        any::<bool>(),
+        any::<bool>(),
+        any::<bool>(),
+        0..6u8,
+    );
+    let counts = (any::<u16>(), any::<[u16; 4]>());
+
+    (flags, counts).prop_map(|(flags, (id, counts))| DnsHeader {
+        id,
+        query_response: flags.0,
+        opcode: flags.1,
+        authoritative_answer: flags.2,
+        truncated_message: flags.3,
+        recursion_desired: flags.4,
+        recursion_available: flags.5,
+        z: flags.6,
+        authed_data: flags.7,
+        checking_disabled: flags.8,
+        response_code: ResultCode::from_number(flags.9),
+        questions_count: counts[0],
+        answers_count: counts[1],
+        authority_count: counts[2],
+        additional_count: counts[3],
+    })
+}
+
+pub fn dns_question(names: BoxedStrategy<String>) -> impl Strategy<Value = DnsQuestion> {
+    (names, qtype(), class()).prop_map(|(name, qtype, qclass)| DnsQuestion {
+        name,
+        qtype,
+        qclass,
+    })
+}
+
+pub fn svc_param() -> impl Strategy<Value = SvcParam> {
+    prop_oneof![
+        vec(any::<u16>(), 0..4).prop_map(SvcParam::Mandatory),
+        vec(vec(any::<u8>(), 1..16), 0..4).prop_map(SvcParam::Alpn),
+        Just(SvcParam::NoDefaultAlpn),
+        any::<u16>().prop_map(SvcParam::Port),
+        vec(any::<Ipv4Addr>(), 1..4).prop_map(SvcParam::Ipv4Hint),
+        bytes().prop_map(SvcParam::Ech),
+        vec(any::<Ipv6Addr>(), 1..4).prop_map(SvcParam::Ipv6Hint),
+        // NOTE: Keys up to 6 are read as the parameters above
+        (7..=u16::MAX, bytes()).prop_map(|(key, value)| SvcParam::Unknown { key, value }),
+    ]
+}
+
+/// The parameters of a SVCB or HTTPS record, in the order of their keys and each key once, as
+/// they are read
+pub fn svc_params() -> impl Strategy<Value = Vec<SvcParam>> {
+    vec(svc_param(), 0..4).prop_map(|mut params| {
+        params.sort_by_key(SvcParam::key);
+        params.dedup_by_key(|param| param.key());
+        params
+    })
+}
+
+/// A record of any variant, its names drawn from `names`
+pub fn dns_record(names: BoxedStrategy<String>) -> impl Strategy<Value = DnsRecord> {
+    let owner = || (names.clone(), class(), any::<u32>());
+    let unknown = any::<u16>().prop_filter("an unknown type", |num| {
+        matches!(QueryType::from_num(*num), QueryType::UNKNOWN(_))
+    });
+    let svcb = || (any::<u16>(), names.clone(), svc_params());
+
+    prop_oneof![
+        (owner(), unknown, bytes()).prop_map(|((domain, class, ttl), qtype, data)| {
+            DnsRecord::UNKNOWN {
+                domain,
+                qtype,
+                class,
+                ttl,
+                data,
+            }
+        }),
+        (owner(), any::<Ipv4Addr>()).prop_map(|((domain, class, ttl), addr)| DnsRecord::A {
+            domain,
+            addr,
+            class,
+            ttl,
+        }),
+        (owner(), names.clone()).prop_map(|((domain, class, ttl), host)| DnsRecord::NS {
+            domain,
+            host,
+            class,
+            ttl,
+        }),
+        (owner(), names.clone()).prop_map(|((domain, class, ttl), host)| DnsRecord::CNAME {
+            domain,
+            host,
+            class,
+            ttl,
+        }),
+        (owner(), names.clone()).prop_map(|((domain, class, ttl), host)| DnsRecord::PTR {
+            domain,
+            host,
+            class,
+            ttl,
+        }),
+        (owner(), names.clone(), names.clone(), any::<[u32; 5]>()).prop_map(
+            |((domain, class, ttl), mname, rname, values)| DnsRecord::SOA {
+                domain,
+                mname,
+                rname,
+                serial: values[0],
+                refresh: values[1],
+                retry: values[2],
+                expire: values[3],
+                minimum: values[4],
+                class,
+                ttl,
+            }
+        ),
+        (owner(), any::<u16>(), names.clone()).prop_map(
+            |((domain, class, ttl), priority, host)| DnsRecord::MX {
+                domain,
+                priority,
+                host,
+                class,
+                ttl,
+            }
+        ),
+        (owner(), vec(vec(any::<u8>(), 0..64), 0..4)).prop_map(|((domain, class, ttl), data)| {
+            DnsRecord::TXT {
+                domain,
+                data,
+                class,
+                ttl,
+            }
+        }),
+        (owner(), any::<Ipv6Addr>()).prop_map(|((domain, class, ttl), addr)| DnsRecord::AAAA {
+            domain,
+            addr,
+            class,
+            ttl,
+        }),
+        (owner(), any::<[u16; 3]>(), names.clone()).prop_map(
+            |((domain, class, ttl), values, host)| DnsRecord::SRV {
+                domain,
+                priority: values[0],
+                weight: values[1],
+                port: values[2],
+                host,
+                class,
+                ttl,
+            }
+        ),
+        (names.clone(), any::<u16>(), any::<u32>(), bytes()).prop_map(
+            |(domain, packet_len, flags, data)| DnsRecord::OPT {
+                domain,
+                packet_len,
+                flags,
+                data,
+            }
+        ),
+        (owner(), any::<(u16, u8, u8)>(), bytes()).prop_map(
+            |((domain, class, ttl), (key_tag, algorithm, digest_type), digest)| DnsRecord::DS {
+                domain,
+                key_tag,
+                algorithm,
+                digest_type,
+                digest,
+                class,
+                ttl,
+            }
+        ),
+        (
+            owner(),
+            qtype(),
+            any::<(u8, u8, u16)>(),
+            any::<[u32; 3]>(),
+            names.clone(),
+            bytes()
+        )
+            .prop_map(
+                |(
+                    (domain, class, ttl),
+                    type_covered,
+                    (algorithm, labels, key_tag),
+                    values,
+                    signer_name,
+                    signature,
+                )| DnsRecord::RRSIG {
+                    domain,
+                    type_covered,
+                    algorithm,
+                    labels,
+                    original_ttl: values[0],
+                    expiration: values[1],
+                    inception: values[2],
+                    key_tag,
+                    signer_name,
+                    signature,
+                    class,
+                    ttl,
+                }
+            ),
+        (owner(), names.clone(), types()).prop_map(|((domain, class, ttl), next_domain, types)| {
+            DnsRecord::NSEC {
+                domain,
+                next_domain,
+                types,
+                class,
+                ttl,
+            }
+        }),
+        (owner(), any::<(u16, u8, u8)>(), bytes()).prop_map(
+            |((domain, class, ttl), (flags, protocol, algorithm), public_key)| {
+                DnsRecord::DNSKEY {
+                    domain,
+                    flags,
+                    protocol,
+                    algorithm,
+                    public_key,
+                    class,
+                    ttl,
+                }
+            }
+        ),
+        (owner(), any::<(u8, u8, u16)>(), bytes(), bytes(), types()).prop_map(
+            |(
+                (domain, class, ttl),
+                (hash_algorithm, flags, iterations),
+                salt,
+                next_hashed_owner,
+                types,
+            )| DnsRecord::NSEC3 {
+                domain,
+                hash_algorithm,
+                flags,
+                iterations,
+                salt,
+                next_hashed_owner,
+                types,
+                class,
+                ttl,
+            }
+        ),
+        (owner(), any::<(u8, u8, u16)>(), bytes()).prop_map(
+            |((domain, class, ttl), (hash_algorithm, flags, iterations), salt)| {
+                DnsRecord::NSEC3PARAM {
+                    domain,
+                    hash_algorithm,
+                    flags,
+                    iterations,
+                    salt,
+                    class,
+                    ttl,
+                }
+            }
+        ),
+        (owner(), svcb()).prop_map(|((domain, class, ttl), (priority, target, params))| {
+            DnsRecord::SVCB {
+                domain,
+                priority,
+                target,
+                params,
+                class,
+                ttl,
+            }
+        }),
+        (owner(), svcb()).prop_map(|((domain, class, ttl), (priority, target, params))| {
+            DnsRecord::HTTPS {
+                domain,
+                priority,
+                target,
+                params,
+                class,
+                ttl,
+            }
+        }),
+        (owner(), any::<u8>(), "[a-zA-Z0-9]{1,15}", bytes()).prop_map(
+            |((domain, class, ttl), flags, tag, value)| DnsRecord::CAA {
+                domain,
+                flags,
+                tag,
+                value,
+                class,
+                ttl,
+            }
+        ),
+    ]
+}
+
+/// A packet of a few questions and records of short names, small enough to fit in a buffer
+pub fn dns_packet() -> impl Strategy<Value = DnsPacket> {
+    let records = || vec(dns_record(short_name()), 0..3);
+
+    (
+        dns_header(),
+        vec(dns_question(short_name()), 0..3),
+        records(),
+        records(),
+        records(),
+    )
+        .prop_map(
+            |(header, questions, answers, authorities, resources)| DnsPacket {
+                header,
+                questions,
+                answers,
+                authorities,
+                resources,
+            },
+        )
+}
@@ -0,0 +1,461 @@
+use super::super::encoding::{from_base64, to_base64};
+use super::super::BytePacketBuffer;
+use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+/// Service parameter of a SVCB or HTTPS record (RFC 9460 §7)
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SvcParam {
+    Mandatory(Vec<u16>),     // 0
+    Alpn(Vec<Vec<u8>>),      // 1
+    NoDefaultAlpn,           // 2
+    Port(u16),               // 3
+    Ipv4Hint(Vec<Ipv4Addr>), // 4
+    Ech(Vec<u8>),            // 5
+    Ipv6Hint(Vec<Ipv6Addr>), // 6
+    Unknown { key: u16, value: Vec<u8> },
+}
+
+impl SvcParam {
+    /// The SvcParamKey of the parameter
+    pub fn key(&self) -> u16 {
+        match *self {
+            SvcParam::Mandatory(_) => 0,
+            SvcParam::Alpn(_) => 1,
+            SvcParam::NoDefaultAlpn => 2,
+            SvcParam::Port(_) => 3,
+            SvcParam::Ipv4Hint(_) => 4,
+            SvcParam::Ech(_) => 5,
+            SvcParam::Ipv6Hint(_) => 6,
+            SvcParam::Unknown { key, .. } => key,
+        }
+    }
+
+    pub fn read(buffer: &mut BytePacketBuffer) -> Result<SvcParam, Box<dyn std::error::Error>> {
+        let key = buffer.read_u16()?;
+        let len = buffer.read_u16()? as usize;
+        let value = buffer.read_bytes(len)?;
+
+        let param = match key {
+            0 => {
+                if !len.is_multiple_of(2) {
+                    return Err("Malformed mandatory SvcParam".into());
+                }
+
+                SvcParam::Mandatory(
+                    value
+                        .chunks(2)
+                        .map(|key| ((key[0] as u16) << 8) | (key[1] as u16))
+                        .collect(),
+                )
+            }
+            1 => {
+                let mut ids = Vec::new();
+                let mut pos = 0;
+                while pos < value.len() {
+                    let id_len = value[pos] as usize;
+                    if id_len == 0 || pos + 1 + id_len > value.len() {
+                        return Err("Malformed alpn SvcParam".into());
+                    }
+
+                    ids.push(value[pos + 1..pos + 1 + id_len].to_vec());
+                    pos += 1 + id_len;
+                }
+
+                SvcParam::Alpn(ids)
+            }
+            2 => {
+                if len != 0 {
+                    return Err("Malformed no-default-alpn SvcParam".into());
+                }
+
+                SvcParam::NoDefaultAlpn
+            }
+            3 => {
+                if len != 2 {
+                    return Err("Malformed port SvcParam".into());
+                }
+
+                SvcParam::Port(((value[0] as u16) << 8) | (value[1] as u16))
+            }
+            4 => {
+                if len == 0 || !len.is_multiple_of(4) {
+                    return Err("Malformed ipv4hint SvcParam".into());
+                }
+
+                SvcParam::Ipv4Hint(
+                    value
+                        .chunks(4)
+                        .map(|addr| Ipv4Addr::new(addr[0], addr[1], addr[2], addr[3]))
+                        .collect(),
+                )
+            }
+            5 => SvcParam::Ech(value),
+            6 => {
+                if len == 0 || !len.is_multiple_of(16) {
+                    return Err("Malformed ipv6hint SvcParam".into());
+                }
+
+                SvcParam::Ipv6Hint(
+                    value
+                        .chunks(16)
+                        .map(|addr| {
+                            let mut octets = [0; 16];
+                            octets.copy_from_slice(addr);
+                            Ipv6Addr::from(octets)
+                        })
+                        .collect(),
+                )
+            }
+            _ => SvcParam::Unknown { key, value },
+        };
+
+        Ok(param)
+    }
+
+    pub fn write(&self, buffer: &mut BytePacketBuffer) -> Result<(), Box<dyn std::error::Error>> {
+        buffer.write_u16(self.key())?;
+
+        let pos = buffer.pos();
+        buffer.write_u16(0)?; // NOTE: preserve for SvcParamValue length
+
+        match self {
+            SvcParam::Mandatory(keys) => {
+                for key in keys {
+                    buffer.write_u16(*key)?;
+                }
+            }
+            SvcParam::Alpn(ids) => {
+                check_alpn_ids(ids)?;
+                for id in ids {
+                    buffer.write_u8(id.len() as u8)?;
+                    for b in id {
+                        buffer.write_u8(*b)?;
+                    }
+                }
+            }
+            SvcParam::NoDefaultAlpn => {}
+            SvcParam::Port(port) => buffer.write_u16(*port)?,
+            SvcParam::Ipv4Hint(addrs) => {
+                for addr in addrs {
+                    for octet in &addr.octets() {
+                        buffer.write_u8(*octet)?;
+                    }
+                }
+            }
+            SvcParam::Ipv6Hint(addrs) => {
+                for addr in addrs {
+                    for octet in &addr.octets() {
+                        buffer.write_u8(*octet)?;
+                    }
+                }
+            }
+            SvcParam::Ech(value) | SvcParam::Unknown { value, .. } => {
+                for b in value {
+                    buffer.write_u8(*b)?;
+                }
+            }
+        }
+
+        let len = buffer.pos - (pos + 2);
+        buffer.set_u16(pos, len as u16)?;
+
+        Ok(())
+    }
+}
+
+/// Parse a parameter from its key and value as in zone files (RFC 9460 §2.1), the value already
+/// freed from the escapes of character strings
+pub fn from_text(key: &str, value: Option<&[u8]>) -> Result<SvcParam, Box<dyn std::error::Error>> {
+    let key = key_number(key)?;
+    let text = || -> Result<String, Box<dyn std::error::Error>> {
+        let value = value.ok_or_else(|| format!("Missing value for {}", key_name(key)))?;
+        Ok(String::from_utf8(value.to_vec())?)
+    };
+    let list = || -> Result<Vec<Vec<u8>>, Box<dyn std::error::Error>> {
+        let value = value.ok_or_else(|| format!("Missing value for {}", key_name(key)))?;
+        Ok(value_list(value))
+    };
+
+    let param = match key {
+        0 => SvcParam::Mandatory(
+            list()?
+                .iter()
+                .map(|key| key_number(&String::from_utf8_lossy(key)))
+                .collect::<Result<_, _>>()?,
+        ),
+        1 => {
+            let ids = list()?;
+            check_alpn_ids(&ids)?;
+            SvcParam::Alpn(ids)
+        }
+        2 if value.is_none() => SvcParam::NoDefaultAlpn,
+        2 => return Err("no-default-alpn takes no value".into()),
+        3 => SvcParam::Port(text()?.parse()?),
+        4 => SvcParam::Ipv4Hint(
+            text()?
+                .split(',')
+                .map(str::parse)
+                .collect::<Result<_, _>>()?,
+        ),
+        5 => SvcParam::Ech(from_base64(&text()?)?),
+        6 => SvcParam::Ipv6Hint(
+            text()?
+                .split(',')
+                .map(str::parse)
+                .collect::<Result<_, _>>()?,
+        ),
+        _ => SvcParam::Unknown {
+            key,
+            value: value.unwrap_or_default().to_vec(),
+        },
+    };
+
+    Ok(param)
+}
+
+/// Check the ids of an alpn parameter are of 1 to 255 bytes, as their length takes a byte (RFC
+/// 9460 §7.1.1)
+fn check_alpn_ids(ids: &[Vec<u8>]) -> Result<(), Box<dyn std::error::Error>> {
+    if ids.iter().any(|id| id.is_empty() || id.len() > 255) {
+        return Err("alpn ids must be 1 to 255 bytes long".into());
+    }
+
+    Ok(())
+}
+
+/// The SvcParamKey of a key name, such as `alpn` or `key65000`
+fn key_number(name: &str) -> Result<u16, Box<dyn std::error::Error>> {
+    let key = match name.to_lowercase().as_str() {
+        "mandatory" => 0,
+        "alpn" => 1,
+        "no-default-alpn" => 2,
+        "port" => 3,
+        "ipv4hint" => 4,
+        "ech" => 5,
+        "ipv6hint" => 6,
+        other => other
+            .strip_prefix("key")
+            .and_then(|number| number.parse().ok())
+            .ok_or_else(|| format!("Unknown SvcParamKey {}", name))?,
+    };
+
+    Ok(key)
+}
+
+/// Split a comma separated list of values, where `\,` and `\\` are a comma and a backslash
+/// within a value (RFC 9460 Appendix A.1)
+fn value_list(value: &[u8]) -> Vec<Vec<u8>> {
+    let mut result = vec![Vec::new()];
+    let mut bytes = value.iter();
+    while let Some(&b) = bytes.next() {
+        match b {
+            b'\\' => {
+                if let (Some(&next), Some(last)) = (bytes.next(), result.last_mut()) {
+                    last.push(next);
+                }
+            }
+            b',' => result.push(Vec::new()),
+            _ => {
+                if let Some(last) = result.last_mut() {
+                    last.push(b);
+                }
+            }
+        }
+    }
+
+    result
+}
+
+/// The presentation name of a SvcParamKey, `key<num>` for the ones we don't know
+pub fn key_name(key: u16) -> String {
+    match key {
+        0 => "mandatory".to_string(),
+        1 => "alpn".to_string(),
+        2 => "no-default-alpn".to_string(),
+        3 => "port".to_string(),
+        4 => "ipv4hint".to_string(),
+        5 => "ech".to_string(),
+        6 => "ipv6hint".to_string(),
+        _ => format!("key{}", key),
+    }
+}
+
+/// Present a value of a comma separated list, escaping the commas and backslashes within it
+fn escaped(value: &[u8]) -> String {
+    let mut result = String::new();
+    for &b in value {
+        match b {
+            // NOTE: Escaped once for the value list and once more for the character string
+            b',' => result.push_str("\\\\,"),
+            b'\\' => result.push_str("\\\\\\\\"),
+            0x21..=0x7e if b != b'"' => result.push(b as char),
+            _ => result.push_str(&format!("\\{:03}", b)),
+        }
+    }
+
+    result
+}
+
+/// Present the parameter as `key=value` as in zone files
+impl fmt::Display for SvcParam {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", key_name(self.key()))?;
+
+        match self {
+            SvcParam::Mandatory(keys) => {
+                let keys: Vec<String> = keys.iter().map(|key| key_name(*key)).collect();
+                write!(f, "={}", keys.join(","))
+            }
+            SvcParam::Alpn(ids) => {
+                let ids: Vec<String> = ids.iter().map(|id| escaped(id)).collect();
+                write!(f, "={}", ids.join(","))
+            }
+            SvcParam::NoDefaultAlpn => Ok(()),
+            SvcParam::Port(port) => write!(f, "={}", port),
+            SvcParam::Ipv4Hint(addrs) => {
+                let addrs: Vec<String> = addrs.iter().map(|addr| addr.to_string()).collect();
+                write!(f, "={}", addrs.join(","))
+            }
+            SvcParam::Ipv6Hint(addrs) => {
+                let addrs: Vec<String> = addrs.iter().map(|addr| addr.to_string()).collect();
+                write!(f, "={}", addrs.join(","))
+            }
+            SvcParam::Ech(value) => write!(f, "={}", to_base64(value)),
+            SvcParam::Unknown { value, .. } if value.is_empty() => Ok(()),
+            SvcParam::Unknown { value, .. } => write!(f, "={}", escaped(value)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BytePacketBuffer;
+    use super::{from_text, SvcParam};
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn svc_param_read_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let expected = [
+            SvcParam::Alpn(vec![b"h2".to_vec(), b"h3".to_vec()]),
+            SvcParam::Ipv4Hint(vec![Ipv4Addr::new(104, 16, 132, 229)]),
+            SvcParam::Unknown {
+                key: 65000,
+                value: vec![0xab],
+            },
+        ];
+        let params = [
+            0x00, 0x01, 0x00, 0x06, 0x02, 0x68, 0x32, 0x02, 0x68, 0x33, 0x00, 0x04, 0x00, 0x04,
+            0x68, 0x10, 0x84, 0xe5, 0xfd, 0xe8, 0x00, 0x01, 0xab,
+        ];
+        let mut buffer = BytePacketBuffer::new();
+        buffer.buf[..params.len()].copy_from_slice(&params);
+
+        // act
+        let actual = [
+            SvcParam::read(&mut buffer)?,
+            SvcParam::read(&mut buffer)?,
+            SvcParam::read(&mut buffer)?,
+        ];
+
+        // assert
+        assert_eq!(expected, actual);
+        assert_eq!(params.len(), buffer.pos());
+
+        Ok(())
+    }
+
+    #[test]
+    fn svc_param_read_malformed_port() {
+        // arrange
+        let mut buffer = BytePacketBuffer::new();
+        buffer.buf[..5].copy_from_slice(&[0x00, 0x03, 0x00, 0x01, 0x50]);
+
+        // act
+        let actual = SvcParam::read(&mut buffer);
+
+        // assert
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn svc_param_alpn_error() {
+        // arrange
+        let long = vec![b'x'; 300];
+
+        // act
+        let empty = from_text("alpn", Some(b""));
+        let parsed = from_text("alpn", Some(&long));
+        let written = SvcParam::Alpn(vec![long.clone()]).write(&mut BytePacketBuffer::new());
+
+        // assert
+        for actual in [empty.map(|_| ()), parsed.map(|_| ()), written] {
+            assert_eq!(
+                "alpn ids must be 1 to 255 bytes long",
+                actual.err().map(|e| e.to_string()).unwrap_or_default()
+            );
+        }
+    }
+
+    #[test]
+    fn svc_param_display_ok() {
+        // arrange
+        let params = [
+            SvcParam::Mandatory(vec![1, 3]),
+            SvcParam::Alpn(vec![b"h2".to_vec(), b"f,oo".to_vec()]),
+            SvcParam::NoDefaultAlpn,
+            SvcParam::Port(8443),
+            SvcParam::Ech(vec![0xfe, 0x0d]),
+        ];
+
+        // act
+        let actual: Vec<String> = params.iter().map(|param| param.to_string()).collect();
+
+        // assert
+        assert_eq!(
+            vec![
+                "mandatory=alpn,port",
+                "alpn=h2,f\\\\,oo",
+                "no-default-alpn",
+                "port=8443",
+                "ech=/g0=",
+            ],
+            actual
+        );
+    }
+
+    #[test]
+    fn from_text_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let expected = [
+            SvcParam::Alpn(vec![b"h2".to_vec(), b"a,b\\".to_vec()]),
+            SvcParam::NoDefaultAlpn,
+            SvcParam::Ipv4Hint(vec![
+                Ipv4Addr::new(192, 0, 2, 1),
+                Ipv4Addr::new(192, 0, 2, 2),
+            ]),
+            SvcParam::Mandatory(vec![1, 65000]),
+            SvcParam::Unknown {
+                key: 65000,
+                value: Vec::new(),
+            },
+        ];
+
+        // act
+        let actual = [
+            from_text("alpn", Some(b"h2,a\\,b\\\\"))?,
+            from_text("no-default-alpn", None)?,
+            from_text("ipv4hint", Some(b"192.0.2.1,192.0.2.2"))?,
+            from_text("mandatory", Some(b"alpn,key65000"))?,
+            from_text("key65000", None)?,
+        ];
+
+        // assert
+        assert_eq!(expected, actual);
+        assert!(from_text("port", Some(b"http")).is_err());
+        assert!(from_text("bogus", None).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,126 @@
+use super::query_type::QueryType;
+
+/// Decode the type bit maps field of NSEC and NSEC3 records (RFC 4034 §4.1.2). The field is a
+/// sequence of windows, each one holding a window number, the length of its bitmap and a bitmap of
+/// up to 32 bytes for the 256 types of the window.
+pub fn decode(bytes: &[u8]) -> Result<Vec<QueryType>, Box<dyn std::error::Error>> {
+    let mut types = Vec::new();
+    let mut pos = 0;
+    let mut last_window = None;
+
+    while pos < bytes.len() {
+        if pos + 2 > bytes.len() {
+            return Err("Truncated type bitmap window".into());
+        }
+
+        let window = bytes[pos];
+        let len = bytes[pos + 1] as usize;
+        if len == 0 || len > 32 || pos + 2 + len > bytes.len() {
+            return Err("Malformed type bitmap window".into());
+        }
+        if last_window.is_some_and(|last| window <= last) {
+            return Err("Type bitmap windows out of order".into());
+        }
+
+        for (i, b) in bytes[pos + 2..pos + 2 + len].iter().enumerate() {
+            for bit in 0..8 {
+                if b & (0x80 >> bit) != 0 {
+                    let num = ((window as u16) << 8) | (i * 8 + bit) as u16;
+                    types.push(QueryType::from_num(num));
+                }
+            }
+        }
+
+        last_window = Some(window);
+        pos += 2 + len;
+    }
+
+    Ok(types)
+}
+
+/// Encode types into the type bit maps field, leaving out empty windows and trailing zero bytes
+pub fn encode(types: &[QueryType]) -> Vec<u8> {
+    let mut nums: Vec<u16> = types.iter().map(|qtype| qtype.to_num()).collect();
+    nums.sort_unstable();
+    nums.dedup();
+
+    let mut bytes = Vec::new();
+    for window in nums.chunk_by(|a, b| a >> 8 == b >> 8) {
+        let mut bitmap = [0u8; 32];
+        let mut len = 0;
+        for num in window {
+            let low = (num & 0xFF) as usize;
+            bitmap[low / 8] |= 0x80 >> (low % 8);
+            len = low / 8 + 1;
+        }
+
+        bytes.push((window[0] >> 8) as u8);
+        bytes.push(len as u8);
+        bytes.extend_from_slice(&bitmap[..len]);
+    }
+
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::QueryType;
+    use super::{decode, encode};
+
+    // RFC 4034 §4.3: A MX RRSIG NSEC TYPE1234
+    const BITMAP: [u8; 37] = [
+        0x00, 0x06, 0x40, 0x01, 0x00, 0x00, 0x00, 0x03, 0x04, 0x1b, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x20,
+    ];
+
+    #[test]
+    fn decode_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let expected = vec![
+            QueryType::A,
+            QueryType::MX,
+            QueryType::RRSIG,
+            QueryType::NSEC,
+            QueryType::UNKNOWN(1234),
+        ];
+
+        // act
+        let actual = decode(&BITMAP)?;
+
+        // assert
+        assert_eq!(expected, actual);
+
+        Ok(())
+    }
+
+    #[test]
+    fn encode_ok() {
+        // arrange
+        let types = [
+            QueryType::UNKNOWN(1234),
+            QueryType::NSEC,
+            QueryType::A,
+            QueryType::RRSIG,
+            QueryType::MX,
+        ];
+
+        // act
+        let actual = encode(&types);
+
+        // assert
+        assert_eq!(BITMAP.to_vec(), actual);
+    }
+
+    #[test]
+    fn decode_error() {
+        // arrange
+        let bytes = [0x00, 0x21, 0x40];
+
+        // act
+        let actual = decode(&bytes);
+
+        // assert
+        assert!(actual.is_err());
+    }
+}
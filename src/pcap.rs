@@ -0,0 +1,598 @@
+//! DNS messages carried over UDP and TCP in pcap and pcapng captures
+
+use super::byte_packet_buffer::{BytePacketBuffer, MAX_PACKET_SIZE};
+use super::model::dns_packet::DnsPacket;
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+const PCAPNG_SECTION_HEADER: u32 = 0x0A0D0D0A;
+
+/// How a message was carried
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Protocol {
+    Udp,
+    Tcp,
+}
+
+/// A DNS message found in a capture
+#[derive(Debug, Clone, PartialEq)]
+pub struct CapturedMessage {
+    /// When the packet was captured, since the epoch
+    pub time: Duration,
+    pub source: SocketAddr,
+    pub destination: SocketAddr,
+    pub protocol: Protocol,
+    pub message: Vec<u8>,
+}
+
+impl CapturedMessage {
+    pub fn packet(&self) -> Result<DnsPacket, Box<dyn std::error::Error>> {
+        if self.message.len() > MAX_PACKET_SIZE {
+            return Err(format!("Message of {} bytes is too large", self.message.len()).into());
+        }
+
+        let mut buffer = BytePacketBuffer::new();
+        buffer.buf[..self.message.len()].copy_from_slice(&self.message);
+        DnsPacket::from_buffer(&mut buffer)
+    }
+}
+
+impl fmt::Display for CapturedMessage {
+    /// `1700000000.123456 192.0.2.1:53000 -> 192.0.2.53:53 UDP 28 bytes`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}.{:06} {} -> {} {} {} bytes",
+            self.time.as_secs(),
+            self.time.subsec_micros(),
+            self.source,
+            self.destination,
+            match self.protocol {
+                Protocol::Udp => "UDP",
+                Protocol::Tcp => "TCP",
+            },
+            self.message.len()
+        )
+    }
+}
+
+/// Read the DNS messages of a pcap or pcapng capture, sent from or to `port` over UDP or TCP.
+/// Fragmented IP packets are skipped, and TCP segments are put together in the order they were
+/// captured, without handling retransmissions.
+pub fn read(data: &[u8], port: u16) -> Result<Vec<CapturedMessage>, Box<dyn std::error::Error>> {
+    let mut reader = Reader::new(data);
+    let magic = reader.u32()?;
+    reader.seek(0);
+
+    let frames = if magic == PCAPNG_SECTION_HEADER {
+        pcapng_frames(&mut reader)?
+    } else {
+        pcap_frames(&mut reader)?
+    };
+
+    let mut messages = Vec::new();
+    let mut streams: HashMap<(SocketAddr, SocketAddr), Vec<u8>> = HashMap::new();
+    for frame in frames {
+        let segment = match ip_payload(frame.link_type, frame.data) {
+            Some(segment) => segment,
+            None => continue,
+        };
+        if segment.source.port() != port && segment.destination.port() != port {
+            continue;
+        }
+
+        let message = |message: &[u8]| CapturedMessage {
+            time: frame.time,
+            source: segment.source,
+            destination: segment.destination,
+            protocol: segment.protocol,
+            message: message.to_vec(),
+        };
+        match segment.protocol {
+            Protocol::Udp => messages.push(message(segment.payload)),
+            Protocol::Tcp => {
+                // NOTE: Messages over TCP are preceded by their length (RFC 1035 §4.2.2), and may
+                // span several segments or share one
+                let stream = streams
+                    .entry((segment.source, segment.destination))
+                    .or_default();
+                if segment.syn {
+                    stream.clear();
+                }
+                stream.extend_from_slice(segment.payload);
+                while stream.len() >= 2 {
+                    let len = u16::from_be_bytes([stream[0], stream[1]]) as usize;
+                    if stream.len() < 2 + len {
+                        break;
+                    }
+                    messages.push(message(&stream[2..2 + len]));
+                    stream.drain(..2 + len);
+                }
+            }
+        }
+    }
+
+    Ok(messages)
+}
+
+/// A captured link layer frame
+struct Frame<'a> {
+    time: Duration,
+    link_type: u32,
+    data: &'a [u8],
+}
+
+fn pcap_frames<'a>(reader: &mut Reader<'a>) -> Result<Vec<Frame<'a>>, Box<dyn std::error::Error>> {
+    let nanos = match reader.u32()? {
+        0xA1B2C3D4 => false,
+        0xA1B23C4D => true,
+        0xD4C3B2A1 | 0x4D3CB2A1 => {
+            reader.big_endian = false;
+            reader.seek(0);
+            reader.u32()? == 0xA1B23C4D
+        }
+        magic => return Err(format!("Not a pcap or pcapng capture ({:#010x})", magic).into()),
+    };
+    reader.skip(16)?;
+    let link_type = reader.u32()? & 0xFFFF;
+
+    let mut frames = Vec::new();
+    while !reader.is_empty() {
+        let secs = reader.u32()? as u64;
+        let fraction = reader.u32()? as u64;
+        let captured = reader.u32()? as usize;
+        reader.skip(4)?;
+        let data = reader.bytes(captured)?;
+
+        frames.push(Frame {
+            time: Duration::from_secs(secs)
+                + Duration::from_nanos(if nanos { fraction } else { fraction * 1000 }),
+            link_type,
+            data,
+        });
+    }
+
+    Ok(frames)
+}
+
+fn pcapng_frames<'a>(
+    reader: &mut Reader<'a>,
+) -> Result<Vec<Frame<'a>>, Box<dyn std::error::Error>> {
+    // Link type and timestamp units per second of each interface of the current section
+    let mut interfaces: Vec<(u32, u64)> = Vec::new();
+    let mut frames = Vec::new();
+
+    while !reader.is_empty() {
+        let start = reader.pos;
+        let block_type = reader.u32()?;
+        if block_type == PCAPNG_SECTION_HEADER {
+            // NOTE: The byte order magic tells the byte order of the whole section
+            reader.skip(4)?;
+            reader.big_endian = true;
+            reader.big_endian = match reader.u32()? {
+                0x1A2B3C4D => true,
+                0x4D3C2B1A => false,
+                magic => return Err(format!("Invalid pcapng byte order {:#010x}", magic).into()),
+            };
+            reader.seek(start + 4);
+            interfaces.clear();
+        }
+        let len = reader.u32()? as usize;
+        if len < 12 || !len.is_multiple_of(4) {
+            return Err(format!("Invalid pcapng block length {}", len).into());
+        }
+        let mut body = Reader {
+            data: reader.bytes(len - 12)?,
+            pos: 0,
+            big_endian: reader.big_endian,
+        };
+        reader.skip(4)?;
+
+        match block_type {
+            // Interface description
+            1 => {
+                let link_type = body.u16()? as u32;
+                body.skip(6)?;
+                interfaces.push((link_type, timestamp_resolution(&mut body)?));
+            }
+            // Enhanced packet
+            6 => {
+                let interface = body.u32()? as usize;
+                let (link_type, resolution) = *interfaces
+                    .get(interface)
+                    .ok_or_else(|| format!("Unknown pcapng interface {}", interface))?;
+                let timestamp = ((body.u32()? as u64) << 32) | body.u32()? as u64;
+                let captured = body.u32()? as usize;
+                body.skip(4)?;
+
+                frames.push(Frame {
+                    // NOTE: Resolutions finer than nanoseconds overflow u64 nanoseconds
+                    time: Duration::from_secs(timestamp / resolution)
+                        + Duration::from_nanos(
+                            ((timestamp % resolution) as u128 * 1_000_000_000 / resolution as u128)
+                                as u64,
+                        ),
+                    link_type,
+                    data: body.bytes(captured)?,
+                });
+            }
+            // Simple packet, on the first interface and without a timestamp
+            3 => {
+                let (link_type, _) = *interfaces.first().ok_or("No pcapng interface")?;
+                let original = body.u32()? as usize;
+                let data = body.bytes(original.min(body.data.len() - body.pos))?;
+
+                frames.push(Frame {
+                    time: Duration::ZERO,
+                    link_type,
+                    data,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    Ok(frames)
+}
+
+/// The units per second of the timestamps of an interface, from its `if_tsresol` option
+fn timestamp_resolution(options: &mut Reader) -> Result<u64, Box<dyn std::error::Error>> {
+    while options.data.len() - options.pos >= 4 {
+        let code = options.u16()?;
+        let len = options.u16()? as usize;
+        let value = options.bytes(len)?;
+        options.skip((4 - len % 4) % 4)?;
+
+        match code {
+            0 => break,
+            9 if len == 1 => {
+                // NOTE: The high bit tells a power of two rather than of ten
+                let exponent = (value[0] & 0x7F) as u32;
+                let base: u64 = if value[0] & 0x80 != 0 { 2 } else { 10 };
+                return base.checked_pow(exponent).ok_or_else(|| {
+                    format!("Unsupported timestamp resolution {}", value[0]).into()
+                });
+            }
+            _ => {}
+        }
+    }
+
+    Ok(1_000_000)
+}
+
+/// The payload of a UDP datagram or TCP segment
+struct Segment<'a> {
+    source: SocketAddr,
+    destination: SocketAddr,
+    protocol: Protocol,
+    syn: bool,
+    payload: &'a [u8],
+}
+
+/// Find the UDP or TCP payload in a frame, unless it's something else or fragmented
+fn ip_payload(link_type: u32, frame: &[u8]) -> Option<Segment<'_>> {
+    let packet = match link_type {
+        // Null and loopback, with the address family first
+        0 | 108 => frame.get(4..)?,
+        // Ethernet, maybe with a VLAN tag
+        1 => match u16::from_be_bytes([*frame.get(12)?, *frame.get(13)?]) {
+            0x8100 => frame.get(18..)?,
+            _ => frame.get(14..)?,
+        },
+        // Raw IP
+        101 | 228 | 229 => frame,
+        // Linux cooked capture
+        113 => frame.get(16..)?,
+        276 => frame.get(20..)?,
+        _ => return None,
+    };
+
+    let (source, destination, protocol, payload): (IpAddr, IpAddr, u8, &[u8]) =
+        match packet.first()? >> 4 {
+            4 => {
+                let header_len = ((packet[0] & 0x0F) as usize) * 4;
+                let total_len = u16::from_be_bytes([*packet.get(2)?, *packet.get(3)?]) as usize;
+                let fragment = u16::from_be_bytes([*packet.get(6)?, *packet.get(7)?]);
+                if fragment & 0x3FFF != 0 {
+                    return None;
+                }
+                let source: [u8; 4] = packet.get(12..16)?.try_into().ok()?;
+                let destination: [u8; 4] = packet.get(16..20)?.try_into().ok()?;
+                (
+                    Ipv4Addr::from(source).into(),
+                    Ipv4Addr::from(destination).into(),
+                    *packet.get(9)?,
+                    packet.get(header_len..total_len.min(packet.len()))?,
+                )
+            }
+            6 => {
+                let payload_len = u16::from_be_bytes([*packet.get(4)?, *packet.get(5)?]) as usize;
+                let source: [u8; 16] = packet.get(8..24)?.try_into().ok()?;
+                let destination: [u8; 16] = packet.get(24..40)?.try_into().ok()?;
+                let mut next = *packet.get(6)?;
+                let mut payload = packet.get(40..(40 + payload_len).min(packet.len()))?;
+                // NOTE: Hop-by-hop, routing and destination options headers are stepped over,
+                // fragments are skipped like with IPv4
+                while matches!(next, 0 | 43 | 60) {
+                    let len = (*payload.get(1)? as usize + 1) * 8;
+                    next = *payload.first()?;
+                    payload = payload.get(len..)?;
+                }
+                (
+                    Ipv6Addr::from(source).into(),
+                    Ipv6Addr::from(destination).into(),
+                    next,
+                    payload,
+                )
+            }
+            _ => return None,
+        };
+
+    let source_port = u16::from_be_bytes([*payload.first()?, *payload.get(1)?]);
+    let destination_port = u16::from_be_bytes([*payload.get(2)?, *payload.get(3)?]);
+    let (protocol, syn, payload) = match protocol {
+        17 => (Protocol::Udp, false, payload.get(8..)?),
+        6 => {
+            let header_len = ((*payload.get(12)? >> 4) as usize) * 4;
+            let syn = payload.get(13)? & 0x02 != 0;
+            (Protocol::Tcp, syn, payload.get(header_len..)?)
+        }
+        _ => return None,
+    };
+
+    Some(Segment {
+        source: SocketAddr::new(source, source_port),
+        destination: SocketAddr::new(destination, destination_port),
+        protocol,
+        syn,
+        payload,
+    })
+}
+
+/// Reads the integers of a capture in its byte order
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    big_endian: bool,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Reader {
+            data,
+            pos: 0,
+            big_endian: true,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    fn seek(&mut self, pos: usize) {
+        self.pos = pos;
+    }
+
+    fn skip(&mut self, len: usize) -> Result<(), Box<dyn std::error::Error>> {
+        self.bytes(len).map(|_| ())
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], Box<dyn std::error::Error>> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or("Truncated capture")?;
+        self.pos += len;
+
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> Result<u16, Box<dyn std::error::Error>> {
+        let bytes = self.bytes(2)?.try_into()?;
+
+        Ok(if self.big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    }
+
+    fn u32(&mut self) -> Result<u32, Box<dyn std::error::Error>> {
+        let bytes = self.bytes(4)?.try_into()?;
+
+        Ok(if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read, CapturedMessage, Protocol};
+    use std::net::SocketAddr;
+    use std::time::Duration;
+
+    /// An Ethernet frame holding an IPv4 UDP or TCP packet with the payload
+    fn frame(source: SocketAddr, destination: SocketAddr, tcp: bool, payload: &[u8]) -> Vec<u8> {
+        let ip = |addr: SocketAddr| match addr.ip() {
+            std::net::IpAddr::V4(ip) => ip.octets(),
+            _ => panic!("IPv4 only"),
+        };
+        let transport_len = if tcp { 20 } else { 8 };
+
+        let mut frame = vec![0; 12];
+        frame.extend([0x08, 0x00]);
+        frame.extend([0x45, 0]);
+        frame.extend(((20 + transport_len + payload.len()) as u16).to_be_bytes());
+        frame.extend([0, 0, 0x40, 0, 64, if tcp { 6 } else { 17 }, 0, 0]);
+        frame.extend(ip(source));
+        frame.extend(ip(destination));
+        frame.extend(source.port().to_be_bytes());
+        frame.extend(destination.port().to_be_bytes());
+        if tcp {
+            frame.extend([0; 8]);
+            frame.extend([0x50, 0x18, 0xFF, 0xFF, 0, 0, 0, 0]);
+        } else {
+            frame.extend(((8 + payload.len()) as u16).to_be_bytes());
+            frame.extend([0, 0]);
+        }
+        frame.extend(payload);
+
+        frame
+    }
+
+    /// A little endian pcap capture of Ethernet frames with their time in microseconds
+    fn pcap(frames: &[(u64, Vec<u8>)]) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend(0xA1B2C3D4u32.to_le_bytes());
+        data.extend(2u16.to_le_bytes());
+        data.extend(4u16.to_le_bytes());
+        data.extend([0; 8]);
+        data.extend(65535u32.to_le_bytes());
+        data.extend(1u32.to_le_bytes());
+        for (micros, frame) in frames {
+            data.extend(((micros / 1_000_000) as u32).to_le_bytes());
+            data.extend(((micros % 1_000_000) as u32).to_le_bytes());
+            data.extend((frame.len() as u32).to_le_bytes());
+            data.extend((frame.len() as u32).to_le_bytes());
+            data.extend(frame);
+        }
+
+        data
+    }
+
+    /// A big endian pcapng capture of one Ethernet interface with timestamps in the `if_tsresol`
+    /// resolution, e.g. 9 for nanoseconds
+    fn pcapng(tsresol: u8, frames: &[(u64, Vec<u8>)]) -> Vec<u8> {
+        let block = |data: &mut Vec<u8>, block_type: u32, body: Vec<u8>| {
+            let len = (12 + body.len()) as u32;
+            data.extend(block_type.to_be_bytes());
+            data.extend(len.to_be_bytes());
+            data.extend(body);
+            data.extend(len.to_be_bytes());
+        };
+
+        let mut data = Vec::new();
+        let mut section = 0x1A2B3C4Du32.to_be_bytes().to_vec();
+        section.extend([0, 1, 0, 0]);
+        section.extend([0xFF; 8]);
+        block(&mut data, 0x0A0D0D0A, section);
+
+        let mut interface = vec![0, 1, 0, 0, 0, 0, 0xFF, 0xFF];
+        interface.extend([0, 9, 0, 1, tsresol, 0, 0, 0, 0, 0, 0, 0]);
+        block(&mut data, 1, interface);
+
+        for (nanos, frame) in frames {
+            let mut packet = vec![0; 4];
+            packet.extend(((nanos >> 32) as u32).to_be_bytes());
+            packet.extend((*nanos as u32).to_be_bytes());
+            packet.extend((frame.len() as u32).to_be_bytes());
+            packet.extend((frame.len() as u32).to_be_bytes());
+            packet.extend(frame);
+            packet.resize(packet.len().div_ceil(4) * 4, 0);
+            block(&mut data, 6, packet);
+        }
+
+        data
+    }
+
+    #[test]
+    fn read_pcap_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let client: SocketAddr = "192.0.2.1:53000".parse()?;
+        let server: SocketAddr = "192.0.2.53:53".parse()?;
+        let other: SocketAddr = "192.0.2.80:80".parse()?;
+        let data = pcap(&[
+            (1_500_000, frame(client, server, false, b"query")),
+            (1_600_000, frame(client, other, true, b"GET /")),
+            (2_000_001, frame(server, client, false, b"response")),
+        ]);
+
+        // act
+        let actual = read(&data, 53)?;
+
+        // assert
+        assert_eq!(
+            vec![
+                CapturedMessage {
+                    time: Duration::from_micros(1_500_000),
+                    source: client,
+                    destination: server,
+                    protocol: Protocol::Udp,
+                    message: b"query".to_vec(),
+                },
+                CapturedMessage {
+                    time: Duration::from_micros(2_000_001),
+                    source: server,
+                    destination: client,
+                    protocol: Protocol::Udp,
+                    message: b"response".to_vec(),
+                },
+            ],
+            actual
+        );
+        assert_eq!(
+            "1.500000 192.0.2.1:53000 -> 192.0.2.53:53 UDP 5 bytes",
+            actual[0].to_string()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_pcapng_tcp_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let client: SocketAddr = "192.0.2.1:53000".parse()?;
+        let server: SocketAddr = "192.0.2.53:53".parse()?;
+        let data = pcapng(
+            9,
+            &[
+                // NOTE: The first message spans two segments, and shares the second with another
+                (
+                    1_000_000_001,
+                    frame(client, server, true, &[0, 5, b'f', b'i']),
+                ),
+                (1_000_000_002, frame(client, server, true, b"rst\0\x03two")),
+            ],
+        );
+
+        // act
+        let actual = read(&data, 53)?;
+
+        // assert
+        let messages: Vec<&[u8]> = actual.iter().map(|m| m.message.as_slice()).collect();
+        assert_eq!(vec![&b"first"[..], &b"two"[..]], messages);
+        assert_eq!(Duration::new(1, 2), actual[0].time);
+        assert_eq!(Protocol::Tcp, actual[1].protocol);
+        assert!(read(b"not a capture", 53).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_pcapng_resolution_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let client: SocketAddr = "192.0.2.1:53000".parse()?;
+        let server: SocketAddr = "192.0.2.53:53".parse()?;
+        let at = |tsresol, timestamp| {
+            pcapng(
+                tsresol,
+                &[(timestamp, frame(client, server, false, b"query"))],
+            )
+        };
+
+        // act
+        let picoseconds = read(&at(12, 1_500_000_000_001), 53)?;
+        let power_of_two = read(&at(0x80 | 40, 3 << 39), 53)?;
+
+        // assert
+        assert_eq!(Duration::from_millis(1500), picoseconds[0].time);
+        assert_eq!(Duration::from_millis(1500), power_of_two[0].time);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,421 @@
+//! Load generation against a server, measuring how many queries it answers and how fast
+
+use super::byte_packet_buffer::{BytePacketBuffer, MAX_MESSAGE_SIZE, MAX_PACKET_SIZE};
+use super::config::PerfConfig;
+use super::model::{
+    dns_packet::DnsPacket, dns_question::DnsQuestion, query_type::QueryType,
+    result_code::ResultCode,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpStream, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How long the receiver waits for a response before checking whether to stop
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Read the queries of a data file in the dnsperf format, a `name type` line per query, skipping
+/// empty lines and `;` or `#` comments
+pub fn read_queries(text: &str) -> Result<Vec<DnsQuestion>, Box<dyn std::error::Error>> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with(';') && !line.starts_with('#'))
+        .map(|line| {
+            let mut words = line.split_whitespace();
+            let name = words.next().unwrap_or_default();
+            let qtype = match words.next() {
+                Some(qtype) => QueryType::from_name(qtype)
+                    .ok_or_else(|| format!("Unknown type {} in {:?}", qtype, line))?,
+                None => QueryType::A,
+            };
+
+            Ok(DnsQuestion::new(
+                name.trim_end_matches('.').to_string(),
+                qtype,
+            ))
+        })
+        .collect()
+}
+
+/// What became of the queries sent
+#[derive(Debug, Clone, Default)]
+pub struct PerfReport {
+    pub sent: usize,
+    /// Queries without a response within the timeout
+    pub lost: usize,
+    /// Time between each query and its response, sorted
+    pub latencies: Vec<Duration>,
+    /// Responses by their code
+    pub response_codes: BTreeMap<String, usize>,
+    /// From the first query sent to the last response awaited
+    pub elapsed: Duration,
+}
+
+impl PerfReport {
+    /// The latency under which the fraction `p` of the responses came, e.g. 0.99
+    pub fn percentile(&self, p: f64) -> Option<Duration> {
+        let last = self.latencies.len().checked_sub(1)?;
+
+        Some(self.latencies[((last as f64 * p).round() as usize).min(last)])
+    }
+}
+
+impl fmt::Display for PerfReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let completed = self.latencies.len();
+        let share = |count: usize| 100.0 * count as f64 / self.sent.max(1) as f64;
+        let millis = |p: f64| {
+            self.percentile(p)
+                .map_or(0.0, |latency| latency.as_secs_f64() * 1000.0)
+        };
+
+        writeln!(f, "Queries sent:         {}", self.sent)?;
+        writeln!(
+            f,
+            "Queries completed:    {} ({:.2}%)",
+            completed,
+            share(completed)
+        )?;
+        writeln!(
+            f,
+            "Queries lost:         {} ({:.2}%)",
+            self.lost,
+            share(self.lost)
+        )?;
+        writeln!(f, "Run time (s):         {:.3}", self.elapsed.as_secs_f64())?;
+        writeln!(
+            f,
+            "Queries per second:   {:.1}",
+            completed as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+        )?;
+        writeln!(
+            f,
+            "Latency (ms):         min {:.3}, p50 {:.3}, p90 {:.3}, p99 {:.3}, max {:.3}",
+            millis(0.0),
+            millis(0.5),
+            millis(0.9),
+            millis(0.99),
+            millis(1.0)
+        )?;
+
+        let codes: Vec<String> = self
+            .response_codes
+            .iter()
+            .map(|(code, count)| {
+                format!(
+                    "{} {} ({:.1}%)",
+                    code,
+                    count,
+                    100.0 * *count as f64 / completed.max(1) as f64
+                )
+            })
+            .collect();
+        writeln!(f, "Response codes:       {}", codes.join(", "))
+    }
+}
+
+/// Send the queries to the server at the configured rate, once through or over and over for the
+/// configured time, and wait for their responses. Queries are sent without waiting for the
+/// previous responses, the way dnsperf does, with ids telling the responses apart.
+pub fn run(
+    queries: &[DnsQuestion],
+    config: &PerfConfig,
+) -> Result<PerfReport, Box<dyn std::error::Error>> {
+    if queries.is_empty() {
+        return Err("No queries to send".into());
+    }
+
+    // NOTE: Queries are written once, only their id changes when sent
+    let messages = queries
+        .iter()
+        .map(|question| {
+            let mut packet = DnsPacket::new();
+            packet.header.recursion_desired = true;
+            packet.header.questions_count = 1;
+            packet.questions.push(question.clone());
+            packet.set_edns(MAX_PACKET_SIZE as u16, false);
+
+            let mut buffer = BytePacketBuffer::new();
+            packet.write(&mut buffer)?;
+            Ok(buffer.buf[..buffer.pos()].to_vec())
+        })
+        .collect::<Result<Vec<_>, Box<dyn std::error::Error>>>()?;
+
+    let server = (config.server, config.port);
+    let (mut sender, receiver) = if config.tcp {
+        let stream = TcpStream::connect_timeout(&server.into(), config.timeout)?;
+        stream.set_read_timeout(Some(POLL_INTERVAL))?;
+        (
+            Connection::Tcp(stream.try_clone()?),
+            Connection::Tcp(stream),
+        )
+    } else {
+        let socket = UdpSocket::bind(("0.0.0.0", 0))?;
+        socket.connect(server)?;
+        socket.set_read_timeout(Some(POLL_INTERVAL))?;
+        (
+            Connection::Udp(socket.try_clone()?),
+            Connection::Udp(socket),
+        )
+    };
+
+    let outstanding: Arc<Mutex<HashMap<u16, Instant>>> = Arc::default();
+    let stop = Arc::new(AtomicBool::new(false));
+    let receiving = {
+        let outstanding = Arc::clone(&outstanding);
+        let stop = Arc::clone(&stop);
+        let timeout = config.timeout;
+        std::thread::spawn(move || receive(receiver, &outstanding, &stop, timeout))
+    };
+
+    let start = Instant::now();
+    let interval = config
+        .rate
+        .filter(|rate| *rate > 0)
+        .map(|rate| Duration::from_secs(1) / rate);
+    let mut sent = 0;
+    let mut overwritten = 0;
+    for (i, message) in messages.iter().cycle().enumerate() {
+        match config.limit {
+            Some(limit) if start.elapsed() >= limit => break,
+            None if i >= messages.len() => break,
+            _ => {}
+        }
+        if let Some(interval) = interval {
+            std::thread::sleep((interval * i as u32).saturating_sub(start.elapsed()));
+        }
+
+        let id = i as u16;
+        let mut message = message.clone();
+        message[0..2].copy_from_slice(&id.to_be_bytes());
+        // NOTE: A query still awaiting its response when its id comes round again is lost
+        if outstanding
+            .lock()
+            .map_err(|_| "The receiver failed")?
+            .insert(id, Instant::now())
+            .is_some()
+        {
+            overwritten += 1;
+        }
+        sender.send(&message)?;
+        sent += 1;
+    }
+
+    // Wait for the last responses
+    let waiting = Instant::now();
+    while waiting.elapsed() < config.timeout
+        && !outstanding
+            .lock()
+            .map_err(|_| "The receiver failed")?
+            .is_empty()
+    {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    stop.store(true, Ordering::Relaxed);
+    let elapsed = start.elapsed();
+
+    let (mut latencies, response_codes, late) =
+        receiving.join().map_err(|_| "The receiver failed")??;
+    latencies.sort();
+    let unanswered = outstanding.lock().map_err(|_| "The receiver failed")?.len();
+
+    Ok(PerfReport {
+        sent,
+        lost: overwritten + late + unanswered,
+        latencies,
+        response_codes,
+        elapsed,
+    })
+}
+
+/// The received responses, as their latencies and counts by code, and how many came too late
+type Received = (Vec<Duration>, BTreeMap<String, usize>, usize);
+
+fn receive(
+    mut connection: Connection,
+    outstanding: &Mutex<HashMap<u16, Instant>>,
+    stop: &AtomicBool,
+    timeout: Duration,
+) -> Result<Received, String> {
+    let mut latencies = Vec::new();
+    let mut response_codes = BTreeMap::new();
+    let mut late = 0;
+    // NOTE: Responses over TCP may be as large as their 16-bit length allows
+    let mut buf = vec![0; MAX_MESSAGE_SIZE];
+
+    while !stop.load(Ordering::Relaxed) {
+        let len = match connection.receive(&mut buf) {
+            Ok(Some(len)) => len,
+            Ok(None) => continue,
+            Err(e) => return Err(e.to_string()),
+        };
+        // NOTE: Only the header is read, so parsing doesn't slow the measure down
+        if len < 12 || buf[2] & 0x80 == 0 {
+            continue;
+        }
+
+        let id = u16::from_be_bytes([buf[0], buf[1]]);
+        let sent = match outstanding.lock().map_err(|e| e.to_string())?.remove(&id) {
+            Some(sent) => sent,
+            None => continue,
+        };
+        let latency = sent.elapsed();
+        if latency > timeout {
+            late += 1;
+            continue;
+        }
+
+        latencies.push(latency);
+        let code = match buf[3] & 0x0F {
+            code @ 0..=5 => format!("{:?}", ResultCode::from_number(code)),
+            code => code.to_string(),
+        };
+        *response_codes.entry(code).or_default() += 1;
+    }
+
+    Ok((latencies, response_codes, late))
+}
+
+enum Connection {
+    Udp(UdpSocket),
+    Tcp(TcpStream),
+}
+
+impl Connection {
+    fn send(&mut self, message: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        match self {
+            Connection::Udp(socket) => {
+                socket.send(message)?;
+            }
+            // NOTE: Messages over TCP are preceded by their length (RFC 1035 §4.2.2)
+            Connection::Tcp(stream) => {
+                let mut framed = (message.len() as u16).to_be_bytes().to_vec();
+                framed.extend_from_slice(message);
+                stream.write_all(&framed)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The length of the next message received, if one came before the read timeout
+    fn receive(&mut self, buf: &mut [u8]) -> std::io::Result<Option<usize>> {
+        let result = match self {
+            Connection::Udp(socket) => socket.recv(buf),
+            Connection::Tcp(stream) => {
+                let mut len = [0; 2];
+                stream.read_exact(&mut len).and_then(|_| {
+                    let len = (u16::from_be_bytes(len) as usize).min(buf.len());
+                    stream.set_read_timeout(None)?;
+                    stream.read_exact(&mut buf[..len])?;
+                    stream.set_read_timeout(Some(POLL_INTERVAL))?;
+                    Ok(len)
+                })
+            }
+        };
+
+        match result {
+            Ok(len) => Ok(Some(len)),
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => Ok(None),
+            // NOTE: Without a server, UDP reports the ICMP port unreachable of an earlier send
+            Err(e) if e.kind() == ErrorKind::ConnectionRefused => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::config::PerfConfig;
+    use super::{read_queries, run, PerfReport, QueryType};
+    use std::net::{Ipv4Addr, UdpSocket};
+    use std::time::Duration;
+
+    #[test]
+    fn read_queries_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let text = "; comment\nwww.example.com A\n\nexample.com. MX\n# comment\nexample.org\n";
+
+        // act
+        let actual = read_queries(text)?;
+
+        // assert
+        let actual: Vec<(&str, QueryType)> = actual
+            .iter()
+            .map(|question| (question.name.as_str(), question.qtype))
+            .collect();
+        assert_eq!(
+            vec![
+                ("www.example.com", QueryType::A),
+                ("example.com", QueryType::MX),
+                ("example.org", QueryType::A),
+            ],
+            actual
+        );
+        assert!(read_queries("example.com BOGUS").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn run_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let socket = UdpSocket::bind(("127.0.0.1", 0))?;
+        let port = socket.local_addr()?.port();
+        // NOTE: Every other query is answered, with NXDOMAIN
+        std::thread::spawn(move || {
+            let mut buf = [0; 512];
+            for i in 0.. {
+                let (len, client) = socket.recv_from(&mut buf).unwrap();
+                if i % 2 == 0 {
+                    buf[2] |= 0x80;
+                    buf[3] = 3;
+                    socket.send_to(&buf[..len], client).unwrap();
+                }
+            }
+        });
+        let config = PerfConfig {
+            server: Ipv4Addr::LOCALHOST,
+            port,
+            timeout: Duration::from_millis(200),
+            ..PerfConfig::default()
+        };
+        let queries = read_queries("a.example A\nb.example AAAA\n".repeat(5).as_str())?;
+
+        // act
+        let actual = run(&queries, &config)?;
+
+        // assert
+        assert_eq!(10, actual.sent);
+        assert_eq!(5, actual.latencies.len());
+        assert_eq!(5, actual.lost);
+        assert_eq!(Some(&5), actual.response_codes.get("NXDOMAIN"));
+        assert!(actual
+            .to_string()
+            .contains("Queries lost:         5 (50.00%)"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn percentile_ok() {
+        // arrange
+        let report = PerfReport {
+            latencies: (1..=100).map(Duration::from_millis).collect(),
+            ..PerfReport::default()
+        };
+
+        // act
+        let actual = [0.0, 0.5, 0.99, 1.0].map(|p| report.percentile(p));
+
+        // assert
+        assert_eq!(
+            [1, 51, 99, 100].map(|millis| Some(Duration::from_millis(millis))),
+            actual
+        );
+        assert_eq!(None, PerfReport::default().percentile(0.5));
+    }
+}
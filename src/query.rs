@@ -1,92 +1,626 @@
-use super::byte_packet_buffer::BytePacketBuffer;
+use super::blocklist::Blocklists;
+use super::byte_packet_buffer::{BytePacketBuffer, MAX_MESSAGE_SIZE, MAX_PACKET_SIZE};
+use super::config::ServerConfig;
+use super::dnssec::name;
+use super::dnssec::validator::{ChainOfTrust, Security, Validator};
+use super::dnssec::zone_signer::OnlineSigner;
+use super::hosts::Hosts;
 use super::model::{
-    dns_packet::DnsPacket, dns_question::DnsQuestion, query_type::QueryType,
-    result_code::ResultCode,
+    dns_class::DnsClass, dns_packet::DnsPacket, dns_question::DnsQuestion, dns_record::DnsRecord,
+    query_type::QueryType, result_code::ResultCode,
 };
-use std::net::{Ipv4Addr, UdpSocket};
+use super::transport::{exchange_stream, Transport, Udp};
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream, UdpSocket};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
+/// For practice, we are always starting with `a.root-servers.net`
+pub const ROOT_SERVER: Ipv4Addr = Ipv4Addr::new(198, 41, 0, 4);
+
+/// How deeply walks nest to find the keys of the zones signing parts of an answer
+const MAX_SIGNER_DEPTH: usize = 4;
+
+/// Resolve the name from the root hints of the settings, asking the servers over UDP on their
+/// upstream port
 pub fn recursive_lookup(
     qname: &str,
     qtype: QueryType,
+    config: &ServerConfig,
 ) -> Result<DnsPacket, Box<dyn std::error::Error>> {
-    // For practice, we are always starting with `a.root-servers.net`
-    let mut ns = Ipv4Addr::new(198, 41, 0, 4);
+    let mut resolver = Resolver::from_config(config);
+    let mut trace = Vec::new();
+    let result = resolver.resolve(qname, qtype, None, false, &mut trace);
+    for step in &trace {
+        log(config, format_args!("{}", step));
+    }
+    let (response, _) = result?;
 
-    loop {
-        println!("attempting lookup of {:?} {} with ns {}", qtype, qname, ns);
-        let copy_ns = ns;
+    Ok(response)
+}
 
-        let server = (copy_ns, 53);
-        let response = lookup(qname, qtype, server)?;
+/// A resolver walking the delegations from its root hints, asking the servers over its transport
+pub struct Resolver<T: Transport> {
+    pub transport: T,
+    pub root_hints: Vec<Ipv4Addr>,
+}
+
+impl Resolver<Udp> {
+    /// Asking over UDP, with the root hints, upstream port and timeout of the settings
+    pub fn from_config(config: &ServerConfig) -> Self {
+        let transport = Udp {
+            port: config.upstream_port,
+            timeout: config.upstream_timeout,
+        };
 
-        // If there are no entries in the answer section, and no errors, it is Ok
-        if !response.answers.is_empty() && response.header.response_code == ResultCode::NOERROR {
-            return Ok(response);
+        Resolver::new(transport, config.root_hints.clone())
+    }
+}
+
+impl<T: Transport> Resolver<T> {
+    pub fn new(transport: T, root_hints: Vec<Ipv4Addr>) -> Self {
+        Resolver {
+            transport,
+            root_hints,
+        }
+    }
+
+    /// Ask a single server for the name, with the DNSSEC records when `dnssec_ok`
+    pub fn lookup(
+        &mut self,
+        qname: &str,
+        qtype: QueryType,
+        server: Ipv4Addr,
+        dnssec_ok: bool,
+    ) -> Result<DnsPacket, Box<dyn std::error::Error>> {
+        let question = DnsQuestion::new(qname.to_string(), qtype);
+        let query = query_packet(&question, true, dnssec_ok);
+        let response = self.transport.send(&query, server)?;
+        if response.header.id != query.header.id {
+            return Err("The response doesn't match the query".into());
         }
 
-        // We might also get a `NXDOMAIN` reply, which is the authorities name servers way of
-        // telling us that the name doesn't exist.
-        if response.header.response_code == ResultCode::NXDOMAIN {
-            return Ok(response);
+        Ok(response)
+    }
+
+    /// Resolve the name from the root hints as `resolve_from_hints` does, asking for the DNSSEC
+    /// records when validating or when `dnssec_ok`
+    pub fn resolve(
+        &mut self,
+        qname: &str,
+        qtype: QueryType,
+        validator: Option<&Validator>,
+        dnssec_ok: bool,
+        trace: &mut Vec<TraceStep>,
+    ) -> Result<(DnsPacket, Security), Box<dyn std::error::Error>> {
+        let dnssec_ok = dnssec_ok || validator.is_some();
+        let root_hints = self.root_hints.clone();
+        let mut lookup = |qname: &str, qtype, server| self.lookup(qname, qtype, server, dnssec_ok);
+
+        resolve_from_hints(qname, qtype, &root_hints, validator, &mut lookup, trace)
+    }
+}
+
+/// Resolve the name iteratively from the root server, asking each server with `lookup`. With a
+/// validator, the chain of trust is followed along the referrals and the answer is validated.
+pub fn resolve<F>(
+    qname: &str,
+    qtype: QueryType,
+    root: Ipv4Addr,
+    validator: Option<&Validator>,
+    lookup: &mut F,
+) -> Result<(DnsPacket, Security), Box<dyn std::error::Error>>
+where
+    F: FnMut(&str, QueryType, Ipv4Addr) -> Result<DnsPacket, Box<dyn std::error::Error>>,
+{
+    resolve_traced(qname, qtype, root, validator, lookup, &mut Vec::new())
+}
+
+/// Same as `resolve`, but records each step of the delegation walk in `trace`, which is kept
+/// when the resolution fails. Queries made to validate the chain of trust aren't recorded.
+pub fn resolve_traced<F>(
+    qname: &str,
+    qtype: QueryType,
+    root: Ipv4Addr,
+    validator: Option<&Validator>,
+    lookup: &mut F,
+    trace: &mut Vec<TraceStep>,
+) -> Result<(DnsPacket, Security), Box<dyn std::error::Error>>
+where
+    F: FnMut(&str, QueryType, Ipv4Addr) -> Result<DnsPacket, Box<dyn std::error::Error>>,
+{
+    walk(qname, qtype, root, validator, lookup, trace, 0)
+}
+
+/// Resolve the name from each of the root servers in turn, until the resolution neither fails
+/// nor ends with `SERVFAIL` or `REFUSED`, as it would from an unreachable or lame root. The steps
+/// from all of them are recorded in `trace`.
+pub fn resolve_from_hints<F>(
+    qname: &str,
+    qtype: QueryType,
+    hints: &[Ipv4Addr],
+    validator: Option<&Validator>,
+    lookup: &mut F,
+    trace: &mut Vec<TraceStep>,
+) -> Result<(DnsPacket, Security), Box<dyn std::error::Error>>
+where
+    F: FnMut(&str, QueryType, Ipv4Addr) -> Result<DnsPacket, Box<dyn std::error::Error>>,
+{
+    let mut result = Err("There are no root hints".into());
+    for root in hints {
+        result = resolve_traced(qname, qtype, *root, validator, lookup, trace);
+        if let Ok((response, _)) = &result {
+            if !is_failure(response) {
+                break;
+            }
         }
+    }
+
+    result
+}
 
-        // Otherwise, we will try to find a new name server based on NS and a corresponding A
-        // record in the additional section. If this succeeds, we can switch name server and retry
-        // again.
-        if let Some(new_ns) = response.get_resolved_ns(qname) {
-            ns = new_ns;
+/// Whether the resolution ended with `SERVFAIL` or `REFUSED`, so another root server might do
+/// better
+pub fn is_failure(response: &DnsPacket) -> bool {
+    matches!(
+        response.header.response_code,
+        ResultCode::SERVFAIL | ResultCode::REFUSED
+    )
+}
+
+/// Where the delegation walk goes after a response
+#[derive(Debug, Clone, PartialEq)]
+pub enum Step<'a> {
+    /// The response ends the walk: an answer, a name error, or nowhere left to go
+    Done,
+    /// Ask the server at the address next, from the glue of the referral
+    Ask(Ipv4Addr),
+    /// Resolve the address of the name server first, the referral having no glue for it
+    Resolve(&'a str),
+}
+
+/// The step after the response to the question about `qname`
+pub fn next_step<'a>(qname: &'a str, response: &'a DnsPacket) -> Step<'a> {
+    // If there are no entries in the answer section, and no errors, it is Ok
+    // We might also get a `NXDOMAIN` reply, which is the authorities name servers way of
+    // telling us that the name doesn't exist.
+    if (!response.answers.is_empty() && response.header.response_code == ResultCode::NOERROR)
+        || response.header.response_code == ResultCode::NXDOMAIN
+    {
+        return Step::Done;
+    }
 
-            continue;
+    // Otherwise, we will try to find a new name server based on NS and a corresponding A
+    // record in the additional section. If not, we will have to resolve the ip of a NS record.
+    // If no NS records exist, we will go with what the last server told us.
+    match response.get_resolved_ns(qname) {
+        Some(new_ns) => Step::Ask(new_ns),
+        None => match response.get_unsolved_ns(qname) {
+            Some(new_ns_name) => Step::Resolve(new_ns_name),
+            None => Step::Done,
+        },
+    }
+}
+
+/// One query of the delegation walk
+#[derive(Debug, Clone)]
+pub struct TraceStep {
+    /// How deeply the walk is nested, the addresses of name servers without glue being resolved
+    /// by walks of their own
+    pub depth: usize,
+    pub server: Ipv4Addr,
+    pub question: DnsQuestion,
+    /// Code of the response, or why there was none
+    pub response_code: Result<ResultCode, String>,
+    pub rtt: Duration,
+    /// Where the response referred to, if it was a referral
+    pub referral: Option<Referral>,
+}
+
+/// A delegation to the name servers of a child zone
+#[derive(Debug, Clone, PartialEq)]
+pub struct Referral {
+    pub zone: String,
+    pub name_servers: Vec<String>,
+    /// Address of the name server asked next
+    pub next: Ipv4Addr,
+    /// Whether the address came as glue in the additional section, rather than being resolved
+    pub glue: bool,
+}
+
+impl fmt::Display for TraceStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:indent$}{} {} @{}",
+            "",
+            self.question.qtype,
+            fqdn(&self.question.name),
+            self.server,
+            indent = self.depth * 2
+        )?;
+
+        match &self.response_code {
+            Ok(code) => write!(f, " {:?} in {} ms", code, self.rtt.as_millis())?,
+            Err(reason) => write!(f, " failed in {} ms: {}", self.rtt.as_millis(), reason)?,
         }
 
-        // If not, we will have to resolve the ip of a NS record. If no NS records exist, we will
-        // go with what the last server told us.
-        let new_ns_name = match response.get_unsolved_ns(qname) {
-            Some(x) => x,
-            None => return Ok(response),
+        if let Some(referral) = &self.referral {
+            let name_servers: Vec<String> =
+                referral.name_servers.iter().map(|ns| fqdn(ns)).collect();
+            write!(
+                f,
+                ", referral to {} ({}) via {}{}",
+                fqdn(&referral.zone),
+                name_servers.join(" "),
+                referral.next,
+                if referral.glue { " from glue" } else { "" }
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The name with its trailing dot
+fn fqdn(name: &str) -> String {
+    format!("{}.", name.trim_end_matches('.'))
+}
+
+fn walk<F>(
+    qname: &str,
+    qtype: QueryType,
+    root: Ipv4Addr,
+    validator: Option<&Validator>,
+    lookup: &mut F,
+    trace: &mut Vec<TraceStep>,
+    depth: usize,
+) -> Result<(DnsPacket, Security), Box<dyn std::error::Error>>
+where
+    F: FnMut(&str, QueryType, Ipv4Addr) -> Result<DnsPacket, Box<dyn std::error::Error>>,
+{
+    let mut ns = root;
+    let mut chain = match validator {
+        Some(validator) => Some(validator.start(ns, lookup)?),
+        None => None,
+    };
+    let mut signer_keys =
+        |zone: &str, lookup: &mut F| trusted_keys(zone, root, validator, lookup, depth);
+
+    loop {
+        let start = Instant::now();
+        let result = lookup(qname, qtype, ns);
+        trace.push(TraceStep {
+            depth,
+            server: ns,
+            question: DnsQuestion::new(qname.to_string(), qtype),
+            response_code: match &result {
+                Ok(response) => Ok(response.header.response_code),
+                Err(e) => Err(e.to_string()),
+            },
+            rtt: start.elapsed(),
+            referral: None,
+        });
+        let response = result?;
+
+        let step = trace.len() - 1;
+        let (new_ns, glue) = match next_step(qname, &response) {
+            Step::Done => {
+                return validated(
+                    chain.as_mut(),
+                    qname,
+                    qtype,
+                    response,
+                    ns,
+                    lookup,
+                    &mut signer_keys,
+                )
+            }
+            Step::Ask(new_ns) => (new_ns, true),
+            Step::Resolve(new_ns_name) => {
+                // NOTE: Addresses of name servers aren't validated, a wrong one can only make the
+                // chain of trust fail
+                let (recursive_response, _) = walk(
+                    new_ns_name,
+                    QueryType::A,
+                    root,
+                    None,
+                    lookup,
+                    trace,
+                    depth + 1,
+                )?;
+
+                // Finally, we pick a random ip from the result, and restart the loop. If no such
+                // record is available, we return the last result we got.
+                match recursive_response.get_random_a() {
+                    Some(new_ns) => (new_ns, false),
+                    None => {
+                        return validated(
+                            chain.as_mut(),
+                            qname,
+                            qtype,
+                            response,
+                            ns,
+                            lookup,
+                            &mut signer_keys,
+                        )
+                    }
+                }
+            }
         };
 
-        let recursive_response = recursive_lookup(&new_ns_name, QueryType::A)?;
+        let name_servers: Vec<(&str, &str)> = response
+            .authorities
+            .iter()
+            .filter_map(|record| match record {
+                DnsRecord::NS { domain, host, .. } if name::is_subdomain(qname, domain) => {
+                    Some((domain.as_str(), host.as_str()))
+                }
+                _ => None,
+            })
+            .collect();
+        let child = name_servers
+            .first()
+            .map(|(domain, _)| *domain)
+            .unwrap_or_default();
+        trace[step].referral = Some(Referral {
+            zone: child.to_string(),
+            name_servers: name_servers
+                .iter()
+                .map(|(_, host)| host.to_string())
+                .collect(),
+            next: new_ns,
+            glue,
+        });
 
-        // Finally, we pick a random ip from the result, and restart the loop. If no such record is
-        // available, we return the last result we got.
-        if let Some(new_ns) = recursive_response.get_random_a() {
-            ns = new_ns
-        } else {
-            return Ok(response);
+        if let Some(chain) = chain.as_mut() {
+            chain.follow_referral(child, &response, new_ns, lookup)?;
         }
+
+        ns = new_ns;
     }
 }
 
-/// Query the name through Google's public DNS
+/// The response along with its security status, unvalidated responses being insecure
+fn validated<F, K>(
+    chain: Option<&mut ChainOfTrust>,
+    qname: &str,
+    qtype: QueryType,
+    response: DnsPacket,
+    server: Ipv4Addr,
+    lookup: &mut F,
+    signer_keys: &mut K,
+) -> Result<(DnsPacket, Security), Box<dyn std::error::Error>>
+where
+    F: FnMut(&str, QueryType, Ipv4Addr) -> Result<DnsPacket, Box<dyn std::error::Error>>,
+    K: FnMut(&str, &mut F) -> Result<Option<Vec<DnsRecord>>, Box<dyn std::error::Error>>,
+{
+    let security = match chain {
+        Some(chain) => chain.validate(qname, qtype, &response, server, lookup, signer_keys)?,
+        None => Security::Insecure,
+    };
+
+    Ok((response, security))
+}
+
+/// The keys of a zone other than the one answering, which signed records of the answer. They are
+/// resolved from the root, and only trusted when the resolution is secure.
+fn trusted_keys<F>(
+    zone: &str,
+    root: Ipv4Addr,
+    validator: Option<&Validator>,
+    lookup: &mut F,
+    depth: usize,
+) -> Result<Option<Vec<DnsRecord>>, Box<dyn std::error::Error>>
+where
+    F: FnMut(&str, QueryType, Ipv4Addr) -> Result<DnsPacket, Box<dyn std::error::Error>>,
+{
+    // NOTE: Signers pointing at each other could otherwise nest the walks forever
+    if validator.is_none() || depth >= MAX_SIGNER_DEPTH {
+        return Ok(None);
+    }
+
+    let (response, security) = walk(
+        zone,
+        QueryType::DNSKEY,
+        root,
+        validator,
+        lookup,
+        &mut Vec::new(),
+        depth + 1,
+    )?;
+    if security != Security::Secure {
+        return Ok(None);
+    }
+
+    Ok(Some(
+        response
+            .answers
+            .into_iter()
+            .filter(|record| {
+                matches!(record, DnsRecord::DNSKEY { .. })
+                    && name::normalize(record.domain()) == name::normalize(zone)
+            })
+            .collect(),
+    ))
+}
+
+/// Query a server for the name, asking for recursion over UDP
 pub fn lookup(
     qname: &str,
     qtype: QueryType,
     server: (Ipv4Addr, u16),
+    dnssec_ok: bool,
 ) -> Result<DnsPacket, Box<dyn std::error::Error>> {
-    let socket = UdpSocket::bind(("0.0.0.0", 43210))?;
+    let question = DnsQuestion::new(qname.to_string(), qtype);
+    let options = LookupOptions {
+        dnssec_ok,
+        ..Default::default()
+    };
+    let (response, _) = lookup_with(&question, server, &options)?;
+
+    Ok(response)
+}
+
+/// How `lookup_with` sends a query
+#[derive(Debug, Clone)]
+pub struct LookupOptions {
+    /// Send the query over TCP instead of UDP
+    pub tcp: bool,
+    /// Ask the server to recurse (the RD bit)
+    pub recursion_desired: bool,
+    /// Ask for DNSSEC records (the DO bit)
+    pub dnssec_ok: bool,
+    /// How long to wait for the response
+    pub timeout: Duration,
+}
+
+impl Default for LookupOptions {
+    fn default() -> Self {
+        LookupOptions {
+            tcp: false,
+            recursion_desired: true,
+            dnssec_ok: false,
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Query a server with the question, giving the response along with the size of its message
+pub fn lookup_with(
+    question: &DnsQuestion,
+    server: (Ipv4Addr, u16),
+    options: &LookupOptions,
+) -> Result<(DnsPacket, usize), Box<dyn std::error::Error>> {
+    let mut packet = query_packet(question, options.recursion_desired, options.dnssec_ok);
+
+    let mut req_buffer = BytePacketBuffer::new();
+    packet.write(&mut req_buffer)?;
 
+    exchange(
+        &req_buffer.buf[0..req_buffer.pos()],
+        server,
+        options.tcp,
+        options.timeout,
+    )
+}
+
+/// A query for the question with a random id, advertising EDNS
+pub fn query_packet(question: &DnsQuestion, recursion_desired: bool, dnssec_ok: bool) -> DnsPacket {
     let mut packet = DnsPacket::new();
 
-    packet.header.id = 1234;
+    packet.header.id = rand::random();
     packet.header.questions_count = 1;
-    packet.header.recursion_desired = true;
+    packet.header.recursion_desired = recursion_desired;
+    packet.questions.push(question.clone());
+    // NOTE: Signed answers rarely fit in 512 bytes
+    packet.set_edns(MAX_PACKET_SIZE as u16, dnssec_ok);
+
     packet
-        .questions
-        .push(DnsQuestion::new(qname.to_string(), qtype));
+}
 
-    let mut req_buffer = BytePacketBuffer::new();
-    packet.write(&mut req_buffer)?;
-    socket.send_to(&req_buffer.buf[0..req_buffer.pos()], server)?;
+/// Send a message to a server as is, giving the response along with the size of its message
+pub fn exchange(
+    request: &[u8],
+    server: impl Into<SocketAddr>,
+    tcp: bool,
+    timeout: Duration,
+) -> Result<(DnsPacket, usize), Box<dyn std::error::Error>> {
+    let server = server.into();
+    if tcp {
+        let mut stream = TcpStream::connect_timeout(&server, timeout)?;
+        stream.set_read_timeout(Some(timeout))?;
+
+        return exchange_stream(&mut stream, request);
+    }
 
     let mut res_buffer = BytePacketBuffer::new();
-    socket.recv_from(&mut res_buffer.buf)?;
+    let local: IpAddr = match server {
+        SocketAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+        SocketAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+    };
+    let socket = UdpSocket::bind((local, 0))?;
+    socket.set_read_timeout(Some(timeout))?;
+    socket.send_to(request, server)?;
+    let size = socket.recv_from(&mut res_buffer.buf)?.0;
+
+    if request.len() < 2 || res_buffer.buf[0..2] != request[0..2] {
+        return Err("The response doesn't match the query".into());
+    }
+    let response = DnsPacket::from_buffer(&mut res_buffer)?;
 
-    Ok(DnsPacket::from_buffer(&mut res_buffer)?)
+    Ok((response, size))
 }
 
-/// Handle a single incoming packet
-pub fn handle_query(socket: &UdpSocket) -> Result<(), Box<dyn std::error::Error>> {
+/// Answer a CHAOS class query about the server itself, such as `version.bind`, from the
+/// configured text. Names we don't serve, or whose text is not configured, are refused.
+pub fn chaos_lookup(question: &DnsQuestion, config: &ServerConfig) -> DnsPacket {
+    let mut packet = DnsPacket::new();
+
+    let text = match question.name.to_lowercase().as_str() {
+        "version.bind" | "version.server" => config.chaos_version.as_ref(),
+        "id.server" | "hostname.bind" => config.chaos_id.as_ref(),
+        _ => None,
+    };
+
+    match text {
+        // NOTE: 255 is the ANY type
+        Some(text) if matches!(question.qtype, QueryType::TXT | QueryType::UNKNOWN(255)) => {
+            packet.answers.push(DnsRecord::TXT {
+                domain: question.name.clone(),
+                data: text.as_bytes().chunks(255).map(|s| s.to_vec()).collect(),
+                class: DnsClass::CH,
+                ttl: 0,
+            });
+            packet.header.authoritative_answer = true;
+        }
+        // The name exists, but has no records of the asked type
+        Some(_) => {}
+        None => packet.header.response_code = ResultCode::REFUSED,
+    }
+
+    packet
+}
+
+/// Answer a query from the zones we serve, the one with the longest origin holding the name,
+/// signed as of `now` (seconds since the epoch). Names outside of them are left to recursion.
+pub fn zone_lookup(
+    question: &DnsQuestion,
+    zones: &mut [OnlineSigner],
+    dnssec_ok: bool,
+    now: u32,
+) -> Option<Result<DnsPacket, Box<dyn std::error::Error>>> {
+    let signer = zones
+        .iter_mut()
+        .filter(|signer| signer.zone.contains(&question.name))
+        .max_by_key(|signer| name::labels(&signer.zone.origin).len())?;
+
+    Some(
+        signer
+            .signed(now)
+            .map(|zone| zone.answer(&question.name, question.qtype, dnssec_ok)),
+    )
+}
+
+/// Print a line of the text log, which the JSON log replaces with a line per response and
+/// nothing else
+fn log(config: &ServerConfig, message: fmt::Arguments) {
+    if !config.json_log {
+        println!("{}", message);
+    }
+}
+
+/// Handle a single incoming packet. Names of the zones we serve are answered from them, signed
+/// online when they have keys. With a validator, answers are validated with DNSSEC unless the
+/// client disabled checking: bogus answers fail with `SERVFAIL`, and secure ones have the AD bit
+/// set.
+pub fn handle_query(
+    socket: &UdpSocket,
+    config: &ServerConfig,
+    validator: Option<&Validator>,
+    zones: &mut [OnlineSigner],
+    hosts: &mut Hosts,
+    blocklists: &mut Blocklists,
+) -> Result<(), Box<dyn std::error::Error>> {
     let mut req_buffer = BytePacketBuffer::new();
 
     // While the socket is ready, we can read a packet. This will `block` until one is received.
@@ -100,34 +634,142 @@ pub fn handle_query(socket: &UdpSocket) -> Result<(), Box<dyn std::error::Error>
     res_packet.header.recursion_desired = true;
     res_packet.header.recursion_available = true;
     res_packet.header.query_response = true;
+    res_packet.header.checking_disabled = request.header.checking_disabled;
+
+    // NOTE: The DNSSEC records are only asked for when validating or when the client wants them
+    let dnssec_ok = request.dnssec_ok();
+    // NOTE: Hosts files edited since they were last looked at are read again, a file which can't
+    // be read keeping its previous names
+    if let Err(e) = hosts.reload() {
+        log(
+            config,
+            format_args!("Failed to reload the hosts files: {}", e),
+        );
+    }
+    let mut resolver = Resolver::from_config(config);
+    let checking = validator.filter(|_| !request.header.checking_disabled);
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as u32;
 
     // In normal case, only one question is present
     if let Some(question) = request.questions.pop() {
-        println!("Received query: {:?}", question);
+        log(config, format_args!("Received query: {}", question));
+        let qtype = question.qtype;
 
+        // Queries in the CHAOS class are about the server itself, so they are answered locally
+        if question.qclass == DnsClass::CH {
+            let result = chaos_lookup(&question, config);
+            res_packet.questions.push(question);
+            res_packet.header.authoritative_answer = result.header.authoritative_answer;
+            res_packet.header.response_code = result.header.response_code;
+            res_packet.answers = result.answers;
+        }
+        // Only the Internet class is resolved, as the root servers serve no other
+        else if question.qclass != DnsClass::IN {
+            res_packet.questions.push(question);
+            res_packet.header.response_code = ResultCode::REFUSED;
+        }
+        // Names overridden locally are answered with authority, even those of our own zones
+        else if let Some(result) = hosts.answer(&question) {
+            res_packet.questions.push(question);
+            res_packet.header.authoritative_answer = result.header.authoritative_answer;
+            res_packet.header.response_code = result.header.response_code;
+            res_packet.answers = result.answers;
+        }
+        // Names of our own zones are answered with authority, without recursion
+        else if let Some(result) = zone_lookup(&question, zones, dnssec_ok, now) {
+            res_packet.questions.push(question);
+
+            match result {
+                Ok(result) => {
+                    res_packet.header.authoritative_answer = result.header.authoritative_answer;
+                    res_packet.header.response_code = result.header.response_code;
+                    res_packet.answers = result.answers;
+                    res_packet.authorities = result.authorities;
+                    res_packet.resources = result.resources;
+                }
+                Err(e) => {
+                    log(config, format_args!("Signing failed: {}", e));
+                    res_packet.header.response_code = ResultCode::SERVFAIL;
+                }
+            }
+        }
+        // Blocked names are answered as configured instead of being resolved
+        else if let Some((result, list)) = blocklists.answer(&question) {
+            log(
+                config,
+                format_args!(
+                    "Blocked {} by {} ({} hits)",
+                    question.name, list.name, list.hits
+                ),
+            );
+            res_packet.questions.push(question);
+            res_packet.header.authoritative_answer = result.header.authoritative_answer;
+            res_packet.header.response_code = result.header.response_code;
+            res_packet.answers = result.answers;
+        }
         // The query can be forwarded to the target server.
         // There's always the possibility that the query will
         // fail, in which case the `SERVFAIL` response code is set to indicate
         // as much to the client.
         // If everything goes as planned, the question and response records as copied into our response packet.
-        if let Ok(result) = recursive_lookup(&question.name, question.qtype) {
+        else {
+            let mut trace = Vec::new();
+            let result = resolver.resolve(
+                &question.name,
+                qtype,
+                checking,
+                validator.is_some() || dnssec_ok,
+                &mut trace,
+            );
             res_packet.questions.push(question);
-            res_packet.header.response_code = result.header.response_code;
-
-            for rec in result.answers {
-                println!("Answer: {:?}", rec);
-                res_packet.answers.push(rec);
+            for step in &trace {
+                log(config, format_args!("{}", step));
             }
-            for rec in result.authorities {
-                println!("Authority: {:?}", rec);
-                res_packet.authorities.push(rec);
+            if let Some(e) = validator.and_then(Validator::take_store_error) {
+                log(
+                    config,
+                    format_args!("Failed to update the trust anchors: {}", e),
+                );
             }
-            for rec in result.resources {
-                println!("Resource(Additional Resource): {:?}", rec);
-                res_packet.resources.push(rec);
+
+            match result {
+                Ok((_, Security::Bogus(reason))) => {
+                    log(config, format_args!("Bogus answer: {}", reason));
+                    res_packet.header.response_code = ResultCode::SERVFAIL;
+                }
+                Ok((result, security)) => {
+                    res_packet.header.response_code = result.header.response_code;
+                    res_packet.header.authed_data =
+                        security == Security::Secure && (dnssec_ok || request.header.authed_data);
+
+                    // NOTE: The OPT record is about the upstream server, and DNSSEC records are
+                    // only for clients asking for them (RFC 4035 §3.2.1)
+                    let keep = |rec: &DnsRecord| match rec {
+                        DnsRecord::OPT { .. } => false,
+                        DnsRecord::RRSIG { .. }
+                        | DnsRecord::NSEC { .. }
+                        | DnsRecord::NSEC3 { .. } => dnssec_ok || rec.qtype() == qtype,
+                        _ => true,
+                    };
+
+                    for rec in result.answers.into_iter().filter(keep) {
+                        log(config, format_args!("Answer: {}", rec));
+                        res_packet.answers.push(rec);
+                    }
+                    for rec in result.authorities.into_iter().filter(keep) {
+                        log(config, format_args!("Authority: {}", rec));
+                        res_packet.authorities.push(rec);
+                    }
+                    for rec in result.resources.into_iter().filter(keep) {
+                        log(
+                            config,
+                            format_args!("Resource(Additional Resource): {}", rec),
+                        );
+                        res_packet.resources.push(rec);
+                    }
+                }
+                Err(_) => res_packet.header.response_code = ResultCode::SERVFAIL,
             }
-        } else {
-            res_packet.header.response_code = ResultCode::SERVFAIL;
         }
     }
     // We have to make sure that a question is actually present.
@@ -136,12 +778,789 @@ pub fn handle_query(socket: &UdpSocket) -> Result<(), Box<dyn std::error::Error>
         res_packet.header.response_code = ResultCode::FORMERR;
     }
 
+    if request.edns().is_some() {
+        res_packet.set_edns(MAX_PACKET_SIZE as u16, dnssec_ok);
+    }
+
     // Transform the response packet to buffer and send it back to our client.
-    let mut res_buffer = BytePacketBuffer::new();
+    let mut res_buffer = BytePacketBuffer::with_size(MAX_MESSAGE_SIZE);
     res_packet.write(&mut res_buffer)?;
 
+    // NOTE: A response larger than the client accepts, or than we send over UDP, is truncated to
+    // its question, telling the client to retry over TCP
+    if res_buffer.pos() > request.max_udp_size().min(MAX_PACKET_SIZE) {
+        res_packet.header.truncated_message = true;
+        res_packet.answers.clear();
+        res_packet.authorities.clear();
+        res_packet
+            .resources
+            .retain(|rec| matches!(rec, DnsRecord::OPT { .. }));
+
+        res_buffer = BytePacketBuffer::new();
+        res_packet.write(&mut res_buffer)?;
+    }
+
+    #[cfg(feature = "json")]
+    if config.json_log {
+        println!("{}", serde_json::to_string(&res_packet)?);
+    }
+
     // Response to the client.
     socket.send_to(&res_buffer.buf[0..res_buffer.pos()], src)?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::super::dnssec::anchor_store::AnchorStore;
+    use super::super::dnssec::zone_signer::{Denial, OnlineSigner};
+    use super::super::dnssec::{crypto, denial, signature, signer::SigningKey};
+    use super::super::encoding::to_base32hex;
+    use super::super::zone::Zone;
+    use super::{chaos_lookup, name, resolve, zone_lookup, DnsPacket, Security, Validator};
+    use super::{next_step, resolve_traced, Referral, Step, TraceStep};
+    use super::{DnsClass, DnsQuestion, DnsRecord, QueryType, ResultCode, ServerConfig};
+    use std::collections::HashMap;
+    use std::net::Ipv4Addr;
+    use std::path::PathBuf;
+
+    /// The answers of in-memory name servers, by server, name and type
+    type World = HashMap<(Ipv4Addr, String, QueryType), DnsPacket>;
+
+    const NOW: u32 = 1_700_000_000;
+    const ROOT: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 1);
+    const EXAMPLE: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 2);
+    const INSECURE: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 3);
+    const OTHER: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 4);
+
+    fn chaos_question(name: &str, qtype: QueryType) -> DnsQuestion {
+        let mut question = DnsQuestion::new(name.to_string(), qtype);
+        question.qclass = DnsClass::CH;
+
+        question
+    }
+
+    #[test]
+    fn chaos_lookup_version_ok() {
+        // arrange
+        let config = ServerConfig {
+            chaos_version: Some("test resolver".to_string()),
+            ..Default::default()
+        };
+
+        // act
+        let actual = chaos_lookup(&chaos_question("VERSION.BIND", QueryType::TXT), &config);
+
+        // assert
+        assert_eq!(ResultCode::NOERROR, actual.header.response_code);
+        assert!(actual.header.authoritative_answer);
+        assert_eq!(
+            vec![DnsRecord::TXT {
+                domain: "VERSION.BIND".to_string(),
+                data: vec![b"test resolver".to_vec()],
+                class: DnsClass::CH,
+                ttl: 0,
+            }],
+            actual.answers
+        );
+    }
+
+    #[test]
+    fn chaos_lookup_refused() {
+        // arrange
+        let config = ServerConfig::default();
+
+        // act
+        let id = chaos_lookup(&chaos_question("id.server", QueryType::TXT), &config);
+        let other = chaos_lookup(&chaos_question("authors.bind", QueryType::TXT), &config);
+
+        // assert
+        assert_eq!(ResultCode::REFUSED, id.header.response_code);
+        assert_eq!(ResultCode::REFUSED, other.header.response_code);
+        assert!(id.answers.is_empty());
+        assert!(!id.header.authoritative_answer);
+    }
+
+    #[test]
+    fn chaos_lookup_no_data() {
+        // arrange
+        let config = ServerConfig::default();
+
+        // act
+        let actual = chaos_lookup(&chaos_question("version.server", QueryType::A), &config);
+
+        // assert
+        assert_eq!(ResultCode::NOERROR, actual.header.response_code);
+        assert!(actual.answers.is_empty());
+        assert!(!actual.header.authoritative_answer);
+    }
+
+    #[test]
+    fn zone_lookup_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let parent = Zone::parse(
+            "@ 3600 SOA ns hostmaster 1 7200 3600 1209600 300\n\
+             sub 3600 NS ns.sub\n\
+             ns.sub 3600 A 192.0.2.53\n",
+            "example",
+        )?;
+        let child = Zone::parse(
+            "@ 3600 SOA ns hostmaster 1 7200 3600 1209600 300\n\
+             www 3600 A 192.0.2.1\n",
+            "sub.example",
+        )?;
+        let key = SigningKey::generate("sub.example", 257, crypto::ED25519)?.0;
+        let mut zones = [
+            OnlineSigner::new(parent, Vec::new(), Denial::Nsec),
+            OnlineSigner::new(child, vec![key], Denial::Nsec),
+        ];
+        let question = DnsQuestion::new("www.sub.example".to_string(), QueryType::A);
+
+        // act
+        let actual = zone_lookup(&question, &mut zones, true, 1_700_000_000).ok_or("no zone")??;
+        let other = zone_lookup(
+            &DnsQuestion::new("www.example.org".to_string(), QueryType::A),
+            &mut zones,
+            true,
+            1_700_000_000,
+        );
+
+        // assert
+        assert!(actual.header.authoritative_answer);
+        assert_eq!(2, actual.answers.len());
+        assert!(matches!(actual.answers[1], DnsRecord::RRSIG { .. }));
+        assert!(other.is_none());
+
+        Ok(())
+    }
+
+    fn packet(
+        response_code: ResultCode,
+        answers: Vec<DnsRecord>,
+        authorities: Vec<DnsRecord>,
+        resources: Vec<DnsRecord>,
+    ) -> DnsPacket {
+        let mut packet = DnsPacket::new();
+        packet.header.response_code = response_code;
+        packet.answers = answers;
+        packet.authorities = authorities;
+        packet.resources = resources;
+
+        packet
+    }
+
+    fn a(domain: &str, addr: Ipv4Addr) -> DnsRecord {
+        DnsRecord::A {
+            domain: domain.to_string(),
+            addr,
+            class: DnsClass::IN,
+            ttl: 3600,
+        }
+    }
+
+    fn ns(domain: &str, host: &str) -> DnsRecord {
+        DnsRecord::NS {
+            domain: domain.to_string(),
+            host: host.to_string(),
+            class: DnsClass::IN,
+            ttl: 3600,
+        }
+    }
+
+    /// The records along with their RRSIG records made with the key
+    fn signed(
+        key: &SigningKey,
+        records: Vec<DnsRecord>,
+    ) -> Result<Vec<DnsRecord>, Box<dyn std::error::Error>> {
+        let rrsigs = signature::rrsets(&records)
+            .iter()
+            .map(|rrset| key.sign_rrset(rrset, NOW - 86400, NOW + 86400))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(records.into_iter().chain(rrsigs).collect())
+    }
+
+    fn ds(key: &SigningKey) -> Result<DnsRecord, Box<dyn std::error::Error>> {
+        Ok(DnsRecord::DS {
+            domain: key.zone.clone(),
+            key_tag: key.key_tag(),
+            algorithm: key.algorithm(),
+            digest_type: crypto::DIGEST_SHA256,
+            digest: crypto::ds_digest(&key.dnskey(3600), crypto::DIGEST_SHA256)
+                .ok_or("No digest")?,
+            class: DnsClass::IN,
+            ttl: 3600,
+        })
+    }
+
+    /// The NSEC3 chain of the zone `example` (RFC 5155 §7.1)
+    fn nsec3_chain() -> Result<Vec<DnsRecord>, Box<dyn std::error::Error>> {
+        let salt = vec![0xaa, 0xbb, 0xcc, 0xdd];
+        let mut hashes = [
+            (
+                "example",
+                vec![QueryType::NS, QueryType::RRSIG, QueryType::DNSKEY],
+            ),
+            ("ns.example", vec![QueryType::A, QueryType::RRSIG]),
+            ("www.example", vec![QueryType::A, QueryType::RRSIG]),
+        ]
+        .into_iter()
+        .map(|(owner, types)| Ok((denial::nsec3_hash(owner, &salt, 12)?, types)))
+        .collect::<Result<Vec<_>, Box<dyn std::error::Error>>>()?;
+        hashes.sort();
+
+        Ok((0..hashes.len())
+            .map(|i| DnsRecord::NSEC3 {
+                domain: format!("{}.example", to_base32hex(&hashes[i].0).to_lowercase()),
+                hash_algorithm: denial::NSEC3_SHA1,
+                flags: 0,
+                iterations: 12,
+                salt: salt.clone(),
+                next_hashed_owner: hashes[(i + 1) % hashes.len()].0.clone(),
+                types: hashes[i].1.clone(),
+                class: DnsClass::IN,
+                ttl: 3600,
+            })
+            .collect())
+    }
+
+    /// A signed root zone delegating to the signed zones `example` and `other`, the latter denying
+    /// names with NSEC records, and the unsigned zone `insecure`, each served by its own server,
+    /// with the validator trusting the root key
+    fn world() -> Result<(World, Validator), Box<dyn std::error::Error>> {
+        let root_ksk = SigningKey::from_pkcs8(
+            "",
+            257,
+            crypto::RSASHA256,
+            include_bytes!("dnssec/testdata/rsasha256.pk8"),
+        )?;
+        let root_zsk = SigningKey::generate("", 256, crypto::ED25519)?.0;
+        let example_ksk = SigningKey::generate("example", 257, crypto::ECDSAP256SHA256)?.0;
+        let example_zsk = SigningKey::generate("example", 256, crypto::ECDSAP384SHA384)?.0;
+        let other_ksk = SigningKey::generate("other", 257, crypto::ED25519)?.0;
+        let other_zsk = SigningKey::generate("other", 256, crypto::ED25519)?.0;
+        let mut world = World::new();
+
+        let root_keys = vec![root_ksk.dnskey(3600), root_zsk.dnskey(3600)];
+        let mut example_delegation = signed(&root_zsk, vec![ds(&example_ksk)?])?;
+        example_delegation.insert(0, ns("example", "ns.example"));
+        let mut insecure_delegation = signed(
+            &root_zsk,
+            vec![DnsRecord::NSEC {
+                domain: "insecure".to_string(),
+                next_domain: "".to_string(),
+                types: vec![QueryType::NS, QueryType::RRSIG, QueryType::NSEC],
+                class: DnsClass::IN,
+                ttl: 3600,
+            }],
+        )?;
+        insecure_delegation.insert(0, ns("insecure", "ns.insecure"));
+        let mut other_delegation = signed(&root_zsk, vec![ds(&other_ksk)?])?;
+        other_delegation.insert(0, ns("other", "ns.other"));
+
+        world.insert(
+            (ROOT, "".to_string(), QueryType::DNSKEY),
+            packet(
+                ResultCode::NOERROR,
+                signed(&root_ksk, root_keys)?,
+                vec![],
+                vec![],
+            ),
+        );
+        for qname in ["www.example", "nope.example", "alias.example"] {
+            world.insert(
+                (ROOT, qname.to_string(), QueryType::A),
+                packet(
+                    ResultCode::NOERROR,
+                    vec![],
+                    example_delegation.clone(),
+                    vec![a("ns.example", EXAMPLE)],
+                ),
+            );
+        }
+        for (qname, qtype) in [("other", QueryType::DNSKEY), ("gone.other", QueryType::A)] {
+            world.insert(
+                (ROOT, qname.to_string(), qtype),
+                packet(
+                    ResultCode::NOERROR,
+                    vec![],
+                    other_delegation.clone(),
+                    vec![a("ns.other", OTHER)],
+                ),
+            );
+        }
+        world.insert(
+            (ROOT, "x.insecure".to_string(), QueryType::A),
+            packet(
+                ResultCode::NOERROR,
+                vec![],
+                insecure_delegation,
+                vec![a("ns.insecure", INSECURE)],
+            ),
+        );
+
+        let example_keys = vec![example_ksk.dnskey(3600), example_zsk.dnskey(3600)];
+        world.insert(
+            (EXAMPLE, "example".to_string(), QueryType::DNSKEY),
+            packet(
+                ResultCode::NOERROR,
+                signed(&example_ksk, example_keys)?,
+                vec![],
+                vec![],
+            ),
+        );
+        world.insert(
+            (EXAMPLE, "www.example".to_string(), QueryType::A),
+            packet(
+                ResultCode::NOERROR,
+                signed(
+                    &example_zsk,
+                    vec![a("www.example", Ipv4Addr::new(192, 0, 2, 1))],
+                )?,
+                vec![],
+                vec![],
+            ),
+        );
+        world.insert(
+            (EXAMPLE, "nope.example".to_string(), QueryType::A),
+            packet(
+                ResultCode::NXDOMAIN,
+                vec![],
+                signed(&example_zsk, nsec3_chain()?)?,
+                vec![],
+            ),
+        );
+
+        world.insert(
+            (EXAMPLE, "alias.example".to_string(), QueryType::A),
+            packet(
+                ResultCode::NOERROR,
+                signed(
+                    &example_zsk,
+                    vec![DnsRecord::CNAME {
+                        domain: "alias.example".to_string(),
+                        host: "www.other".to_string(),
+                        class: DnsClass::IN,
+                        ttl: 3600,
+                    }],
+                )?
+                .into_iter()
+                .chain(signed(
+                    &other_zsk,
+                    vec![a("www.other", Ipv4Addr::new(192, 0, 2, 4))],
+                )?)
+                .collect(),
+                vec![],
+                vec![],
+            ),
+        );
+
+        let other_keys = vec![other_ksk.dnskey(3600), other_zsk.dnskey(3600)];
+        world.insert(
+            (OTHER, "other".to_string(), QueryType::DNSKEY),
+            packet(
+                ResultCode::NOERROR,
+                signed(&other_ksk, other_keys)?,
+                vec![],
+                vec![],
+            ),
+        );
+        world.insert(
+            (OTHER, "gone.other".to_string(), QueryType::A),
+            packet(
+                ResultCode::NXDOMAIN,
+                vec![],
+                signed(
+                    &other_zsk,
+                    vec![DnsRecord::NSEC {
+                        domain: "other".to_string(),
+                        next_domain: "ns.other".to_string(),
+                        types: vec![
+                            QueryType::NS,
+                            QueryType::RRSIG,
+                            QueryType::NSEC,
+                            QueryType::DNSKEY,
+                        ],
+                        class: DnsClass::IN,
+                        ttl: 3600,
+                    }],
+                )?,
+                vec![],
+            ),
+        );
+
+        world.insert(
+            (INSECURE, "x.insecure".to_string(), QueryType::A),
+            packet(
+                ResultCode::NOERROR,
+                vec![a("x.insecure", Ipv4Addr::new(192, 0, 2, 3))],
+                vec![],
+                vec![],
+            ),
+        );
+
+        let mut validator = Validator::new(vec![ds(&root_ksk)?]);
+        validator.now = Some(NOW);
+
+        Ok((world, validator))
+    }
+
+    fn resolve_in(
+        world: &World,
+        validator: &Validator,
+        qname: &str,
+    ) -> Result<(DnsPacket, Security), Box<dyn std::error::Error>> {
+        let mut lookup =
+            |qname: &str, qtype, server| -> Result<DnsPacket, Box<dyn std::error::Error>> {
+                world
+                    .get(&(server, name::normalize(qname), qtype))
+                    .cloned()
+                    .ok_or_else(|| format!("No answer for {} {}", qname, qtype).into())
+            };
+
+        resolve(qname, QueryType::A, ROOT, Some(validator), &mut lookup)
+    }
+
+    /// A delegation of `example` to `ns.example`, whose address the root server answers itself
+    fn unglued_world() -> World {
+        let mut world = World::new();
+        world.insert(
+            (ROOT, "www.example".to_string(), QueryType::A),
+            packet(
+                ResultCode::NOERROR,
+                vec![],
+                vec![ns("example", "ns.example")],
+                vec![],
+            ),
+        );
+        world.insert(
+            (ROOT, "ns.example".to_string(), QueryType::A),
+            packet(
+                ResultCode::NOERROR,
+                vec![a("ns.example", EXAMPLE)],
+                vec![],
+                vec![],
+            ),
+        );
+        world.insert(
+            (EXAMPLE, "www.example".to_string(), QueryType::A),
+            packet(
+                ResultCode::NOERROR,
+                vec![a("www.example", Ipv4Addr::new(192, 0, 2, 1))],
+                vec![],
+                vec![],
+            ),
+        );
+
+        world
+    }
+
+    fn resolve_traced_in(
+        world: &World,
+        trace: &mut Vec<TraceStep>,
+    ) -> Result<(DnsPacket, Security), Box<dyn std::error::Error>> {
+        let mut lookup =
+            |qname: &str, qtype, server| -> Result<DnsPacket, Box<dyn std::error::Error>> {
+                world
+                    .get(&(server, name::normalize(qname), qtype))
+                    .cloned()
+                    .ok_or_else(|| format!("No answer for {} {}", qname, qtype).into())
+            };
+
+        resolve_traced("www.example", QueryType::A, ROOT, None, &mut lookup, trace)
+    }
+
+    #[test]
+    fn resolve_traced_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let world = unglued_world();
+        let mut trace = Vec::new();
+
+        // act
+        resolve_traced_in(&world, &mut trace)?;
+
+        // assert
+        let actual: Vec<(usize, Ipv4Addr, &str)> = trace
+            .iter()
+            .map(|step| (step.depth, step.server, step.question.name.as_str()))
+            .collect();
+        assert_eq!(
+            vec![
+                (0, ROOT, "www.example"),
+                (1, ROOT, "ns.example"),
+                (0, EXAMPLE, "www.example"),
+            ],
+            actual
+        );
+        assert_eq!(
+            Some(Referral {
+                zone: "example".to_string(),
+                name_servers: vec!["ns.example".to_string()],
+                next: EXAMPLE,
+                glue: false,
+            }),
+            trace[0].referral
+        );
+        assert!(trace[1..].iter().all(|step| step.referral.is_none()));
+        assert!(trace
+            .iter()
+            .all(|step| step.response_code == Ok(ResultCode::NOERROR)));
+        assert!(trace[0]
+            .to_string()
+            .ends_with(", referral to example. (ns.example.) via 10.0.0.2"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_traced_error() {
+        // arrange
+        let mut world = unglued_world();
+        world.remove(&(EXAMPLE, "www.example".to_string(), QueryType::A));
+        let mut trace = Vec::new();
+
+        // act
+        let actual = resolve_traced_in(&world, &mut trace);
+
+        // assert
+        assert!(actual.is_err());
+        assert_eq!(3, trace.len());
+        assert_eq!(
+            Err("No answer for www.example A".to_string()),
+            trace[2].response_code
+        );
+        assert!(trace[2]
+            .to_string()
+            .starts_with("A www.example. @10.0.0.2 failed in "));
+    }
+
+    #[test]
+    fn resolve_store_error() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let (world, validator) = world()?;
+        let mut store = AnchorStore::seed(validator.anchors.clone(), NOW);
+        store.path = Some(PathBuf::from("/nonexistent/root-anchors"));
+        let mut validator = Validator::with_store(store);
+        validator.now = Some(NOW);
+
+        // act
+        let (_, security) = resolve_in(&world, &validator, "www.example")?;
+
+        // assert
+        assert_eq!(Security::Secure, security);
+        assert!(validator.take_store_error().is_some());
+        assert_eq!(None, validator.take_store_error());
+
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_secure_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let (world, validator) = world()?;
+
+        // act
+        let (response, security) = resolve_in(&world, &validator, "www.example")?;
+
+        // assert
+        assert_eq!(Security::Secure, security);
+        assert_eq!(Some(Ipv4Addr::new(192, 0, 2, 1)), response.get_random_a());
+
+        Ok(())
+    }
+
+    #[test]
+    fn next_step_ok() {
+        // arrange
+        let mut referral = DnsPacket::new();
+        referral.authorities.push(ns("example", "ns.example"));
+        let mut glued = referral.clone();
+        glued.resources.push(a("ns.example", EXAMPLE));
+        let mut answer = DnsPacket::new();
+        answer.answers.push(a("www.example", EXAMPLE));
+
+        // act
+        let resolve = next_step("www.example", &referral);
+        let ask = next_step("www.example", &glued);
+        let done = next_step("www.example", &answer);
+
+        // assert
+        assert_eq!(Step::Resolve("ns.example"), resolve);
+        assert_eq!(Step::Ask(EXAMPLE), ask);
+        assert_eq!(Step::Done, done);
+    }
+
+    #[test]
+    fn resolve_nxdomain_secure_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let (world, validator) = world()?;
+
+        // act
+        let (response, security) = resolve_in(&world, &validator, "nope.example")?;
+
+        // assert
+        assert_eq!(Security::Secure, security);
+        assert_eq!(ResultCode::NXDOMAIN, response.header.response_code);
+
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_insecure_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let (world, validator) = world()?;
+
+        // act
+        let (response, security) = resolve_in(&world, &validator, "x.insecure")?;
+
+        // assert
+        assert_eq!(Security::Insecure, security);
+        assert_eq!(Some(Ipv4Addr::new(192, 0, 2, 3)), response.get_random_a());
+
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_nsec_nxdomain_secure_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let (world, validator) = world()?;
+
+        // act
+        let (response, security) = resolve_in(&world, &validator, "gone.other")?;
+
+        // assert
+        assert_eq!(Security::Secure, security);
+        assert_eq!(ResultCode::NXDOMAIN, response.header.response_code);
+
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_cname_other_signer_secure_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let (world, validator) = world()?;
+
+        // act
+        let (response, security) = resolve_in(&world, &validator, "alias.example")?;
+
+        // assert
+        assert_eq!(Security::Secure, security);
+        assert_eq!(Some(Ipv4Addr::new(192, 0, 2, 4)), response.get_random_a());
+
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_cname_other_signer_bogus() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let (mut world, validator) = world()?;
+        let response = world
+            .get_mut(&(ROOT, "other".to_string(), QueryType::DNSKEY))
+            .ok_or("No answer")?;
+        response
+            .authorities
+            .retain(|record| matches!(record, DnsRecord::NS { .. }));
+
+        // act
+        let (_, security) = resolve_in(&world, &validator, "alias.example")?;
+
+        // assert
+        assert_eq!(
+            Security::Bogus("No trusted keys for the signer other.".to_string()),
+            security
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_root_keys_cached_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let (world, mut validator) = world()?;
+        let mut root_keys = 0;
+        let mut lookup =
+            |qname: &str, qtype, server| -> Result<DnsPacket, Box<dyn std::error::Error>> {
+                if server == ROOT && qtype == QueryType::DNSKEY {
+                    root_keys += 1;
+                }
+                world
+                    .get(&(server, name::normalize(qname), qtype))
+                    .cloned()
+                    .ok_or_else(|| format!("No answer for {} {}", qname, qtype).into())
+            };
+
+        // act
+        resolve(
+            "www.example",
+            QueryType::A,
+            ROOT,
+            Some(&validator),
+            &mut lookup,
+        )?;
+        resolve(
+            "www.example",
+            QueryType::A,
+            ROOT,
+            Some(&validator),
+            &mut lookup,
+        )?;
+        validator.now = Some(NOW + 3600);
+        let (_, security) = resolve(
+            "www.example",
+            QueryType::A,
+            ROOT,
+            Some(&validator),
+            &mut lookup,
+        )?;
+
+        // assert
+        assert_eq!(Security::Secure, security);
+        assert_eq!(2, root_keys);
+
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_tampered_bogus() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let (mut world, validator) = world()?;
+        let response = world
+            .get_mut(&(EXAMPLE, "www.example".to_string(), QueryType::A))
+            .ok_or("No answer")?;
+        if let Some(DnsRecord::A { addr, .. }) = response.answers.first_mut() {
+            *addr = Ipv4Addr::new(192, 0, 2, 66);
+        }
+
+        // act
+        let (_, security) = resolve_in(&world, &validator, "www.example")?;
+
+        // assert
+        assert_eq!(
+            Security::Bogus("RRSIG of www.example A does not verify".to_string()),
+            security
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_stripped_proof_bogus() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let (mut world, validator) = world()?;
+        let response = world
+            .get_mut(&(ROOT, "x.insecure".to_string(), QueryType::A))
+            .ok_or("No answer")?;
+        response
+            .authorities
+            .retain(|record| matches!(record, DnsRecord::NS { .. }));
+
+        // act
+        let (_, security) = resolve_in(&world, &validator, "x.insecure")?;
+
+        // assert
+        assert!(matches!(security, Security::Bogus(_)));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,261 @@
+//! Replaying the queries of a capture against a server, comparing its answers with the recorded
+//! ones
+
+use super::model::{dns_packet::DnsPacket, dns_record::DnsRecord};
+use super::pcap::{CapturedMessage, Protocol};
+use super::query::exchange;
+use std::net::Ipv4Addr;
+use std::time::{Duration, Instant};
+
+/// What became of the replayed queries, each described by its question
+#[derive(Debug, Default)]
+pub struct ReplayReport {
+    pub queries: usize,
+    /// Queries the server didn't answer, with why
+    pub failures: Vec<(String, String)>,
+    /// Queries answered differently than recorded, with how
+    pub mismatches: Vec<(String, String)>,
+}
+
+/// Send the queries captured to `port` to the server, over the protocol they were captured on,
+/// and compare the answers with the responses recorded for them. With `paced`, queries are sent
+/// at the times they were captured, relative to the first one; as they're sent one at a time,
+/// a slow answer delays the following queries.
+pub fn replay(
+    messages: &[CapturedMessage],
+    port: u16,
+    server: (Ipv4Addr, u16),
+    timeout: Duration,
+    paced: bool,
+) -> ReplayReport {
+    let mut report = ReplayReport::default();
+    let start = Instant::now();
+    let first = messages.first().map(|m| m.time).unwrap_or_default();
+
+    for (i, captured) in messages.iter().enumerate() {
+        if captured.destination.port() != port {
+            continue;
+        }
+        let query = match captured.packet() {
+            Ok(query) if !query.header.query_response => query,
+            _ => continue,
+        };
+        let question = query
+            .questions
+            .first()
+            .map(|q| q.to_string())
+            .unwrap_or_default();
+        report.queries += 1;
+
+        if paced {
+            let due = captured.time.saturating_sub(first);
+            std::thread::sleep(due.saturating_sub(start.elapsed()));
+        }
+
+        let tcp = captured.protocol == Protocol::Tcp;
+        let actual = match exchange(&captured.message, server, tcp, timeout) {
+            Ok((actual, _)) => actual,
+            Err(e) => {
+                report.failures.push((question, e.to_string()));
+                continue;
+            }
+        };
+
+        // NOTE: The recorded response is the next one between the same ends with the same id
+        let recorded = messages[i + 1..]
+            .iter()
+            .filter(|m| m.source == captured.destination && m.destination == captured.source)
+            .filter_map(|m| m.packet().ok())
+            .find(|r| r.header.query_response && r.header.id == query.header.id);
+        if let Some(difference) = recorded.and_then(|recorded| compare(&recorded, &actual)) {
+            report.mismatches.push((question, difference));
+        }
+    }
+
+    report
+}
+
+/// How the actual response differs from the recorded one, in its response code or answers,
+/// ignoring their TTLs and order
+pub fn compare(recorded: &DnsPacket, actual: &DnsPacket) -> Option<String> {
+    if recorded.header.response_code != actual.header.response_code {
+        return Some(format!(
+            "{:?} recorded, {:?} replayed",
+            recorded.header.response_code, actual.header.response_code
+        ));
+    }
+
+    let recorded_answers = answers(recorded);
+    let actual_answers = answers(actual);
+    if recorded_answers != actual_answers {
+        return Some(format!(
+            "answers differ, [{}] recorded, [{}] replayed",
+            recorded_answers.join(", "),
+            actual_answers.join(", ")
+        ));
+    }
+
+    None
+}
+
+/// The answers in presentation format without their TTL, sorted
+fn answers(packet: &DnsPacket) -> Vec<String> {
+    let mut answers: Vec<String> = packet
+        .answers
+        .iter()
+        .filter(|rec| !matches!(rec, DnsRecord::OPT { .. }))
+        .map(|rec| {
+            format!(
+                "{} {} {} {}",
+                fqdn(rec.domain()),
+                rec.class(),
+                rec.qtype(),
+                rec.rdata()
+            )
+        })
+        .collect();
+    answers.sort();
+
+    answers
+}
+
+/// Present a domain name as a fully qualified name, where the root is a single dot
+fn fqdn(name: &str) -> String {
+    if name.ends_with('.') {
+        name.to_string()
+    } else {
+        format!("{}.", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::byte_packet_buffer::BytePacketBuffer;
+    use super::super::model::dns_class::DnsClass;
+    use super::super::model::dns_question::DnsQuestion;
+    use super::super::model::query_type::QueryType;
+    use super::super::model::result_code::ResultCode;
+    use super::super::pcap::{CapturedMessage, Protocol};
+    use super::{compare, replay, DnsPacket, DnsRecord};
+    use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+    use std::time::Duration;
+
+    fn packet(id: u16, qname: &str, addr: Option<Ipv4Addr>) -> DnsPacket {
+        let mut packet = DnsPacket::new();
+        packet.header.id = id;
+        packet.header.query_response = addr.is_some();
+        packet
+            .questions
+            .push(DnsQuestion::new(qname.to_string(), QueryType::A));
+        if let Some(addr) = addr {
+            packet.answers.push(DnsRecord::A {
+                domain: qname.to_string(),
+                addr,
+                class: DnsClass::IN,
+                ttl: 300,
+            });
+        }
+
+        packet
+    }
+
+    fn bytes(mut packet: DnsPacket) -> Vec<u8> {
+        let mut buffer = BytePacketBuffer::new();
+        packet.write(&mut buffer).unwrap();
+
+        buffer.buf[..buffer.pos()].to_vec()
+    }
+
+    #[test]
+    fn replay_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let socket = UdpSocket::bind(("127.0.0.1", 0))?;
+        let port = socket.local_addr()?.port();
+        let server = std::thread::spawn(move || {
+            for _ in 0..2 {
+                let mut buffer = BytePacketBuffer::new();
+                let (_, client) = socket.recv_from(&mut buffer.buf).unwrap();
+                let query = DnsPacket::from_buffer(&mut buffer).unwrap();
+                let response = packet(
+                    query.header.id,
+                    &query.questions[0].name,
+                    Some(Ipv4Addr::new(192, 0, 2, 1)),
+                );
+                socket.send_to(&bytes(response), client).unwrap();
+            }
+        });
+
+        let client: SocketAddr = "192.0.2.10:40000".parse()?;
+        let recorded: SocketAddr = "192.0.2.53:53".parse()?;
+        let message = |time: u64, from, to, packet| CapturedMessage {
+            time: Duration::from_millis(time),
+            source: from,
+            destination: to,
+            protocol: Protocol::Udp,
+            message: bytes(packet),
+        };
+        let messages = vec![
+            message(0, client, recorded, packet(1, "a.example", None)),
+            message(1, client, recorded, packet(2, "b.example", None)),
+            message(
+                2,
+                recorded,
+                client,
+                packet(2, "b.example", Some(Ipv4Addr::new(192, 0, 2, 2))),
+            ),
+            message(
+                3,
+                recorded,
+                client,
+                packet(1, "a.example", Some(Ipv4Addr::new(192, 0, 2, 1))),
+            ),
+        ];
+
+        // act
+        let actual = replay(
+            &messages,
+            53,
+            (Ipv4Addr::LOCALHOST, port),
+            Duration::from_secs(5),
+            true,
+        );
+        server.join().map_err(|_| "The server failed")?;
+
+        // assert
+        assert_eq!(2, actual.queries);
+        assert!(actual.failures.is_empty());
+        assert_eq!(
+            vec![(
+                "b.example. IN A".to_string(),
+                "answers differ, [b.example. IN A 192.0.2.2] recorded, \
+                 [b.example. IN A 192.0.2.1] replayed"
+                    .to_string()
+            )],
+            actual.mismatches
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn compare_ok() {
+        // arrange
+        let recorded = packet(1, "a.example", Some(Ipv4Addr::new(192, 0, 2, 1)));
+        let mut same = recorded.clone();
+        if let Some(DnsRecord::A { ttl, .. }) = same.answers.first_mut() {
+            *ttl = 60;
+        }
+        let mut failed = recorded.clone();
+        failed.header.response_code = ResultCode::SERVFAIL;
+
+        // act
+        let actual = [compare(&recorded, &same), compare(&recorded, &failed)];
+
+        // assert
+        assert_eq!(None, actual[0]);
+        assert_eq!(
+            Some("NOERROR recorded, SERVFAIL replayed".to_string()),
+            actual[1]
+        );
+    }
+}
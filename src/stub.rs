@@ -0,0 +1,486 @@
+//! A stub resolver, as used by client tools: it asks the recursive servers of `/etc/resolv.conf`
+//! to resolve names for it, expanding them with the search list as the C library does.
+
+use super::byte_packet_buffer::BytePacketBuffer;
+use super::model::{
+    dns_packet::DnsPacket, dns_question::DnsQuestion, dns_record::DnsRecord, query_type::QueryType,
+    result_code::ResultCode,
+};
+use super::query::{exchange, query_packet};
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::Duration;
+
+/// Where the C library reads its resolver settings from
+pub const RESOLV_CONF: &str = "/etc/resolv.conf";
+
+/// The C library asks no more than this many name servers (`MAXNS`)
+const MAX_NAMESERVERS: usize = 3;
+
+/// Resolver settings, in the format of `resolv.conf(5)`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvConf {
+    /// The servers to ask, in order, the local one when none is given
+    pub nameservers: Vec<IpAddr>,
+    /// Domains appended to names with fewer dots than `ndots`, from `search` or `domain`
+    pub search: Vec<String>,
+    /// Names with at least as many dots are tried as they are before the search list
+    pub ndots: usize,
+    /// How long to wait for each server
+    pub timeout: Duration,
+    /// How many rounds of asking every server
+    pub attempts: usize,
+    /// Spread the queries over the servers instead of always starting with the first one
+    pub rotate: bool,
+    /// Advertise EDNS (RFC 6891), allowing responses larger than 512 bytes over UDP
+    pub edns0: bool,
+}
+
+impl Default for ResolvConf {
+    fn default() -> Self {
+        ResolvConf {
+            nameservers: vec![Ipv4Addr::LOCALHOST.into()],
+            search: Vec::new(),
+            ndots: 1,
+            timeout: Duration::from_secs(5),
+            attempts: 2,
+            rotate: false,
+            edns0: false,
+        }
+    }
+}
+
+impl ResolvConf {
+    /// Parse the settings as the C library does: lines it doesn't understand, such as unknown
+    /// options or addresses that don't parse, are skipped, and values are capped at its limits
+    pub fn parse(text: &str) -> Self {
+        let mut conf = ResolvConf {
+            nameservers: Vec::new(),
+            ..Default::default()
+        };
+
+        for line in text.lines() {
+            if line.starts_with(['#', ';']) {
+                continue;
+            }
+            let mut words = line.split_whitespace();
+
+            match words.next() {
+                Some("nameserver") => {
+                    let addr = words.next().and_then(|addr| addr.parse().ok());
+                    if let Some(addr) = addr.filter(|_| conf.nameservers.len() < MAX_NAMESERVERS) {
+                        conf.nameservers.push(addr);
+                    }
+                }
+                // NOTE: `domain` and `search` replace each other, the last one wins
+                Some("domain") => conf.search = words.next().map(domain).into_iter().collect(),
+                Some("search") => conf.search = words.map(domain).collect(),
+                Some("options") => {
+                    for option in words {
+                        conf.set_option(option);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if conf.nameservers.is_empty() {
+            conf.nameservers = ResolvConf::default().nameservers;
+        }
+
+        conf
+    }
+
+    /// Read the settings of the system
+    pub fn system() -> Result<Self, Box<dyn std::error::Error>> {
+        let text = std::fs::read_to_string(RESOLV_CONF)
+            .map_err(|e| format!("Failed to read {}: {}", RESOLV_CONF, e))?;
+
+        Ok(ResolvConf::parse(&text))
+    }
+
+    fn set_option(&mut self, option: &str) {
+        let (name, value) = match option.split_once(':') {
+            Some((name, value)) => (name, value.parse::<u64>().ok()),
+            None => (option, None),
+        };
+
+        match (name, value) {
+            ("ndots", Some(ndots)) => self.ndots = ndots.min(15) as usize,
+            ("timeout", Some(timeout)) => self.timeout = Duration::from_secs(timeout.clamp(1, 30)),
+            ("attempts", Some(attempts)) => self.attempts = attempts.clamp(1, 5) as usize,
+            ("rotate", None) => self.rotate = true,
+            ("edns0", None) => self.edns0 = true,
+            _ => {}
+        }
+    }
+
+    /// The names to try for the name, in order: an absolute name (with a trailing dot) as it is
+    /// only, otherwise the name as it is before or after it is expanded with the search list,
+    /// depending on whether it has at least `ndots` dots
+    pub fn candidates(&self, name: &str) -> Vec<String> {
+        if let Some(absolute) = name.strip_suffix('.') {
+            return vec![absolute.to_string()];
+        }
+
+        let expanded = self
+            .search
+            .iter()
+            .map(|domain| format!("{}.{}", name, domain));
+        if name.matches('.').count() >= self.ndots {
+            std::iter::once(name.to_string()).chain(expanded).collect()
+        } else {
+            expanded.chain(std::iter::once(name.to_string())).collect()
+        }
+    }
+}
+
+/// The name of a search domain, without its trailing dot
+fn domain(name: &str) -> String {
+    name.trim_end_matches('.').to_string()
+}
+
+/// Asks the servers of its settings for names, as a client of recursive servers
+#[derive(Debug, Clone)]
+pub struct StubResolver {
+    pub conf: ResolvConf,
+    /// Port the servers are asked on
+    pub port: u16,
+    /// The server asked first by the next query, when rotating
+    next: usize,
+}
+
+impl StubResolver {
+    pub fn new(conf: ResolvConf) -> Self {
+        StubResolver {
+            conf,
+            port: 53,
+            next: 0,
+        }
+    }
+
+    /// With the settings of the system
+    pub fn system() -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(StubResolver::new(ResolvConf::system()?))
+    }
+
+    /// The addresses of the name, both IPv4 and IPv6, none if it doesn't exist
+    pub fn lookup_ip(&mut self, name: &str) -> Result<Vec<IpAddr>, Box<dyn std::error::Error>> {
+        let mut addrs = self.lookup_ipv4(name)?;
+        addrs.extend(self.lookup_ipv6(name)?);
+
+        Ok(addrs)
+    }
+
+    pub fn lookup_ipv4(&mut self, name: &str) -> Result<Vec<IpAddr>, Box<dyn std::error::Error>> {
+        Ok(addresses(&self.query(name, QueryType::A)?))
+    }
+
+    pub fn lookup_ipv6(&mut self, name: &str) -> Result<Vec<IpAddr>, Box<dyn std::error::Error>> {
+        Ok(addresses(&self.query(name, QueryType::AAAA)?))
+    }
+
+    /// Ask for the name, trying each of its candidates in turn until one has records of the
+    /// type. Without such records, the response is that of a candidate which exists without
+    /// them, if any, as the C library does, then that of one which doesn't exist, and otherwise
+    /// why the last one failed.
+    pub fn query(
+        &mut self,
+        name: &str,
+        qtype: QueryType,
+    ) -> Result<DnsPacket, Box<dyn std::error::Error>> {
+        let mut nodata = None;
+        let mut negative = None;
+        let mut failure = Err(format!("No name to try for {}", name).into());
+
+        for candidate in self.conf.candidates(name) {
+            let result = self.query_servers(&candidate, qtype);
+            match &result {
+                Ok(response) if response.header.response_code == ResultCode::NOERROR => {
+                    if !response.answers.is_empty() {
+                        return result;
+                    }
+                    nodata = nodata.or(Some(result));
+                }
+                Ok(_) => negative = negative.or(Some(result)),
+                Err(_) => failure = result,
+            }
+        }
+
+        nodata.or(negative).unwrap_or(failure)
+    }
+
+    /// Ask the servers for the name, for the rounds of the settings, until one of them answers
+    /// without failing. Truncated responses are asked for again over TCP.
+    fn query_servers(
+        &mut self,
+        qname: &str,
+        qtype: QueryType,
+    ) -> Result<DnsPacket, Box<dyn std::error::Error>> {
+        let mut query = query_packet(&DnsQuestion::new(qname.to_string(), qtype), true, false);
+        if !self.conf.edns0 {
+            query.resources.clear();
+        }
+        let mut buffer = BytePacketBuffer::new();
+        query.write(&mut buffer)?;
+        let request = &buffer.buf[..buffer.pos()];
+
+        let servers = self.conf.nameservers.len();
+        let first = self.next;
+        if self.conf.rotate && servers > 0 {
+            self.next = (self.next + 1) % servers;
+        }
+
+        let mut last = Err("There are no name servers".into());
+        for _ in 0..self.conf.attempts {
+            for i in 0..servers {
+                let server = (self.conf.nameservers[(first + i) % servers], self.port);
+                let mut result = exchange(request, server, false, self.conf.timeout);
+                if matches!(&result, Ok((response, _)) if response.header.truncated_message) {
+                    result = exchange(request, server, true, self.conf.timeout);
+                }
+
+                match result {
+                    Ok((response, _))
+                        if !matches!(
+                            response.header.response_code,
+                            ResultCode::SERVFAIL | ResultCode::REFUSED | ResultCode::NOTIMP
+                        ) =>
+                    {
+                        return Ok(response);
+                    }
+                    Ok((response, _)) => {
+                        last = Err(format!(
+                            "{} answered {:?}",
+                            server.0, response.header.response_code
+                        )
+                        .into())
+                    }
+                    Err(e) => last = Err(format!("{} failed: {}", server.0, e).into()),
+                }
+            }
+        }
+
+        last
+    }
+}
+
+/// The addresses in the answers of the response, those of the name it is an alias of included
+fn addresses(response: &DnsPacket) -> Vec<IpAddr> {
+    response
+        .answers
+        .iter()
+        .filter_map(|record| match record {
+            DnsRecord::A { addr, .. } => Some(IpAddr::from(*addr)),
+            DnsRecord::AAAA { addr, .. } => Some(IpAddr::from(*addr)),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ResolvConf;
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+    use std::time::Duration;
+
+    #[test]
+    fn parse_ok() {
+        // arrange
+        let text = "\
+# Generated by NetworkManager
+domain example.test
+search corp.test. lab.corp.test
+nameserver 192.0.2.53
+nameserver not-an-address
+nameserver 2001:db8::53
+; a comment
+nameserver 192.0.2.54
+nameserver 192.0.2.55
+options ndots:2 timeout:1 attempts:9 rotate edns0 inet6 ndots:x
+sortlist 130.155.160.0/255.255.240.0
+";
+
+        // act
+        let actual = ResolvConf::parse(text);
+
+        // assert
+        assert_eq!(
+            ResolvConf {
+                nameservers: vec![
+                    IpAddr::V4(Ipv4Addr::new(192, 0, 2, 53)),
+                    IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0x53)),
+                    IpAddr::V4(Ipv4Addr::new(192, 0, 2, 54)),
+                ],
+                search: vec!["corp.test".to_string(), "lab.corp.test".to_string()],
+                ndots: 2,
+                timeout: Duration::from_secs(1),
+                attempts: 5,
+                rotate: true,
+                edns0: true,
+            },
+            actual
+        );
+        assert_eq!(ResolvConf::default(), ResolvConf::parse(""));
+    }
+
+    #[test]
+    fn candidates_ok() {
+        // arrange
+        let conf = ResolvConf::parse("search corp.test lab.test\noptions ndots:2");
+
+        // act
+        let short = conf.candidates("www");
+        let dotted = conf.candidates("www.lab.test");
+        let absolute = conf.candidates("www.");
+
+        // assert
+        assert_eq!(vec!["www.corp.test", "www.lab.test", "www"], short);
+        assert_eq!(
+            vec![
+                "www.lab.test",
+                "www.lab.test.corp.test",
+                "www.lab.test.lab.test"
+            ],
+            dotted
+        );
+        assert_eq!(vec!["www"], absolute);
+    }
+
+    /// Tests against the mock servers, which start on Linux only
+    #[cfg(target_os = "linux")]
+    mod loopback {
+        use super::super::super::mock::fixture::{leaf_zone, BACKUP, LEAF, SLOW_DELAY};
+        use super::super::super::mock::{Behavior, MockNetwork, MockServer};
+        use super::super::{QueryType, ResolvConf, ResultCode, StubResolver};
+        use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+        use std::time::Duration;
+
+        /// The leaf server and its backup, both serving `example.test`, the first answering as
+        /// given
+        fn network(first: Behavior) -> Result<MockNetwork, Box<dyn std::error::Error>> {
+            let zone = leaf_zone("127.0.53.21");
+
+            MockNetwork::start(vec![
+                MockServer::new(LEAF, "example.test", &zone).behaving(first),
+                MockServer::new(BACKUP, "example.test", &zone),
+            ])
+        }
+
+        fn stub(network: &MockNetwork, conf: &str) -> StubResolver {
+            let mut stub = StubResolver::new(ResolvConf::parse(conf));
+            stub.port = network.port;
+            stub.conf.timeout = Duration::from_millis(100);
+
+            stub
+        }
+
+        #[test]
+        fn lookup_ip_ok() -> Result<(), Box<dyn std::error::Error>> {
+            // arrange
+            let network = network(Behavior::Authoritative)?;
+            let mut stub = stub(&network, "nameserver 127.0.53.21\nsearch example.test");
+
+            // act
+            let www = stub.lookup_ip("www")?;
+            let alias = stub.lookup_ipv4("alias.example.test")?;
+            let dotted = stub.lookup_ipv4("www.lab")?;
+            let nodata = stub.query("mail", QueryType::A)?;
+            let nxdomain = stub.query("nope", QueryType::A)?;
+
+            // assert
+            assert_eq!(
+                vec![
+                    IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)),
+                    IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1)),
+                ],
+                www
+            );
+            assert_eq!(vec![IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1))], alias);
+            assert_eq!(vec![IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2))], dotted);
+            assert_eq!(ResultCode::NOERROR, nodata.header.response_code);
+            assert_eq!("mail.example.test", nodata.questions[0].name);
+            assert_eq!(ResultCode::NXDOMAIN, nxdomain.header.response_code);
+            assert_eq!(
+                vec![
+                    (LEAF, "www.example.test. IN A".to_string()),
+                    (LEAF, "www.example.test. IN AAAA".to_string()),
+                    (LEAF, "alias.example.test. IN A".to_string()),
+                    (LEAF, "www.lab. IN A".to_string()),
+                    (LEAF, "www.lab. IN A".to_string()),
+                    (LEAF, "www.lab.example.test. IN A".to_string()),
+                ],
+                network.queries()[..6]
+            );
+
+            Ok(())
+        }
+
+        #[test]
+        fn query_failover_ok() -> Result<(), Box<dyn std::error::Error>> {
+            // arrange
+            let network = network(Behavior::Lame)?;
+            let conf = "nameserver 127.0.53.21\nnameserver 127.0.53.25\noptions attempts:1";
+            let mut stub = stub(&network, conf);
+
+            // act
+            let www = stub.lookup_ipv4("www.example.test")?;
+
+            // assert
+            assert_eq!(vec![IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1))], www);
+            assert_eq!(
+                vec![
+                    (LEAF, "www.example.test. IN A".to_string()),
+                    (BACKUP, "www.example.test. IN A".to_string()),
+                ],
+                network.queries()
+            );
+
+            Ok(())
+        }
+
+        #[test]
+        fn query_rotate_ok() -> Result<(), Box<dyn std::error::Error>> {
+            // arrange
+            let network = network(Behavior::Authoritative)?;
+            let conf = "nameserver 127.0.53.21\nnameserver 127.0.53.25\noptions rotate";
+            let mut stub = stub(&network, conf);
+
+            // act
+            for _ in 0..3 {
+                stub.lookup_ipv4("www.example.test")?;
+            }
+
+            // assert
+            assert_eq!(
+                vec![LEAF, BACKUP, LEAF],
+                network
+                    .queries()
+                    .iter()
+                    .map(|(server, _)| *server)
+                    .collect::<Vec<_>>()
+            );
+
+            Ok(())
+        }
+
+        #[test]
+        fn query_error() -> Result<(), Box<dyn std::error::Error>> {
+            // arrange
+            let network = network(Behavior::Slow(SLOW_DELAY))?;
+            let conf = "nameserver 127.0.53.21\noptions attempts:2";
+            let mut stub = stub(&network, conf);
+
+            // act
+            let actual = stub.query("www.example.test.", QueryType::A);
+
+            // assert
+            assert_eq!(
+                Some(true),
+                actual
+                    .err()
+                    .map(|e| e.to_string().starts_with("127.0.53.21 failed"))
+            );
+            Ok(())
+        }
+    }
+}
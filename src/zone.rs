@@ -0,0 +1,552 @@
+//! Zones served authoritatively: read from zone files (RFC 1035 §5.1) and answered from memory,
+//! along with the signatures and denial proofs of signed zones for clients asking for them
+
+use super::dnssec::{denial, name};
+use super::encoding::{from_base32hex, to_base32hex};
+use super::model::presentation::{self, absolute, entries, parse_ttl, Token};
+use super::model::{
+    dns_packet::DnsPacket, dns_record::DnsRecord, query_type::QueryType, result_code::ResultCode,
+};
+use std::cmp::Ordering;
+use std::fmt;
+
+/// CNAME records followed inside a zone before giving up, so a loop can't hang us
+const MAX_CNAME_CHAIN: usize = 8;
+
+/// The records of a zone, all owned by its origin or names below it
+#[derive(Debug, Clone, Default)]
+pub struct Zone {
+    pub origin: String,
+    pub records: Vec<DnsRecord>,
+}
+
+impl Zone {
+    /// Parse a zone file, relative names being completed with `origin` unless `$ORIGIN` changes
+    /// it. Record types without a presentation format here can use the generic one of RFC 3597,
+    /// e.g. `TYPE62347 \# 4 0a000001`.
+    pub fn parse(text: &str, origin: &str) -> Result<Zone, Box<dyn std::error::Error>> {
+        let mut zone = Zone {
+            origin: name::normalize(origin),
+            records: Vec::new(),
+        };
+        let mut current_origin = zone.origin.clone();
+        let mut default_ttl = None;
+        let mut last_owner = None;
+        let mut last_ttl = None;
+
+        for (owner_given, tokens) in entries(text)? {
+            let word = |i: usize| -> Result<String, Box<dyn std::error::Error>> {
+                tokens
+                    .get(i)
+                    .map(Token::text)
+                    .ok_or_else(|| "Missing value in zone file directive".into())
+            };
+
+            match word(0)?.to_uppercase().as_str() {
+                "$ORIGIN" => {
+                    current_origin = absolute(&word(1)?, &current_origin);
+                    continue;
+                }
+                "$TTL" => {
+                    default_ttl = Some(parse_ttl(&word(1)?)?);
+                    continue;
+                }
+                directive if directive.starts_with('$') => {
+                    return Err(format!("Unsupported zone file directive {}", directive).into())
+                }
+                _ => {}
+            }
+
+            let mut rest = &tokens[..];
+            let owner = if owner_given {
+                let owner = absolute(&rest[0].text(), &current_origin);
+                rest = &rest[1..];
+                owner
+            } else {
+                last_owner
+                    .clone()
+                    .ok_or("No owner name for the first record")?
+            };
+
+            let record = presentation::parse_record(
+                &owner,
+                rest,
+                default_ttl.or(last_ttl),
+                &current_origin,
+            )?;
+            if !name::is_subdomain(&owner, &zone.origin) {
+                return Err(format!("{} is outside of the zone {}", owner, zone.origin).into());
+            }
+
+            last_ttl = Some(record.ttl());
+            zone.records.push(record);
+            last_owner = Some(owner);
+        }
+
+        Ok(zone)
+    }
+
+    /// Whether the name is in the zone, or below one of its delegations
+    pub fn contains(&self, qname: &str) -> bool {
+        name::is_subdomain(qname, &self.origin)
+    }
+
+    /// The SOA record at the apex of the zone
+    pub fn soa(&self) -> Option<&DnsRecord> {
+        self.records.iter().find(|record| {
+            record.qtype() == QueryType::SOA && name::normalize(record.domain()) == self.origin
+        })
+    }
+
+    /// Answer a question about a name of the zone: with the records asked for, a referral to a
+    /// child zone, or a negative answer. With `dnssec_ok`, the RRSIG records and the NSEC or NSEC3
+    /// proofs of a signed zone come along (RFC 4035 §3.1).
+    pub fn answer(&self, qname: &str, qtype: QueryType, dnssec_ok: bool) -> DnsPacket {
+        let mut packet = DnsPacket::new();
+        packet.header.authoritative_answer = true;
+        let qname = name::normalize(qname);
+
+        // NOTE: The DS records of a child zone are answered by its parent
+        if let Some(cut) = self.delegation(&qname) {
+            if qtype != QueryType::DS || cut != qname {
+                self.referral(&cut, dnssec_ok, &mut packet);
+                return packet;
+            }
+        }
+
+        let mut target = qname;
+        for _ in 0..MAX_CNAME_CHAIN {
+            if !self.rrset(&target, qtype).is_empty() {
+                self.add(&mut packet.answers, &target, qtype, dnssec_ok);
+                return packet;
+            }
+
+            let cname = self.rrset(&target, QueryType::CNAME);
+            if let (Some(DnsRecord::CNAME { host, .. }), false) =
+                (cname.first(), qtype == QueryType::CNAME)
+            {
+                self.add(&mut packet.answers, &target, QueryType::CNAME, dnssec_ok);
+                target = name::normalize(host);
+                if self.contains(&target) && self.delegation(&target).is_none() {
+                    continue;
+                }
+                return packet;
+            }
+
+            if self.exists(&target) {
+                self.negative(&mut packet, dnssec_ok, self.nodata_proof(&target));
+                return packet;
+            }
+
+            // Names which don't exist may be synthesized from a wildcard (RFC 4592)
+            let encloser = self.closest_encloser(&target);
+            let wildcard = wildcard_of(&encloser);
+            if self.exists(&wildcard) {
+                let mut records = self.rrset(&wildcard, qtype);
+                if records.is_empty() {
+                    let mut proof = self.wildcard_proof(&target, &encloser);
+                    proof.extend(self.nodata_proof(&wildcard));
+                    self.negative(&mut packet, dnssec_ok, proof);
+                    return packet;
+                }
+
+                if dnssec_ok {
+                    records.extend(self.rrsigs(&wildcard, qtype));
+                    packet
+                        .authorities
+                        .extend(self.with_rrsigs(self.wildcard_proof(&target, &encloser)));
+                }
+                for mut record in records {
+                    *record.domain_mut() = target.clone();
+                    packet.answers.push(record);
+                }
+                return packet;
+            }
+
+            packet.header.response_code = ResultCode::NXDOMAIN;
+            self.negative(
+                &mut packet,
+                dnssec_ok,
+                self.nxdomain_proof(&target, &encloser),
+            );
+            return packet;
+        }
+
+        packet
+    }
+
+    /// The records of the zone owned by the name, of the type
+    fn rrset(&self, owner: &str, qtype: QueryType) -> Vec<DnsRecord> {
+        self.records
+            .iter()
+            .filter(|record| record.qtype() == qtype && name::normalize(record.domain()) == owner)
+            .cloned()
+            .collect()
+    }
+
+    /// The RRSIG records covering the record set of the name and the type
+    fn rrsigs(&self, owner: &str, qtype: QueryType) -> Vec<DnsRecord> {
+        self.records
+            .iter()
+            .filter(|record| match record {
+                DnsRecord::RRSIG {
+                    domain,
+                    type_covered,
+                    ..
+                } => *type_covered == qtype && name::normalize(domain) == owner,
+                _ => false,
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Add a record set to a section, with its RRSIG records when asked for
+    fn add(&self, section: &mut Vec<DnsRecord>, owner: &str, qtype: QueryType, dnssec_ok: bool) {
+        section.extend(self.rrset(owner, qtype));
+        if dnssec_ok {
+            section.extend(self.rrsigs(owner, qtype));
+        }
+    }
+
+    /// Whether the name owns records, or is an empty non-terminal with records below it
+    fn exists(&self, owner: &str) -> bool {
+        self.records.iter().any(|record| {
+            !matches!(record, DnsRecord::NSEC3 { .. } | DnsRecord::RRSIG { .. })
+                && name::is_subdomain(record.domain(), owner)
+        })
+    }
+
+    /// The closest existing ancestor of a name (RFC 4592 §3.3.1)
+    fn closest_encloser(&self, qname: &str) -> String {
+        let count = name::labels(qname).len();
+        (name::labels(&self.origin).len()..count)
+            .rev()
+            .map(|count| name::ancestor(qname, count))
+            .find(|ancestor| self.exists(ancestor))
+            .unwrap_or_else(|| self.origin.clone())
+    }
+
+    /// The delegation point at or above the name, if it is below the apex
+    fn delegation(&self, qname: &str) -> Option<String> {
+        let start = name::labels(&self.origin).len() + 1;
+
+        (start..=name::labels(qname).len())
+            .map(|count| name::ancestor(qname, count))
+            .find(|ancestor| !self.rrset(ancestor, QueryType::NS).is_empty())
+    }
+
+    /// Refer the client to the name servers of the child zone, with the glue records we have
+    /// and, for signed zones, the DS records or the proof there are none
+    fn referral(&self, cut: &str, dnssec_ok: bool, packet: &mut DnsPacket) {
+        packet.header.authoritative_answer = false;
+
+        let ns = self.rrset(cut, QueryType::NS);
+        packet.authorities.extend(ns.iter().cloned());
+        if dnssec_ok {
+            if self.rrset(cut, QueryType::DS).is_empty() {
+                packet
+                    .authorities
+                    .extend(self.with_rrsigs(self.nodata_proof(cut)));
+            } else {
+                self.add(&mut packet.authorities, cut, QueryType::DS, true);
+            }
+        }
+
+        for record in &ns {
+            if let DnsRecord::NS { host, .. } = record {
+                let host = name::normalize(host);
+                self.add(&mut packet.resources, &host, QueryType::A, false);
+                self.add(&mut packet.resources, &host, QueryType::AAAA, false);
+            }
+        }
+    }
+
+    /// Fill in a negative answer: the SOA record, which tells how long to cache it, and the
+    /// proof of denial of signed zones
+    fn negative(&self, packet: &mut DnsPacket, dnssec_ok: bool, proof: Vec<DnsRecord>) {
+        self.add(
+            &mut packet.authorities,
+            &self.origin,
+            QueryType::SOA,
+            dnssec_ok,
+        );
+        if dnssec_ok {
+            packet.authorities.extend(self.with_rrsigs(proof));
+        }
+    }
+
+    /// The records along with their RRSIG records, each once
+    fn with_rrsigs(&self, records: Vec<DnsRecord>) -> Vec<DnsRecord> {
+        let mut result: Vec<DnsRecord> = Vec::new();
+
+        for record in records {
+            if result.contains(&record) {
+                continue;
+            }
+            let rrsigs = self.rrsigs(&name::normalize(record.domain()), record.qtype());
+            result.push(record);
+            result.extend(rrsigs);
+        }
+
+        result
+    }
+
+    /// The proof that the name exists without the asked type: the NSEC or NSEC3 record of the
+    /// name, or for NSEC3 opt-out delegations the closest encloser proof (RFC 5155 §7.2.4)
+    fn nodata_proof(&self, qname: &str) -> Vec<DnsRecord> {
+        if self.nsec3_params().is_none() {
+            return self.nsec_for(qname).into_iter().collect();
+        }
+
+        match self.nsec3_matching(qname) {
+            Some(nsec3) => vec![nsec3],
+            None => self.nsec3_closest_encloser_proof(qname).0,
+        }
+    }
+
+    /// The proof that the name doesn't exist, nor a wildcard which could have answered it
+    fn nxdomain_proof(&self, qname: &str, encloser: &str) -> Vec<DnsRecord> {
+        if self.nsec3_params().is_none() {
+            return self
+                .nsec_for(qname)
+                .into_iter()
+                .chain(self.nsec_for(&wildcard_of(encloser)))
+                .collect();
+        }
+
+        let (mut proof, encloser) = self.nsec3_closest_encloser_proof(qname);
+        proof.extend(self.nsec3_covering(&wildcard_of(&encloser)));
+
+        proof
+    }
+
+    /// The proof that no name closer than the wildcard could have answered the question
+    fn wildcard_proof(&self, qname: &str, encloser: &str) -> Vec<DnsRecord> {
+        if self.nsec3_params().is_none() {
+            return self.nsec_for(qname).into_iter().collect();
+        }
+
+        let next_closer = name::ancestor(qname, name::labels(encloser).len() + 1);
+        self.nsec3_covering(&next_closer).into_iter().collect()
+    }
+
+    /// The NSEC record owned by the name, or else the one covering it
+    fn nsec_for(&self, qname: &str) -> Option<DnsRecord> {
+        let mut nsecs: Vec<&DnsRecord> = self
+            .records
+            .iter()
+            .filter(|record| matches!(record, DnsRecord::NSEC { .. }))
+            .collect();
+        nsecs.sort_by(|a, b| name::canonical_cmp(a.domain(), b.domain()));
+
+        // NOTE: The last NSEC record of the chain covers the names after it
+        nsecs
+            .into_iter()
+            .rev()
+            .find(|nsec| name::canonical_cmp(nsec.domain(), qname) != Ordering::Greater)
+            .cloned()
+    }
+
+    /// The salt and iterations of the NSEC3 chain of the zone, if it has one
+    fn nsec3_params(&self) -> Option<(Vec<u8>, u16)> {
+        self.records.iter().find_map(|record| match record {
+            DnsRecord::NSEC3PARAM {
+                salt, iterations, ..
+            }
+            | DnsRecord::NSEC3 {
+                salt, iterations, ..
+            } => Some((salt.clone(), *iterations)),
+            _ => None,
+        })
+    }
+
+    /// The hash of the name with the parameters of the NSEC3 chain
+    fn nsec3_hash(&self, qname: &str) -> Option<Vec<u8>> {
+        let (salt, iterations) = self.nsec3_params()?;
+        denial::nsec3_hash(qname, &salt, iterations).ok()
+    }
+
+    /// The NSEC3 records of the zone, along with the hash of their owner names
+    fn nsec3s(&self) -> Vec<(Vec<u8>, &DnsRecord)> {
+        self.records
+            .iter()
+            .filter_map(|record| match record {
+                DnsRecord::NSEC3 { domain, .. } => {
+                    let label = name::labels(domain).first()?.to_string();
+                    Some((from_base32hex(&label).ok()?, record))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn nsec3_matching(&self, qname: &str) -> Option<DnsRecord> {
+        let hash = self.nsec3_hash(qname)?;
+        self.nsec3s()
+            .into_iter()
+            .find(|(owner, _)| *owner == hash)
+            .map(|(_, record)| record.clone())
+    }
+
+    fn nsec3_covering(&self, qname: &str) -> Option<DnsRecord> {
+        let hash = self.nsec3_hash(qname)?;
+        self.nsec3s()
+            .into_iter()
+            .find(|(owner, record)| match record {
+                DnsRecord::NSEC3 {
+                    next_hashed_owner, ..
+                } => {
+                    // NOTE: The last record of the chain wraps around to the first hash
+                    if owner < next_hashed_owner {
+                        *owner < hash && hash < *next_hashed_owner
+                    } else {
+                        hash > *owner || hash < *next_hashed_owner
+                    }
+                }
+                _ => false,
+            })
+            .map(|(_, record)| record.clone())
+    }
+
+    /// The closest encloser proof (RFC 5155 §7.2.1): the NSEC3 record of the closest ancestor
+    /// in the chain and the one covering the next closer name, with that ancestor
+    fn nsec3_closest_encloser_proof(&self, qname: &str) -> (Vec<DnsRecord>, String) {
+        let count = name::labels(qname).len();
+
+        for ancestor_count in (name::labels(&self.origin).len()..count).rev() {
+            let encloser = name::ancestor(qname, ancestor_count);
+            if let Some(matching) = self.nsec3_matching(&encloser) {
+                let next_closer = name::ancestor(qname, ancestor_count + 1);
+                let proof = std::iter::once(matching)
+                    .chain(self.nsec3_covering(&next_closer))
+                    .collect();
+                return (proof, encloser);
+            }
+        }
+
+        (Vec::new(), self.origin.clone())
+    }
+
+    /// The NSEC3 owner name of a name of the zone
+    pub fn nsec3_owner(&self, hash: &[u8]) -> String {
+        format!("{}.{}", to_base32hex(hash).to_lowercase(), self.origin)
+    }
+}
+
+/// The zone in zone file syntax, one absolute record per line after the `$ORIGIN` directive
+impl fmt::Display for Zone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "$ORIGIN {}.", self.origin)?;
+        for record in &self.records {
+            writeln!(f, "{}", record)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn wildcard_of(encloser: &str) -> String {
+    if encloser.is_empty() {
+        "*".to_string()
+    } else {
+        format!("*.{}", encloser)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DnsRecord, QueryType, ResultCode, Zone};
+    use std::net::Ipv4Addr;
+
+    const EXAMPLE: &str = "\
+$ORIGIN example.
+$TTL 1h
+@       IN SOA ns1 hostmaster ( 2024010101 ; serial
+                7200 3600 2w 300 )
+        IN NS  ns1
+ns1     IN A   192.0.2.53
+www  300 IN A  192.0.2.1
+        IN TXT \"hello \\\"world\\\"\" plain
+alias   IN CNAME www
+*.wild  IN A   192.0.2.9
+a.b.c   IN TYPE62347 \\# 4 0a000001
+child   IN NS  ns.child
+ns.child IN A  192.0.2.77
+";
+
+    #[test]
+    fn parse_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // act
+        let actual = Zone::parse(EXAMPLE, "example")?;
+
+        // assert
+        assert_eq!(10, actual.records.len());
+        assert_eq!(
+            Some("example. 3600 IN SOA ns1.example. hostmaster.example. 2024010101 7200 3600 1209600 300".to_string()),
+            actual.soa().map(|soa| soa.to_string())
+        );
+        assert_eq!(
+            "www.example. 300 IN A 192.0.2.1",
+            actual.records[3].to_string()
+        );
+        assert_eq!(
+            "www.example. 3600 IN TXT \"hello \\\"world\\\"\" \"plain\"",
+            actual.records[4].to_string()
+        );
+        assert_eq!(
+            "a.b.c.example. 3600 IN TYPE62347 \\# 4 0a000001",
+            actual.records[7].to_string()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_error() {
+        // assert
+        assert!(Zone::parse("@ 3600 IN A 192.0.2.1\n", "example").is_ok());
+        assert!(Zone::parse("@ 99999999999h IN A 192.0.2.1\n", "example").is_ok());
+        assert!(Zone::parse("@ IN A 192.0.2.1\n", "example").is_err());
+        assert!(Zone::parse("@ 60 IN BOGUS 1\n", "example").is_err());
+        assert!(Zone::parse("@ 60 IN A ( 192.0.2.1\n", "example").is_err());
+        assert!(Zone::parse("other. 60 IN A 192.0.2.1\n", "example").is_err());
+    }
+
+    #[test]
+    fn answer_ok() -> Result<(), Box<dyn std::error::Error>> {
+        // arrange
+        let zone = Zone::parse(EXAMPLE, "example")?;
+
+        // act
+        let www = zone.answer("WWW.example.", QueryType::A, false);
+        let alias = zone.answer("alias.example", QueryType::A, false);
+        let wild = zone.answer("x.wild.example", QueryType::A, false);
+        let nodata = zone.answer("www.example", QueryType::MX, false);
+        let empty_non_terminal = zone.answer("b.c.example", QueryType::A, false);
+        let nxdomain = zone.answer("nope.example", QueryType::A, false);
+        let referral = zone.answer("www.child.example", QueryType::A, false);
+
+        // assert
+        assert!(www.header.authoritative_answer);
+        assert_eq!(Some(Ipv4Addr::new(192, 0, 2, 1)), www.get_random_a());
+        assert_eq!(2, alias.answers.len());
+        assert_eq!(Some(Ipv4Addr::new(192, 0, 2, 1)), alias.get_random_a());
+        assert_eq!("x.wild.example", wild.answers[0].domain());
+        assert_eq!(ResultCode::NOERROR, nodata.header.response_code);
+        assert!(nodata.answers.is_empty());
+        assert_eq!(QueryType::SOA, nodata.authorities[0].qtype());
+        assert_eq!(ResultCode::NOERROR, empty_non_terminal.header.response_code);
+        assert_eq!(ResultCode::NXDOMAIN, nxdomain.header.response_code);
+        assert!(!referral.header.authoritative_answer);
+        assert!(matches!(referral.authorities[0], DnsRecord::NS { .. }));
+        assert_eq!(
+            Some(Ipv4Addr::new(192, 0, 2, 77)),
+            referral.resources.iter().find_map(|record| match record {
+                DnsRecord::A { addr, .. } => Some(*addr),
+                _ => None,
+            })
+        );
+
+        Ok(())
+    }
+}